@@ -0,0 +1,6 @@
+// getdoc - bin/cargo-getdoc.rs
+//
+// The `getdoc` program under the name cargo looks for, so that once installed it also runs
+// as `cargo getdoc`.
+
+include!("../main.rs");
@@ -0,0 +1,258 @@
+// getdoc - capture.rs
+//
+// Raw captures of cargo runs (`--emit-raw-json`): each feature set's untouched
+// `--message-format=json` stream is saved as `<feature set>-<hash>.json`, next to a
+// `<feature set>-<hash>.meta.json` sidecar with the feature set, cargo's stderr, and its exit
+// status. `--input <dir>` replays such a directory, one run per capture.
+
+// --- Standard Library Imports ---
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+// --- External Crate Imports ---
+use chrono::{Local, SecondsFormat};
+use serde::{Deserialize, Serialize};
+
+// --- Crate Imports ---
+use crate::cargo::{DiagnosticsSource, RawRunOutput};
+use crate::error::GetdocError;
+use crate::features::FeatureSet;
+use crate::util::stable_bytes_hash;
+
+/// Suffix of the metadata sidecars; the messages file has the same stem and `.json`.
+const METADATA_SUFFIX: &str = ".meta.json";
+
+/// Longest file stem taken from a feature-set description before it is shortened.
+const MAX_STEM_LENGTH: usize = 100;
+
+/// The contents of a `.meta.json` sidecar.
+#[derive(Debug, Serialize, Deserialize)]
+struct CaptureMetadata {
+    /// Description of the feature set, as used in the report.
+    feature_set: String,
+    /// The cargo feature arguments the set was checked with.
+    feature_args: Vec<String>,
+    /// Name of the file holding the JSON messages, in the same directory.
+    messages_file: String,
+    /// When the run started (RFC 3339); captures are replayed in this order.
+    started_at: String,
+    /// Cargo's exit status; absent when it was killed by a signal.
+    exit_code: Option<i32>,
+    /// Cargo's human-readable output.
+    stderr: String,
+}
+
+/// A file stem for the captures of `feature_desc`: the description with every run of
+/// characters other than letters, digits, `-`, and `_` turned into one `_` (cut short when
+/// overlong), followed by a short hash of the whole description. The hash keeps sets apart
+/// that read the same once cleaned up, e.g. `--features serde/std` and `serde_std`.
+pub fn capture_stem(feature_desc: &str) -> String {
+    let mut stem = String::new();
+    for c in feature_desc.chars() {
+        if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+            stem.push(c);
+        } else if !stem.ends_with('_') {
+            stem.push('_');
+        }
+    }
+    // A leading `-` would read as an option to most shell tools.
+    let stem = stem.trim_start_matches(['-', '_']).trim_end_matches('_');
+    let stem = if stem.is_empty() { "run" } else { stem };
+    let stem = &stem[..stem.len().min(MAX_STEM_LENGTH)];
+    // The hash of the bytes alone, so archived captures keep their names under any toolchain.
+    let hash = stable_bytes_hash(feature_desc.as_bytes());
+    format!("{}-{:08x}", stem, hash & 0xffff_ffff)
+}
+
+impl CaptureMetadata {
+    /// The messages file in `dir`, or `None` when the sidecar names a file anywhere else
+    /// (a path with a separator or `..`), which no capture ever does.
+    fn messages_path(&self, dir: &Path) -> Option<PathBuf> {
+        let name = &self.messages_file;
+        let plain_name =
+            !name.is_empty() && name != "." && !name.contains("..") && !name.contains(['/', '\\']);
+        plain_name.then(|| dir.join(name))
+    }
+}
+
+/// Removes the captures an earlier run left in `dir` (each sidecar and its messages file),
+/// so that the directory only ever holds one run's captures. Other files are left alone,
+/// including `.meta.json` files that are not getdoc sidecars and messages files named
+/// outside the directory.
+pub fn clear_captures(dir: &Path) -> io::Result<usize> {
+    let mut removed = 0;
+    for (metadata_path, metadata) in read_metadata(dir)? {
+        let Some(metadata) = metadata else {
+            continue;
+        };
+        if let Some(messages_path) = metadata.messages_path(dir) {
+            let _ = fs::remove_file(messages_path);
+        }
+        fs::remove_file(metadata_path)?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+/// Every sidecar in `dir`, with its parsed contents (`None` when it cannot be parsed).
+fn read_metadata(dir: &Path) -> io::Result<Vec<(PathBuf, Option<CaptureMetadata>)>> {
+    let mut sidecars = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_sidecar = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().ends_with(METADATA_SUFFIX));
+        if is_sidecar {
+            let metadata = fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| serde_json::from_str(&content).ok());
+            sidecars.push((path, metadata));
+        }
+    }
+    sidecars.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(sidecars)
+}
+
+/// A capture being written: the messages file is filled while cargo runs, and the sidecar
+/// is added by [`RawCapture::finish`] once it has exited.
+pub(crate) struct RawCapture {
+    dir: PathBuf,
+    stem: String,
+    feature_set: String,
+    feature_args: Vec<String>,
+    started_at: String,
+}
+
+impl RawCapture {
+    /// Starts the capture of one run in `dir`, returning the file its messages go to.
+    pub(crate) fn create(
+        dir: &Path,
+        feature_desc: &str,
+        feature_args: &[String],
+    ) -> io::Result<(RawCapture, File)> {
+        let capture = RawCapture {
+            dir: dir.to_path_buf(),
+            stem: capture_stem(feature_desc),
+            feature_set: feature_desc.to_string(),
+            feature_args: feature_args.to_vec(),
+            started_at: Local::now().to_rfc3339_opts(SecondsFormat::Micros, true),
+        };
+        let file = File::create(capture.messages_path())?;
+        Ok((capture, file))
+    }
+
+    fn messages_path(&self) -> PathBuf {
+        self.dir.join(format!("{}.json", self.stem))
+    }
+
+    /// Writes the sidecar for the finished run.
+    pub(crate) fn finish(self, exit_code: Option<i32>, stderr: &str) -> io::Result<()> {
+        let metadata = CaptureMetadata {
+            messages_file: format!("{}.json", self.stem),
+            feature_set: self.feature_set,
+            feature_args: self.feature_args,
+            started_at: self.started_at,
+            exit_code,
+            stderr: stderr.to_string(),
+        };
+        let content = serde_json::to_string_pretty(&metadata).map_err(io::Error::other)?;
+        fs::write(
+            self.dir.join(format!("{}{}", self.stem, METADATA_SUFFIX)),
+            content,
+        )
+    }
+
+    /// Drops the partial messages file of a run that did not finish.
+    pub(crate) fn discard(self) {
+        let _ = fs::remove_file(self.messages_path());
+    }
+}
+
+/// Replays a directory written by `--emit-raw-json`: one run per capture, each served for
+/// the feature set it was captured under.
+#[derive(Debug)]
+pub struct RawJsonDir {
+    dir: PathBuf,
+    captures: Vec<CaptureMetadata>,
+}
+
+impl RawJsonDir {
+    /// Reads the sidecars in `dir`, in the order the runs were made.
+    pub fn open(dir: &Path) -> Result<RawJsonDir, GetdocError> {
+        let input_error = |source| GetdocError::ReadInput {
+            path: dir.to_path_buf(),
+            source,
+        };
+        let mut captures = Vec::new();
+        for (path, metadata) in read_metadata(dir).map_err(input_error)? {
+            let metadata = metadata.ok_or_else(|| GetdocError::ReadInput {
+                path,
+                source: io::Error::new(io::ErrorKind::InvalidData, "not a getdoc capture"),
+            })?;
+            captures.push(metadata);
+        }
+        if captures.is_empty() {
+            return Err(input_error(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no `--emit-raw-json` captures (`*.meta.json`) in the directory",
+            )));
+        }
+        captures.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+        Ok(RawJsonDir {
+            dir: dir.to_path_buf(),
+            captures,
+        })
+    }
+
+    /// The captured feature sets, each with its description and cargo arguments.
+    pub fn feature_sets(&self) -> Vec<FeatureSet> {
+        self.captures
+            .iter()
+            .map(|capture| FeatureSet {
+                description: capture.feature_set.clone(),
+                ..FeatureSet::new(capture.feature_args.clone())
+            })
+            .collect()
+    }
+}
+
+impl DiagnosticsSource for RawJsonDir {
+    fn run(
+        &self,
+        _feature_args: &[String],
+        feature_desc: &str,
+    ) -> Result<RawRunOutput, GetdocError> {
+        let Some(capture) = self
+            .captures
+            .iter()
+            .find(|capture| capture.feature_set == feature_desc)
+        else {
+            return Err(GetdocError::ReadInput {
+                path: self.dir.clone(),
+                source: io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no capture of feature set '{}'", feature_desc),
+                ),
+            });
+        };
+        let Some(path) = capture.messages_path(&self.dir) else {
+            return Err(GetdocError::ReadInput {
+                path: self.dir.clone(),
+                source: io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "the capture of '{}' names a messages file outside the directory: {}",
+                        feature_desc, capture.messages_file
+                    ),
+                ),
+            });
+        };
+        let json_messages =
+            fs::read_to_string(&path).map_err(|source| GetdocError::ReadInput { path, source })?;
+        Ok(RawRunOutput {
+            json_messages,
+            stderr: capture.stderr.clone(),
+        })
+    }
+}
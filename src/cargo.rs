@@ -0,0 +1,857 @@
+// getdoc - cargo.rs
+//
+// Running `cargo check` (or `cargo build`) and reading its JSON message stream.
+
+// --- Standard Library Imports ---
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+// --- External Crate Imports ---
+use serde::Deserialize;
+
+// --- Crate Imports ---
+use crate::Options;
+use crate::capture::RawCapture;
+use crate::cargo_config::target_rustflags;
+use crate::diagnostics::{
+    DiagnosticOriginInfo, DisplayableDiagnostic, RustcDiagnosticData, normalize_cargo_home_paths,
+    process_single_diagnostic_data, strip_ansi_escapes,
+};
+use crate::error::GetdocError;
+use crate::interrupt::interrupted;
+use crate::progress::warning;
+use crate::timing::PhaseTimer;
+
+/// Results of processing one `cargo check` run (or one pre-captured JSON stream):
+/// the displayable diagnostics, the third-party files they implicate (with the
+/// implicated line numbers), and which diagnostics referenced each of those files.
+pub type CheckRunResult = (
+    Vec<DisplayableDiagnostic>,
+    HashMap<PathBuf, BTreeSet<usize>>,
+    HashMap<PathBuf, HashSet<DiagnosticOriginInfo>>,
+);
+
+/// The cargo subcommand each feature set is run with.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CargoCommand {
+    /// `cargo check`: fast, and enough for type and borrow errors.
+    #[default]
+    Check,
+    /// `cargo build`: also codegen and linking, so post-monomorphization, const-eval, and
+    /// linker errors show up.
+    Build,
+    /// `cargo test --no-run`: also compiles `#[cfg(test)]` code, tests, and benches, with
+    /// dev-dependencies.
+    TestCompile,
+    /// `cargo doc --no-deps`: rustdoc's lints, such as broken intra-doc links.
+    Doc,
+}
+
+impl CargoCommand {
+    /// The subcommand and its fixed arguments, as passed to cargo.
+    pub fn args(self) -> &'static [&'static str] {
+        match self {
+            CargoCommand::Check => &["check"],
+            CargoCommand::Build => &["build"],
+            CargoCommand::TestCompile => &["test", "--no-run"],
+            CargoCommand::Doc => &["doc", "--no-deps"],
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct TopLevelCargoMessage {
+    reason: String,
+    #[serde(default)]
+    message: Option<RustcDiagnosticData>,
+    /// The package a `compiler-message` or `compiler-artifact` is about.
+    #[serde(default)]
+    package_id: Option<String>,
+    /// The target a `compiler-message` or `compiler-artifact` is about.
+    #[serde(default)]
+    target: Option<CargoTarget>,
+    /// Whether the build succeeded, in the final `build-finished` message.
+    #[serde(default)]
+    success: Option<bool>,
+}
+
+/// A package target as cargo's JSON messages describe it.
+#[derive(Deserialize, Debug, Clone, Default)]
+struct CargoTarget {
+    #[serde(default)]
+    name: String,
+    /// E.g. `["lib"]`, `["bin"]`, `["custom-build"]`, or `["proc-macro"]`.
+    #[serde(default)]
+    kind: Vec<String>,
+}
+
+/// Names the package and target of `package_id` for a report, e.g. `home v0.5.12 (lib)`
+/// or `my-app v0.1.0 (bin server)`. Package IDs are either in cargo's current spec format
+/// (`registry+https://...#home@0.5.12`, or `path+file:///work/my-app#0.1.0` when the name
+/// is the last path segment) or the older `home 0.5.12 (registry+https://...)`.
+fn producing_crate_label(package_id: &str, target: &CargoTarget) -> String {
+    let (name, version) = match package_id.rsplit_once('#') {
+        Some((source, fragment)) => match fragment.split_once('@') {
+            Some((name, version)) => (name.to_string(), version.to_string()),
+            None => {
+                let path = source.split(['?', '#']).next().unwrap_or(source);
+                let name = path
+                    .trim_end_matches('/')
+                    .rsplit('/')
+                    .next()
+                    .unwrap_or(path);
+                (name.to_string(), fragment.to_string())
+            }
+        },
+        None => {
+            let mut words = package_id.split_whitespace();
+            (
+                words.next().unwrap_or(package_id).to_string(),
+                words.next().unwrap_or_default().to_string(),
+            )
+        }
+    };
+    let kind = if target.kind.iter().any(|kind| kind == "custom-build") {
+        "build script".to_string()
+    } else {
+        target.kind.join(", ")
+    };
+    // A library target is named after its package (with `-` as `_`, or unchanged in older
+    // cargo versions), so only other targets need a name.
+    let is_library = target.kind.iter().any(|kind| {
+        matches!(
+            kind.as_str(),
+            "lib" | "rlib" | "dylib" | "cdylib" | "staticlib" | "proc-macro"
+        )
+    });
+    let target_label = if target.name.is_empty()
+        || kind == "build script"
+        || (is_library && (target.name == name || target.name == name.replace('-', "_")))
+    {
+        kind
+    } else {
+        format!("{} {}", kind, target.name)
+    };
+    match (version.is_empty(), target_label.is_empty()) {
+        (true, true) => name,
+        (true, false) => format!("{} ({})", name, target_label),
+        (false, true) => format!("{} v{}", name, version),
+        (false, false) => format!("{} v{} ({})", name, version, target_label),
+    }
+}
+
+/// Cargo target-selection arguments (`--lib`, `--bin <NAME>`, `--example <NAME>`) for the
+/// targets chosen on the command line; empty when cargo should pick its default targets.
+pub(crate) fn target_selection_args(options: &Options) -> Vec<String> {
+    let mut target_args = Vec::new();
+    if options.lib {
+        target_args.push("--lib".to_string());
+    }
+    for bin in &options.bin {
+        target_args.extend(["--bin".to_string(), bin.clone()]);
+    }
+    for example in &options.example {
+        target_args.extend(["--example".to_string(), example.clone()]);
+    }
+    target_args
+}
+
+/// Splits a `RUSTFLAGS`-style string into individual flags on whitespace.
+/// Single- or double-quoted sections are kept together (with the quotes removed),
+/// so `--cfg 'feature="a b"'` yields `["--cfg", "feature=\"a b\""]`.
+pub(crate) fn split_rustflags(flags: &str) -> Vec<String> {
+    let mut split_flags = Vec::new();
+    let mut current_flag = String::new();
+    let mut has_flag = false; // Distinguishes an empty quoted flag (`''`) from no flag at all
+    let mut active_quote: Option<char> = None;
+
+    for c in flags.chars() {
+        match active_quote {
+            Some(quote) if c == quote => active_quote = None,
+            Some(_) => current_flag.push(c),
+            None if c == '\'' || c == '"' => {
+                active_quote = Some(c);
+                has_flag = true;
+            }
+            None if c.is_whitespace() => {
+                if has_flag {
+                    split_flags.push(std::mem::take(&mut current_flag));
+                    has_flag = false;
+                }
+            }
+            None => {
+                current_flag.push(c);
+                has_flag = true;
+            }
+        }
+    }
+    if has_flag {
+        split_flags.push(current_flag);
+    }
+    split_flags
+}
+
+/// The rustflags cargo would use without `--rustflags` when they do not come from
+/// `build.rustflags`: those in `CARGO_ENCODED_RUSTFLAGS` or `RUSTFLAGS`, or else under
+/// `[target.<triple>]` or `[target.'cfg(...)']` in cargo's configuration, which take
+/// precedence over `build.rustflags` (see [`target_rustflags`]). Fails when the target tables
+/// cannot be evaluated.
+pub(crate) fn inherited_rustflags(
+    config_overrides: &[String],
+) -> Result<Option<Vec<String>>, String> {
+    if let Ok(encoded) = std::env::var("CARGO_ENCODED_RUSTFLAGS") {
+        Ok(Some(
+            encoded
+                .split('\x1f')
+                .filter(|flag| !flag.is_empty())
+                .map(String::from)
+                .collect(),
+        ))
+    } else if let Ok(flags) = std::env::var("RUSTFLAGS") {
+        Ok(Some(flags.split_whitespace().map(String::from).collect()))
+    } else {
+        target_rustflags(config_overrides)
+    }
+}
+
+/// Adds `--rustflags` to a cargo command on top of the flags cargo would use anyway.
+/// Cargo uses only the first of `CARGO_ENCODED_RUSTFLAGS`, `RUSTFLAGS`, the target tables of
+/// its configuration, and `build.rustflags`, so flags from the first three are passed on with
+/// ours in `CARGO_ENCODED_RUSTFLAGS`; otherwise ours go in through `--config`, which cargo
+/// concatenates with `build.rustflags` from `.cargo/config.toml` rather than replacing it.
+fn add_rustflags(command: &mut Command, rustflags: &[String], config_overrides: &[String]) {
+    if rustflags.is_empty() {
+        return;
+    }
+    // A failure is reported once, by `run`, before any check.
+    let inherited_flags = inherited_rustflags(config_overrides).unwrap_or(None);
+    match inherited_flags {
+        Some(mut flags) => {
+            flags.extend(rustflags.iter().cloned());
+            // The encoded form separates flags with 0x1f, so flags containing spaces survive intact.
+            command.env("CARGO_ENCODED_RUSTFLAGS", flags.join("\x1f"));
+        }
+        None => {
+            let flag_array = toml::Value::Array(
+                rustflags
+                    .iter()
+                    .map(|flag| toml::Value::String(flag.clone()))
+                    .collect(),
+            );
+            command
+                .arg("--config")
+                .arg(format!("build.rustflags={}", flag_array));
+        }
+    }
+}
+
+// --- Diagnostics Sources ---
+
+/// What one diagnostics run produced: cargo's JSON message stream and its stderr.
+#[derive(Debug, Default, Clone)]
+pub struct RawRunOutput {
+    /// Line-delimited `--message-format=json` messages; other lines are ignored.
+    pub json_messages: String,
+    /// Cargo's human-readable output, shown to the user when it reports an error.
+    pub stderr: String,
+}
+
+/// Produces cargo's JSON diagnostics for a feature selection: a live `cargo check`
+/// ([`CargoCli`]), a replay of saved output ([`JsonFile`]), or any other backend.
+pub trait DiagnosticsSource {
+    /// Runs the check for one feature set. `feature_args` are the cargo feature arguments
+    /// (e.g. `--no-default-features --features x`); `feature_desc` names the set in errors.
+    fn run(&self, feature_args: &[String], feature_desc: &str)
+    -> Result<RawRunOutput, GetdocError>;
+}
+
+/// A `cargo` command with each of `config_overrides` passed as `--config <override>`, so
+/// every cargo getdoc runs sees the configuration its checks do. As a cargo subcommand
+/// (`cargo getdoc`), this is the cargo that ran getdoc, which cargo passes on in `CARGO`;
+/// that keeps the same toolchain when several are installed.
+pub(crate) fn cargo_command(config_overrides: &[String]) -> Command {
+    let mut command = Command::new(std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into()));
+    add_config_overrides(&mut command, config_overrides);
+    command
+}
+
+/// `cargo +nightly`, with `config_overrides` as for [`cargo_command`]. Unlike that, this is
+/// always the `cargo` on `PATH`, since only rustup's proxy understands `+nightly`.
+pub(crate) fn nightly_cargo_command(config_overrides: &[String]) -> Command {
+    let mut command = Command::new("cargo");
+    command.arg("+nightly");
+    add_config_overrides(&mut command, config_overrides);
+    command
+}
+
+/// Passes each of `config_overrides` as cargo's global `--config` option.
+fn add_config_overrides(command: &mut Command, config_overrides: &[String]) {
+    for config_override in config_overrides {
+        command.arg("--config").arg(config_override);
+    }
+}
+
+/// Whether a nightly toolchain can be invoked as `cargo +nightly` (through rustup).
+pub(crate) fn nightly_available() -> bool {
+    nightly_cargo_command(&[])
+        .arg("--version")
+        .stdin(Stdio::null())
+        .output()
+        .is_ok_and(|output| {
+            output.status.success() && String::from_utf8_lossy(&output.stdout).contains("nightly")
+        })
+}
+
+/// Runs the chosen cargo subcommand (`check`, `build`, `test --no-run`, or `doc`) with
+/// `--message-format=json` in the current directory, adding the target selection, rustflags,
+/// and `--config` overrides to every run and, with `--emit-raw-json`, saving its raw output.
+#[derive(Debug, Default, Clone)]
+pub struct CargoCli {
+    /// Which cargo subcommand to run.
+    pub command: CargoCommand,
+    /// Pass `--document-private-items` (only with [`CargoCommand::Doc`]).
+    pub document_private_items: bool,
+    /// Target-selection arguments (`--lib`, `--bin <NAME>`, ...) added to every run.
+    pub target_args: Vec<String>,
+    /// Extra rustc flags, added to the rustflags cargo already uses.
+    pub rustflags: Vec<String>,
+    /// Cargo configuration overrides, each passed as `--config <override>`.
+    pub config_overrides: Vec<String>,
+    /// Directory each run's raw output is saved to as it streams in (`--emit-raw-json`).
+    pub raw_json_dir: Option<PathBuf>,
+}
+
+impl CargoCli {
+    /// The subcommand and the arguments every run passes with it, e.g. `["doc", "--no-deps"]`.
+    pub fn subcommand_args(&self) -> Vec<&'static str> {
+        let mut args = self.command.args().to_vec();
+        if self.document_private_items && self.command == CargoCommand::Doc {
+            args.push("--document-private-items");
+        }
+        args
+    }
+
+    /// How the command reads in messages, e.g. `cargo test --no-run`.
+    pub fn display_name(&self) -> String {
+        format!("cargo {}", self.subcommand_args().join(" "))
+    }
+
+    /// The command run for the feature set with `feature_args`.
+    fn command(&self, feature_args: &[String]) -> Command {
+        let mut command = cargo_command(&self.config_overrides);
+        command
+            .args(self.subcommand_args())
+            .arg("--message-format=json");
+        command.args(feature_args);
+        command.args(&self.target_args);
+        add_rustflags(&mut command, &self.rustflags, &self.config_overrides);
+        command
+    }
+
+    /// A shell command line that reruns the check of a feature set by hand, from the project
+    /// directory: the exact arguments and environment getdoc runs cargo with, under the
+    /// toolchain getdoc was started with (`cargo +<toolchain> getdoc`), but printing cargo's
+    /// usual human-readable output instead of JSON.
+    pub fn reproduction_command(&self, feature_args: &[String]) -> String {
+        let command = self.command(feature_args);
+        let mut words: Vec<String> = command
+            .get_envs()
+            .filter_map(|(key, value)| Some((key.to_string_lossy(), value?.to_string_lossy())))
+            .map(|(key, value)| match key.as_ref() {
+                // Written as plain `RUSTFLAGS` when no flag needs the 0x1f separators.
+                "CARGO_ENCODED_RUSTFLAGS" if !value.contains(char::is_whitespace) => {
+                    format!("RUSTFLAGS={}", shell_quote(&value.replace('\x1f', " ")))
+                }
+                // POSIX shells have no escape for 0x1f inside quotes; `printf %b` writes it
+                // from `\0037`, whose three digits stop the escape before any flag text.
+                "CARGO_ENCODED_RUSTFLAGS" => format!(
+                    "{}=\"$(printf %b {})\"",
+                    key,
+                    shell_quote(&value.replace('\\', "\\\\").replace('\x1f', "\\0037"))
+                ),
+                _ => format!("{}={}", key, shell_quote(&value)),
+            })
+            .collect();
+        words.push("cargo".to_string());
+        if let Ok(toolchain) = std::env::var("RUSTUP_TOOLCHAIN") {
+            words.push(format!("+{}", toolchain));
+        }
+        words.extend(
+            command
+                .get_args()
+                .map(|arg| arg.to_string_lossy())
+                .filter(|arg| arg != "--message-format=json")
+                .map(|arg| shell_quote(&arg)),
+        );
+        words.join(" ")
+    }
+}
+
+/// Quotes `word` for a POSIX shell when it contains anything but plain characters.
+fn shell_quote(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+    if plain {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+impl DiagnosticsSource for CargoCli {
+    fn run(
+        &self,
+        feature_args: &[String],
+        feature_desc: &str,
+    ) -> Result<RawRunOutput, GetdocError> {
+        let mut command = self.command(feature_args);
+
+        let interrupted_error = || GetdocError::Interrupted {
+            feature_set: feature_desc.to_string(),
+        };
+        let check_error = |source| GetdocError::CargoCheck {
+            feature_set: feature_desc.to_string(),
+            source,
+        };
+        if interrupted() {
+            return Err(interrupted_error());
+        }
+        let mut child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|source| match source.kind() {
+                std::io::ErrorKind::NotFound => GetdocError::CargoNotFound { source },
+                _ => check_error(source),
+            })?;
+        let (capture, capture_file) = match &self.raw_json_dir {
+            Some(dir) => match RawCapture::create(dir, feature_desc, feature_args) {
+                Ok((capture, file)) => (Some(capture), Some(file)),
+                Err(e) => {
+                    warning!(
+                        "[getdoc] Warning: Could not save the raw output of '{}' in {}: {}",
+                        feature_desc,
+                        dir.display(),
+                        e
+                    );
+                    (None, None)
+                }
+            },
+            None => (None, None),
+        };
+        let stdout_reader = read_pipe_in_background(child.stdout.take(), capture_file);
+        let stderr_reader = read_pipe_in_background(child.stderr.take(), None);
+        let finished = wait_unless_interrupted(&mut child).map_err(check_error)?;
+        let stdout = stdout_reader.join().unwrap_or_default();
+        let stderr =
+            String::from_utf8_lossy(&stderr_reader.join().unwrap_or_default()).into_owned();
+        if !finished {
+            if let Some(capture) = capture {
+                capture.discard();
+            }
+            return Err(interrupted_error());
+        }
+        if let Some(capture) = capture {
+            // The status was reaped by `wait_unless_interrupted`; this only reads it back.
+            let exit_code = child
+                .try_wait()
+                .ok()
+                .flatten()
+                .and_then(|status| status.code());
+            if let Err(e) = capture.finish(exit_code, &stderr) {
+                warning!(
+                    "[getdoc] Warning: Could not save cargo's stderr and exit status for '{}': {}",
+                    feature_desc,
+                    e
+                );
+            }
+        }
+        Ok(RawRunOutput {
+            json_messages: String::from_utf8_lossy(&stdout).into_owned(),
+            stderr,
+        })
+    }
+}
+
+/// Drains a child's output pipe on another thread, so the child never blocks on a full pipe
+/// while we wait for it. Everything read is also copied to `tee` as it arrives.
+fn read_pipe_in_background(
+    pipe: Option<impl Read + Send + 'static>,
+    mut tee: Option<File>,
+) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let Some(mut pipe) = pipe else {
+            return buffer;
+        };
+        let mut chunk = [0; 8192];
+        loop {
+            let read = match pipe.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            };
+            buffer.extend_from_slice(&chunk[..read]);
+            if let Some(file) = &mut tee
+                && let Err(e) = file.write_all(&chunk[..read])
+            {
+                warning!("[getdoc] Warning: Could not save cargo's raw output: {}", e);
+                tee = None;
+            }
+        }
+        buffer
+    })
+}
+
+/// Waits for `child` to exit, killing it if Ctrl-C is pressed first. Returns whether it ran
+/// to completion.
+fn wait_unless_interrupted(child: &mut Child) -> std::io::Result<bool> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            if status.code().is_none() {
+                // Killed by a signal: in a terminal, Ctrl-C reaches cargo as well, possibly
+                // just before our handler has run.
+                thread::sleep(Duration::from_millis(100));
+            }
+            return Ok(!interrupted());
+        }
+        if interrupted() {
+            // It may have exited on the same SIGINT already; reap it either way.
+            let _ = child.kill();
+            child.wait()?;
+            return Ok(false);
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Replays saved `cargo check --message-format=json` output from `path` (`-` reads stdin),
+/// e.g. from a CI log. The same messages are returned whatever feature set is asked for.
+#[derive(Debug, Clone)]
+pub struct JsonFile {
+    /// File holding the captured messages.
+    pub path: PathBuf,
+}
+
+impl DiagnosticsSource for JsonFile {
+    fn run(
+        &self,
+        _feature_args: &[String],
+        _feature_desc: &str,
+    ) -> Result<RawRunOutput, GetdocError> {
+        let json_messages = if self.path == Path::new("-") {
+            let mut buffer = String::new();
+            std::io::stdin().read_to_string(&mut buffer).map(|_| buffer)
+        } else {
+            fs::read_to_string(&self.path)
+        };
+        let json_messages = json_messages.map_err(|source| GetdocError::ReadInput {
+            path: self.path.clone(),
+            source,
+        })?;
+        Ok(RawRunOutput {
+            json_messages,
+            stderr: String::new(),
+        })
+    }
+}
+
+/// Checks one feature set with `source` and collects the diagnostics in its output.
+pub fn check_feature_set(
+    source: &dyn DiagnosticsSource,
+    feature_args: &[String],
+    feature_desc: &str,
+) -> Result<CheckRunResult, GetdocError> {
+    check_feature_set_timed(
+        source,
+        feature_args,
+        feature_desc,
+        &PhaseTimer::default(),
+        "cargo check",
+        CheckOptions::default(),
+    )
+}
+
+/// How the messages of a feature set's run are processed.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct CheckOptions {
+    /// Keep terminal escape sequences in rendered messages.
+    pub(crate) keep_ansi: bool,
+    /// Keep top-level notes that implicate third-party files as diagnostics.
+    pub(crate) include_notes: bool,
+    /// Print lines that look like JSON messages but fail to parse, with the parse error.
+    pub(crate) debug_json: bool,
+}
+
+impl From<&Options> for CheckOptions {
+    fn from(options: &Options) -> Self {
+        CheckOptions {
+            keep_ansi: options.keep_ansi,
+            include_notes: options.include_notes_as_diagnostics,
+            debug_json: options.debug_json,
+        }
+    }
+}
+
+/// [`check_feature_set`], adding the time `source` takes to `run_phase` and the time spent
+/// processing its JSON messages to "JSON processing", with the messages processed as
+/// `check_options` says.
+pub(crate) fn check_feature_set_timed(
+    source: &dyn DiagnosticsSource,
+    feature_args: &[String],
+    feature_desc: &str,
+    timer: &PhaseTimer,
+    run_phase: &str,
+    check_options: CheckOptions,
+) -> Result<CheckRunResult, GetdocError> {
+    let raw_output = timer.time(run_phase, || source.run(feature_args, feature_desc))?;
+
+    let ((mut diagnostics, implicated_files, referencers), build_succeeded) = timer
+        .time("JSON processing", || {
+            process_cargo_json_output(
+                &raw_output.json_messages,
+                feature_desc,
+                check_options.include_notes,
+                check_options.debug_json,
+            )
+        })
+        .map_err(|source| GetdocError::CargoCheck {
+            feature_set: feature_desc.to_string(),
+            source,
+        })?;
+    for diagnostic in &mut diagnostics {
+        if !check_options.keep_ansi && diagnostic.rendered.contains('\u{1b}') {
+            diagnostic.rendered = strip_ansi_escapes(&diagnostic.rendered);
+        }
+        // Paths into the cargo home differ between machines; consolidation and baseline
+        // comparisons should not.
+        diagnostic.rendered = normalize_cargo_home_paths(&diagnostic.rendered);
+        if diagnostic.message.starts_with("linking with") {
+            let libraries = missing_native_libraries(&diagnostic.rendered);
+            if !libraries.is_empty() {
+                diagnostic.rendered.push_str(&format!(
+                    "\n>>> native libraries not found: {}",
+                    libraries.join(", ")
+                ));
+            }
+        }
+    }
+    diagnostics.extend(native_build_failure(&raw_output.stderr));
+
+    // `build-finished` says whether the build failed; output without it (older cargo, or a
+    // captured excerpt) only has the errors cargo printed to go by.
+    let stderr_text = raw_output.stderr.trim();
+    let build_failed = match build_succeeded {
+        Some(succeeded) => !succeeded,
+        None => stderr_text.contains("error:"),
+    };
+    if build_failed && !stderr_text.is_empty() {
+        warning!(
+            "[getdoc] Cargo command stderr (for '{}'):\n{}",
+            feature_desc,
+            raw_output.stderr
+        );
+    }
+    if build_succeeded == Some(false)
+        && !diagnostics
+            .iter()
+            .any(|d| d.level == "error" || d.level == "TOOL_ERROR")
+    {
+        diagnostics.push(unexplained_build_failure(stderr_text));
+    }
+    Ok((diagnostics, implicated_files, referencers))
+}
+
+/// A `TOOL_ERROR` for a build cargo reported as failed without any error message to show
+/// for it, with the error lines of its stderr.
+fn unexplained_build_failure(stderr: &str) -> DisplayableDiagnostic {
+    let message = "cargo reported that the build failed, without a compiler error".to_string();
+    let excerpt: Vec<&str> = stderr
+        .lines()
+        .filter(|line| line.trim_start().starts_with("error"))
+        .take(NATIVE_FAILURE_EXCERPT_LINES)
+        .collect();
+    DisplayableDiagnostic {
+        level: "TOOL_ERROR".to_string(),
+        code: None,
+        code_explanation: None,
+        rendered: format!(">>> {}\n{}", message, excerpt.join("\n"))
+            .trim_end()
+            .to_string(),
+        message,
+        primary_location_of_diagnostic: None,
+        implicated_third_party_files_details: vec![],
+        suggestions: vec![],
+        producing_crate: None,
+    }
+}
+
+/// Native libraries that linker or build-script output says could not be found, in order
+/// of first mention: `cannot find -lssl` (GNU ld), `library not found for -lssl` (Apple ld),
+/// `unable to find library -lssl` (lld), ``could not find native static library `ssl` ``
+/// (rustc), ``The system library `ssl` ... was not found`` (pkg-config), and
+/// `cannot open input file 'ssl.lib'` (MSVC).
+fn missing_native_libraries(text: &str) -> Vec<String> {
+    const MARKERS: [(&str, &[char]); 6] = [
+        ("cannot find -l", &[' ', ':', '\'', '"']),
+        ("library not found for -l", &[' ', ':', '\'', '"']),
+        ("unable to find library -l", &[' ', ':', '\'', '"']),
+        ("could not find native static library `", &['`']),
+        ("The system library `", &['`']),
+        ("cannot open input file '", &['\'']),
+    ];
+    let mut libraries: Vec<String> = Vec::new();
+    for line in text.lines() {
+        for (marker, terminators) in MARKERS {
+            let Some(start) = line.find(marker) else {
+                continue;
+            };
+            let rest = &line[start + marker.len()..];
+            let name =
+                rest[..rest.find(terminators).unwrap_or(rest.len())].trim_end_matches(".lib");
+            if !name.is_empty() && !libraries.iter().any(|known| known == name) {
+                libraries.push(name.to_string());
+            }
+        }
+    }
+    libraries
+}
+
+/// Longest excerpt of cargo's stderr kept for a build-script or native-library failure.
+const NATIVE_FAILURE_EXCERPT_LINES: usize = 20;
+
+/// A `TOOL_ERROR` diagnostic for failures that only cargo's plain-text stderr describes: a
+/// build script (typically a `-sys` crate's) that failed, or native libraries that could not
+/// be found. Its text is the relevant stderr lines, headed by the missing libraries.
+fn native_build_failure(stderr: &str) -> Option<DisplayableDiagnostic> {
+    let libraries = missing_native_libraries(stderr);
+    let build_script_failed = stderr.contains("failed to run custom build command");
+    if libraries.is_empty() && !build_script_failed {
+        return None;
+    }
+    let message = if libraries.is_empty() {
+        "a build script failed".to_string()
+    } else {
+        format!("native libraries not found: {}", libraries.join(", "))
+    };
+    let excerpt: Vec<&str> = stderr
+        .lines()
+        .filter(|line| {
+            line.trim_start().starts_with("error") || !missing_native_libraries(line).is_empty()
+        })
+        .take(NATIVE_FAILURE_EXCERPT_LINES)
+        .collect();
+    Some(DisplayableDiagnostic {
+        level: "TOOL_ERROR".to_string(),
+        code: None,
+        code_explanation: None,
+        rendered: format!(">>> {}\n{}", message, excerpt.join("\n")),
+        message,
+        primary_location_of_diagnostic: None,
+        implicated_third_party_files_details: vec![],
+        suggestions: vec![],
+        producing_crate: None,
+    })
+}
+
+/// Parses line-delimited cargo JSON messages and collects the diagnostics they contain,
+/// each attributed to the package and target being compiled, along with whether the build
+/// succeeded (`None` without a `build-finished` message). Lines that are not JSON messages
+/// are skipped; with `debug_json`, the ones that start like one are printed along with the
+/// reason they could not be parsed.
+fn process_cargo_json_output(
+    json_output: &str,
+    feature_desc: &str,
+    include_notes: bool,
+    debug_json: bool,
+) -> std::io::Result<(CheckRunResult, Option<bool>)> {
+    let mut displayable_diagnostics: Vec<DisplayableDiagnostic> = Vec::new();
+    let mut implicated_files_this_run: HashMap<PathBuf, BTreeSet<usize>> = HashMap::new();
+    let mut referencers_this_run: HashMap<PathBuf, HashSet<DiagnosticOriginInfo>> = HashMap::new();
+
+    let current_dir = std::env::current_dir()?;
+    let cargo_home_dir = home::cargo_home().ok();
+    let mut build_succeeded = None;
+    // The target of each package that produced an artifact, for messages that name only
+    // their package; and the diagnostics of such messages, by package.
+    let mut artifact_targets: HashMap<String, CargoTarget> = HashMap::new();
+    let mut unattributed: Vec<(usize, String)> = Vec::new();
+
+    for (line_index, line) in json_output.lines().enumerate() {
+        if line.trim().is_empty() || !line.starts_with('{') {
+            continue;
+        }
+        match serde_json::from_str::<TopLevelCargoMessage>(line) {
+            Ok(top_level_msg) => match top_level_msg.reason.as_str() {
+                "compiler-message" if let Some(mut diag_data) = top_level_msg.message => {
+                    diag_data.attribute_doctest_spans();
+                    let new_index = displayable_diagnostics.len();
+                    process_single_diagnostic_data(
+                        &diag_data,
+                        &mut displayable_diagnostics,
+                        &mut implicated_files_this_run,
+                        &mut referencers_this_run,
+                        &current_dir,
+                        &cargo_home_dir,
+                        feature_desc,
+                        include_notes,
+                    );
+                    // A message yields at most one diagnostic, at `new_index`.
+                    if let Some(diagnostic) = displayable_diagnostics.get_mut(new_index)
+                        && let Some(package_id) = top_level_msg.package_id
+                    {
+                        match &top_level_msg.target {
+                            Some(target) => {
+                                diagnostic.producing_crate =
+                                    Some(producing_crate_label(&package_id, target));
+                            }
+                            None => unattributed.push((new_index, package_id)),
+                        }
+                    }
+                }
+                "compiler-artifact" => {
+                    if let (Some(package_id), Some(target)) =
+                        (top_level_msg.package_id, top_level_msg.target)
+                    {
+                        artifact_targets.insert(package_id, target);
+                    }
+                }
+                "build-finished" => build_succeeded = top_level_msg.success,
+                _ => {}
+            },
+            Err(e) if debug_json => warning!(
+                "[getdoc] --debug-json: Could not parse line {} of the output for '{}' ({}):\n{}",
+                line_index + 1,
+                feature_desc,
+                e,
+                line
+            ),
+            Err(_) => {}
+        }
+    }
+    // Artifacts are reported after their crate's messages, so these are resolved last.
+    for (index, package_id) in unattributed {
+        let target = artifact_targets
+            .get(&package_id)
+            .cloned()
+            .unwrap_or_default();
+        displayable_diagnostics[index].producing_crate =
+            Some(producing_crate_label(&package_id, &target));
+    }
+    Ok((
+        (
+            displayable_diagnostics,
+            implicated_files_this_run,
+            referencers_this_run,
+        ),
+        build_succeeded,
+    ))
+}
@@ -0,0 +1,307 @@
+// getdoc - cargo_config.rs
+//
+// Reading cargo's configuration where getdoc has to know what cargo will do with it: which
+// files apply, and the `target.<triple>.rustflags` / `target.'cfg(...)'.rustflags` that
+// replace `build.rustflags` when present.
+
+// --- Standard Library Imports ---
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The cargo configuration files that apply here: `.cargo/config.toml` and `.cargo/config`
+/// in the current directory and each of its parents, and in the cargo home, from the
+/// highest precedence to the lowest.
+pub(crate) fn cargo_config_files() -> Vec<PathBuf> {
+    let mut config_dirs: Vec<PathBuf> = fs::canonicalize(".")
+        .map(|dir| {
+            dir.ancestors()
+                .map(|ancestor| ancestor.join(".cargo"))
+                .collect()
+        })
+        .unwrap_or_default();
+    config_dirs.extend(home::cargo_home().ok());
+    let mut seen = BTreeSet::new();
+    config_dirs
+        .into_iter()
+        .flat_map(|dir| [dir.join("config.toml"), dir.join("config")])
+        .filter(|path| path.is_file() && seen.insert(fs::canonicalize(path).ok()))
+        .collect()
+}
+
+/// The rustflags cargo takes from `target.<triple>.rustflags` and every matching
+/// `target.'cfg(...)'.rustflags` (in that order), for the target it builds for: `build.target`
+/// or the host. `None` when no such table sets any, so `build.rustflags` applies. Values are
+/// merged the way cargo merges its configuration: config files from the cargo home to the
+/// current directory, then `CARGO_TARGET_<TRIPLE>_RUSTFLAGS`, then `config_overrides`. Fails
+/// when the target or its cfg values cannot be determined.
+pub(crate) fn target_rustflags(config_overrides: &[String]) -> Result<Option<Vec<String>>, String> {
+    let mut tables: Vec<toml::Table> = cargo_config_files()
+        .iter()
+        .rev()
+        .filter_map(|path| fs::read_to_string(path).ok()?.parse().ok())
+        .collect();
+    let override_tables: Vec<toml::Table> = config_overrides
+        .iter()
+        .filter_map(|config_override| {
+            let path = Path::new(config_override);
+            if path.is_file() {
+                fs::read_to_string(path).ok()?.parse().ok()
+            } else {
+                config_override.parse().ok()
+            }
+        })
+        .collect();
+    let has_target_rustflags = tables.iter().chain(&override_tables).any(|table| {
+        table
+            .get("target")
+            .and_then(toml::Value::as_table)
+            .is_some_and(|targets| {
+                targets
+                    .values()
+                    .any(|target| target.get("rustflags").is_some())
+            })
+    });
+    if !has_target_rustflags
+        && !std::env::vars()
+            .any(|(name, _)| name.starts_with("CARGO_TARGET_") && name.ends_with("_RUSTFLAGS"))
+    {
+        return Ok(None);
+    }
+
+    let build_target = tables
+        .iter()
+        .chain(&override_tables)
+        .filter_map(|table| table.get("build")?.get("target").cloned())
+        .chain(
+            std::env::var("CARGO_BUILD_TARGET")
+                .ok()
+                .map(toml::Value::String),
+        )
+        .last();
+    let triple = match build_target {
+        None => host_triple()?,
+        Some(toml::Value::String(triple)) => triple,
+        Some(_) => return Err("`build.target` names several targets".to_string()),
+    };
+
+    // The variable ranks above the files and below `--config`.
+    let variable = format!(
+        "CARGO_TARGET_{}_RUSTFLAGS",
+        triple.to_uppercase().replace(['-', '.'], "_")
+    );
+    if let Ok(flags) = std::env::var(&variable) {
+        let mut table = toml::Table::new();
+        table.insert("rustflags".to_string(), toml::Value::String(flags));
+        let mut targets = toml::Table::new();
+        targets.insert(triple.clone(), toml::Value::Table(table));
+        let mut config = toml::Table::new();
+        config.insert("target".to_string(), toml::Value::Table(targets));
+        tables.push(config);
+    }
+    tables.extend(override_tables);
+
+    // Every `[target.'cfg(...)']` key with rustflags, in name order as cargo applies them.
+    let cfg_keys: BTreeSet<&str> = tables
+        .iter()
+        .filter_map(|table| table.get("target")?.as_table())
+        .flat_map(|targets| targets.iter())
+        .filter(|(key, target)| key.starts_with("cfg(") && target.get("rustflags").is_some())
+        .map(|(key, _)| key.as_str())
+        .collect();
+    let target_cfg = if cfg_keys.is_empty() {
+        Vec::new()
+    } else {
+        rustc_cfg(&triple)?
+    };
+    let mut keys = vec![triple.as_str()];
+    for key in cfg_keys {
+        if CfgExpr::parse(key)?.matches(&target_cfg) {
+            keys.push(key);
+        }
+    }
+
+    let mut flags = None;
+    for key in keys {
+        for table in &tables {
+            let Some(value) = table
+                .get("target")
+                .and_then(|targets| targets.get(key))
+                .and_then(|target| target.get("rustflags"))
+            else {
+                continue;
+            };
+            let merged: &mut Vec<String> = flags.get_or_insert_with(Vec::new);
+            match value {
+                toml::Value::String(list) => {
+                    merged.extend(list.split_whitespace().map(String::from));
+                }
+                toml::Value::Array(list) => merged.extend(
+                    list.iter()
+                        .filter_map(|flag| flag.as_str())
+                        .map(String::from),
+                ),
+                _ => return Err(format!("`target.{}.rustflags` is not a list", key)),
+            }
+        }
+    }
+    Ok(flags)
+}
+
+/// The `rustc` cargo runs: `RUSTC`, or the one on `PATH`.
+fn rustc_command() -> Command {
+    Command::new(std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into()))
+}
+
+/// The host's target triple, from `rustc -vV`.
+fn host_triple() -> Result<String, String> {
+    let output = rustc_command()
+        .arg("-vV")
+        .output()
+        .map_err(|e| format!("could not run rustc: {}", e))?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .map(|host| host.trim().to_string())
+        .ok_or_else(|| "`rustc -vV` did not name the host".to_string())
+}
+
+/// The cfg values of `triple`, from `rustc --print cfg`: `unix`, `target_os="linux"`, ...
+fn rustc_cfg(triple: &str) -> Result<Vec<String>, String> {
+    let output = rustc_command()
+        .args(["--print", "cfg", "--target", triple])
+        .output()
+        .map_err(|e| format!("could not run rustc: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "`rustc --print cfg --target {}` failed: {}",
+            triple,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(String::from)
+        .collect())
+}
+
+// --- cfg Expressions ---
+
+/// A `cfg(...)` predicate, as in `[target.'cfg(all(unix, target_arch = "x86_64"))']`.
+#[derive(Debug)]
+enum CfgExpr {
+    Name(String),
+    KeyValue(String, String),
+    All(Vec<CfgExpr>),
+    Any(Vec<CfgExpr>),
+    Not(Box<CfgExpr>),
+}
+
+impl CfgExpr {
+    /// Parses a `cfg(...)` target key.
+    fn parse(key: &str) -> Result<CfgExpr, String> {
+        let invalid = || format!("cannot read the target key `{}`", key);
+        let tokens = cfg_tokens(key).ok_or_else(invalid)?;
+        let mut position = 0;
+        match parse_cfg_predicate(&tokens, &mut position) {
+            Some(CfgExpr::All(mut predicates))
+                if position == tokens.len()
+                    && predicates.len() == 1
+                    && tokens.first().map(String::as_str) == Some("cfg") =>
+            {
+                Ok(predicates.remove(0))
+            }
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Whether the predicate holds for the `rustc --print cfg` lines `target_cfg`.
+    fn matches(&self, target_cfg: &[String]) -> bool {
+        match self {
+            CfgExpr::Name(name) => target_cfg.iter().any(|cfg| cfg == name),
+            CfgExpr::KeyValue(key, value) => target_cfg
+                .iter()
+                .any(|cfg| *cfg == format!("{}=\"{}\"", key, value)),
+            CfgExpr::All(predicates) => predicates.iter().all(|p| p.matches(target_cfg)),
+            CfgExpr::Any(predicates) => predicates.iter().any(|p| p.matches(target_cfg)),
+            CfgExpr::Not(predicate) => !predicate.matches(target_cfg),
+        }
+    }
+}
+
+/// Splits a cfg key into identifiers, quoted strings (kept with their quotes), and the
+/// punctuation `(`, `)`, `,`, and `=`. `None` on anything else.
+fn cfg_tokens(key: &str) -> Option<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut chars = key.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if "(),=".contains(c) {
+            tokens.push(c.to_string());
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut string = String::from('"');
+            loop {
+                let c = chars.next()?;
+                string.push(c);
+                if c == '"' {
+                    break;
+                }
+            }
+            tokens.push(string);
+        } else if c.is_alphanumeric() || c == '_' {
+            let mut identifier = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_') {
+                identifier.push(c);
+                chars.next();
+            }
+            tokens.push(identifier);
+        } else {
+            return None;
+        }
+    }
+    Some(tokens)
+}
+
+/// Parses one predicate at `tokens[*position]`. The outer `cfg(...)` reads as an `all` of
+/// its single predicate.
+fn parse_cfg_predicate(tokens: &[String], position: &mut usize) -> Option<CfgExpr> {
+    let name = tokens.get(*position)?.clone();
+    if name.starts_with('"') || !name.starts_with(|c: char| c.is_alphanumeric() || c == '_') {
+        return None;
+    }
+    *position += 1;
+    match tokens.get(*position).map(String::as_str) {
+        Some("=") => {
+            let value = tokens.get(*position + 1)?;
+            let value = value.strip_prefix('"')?.strip_suffix('"')?.to_string();
+            *position += 2;
+            Some(CfgExpr::KeyValue(name, value))
+        }
+        Some("(") => {
+            *position += 1;
+            let mut predicates = Vec::new();
+            while tokens.get(*position).map(String::as_str) != Some(")") {
+                predicates.push(parse_cfg_predicate(tokens, position)?);
+                match tokens.get(*position).map(String::as_str) {
+                    Some(",") => *position += 1,
+                    Some(")") => {}
+                    _ => return None,
+                }
+            }
+            *position += 1;
+            match name.as_str() {
+                "all" | "cfg" => Some(CfgExpr::All(predicates)),
+                "any" => Some(CfgExpr::Any(predicates)),
+                "not" if predicates.len() == 1 => {
+                    Some(CfgExpr::Not(Box::new(predicates.remove(0))))
+                }
+                _ => None,
+            }
+        }
+        _ => Some(CfgExpr::Name(name)),
+    }
+}
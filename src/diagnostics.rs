@@ -0,0 +1,1204 @@
+// getdoc - diagnostics.rs
+//
+// Turning rustc diagnostics into getdoc's displayable and consolidated forms.
+
+// --- Standard Library Imports ---
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+// --- External Crate Imports ---
+use serde::{Deserialize, Serialize};
+
+// --- Crate Imports ---
+use crate::error::GetdocError;
+use crate::util::StableHasher;
+
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct RustcDiagnosticData {
+    #[serde(default)]
+    code: Option<RustcErrorCode>,
+    level: String,
+    #[serde(default)]
+    message: String,
+    spans: Vec<RustcSpan>,
+    children: Vec<RustcDiagnosticData>,
+    rendered: Option<String>,
+}
+
+impl RustcDiagnosticData {
+    /// Points spans in doctests back at the file containing them. rustdoc names a doctest
+    /// `src/lib.rs - Foo (line 42)` and counts its lines from the code fence on line 42 of
+    /// `src/lib.rs`, so line 1 of the doctest is line 43 of the file.
+    pub(crate) fn attribute_doctest_spans(&mut self) {
+        for span in &mut self.spans {
+            if let Some((file, fence_line)) = doctest_origin(&span.file_name) {
+                span.file_name = file;
+                span.line_start += fence_line;
+                span.line_end += fence_line;
+            }
+        }
+        for child in &mut self.children {
+            child.attribute_doctest_spans();
+        }
+    }
+}
+
+/// The containing file and code-fence line of a doctest pseudo file name such as
+/// `src/lib.rs - Foo (line 42)`, or `None` for an ordinary path.
+fn doctest_origin(file_name: &str) -> Option<(String, usize)> {
+    let (name, line) = file_name.strip_suffix(')')?.rsplit_once(" (line ")?;
+    let (file, _item) = name.split_once(" - ")?;
+    Some((file.to_string(), line.parse().ok()?))
+}
+
+#[derive(Deserialize, Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+struct RustcErrorCode {
+    code: String,
+    explanation: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct RustcSpan {
+    file_name: String,
+    is_primary: bool,
+    line_start: usize,
+    #[serde(default)]
+    column_start: usize,
+    #[serde(default)]
+    line_end: usize,
+    #[serde(default)]
+    column_end: usize,
+    // Set on the spans of `help` children that carry a suggested fix.
+    #[serde(default)]
+    suggested_replacement: Option<String>,
+    #[serde(default)]
+    suggestion_applicability: Option<Applicability>,
+    // The source lines the span covers, as rustc read them.
+    #[serde(default)]
+    text: Vec<RustcSpanLine>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct RustcSpanLine {
+    text: String,
+    // 1-based character columns of the span on this line; the end is exclusive.
+    highlight_start: usize,
+    highlight_end: usize,
+}
+
+/// How confident rustc is that a suggested fix is correct.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Hash, Eq, PartialEq, Default)]
+#[serde(rename_all(serialize = "kebab-case"))]
+pub enum Applicability {
+    /// The fix is definitely what the user intended and can be applied automatically.
+    MachineApplicable,
+    /// The fix may be what the user intended, but needs review.
+    MaybeIncorrect,
+    /// The fix contains placeholders (e.g. `(...)`) that have to be filled in.
+    HasPlaceholders,
+    /// rustc did not say.
+    #[default]
+    Unspecified,
+}
+
+impl Applicability {
+    /// The name rustc's documentation uses, e.g. `machine-applicable`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Applicability::MachineApplicable => "machine-applicable",
+            Applicability::MaybeIncorrect => "maybe-incorrect",
+            Applicability::HasPlaceholders => "has-placeholders",
+            Applicability::Unspecified => "unspecified",
+        }
+    }
+}
+
+/// A fix rustc suggests for a diagnostic: replace the code at `location` with `replacement`.
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize)]
+pub struct Suggestion {
+    /// The help message the fix belongs to, e.g. "consider borrowing here".
+    pub message: String,
+    /// `file:line:column` where the replaced code starts.
+    pub location: String,
+    /// `file:line:column` where the replaced code ends; equal to `location` for an insertion.
+    pub end_location: String,
+    /// The code to put there (empty for a removal).
+    pub replacement: String,
+    /// Whether the fix is safe to apply without review.
+    pub applicability: Applicability,
+    /// The source lines the fix touches, as they are now (empty if rustc did not include them).
+    pub original_code: String,
+    /// The same lines with the fix applied (empty if rustc did not include them).
+    pub fixed_code: String,
+}
+
+/// Where a diagnostic points in the project: its primary span's file and line, or its
+/// first span's when none is primary. Displays as `src/lib.rs:42`, with ` (non-primary)`
+/// appended in the latter case, and sorts by file, then line and column.
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Location {
+    /// The span's file, relative to the project when it is inside it.
+    pub file: PathBuf,
+    /// 1-based line of the span's start.
+    pub line: usize,
+    /// 1-based column of the span's start, when known.
+    pub column: Option<usize>,
+    /// Whether this is the diagnostic's primary span.
+    pub is_primary: bool,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file.display(), self.line)?;
+        if !self.is_primary {
+            write!(f, " (non-primary)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reports list a diagnostic without any span (e.g. a check that failed to run) as `N/A`.
+pub(crate) fn display_location(location: Option<&Location>) -> String {
+    location.map_or_else(|| "N/A".to_string(), Location::to_string)
+}
+
+/// Serializes a location as it is displayed, so the JSON report keeps its string form.
+fn serialize_location<S: serde::Serializer>(
+    location: &Option<Location>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&display_location(location.as_ref()))
+}
+
+/// A diagnostic that referenced an implicated third-party file, as listed under that file.
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, Serialize)]
+pub struct DiagnosticOriginInfo {
+    /// Diagnostic level, e.g. `error` or `warning`.
+    pub level: String,
+    /// Error code, e.g. `E0277`, if rustc gave one.
+    pub code: Option<String>,
+    /// The diagnostic's location in the project.
+    #[serde(serialize_with = "serialize_location")]
+    pub originating_diagnostic_span_location: Option<Location>,
+    /// Description of the feature set it occurred under.
+    pub feature_set_desc: String,
+}
+
+/// One diagnostic from a single feature-set run, before [`consolidate_diagnostics`] merges
+/// identical ones across runs.
+#[derive(Debug)]
+pub struct DisplayableDiagnostic {
+    /// Diagnostic level, e.g. `error` or `warning` (`TOOL_ERROR` when a check failed to run).
+    pub level: String,
+    /// Error code, e.g. `E0277`, if rustc gave one.
+    pub code: Option<String>,
+    /// rustc's explanation of `code`, if any.
+    pub code_explanation: Option<String>,
+    /// rustc's one-line message, e.g. "mismatched types".
+    pub message: String,
+    /// The full message as rustc renders it on the terminal.
+    pub rendered: String,
+    /// The diagnostic's location, or `None` when it has no spans.
+    pub primary_location_of_diagnostic: Option<Location>,
+    /// Implicated third-party files as (canonical path, `file:line` or `file:first-last`
+    /// detail) pairs, nearby lines of a file merged into one range.
+    pub implicated_third_party_files_details: Vec<(PathBuf, String)>,
+    /// Fixes rustc suggests, from the diagnostic's `help` children.
+    pub suggestions: Vec<Suggestion>,
+    /// The package and target cargo was compiling when rustc emitted the diagnostic, e.g.
+    /// `home v0.5.12 (lib)`, when the message says.
+    pub producing_crate: Option<String>,
+}
+
+/// A key to uniquely identify a specific diagnostic instance.
+/// Uniqueness is determined by the error level, code, primary location,
+/// the full rendered message, and a signature of implicated third-party files.
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+struct DiagnosticInstanceKey {
+    level: String,
+    code: Option<String>,
+    primary_location: Option<Location>,
+    rendered_message: String,
+    implicated_files_signature: String, // A sorted, concatenated string of implicated file paths and their detail strings
+}
+
+/// Represents a diagnostic instance that has been consolidated.
+/// It holds the common information for the diagnostic and a set of all
+/// feature sets under which this exact instance occurred.
+#[derive(Debug, Clone, Serialize)]
+pub struct AggregatedDiagnosticInstance {
+    /// Identifies this diagnostic across runs and machines: a hash of its level, code,
+    /// primary location, rendered message, and implicated files, e.g. `3f2a9c0b7d1e4a65`.
+    pub id: String,
+    /// Diagnostic level, e.g. `error` or `warning` (`TOOL_ERROR` when a check failed to run).
+    pub level: String,
+    /// Error code, e.g. `E0277`, if rustc gave one. Explanations are collected separately
+    /// in [`Report::unique_explanations`](crate::Report::unique_explanations).
+    pub code: Option<String>,
+    /// rustc's one-line message, e.g. "mismatched types".
+    pub message: String,
+    /// The full message as rustc renders it on the terminal.
+    pub rendered_message: String,
+    /// The diagnostic's location, or `None` (`N/A` in reports) when it has no spans.
+    #[serde(serialize_with = "serialize_location")]
+    pub primary_location: Option<Location>,
+    /// Implicated third-party files as (canonical path, `file:line` or `file:first-last`
+    /// detail) pairs, nearby lines of a file merged into one range.
+    pub implicated_third_party_files_details: Vec<(PathBuf, String)>,
+    /// Fixes rustc suggests, from the diagnostic's `help` children.
+    pub suggestions: Vec<Suggestion>,
+    /// Every package and target cargo was compiling when rustc emitted the diagnostic, e.g.
+    /// `home v0.5.12 (lib)`, as far as the messages say. A module shared by a library and a
+    /// binary yields the same diagnostic from both.
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub producing_crates: BTreeSet<String>,
+    /// Feature sets that produced this exact diagnostic.
+    pub feature_set_descriptors: BTreeSet<String>,
+    /// Feature sets that were checked without producing it; see [`record_absent_feature_sets`].
+    pub absent_feature_set_descriptors: BTreeSet<String>,
+}
+
+impl AggregatedDiagnosticInstance {
+    /// Creates a new AggregatedDiagnosticInstance from a DisplayableDiagnostic and a feature set.
+    fn new(
+        diag_disp: &DisplayableDiagnostic,
+        feature_desc: &str,
+        workspace_root: Option<&Path>,
+    ) -> Self {
+        Self {
+            id: DiagnosticInstanceKey::of(diag_disp).stable_id(workspace_root),
+            level: diag_disp.level.clone(),
+            code: diag_disp.code.clone(),
+            message: diag_disp.message.clone(),
+            rendered_message: diag_disp.rendered.clone(),
+            primary_location: diag_disp.primary_location_of_diagnostic.clone(),
+            implicated_third_party_files_details: diag_disp
+                .implicated_third_party_files_details
+                .clone(),
+            suggestions: diag_disp.suggestions.clone(),
+            producing_crates: diag_disp.producing_crate.iter().cloned().collect(),
+            feature_set_descriptors: {
+                let mut set = BTreeSet::new();
+                set.insert(feature_desc.to_string());
+                set
+            },
+            absent_feature_set_descriptors: BTreeSet::new(),
+        }
+    }
+
+    /// Whether the diagnostic came out under every checked feature set (of two or more), so
+    /// no feature gate is involved.
+    pub fn is_feature_independent(&self) -> bool {
+        self.absent_feature_set_descriptors.is_empty() && self.feature_set_descriptors.len() > 1
+    }
+}
+
+impl DiagnosticInstanceKey {
+    fn of(diag_disp: &DisplayableDiagnostic) -> Self {
+        DiagnosticInstanceKey {
+            level: diag_disp.level.clone(),
+            code: diag_disp.code.clone(),
+            primary_location: diag_disp.primary_location_of_diagnostic.clone(),
+            rendered_message: diag_disp.rendered.clone(),
+            implicated_files_signature: diag_disp.get_implicated_files_signature(),
+        }
+    }
+
+    /// A [`StableHasher`] hash of the key's fields as 16 hex digits. Paths in them are
+    /// normalized first (see [`normalize_id_paths`]), so IDs can be stored and compared
+    /// later, on other machines and from other checkouts.
+    fn stable_id(&self, workspace_root: Option<&Path>) -> String {
+        let location = self
+            .primary_location
+            .as_ref()
+            .map_or_else(String::new, |location| {
+                format!(
+                    "{}:{}:{}:{}",
+                    normalize_id_paths(&location.file.to_string_lossy(), workspace_root),
+                    location.line,
+                    location.column.unwrap_or(0),
+                    location.is_primary
+                )
+            });
+        let implicated_files: Vec<String> = self
+            .implicated_files_signature
+            .split(';')
+            .map(|file| normalize_id_paths(file, workspace_root))
+            .collect();
+        let implicated_files = implicated_files.join(";");
+        let rendered_message = normalize_id_paths(&self.rendered_message, workspace_root);
+        let fields = [
+            self.level.as_str(),
+            self.code.as_deref().unwrap_or(""),
+            &location,
+            &rendered_message,
+            &implicated_files,
+        ];
+        let mut hasher = StableHasher::default();
+        // Each field ends in a NUL byte, so `("ab", "c")` and `("a", "bc")` differ.
+        for field in fields {
+            hasher.write(field.as_bytes());
+            hasher.write(&[0]);
+        }
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+/// A diagnostic that came out in some but not all repeated runs of one feature set
+/// (`--repeat`), e.g. from a nondeterministic proc macro.
+#[derive(Debug, Clone, Serialize)]
+pub struct FlakyDiagnostic {
+    /// The feature set that was run repeatedly.
+    pub feature_set_desc: String,
+    /// Diagnostic level, e.g. `error`.
+    pub level: String,
+    /// Error code, e.g. `E0277`, if rustc gave one.
+    pub code: Option<String>,
+    /// rustc's one-line message.
+    pub message: String,
+    /// The diagnostic's location, or `None` (`N/A` in reports) when it has no spans.
+    #[serde(serialize_with = "serialize_location")]
+    pub primary_location: Option<Location>,
+    /// In how many of the runs it appeared.
+    pub runs_seen: usize,
+    /// How many runs completed.
+    pub runs: usize,
+}
+
+/// Combines repeated runs of the feature set `feature_desc`: every diagnostic that appeared
+/// in any run, once, in the order first seen, and the ones among them that did not appear
+/// in every run.
+pub fn merge_repeated_runs(
+    runs: Vec<Vec<DisplayableDiagnostic>>,
+    feature_desc: &str,
+) -> (Vec<DisplayableDiagnostic>, Vec<FlakyDiagnostic>) {
+    let run_count = runs.len();
+    let mut runs_seen: HashMap<DiagnosticInstanceKey, usize> = HashMap::new();
+    let mut merged: Vec<(DiagnosticInstanceKey, DisplayableDiagnostic)> = Vec::new();
+    for run in runs {
+        let mut seen_in_run = HashSet::new();
+        for diag_disp in run {
+            let key = DiagnosticInstanceKey::of(&diag_disp);
+            if !seen_in_run.insert(key.clone()) {
+                continue;
+            }
+            let count = runs_seen.entry(key.clone()).or_default();
+            *count += 1;
+            if *count == 1 {
+                merged.push((key, diag_disp));
+            }
+        }
+    }
+    let flaky = merged
+        .iter()
+        .filter(|(key, _)| runs_seen[key] < run_count)
+        .map(|(key, diag_disp)| FlakyDiagnostic {
+            feature_set_desc: feature_desc.to_string(),
+            level: diag_disp.level.clone(),
+            code: diag_disp.code.clone(),
+            message: diag_disp.message.clone(),
+            primary_location: diag_disp.primary_location_of_diagnostic.clone(),
+            runs_seen: runs_seen[key],
+            runs: run_count,
+        })
+        .collect();
+    (
+        merged.into_iter().map(|(_, diag_disp)| diag_disp).collect(),
+        flaky,
+    )
+}
+
+/// IDs of the diagnostics a project suppresses in its manifest, listed as `ids` under
+/// `[package.metadata.getdoc.suppress]` or `[workspace.metadata.getdoc.suppress]`. A
+/// manifest that cannot be read suppresses nothing.
+pub(crate) fn manifest_suppressed_ids(manifest_path: &Path) -> Vec<String> {
+    let Some(manifest) = fs::read_to_string(manifest_path)
+        .ok()
+        .and_then(|content| content.parse::<toml::Table>().ok())
+    else {
+        return Vec::new();
+    };
+    ["package", "workspace"]
+        .into_iter()
+        .filter_map(|section| {
+            manifest
+                .get(section)?
+                .get("metadata")?
+                .get("getdoc")?
+                .get("suppress")?
+                .get("ids")?
+                .as_array()
+        })
+        .flatten()
+        .filter_map(|id| id.as_str().map(str::to_string))
+        .collect()
+}
+
+/// Removes the diagnostics whose ID is in `suppressed_ids`, and rebuilds what is implicated
+/// from the ones kept: a file (or line) stays implicated only where a kept diagnostic's
+/// details list it, and keeps the "Referenced by" entries of kept diagnostics, dropping
+/// those only removed ones left (see [`is_origin_of`]). Returns how many diagnostics were
+/// removed and the IDs that matched none.
+pub fn suppress_diagnostics(
+    diagnostics: &mut Vec<AggregatedDiagnosticInstance>,
+    file_referencers: &mut HashMap<PathBuf, HashSet<DiagnosticOriginInfo>>,
+    implicated_files: &mut HashMap<PathBuf, BTreeSet<usize>>,
+    suppressed_ids: &[String],
+) -> (usize, Vec<String>) {
+    let (suppressed, kept): (Vec<_>, Vec<_>) = std::mem::take(diagnostics)
+        .into_iter()
+        .partition(|diagnostic| suppressed_ids.contains(&diagnostic.id));
+    *diagnostics = kept;
+    let mut unmatched_ids: Vec<String> = suppressed_ids
+        .iter()
+        .filter(|id| !suppressed.iter().any(|diagnostic| &diagnostic.id == *id))
+        .cloned()
+        .collect();
+    unmatched_ids.sort();
+    unmatched_ids.dedup();
+    if suppressed.is_empty() {
+        return (0, unmatched_ids);
+    }
+
+    // Files stay implicated, at the lines they were implicated at, only where a kept
+    // diagnostic's details still list them.
+    let mut kept_ranges: HashMap<&Path, Vec<(usize, usize)>> = HashMap::new();
+    for diagnostic in diagnostics.iter() {
+        for (path, detail) in &diagnostic.implicated_third_party_files_details {
+            if let Some(range) = detail_line_range(detail) {
+                kept_ranges.entry(path.as_path()).or_default().push(range);
+            }
+        }
+    }
+    implicated_files.retain(|file, lines| {
+        let Some(ranges) = kept_ranges.get(file.as_path()) else {
+            return false;
+        };
+        lines.retain(|line| {
+            ranges
+                .iter()
+                .any(|(first, last)| (first..=last).contains(&line))
+        });
+        !lines.is_empty()
+    });
+    file_referencers.retain(|file, _| implicated_files.contains_key(file));
+    for origins in file_referencers.values_mut() {
+        origins.retain(|origin| {
+            diagnostics
+                .iter()
+                .any(|diagnostic| is_origin_of(origin, diagnostic))
+                || !suppressed
+                    .iter()
+                    .any(|diagnostic| is_origin_of(origin, diagnostic))
+        });
+    }
+    file_referencers.retain(|_, origins| !origins.is_empty());
+    (suppressed.len(), unmatched_ids)
+}
+
+/// Whether `origin` is `diagnostic`'s own "Referenced by" entry, or one of its notes' and help
+/// messages', which its rendered text points at.
+fn is_origin_of(origin: &DiagnosticOriginInfo, diagnostic: &AggregatedDiagnosticInstance) -> bool {
+    if !diagnostic
+        .feature_set_descriptors
+        .contains(&origin.feature_set_desc)
+    {
+        return false;
+    }
+    let location = origin.originating_diagnostic_span_location.as_ref();
+    let is_own = origin.level == diagnostic.level
+        && origin.code == diagnostic.code
+        && location == diagnostic.primary_location.as_ref();
+    let is_child = (origin.level == "note" || origin.level == "help")
+        && location.is_some_and(|location| {
+            let path = normalize_cargo_home_paths(&location.file.to_string_lossy());
+            diagnostic
+                .rendered_message
+                .contains(&format!("{}:{}:", path, location.line))
+        });
+    is_own || is_child
+}
+
+/// The lines an implicated-file detail covers: `mod.rs:210` or `mod.rs:210-212`.
+fn detail_line_range(detail: &str) -> Option<(usize, usize)> {
+    let (_, lines) = detail.rsplit_once(':')?;
+    match lines.split_once('-') {
+        Some((first, last)) => Some((first.parse().ok()?, last.parse().ok()?)),
+        None => {
+            let line = lines.parse().ok()?;
+            Some((line, line))
+        }
+    }
+}
+
+/// How many diagnostics the checks reported, before consolidation (a diagnostic reported
+/// under three feature sets counts three times), and how many of them point into
+/// third-party files. `TOOL_ERROR`s (build-script failures, checks that could not run) are
+/// not compiler diagnostics and are not counted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DiagnosticCounts {
+    /// Errors and warnings (and notes kept with `--include-notes-as-diagnostics`).
+    pub reported: usize,
+    /// Those implicating at least one third-party file.
+    pub implicating_third_party: usize,
+}
+
+impl DiagnosticCounts {
+    /// Counts the diagnostics of every checked feature set.
+    pub fn of(all_displayable_diagnostics: &[(String, Vec<DisplayableDiagnostic>)]) -> Self {
+        let mut counts = DiagnosticCounts::default();
+        for diag_disp in all_displayable_diagnostics
+            .iter()
+            .flat_map(|(_, diagnostics_for_run)| diagnostics_for_run)
+            .filter(|diag_disp| diag_disp.level != "TOOL_ERROR")
+        {
+            counts.reported += 1;
+            if !diag_disp.implicated_third_party_files_details.is_empty() {
+                counts.implicating_third_party += 1;
+            }
+        }
+        counts
+    }
+}
+
+impl DisplayableDiagnostic {
+    /// Creates a stable string signature of implicated third-party files for keying.
+    /// The signature is a sorted list of "canonicalized_path_string:detail_location_string" strings, joined by ';'.
+    /// Nearby lines are already merged into one detail, so diagnostics implicating the
+    /// same lines share a signature.
+    fn get_implicated_files_signature(&self) -> String {
+        let mut signature_parts: Vec<String> = self
+            .implicated_third_party_files_details
+            .iter()
+            .map(|(path, detail_loc)| format!("{}:{}", path.to_string_lossy(), detail_loc))
+            .collect();
+        // Sorting here again for stability even if the source Vec wasn't pre-sorted,
+        // though pre-sorting in process_single_diagnostic_data is preferred.
+        signature_parts.sort();
+        signature_parts.join(";")
+    }
+}
+
+/// Fills in each diagnostic's `absent_feature_set_descriptors`: the feature sets in
+/// `checked_feature_sets` (those whose check ran to completion) it did not occur under.
+pub fn record_absent_feature_sets(
+    diagnostics: &mut [AggregatedDiagnosticInstance],
+    checked_feature_sets: &[String],
+) {
+    for diagnostic in diagnostics {
+        diagnostic.absent_feature_set_descriptors = checked_feature_sets
+            .iter()
+            .filter(|feature_set| !diagnostic.feature_set_descriptors.contains(*feature_set))
+            .cloned()
+            .collect();
+    }
+}
+
+/// Merges identical diagnostics reported under several feature sets into one instance each
+/// (sorted by location, code, and message), and collects the error-code explanations they carry.
+pub fn consolidate_diagnostics(
+    all_displayable_diagnostics: &[(String, Vec<DisplayableDiagnostic>)],
+) -> (Vec<AggregatedDiagnosticInstance>, HashMap<String, String>) {
+    let mut consolidated_diagnostic_instances: HashMap<
+        DiagnosticInstanceKey,
+        AggregatedDiagnosticInstance,
+    > = HashMap::new();
+    let mut unique_explanations: HashMap<String, String> = HashMap::new();
+    let workspace_root = workspace_root();
+
+    for (feature_desc, diagnostics_for_run) in all_displayable_diagnostics {
+        for diag_disp in diagnostics_for_run {
+            if let (Some(code), Some(explanation)) = (&diag_disp.code, &diag_disp.code_explanation)
+                && !explanation.trim().is_empty()
+            {
+                unique_explanations
+                    .entry(code.clone())
+                    .or_insert_with(|| explanation.clone());
+            }
+
+            let key = DiagnosticInstanceKey::of(diag_disp);
+
+            let agg_diag_entry =
+                consolidated_diagnostic_instances
+                    .entry(key)
+                    .or_insert_with(|| {
+                        AggregatedDiagnosticInstance::new(
+                            diag_disp,
+                            feature_desc,
+                            workspace_root.as_deref(),
+                        )
+                    });
+
+            agg_diag_entry
+                .feature_set_descriptors
+                .insert(feature_desc.clone());
+            agg_diag_entry
+                .producing_crates
+                .extend(diag_disp.producing_crate.iter().cloned());
+        }
+    }
+
+    let mut sorted_consolidated_diagnostics: Vec<AggregatedDiagnosticInstance> =
+        consolidated_diagnostic_instances.into_values().collect();
+    sorted_consolidated_diagnostics.sort_by(|a, b| {
+        a.primary_location
+            .cmp(&b.primary_location)
+            .then_with(|| a.code.cmp(&b.code))
+            .then_with(|| a.rendered_message.cmp(&b.rendered_message))
+    });
+
+    (sorted_consolidated_diagnostics, unique_explanations)
+}
+
+/// Orders for the consolidated diagnostics (`--sort`).
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DiagnosticSort {
+    /// By primary location, then code and message.
+    #[default]
+    Location,
+    /// By error code; diagnostics without one last.
+    Code,
+    /// Errors (and failed checks) first, then warnings, then notes.
+    Level,
+    /// Those affecting the most feature sets first.
+    Count,
+}
+
+/// Re-sorts diagnostics from [`consolidate_diagnostics`] by `sort`. Ties keep the
+/// location order.
+pub fn sort_diagnostics(diagnostics: &mut [AggregatedDiagnosticInstance], sort: DiagnosticSort) {
+    match sort {
+        DiagnosticSort::Location => {}
+        DiagnosticSort::Code => diagnostics.sort_by(|a, b| match (&a.code, &b.code) {
+            (Some(a), Some(b)) => a.cmp(b),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        }),
+        DiagnosticSort::Level => {
+            diagnostics.sort_by_key(|diagnostic| match diagnostic.level.as_str() {
+                "error" | "TOOL_ERROR" => 0,
+                "warning" => 1,
+                _ => 2,
+            })
+        }
+        DiagnosticSort::Count => diagnostics
+            .sort_by_key(|diagnostic| std::cmp::Reverse(diagnostic.feature_set_descriptors.len())),
+    }
+}
+
+/// Implicated lines of one file at most this far apart are merged into one range.
+const LINE_RANGE_GAP: usize = 2;
+
+/// The `file:line` details of a diagnostic's implicated files, in path and line order, with
+/// nearby lines of a file (at most [`LINE_RANGE_GAP`] apart) merged into one range, e.g.
+/// `mod.rs:210-212` for spans on lines 210, 211, and 212.
+fn implicated_file_details(
+    lines_by_file: &BTreeMap<PathBuf, BTreeSet<usize>>,
+) -> Vec<(PathBuf, String)> {
+    let mut details = Vec::new();
+    for (path, lines) in lines_by_file {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for &line in lines {
+            match ranges.last_mut() {
+                Some((_, last)) if line - *last <= LINE_RANGE_GAP => *last = line,
+                _ => ranges.push((line, line)),
+            }
+        }
+        details.extend(ranges.into_iter().map(|(first, last)| {
+            let detail = if first == last {
+                format!("{}:{}", file_name, first)
+            } else {
+                format!("{}:{}-{}", file_name, first, last)
+            };
+            (path.clone(), detail)
+        }));
+    }
+    details
+}
+
+/// Collects what `diag_data` and its children implicate and, for errors and warnings (and,
+/// with `keep_notes`, notes whose own spans implicate third-party files), a displayable
+/// diagnostic. Children are shown as part of their parent's rendered text, so `keep_notes`
+/// applies to `diag_data` itself and not to its children.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn process_single_diagnostic_data(
+    diag_data: &RustcDiagnosticData,
+    displayable_diagnostics: &mut Vec<DisplayableDiagnostic>,
+    implicated_files_overall_run: &mut HashMap<PathBuf, BTreeSet<usize>>,
+    referencers_for_run: &mut HashMap<PathBuf, HashSet<DiagnosticOriginInfo>>,
+    current_dir: &Path,
+    cargo_home_dir: &Option<PathBuf>,
+    feature_desc: &str,
+    keep_notes: bool,
+) {
+    let mut implicated_lines_by_file: BTreeMap<PathBuf, BTreeSet<usize>> = BTreeMap::new();
+    let primary_location = diag_data
+        .spans
+        .iter()
+        .find(|span| span.is_primary)
+        .or(diag_data.spans.first())
+        .map(|span| Location {
+            file: span_display_path(span, current_dir),
+            line: span.line_start,
+            // Hand-written and older JSON can leave columns out (read as 0).
+            column: (span.column_start > 0).then_some(span.column_start),
+            is_primary: span.is_primary,
+        });
+
+    for span in &diag_data.spans {
+        let path_obj = PathBuf::from(&span.file_name);
+        let absolute_path = if path_obj.is_absolute() {
+            path_obj.clone()
+        } else {
+            current_dir.join(&path_obj)
+        };
+
+        // A registry file that is not on disk (e.g. `~/.cargo` was pruned since the check,
+        // or the output came from another machine) is still implicated, so it can be fetched
+        // or reported as missing.
+        let canonical_path = fs::canonicalize(&absolute_path).ok().or_else(|| {
+            cargo_home_dir
+                .as_ref()
+                .is_some_and(|ch| absolute_path.starts_with(ch.join("registry").join("src")))
+                .then(|| absolute_path.clone())
+        });
+        if let Some(canonical_path) = canonical_path {
+            let is_in_cargo_registry = cargo_home_dir
+                .as_ref()
+                .is_some_and(|ch| canonical_path.starts_with(ch.join("registry").join("src")));
+            let is_in_cargo_git = cargo_home_dir
+                .as_ref()
+                .is_some_and(|ch| canonical_path.starts_with(ch.join("git").join("checkouts")));
+            let is_third_party = !canonical_path.starts_with(current_dir)
+                && (is_in_cargo_registry || is_in_cargo_git);
+            // Build-script output usually lives under the project's own `target/` directory.
+            let is_build_script_output = build_script_output_crate(&canonical_path).is_some();
+
+            if (is_third_party || is_build_script_output)
+                && (canonical_path.is_file() || !canonical_path.exists())
+            {
+                implicated_lines_by_file
+                    .entry(canonical_path.clone())
+                    .or_default()
+                    .insert(span.line_start);
+                implicated_files_overall_run
+                    .entry(canonical_path.clone())
+                    .or_default()
+                    .insert(span.line_start);
+
+                let origin_info = DiagnosticOriginInfo {
+                    level: diag_data.level.clone(),
+                    code: diag_data.code.as_ref().map(|c| c.code.clone()),
+                    originating_diagnostic_span_location: primary_location.clone(),
+                    feature_set_desc: feature_desc.to_string(),
+                };
+                referencers_for_run
+                    .entry(canonical_path)
+                    .or_default()
+                    .insert(origin_info);
+            }
+        }
+    }
+    let current_diag_implicated_tp_files_details =
+        implicated_file_details(&implicated_lines_by_file);
+
+    // A standalone note often says where an opaque error really comes from, e.g. "this error
+    // originates in the macro `serde::forward_to_deserialize_any`".
+    let is_kept_note = keep_notes
+        && diag_data.level == "note"
+        && !current_diag_implicated_tp_files_details.is_empty();
+    if (diag_data.level == "error" || diag_data.level == "warning" || is_kept_note)
+        && let Some(rendered) = &diag_data.rendered
+        && !rendered.trim().is_empty()
+    {
+        let item_code = diag_data.code.as_ref().map(|c| c.code.clone());
+        let item_code_explanation = diag_data.code.as_ref().and_then(|c| c.explanation.clone());
+
+        displayable_diagnostics.push(DisplayableDiagnostic {
+            level: diag_data.level.clone(),
+            code: item_code,
+            code_explanation: item_code_explanation,
+            message: diag_data.message.clone(),
+            // Cargo-home paths are normalized once escape sequences have been dealt with.
+            rendered: rendered.trim_end().to_string(),
+            implicated_third_party_files_details: current_diag_implicated_tp_files_details,
+            suggestions: collect_suggestions(diag_data, current_dir),
+            primary_location_of_diagnostic: primary_location.clone(),
+            producing_crate: None,
+        });
+    }
+
+    for child in &diag_data.children {
+        process_single_diagnostic_data(
+            child,
+            displayable_diagnostics,
+            implicated_files_overall_run,
+            referencers_for_run,
+            current_dir,
+            cargo_home_dir,
+            feature_desc,
+            false,
+        );
+    }
+}
+
+/// Removes terminal escape sequences (colors and the like) from `text`, as found in messages
+/// rendered with `--message-format=json-diagnostic-rendered-ansi`: CSI sequences
+/// (`ESC [ ... m`), OSC sequences (`ESC ] ... BEL`, e.g. hyperlinks), and two-byte escapes.
+pub(crate) fn strip_ansi_escapes(text: &str) -> String {
+    ansi_segments(text)
+        .into_iter()
+        .filter(|(is_escape, _)| !is_escape)
+        .map(|(_, segment)| segment)
+        .collect()
+}
+
+/// `text` split into terminal escape sequences (`true`) and the text between them (`false`).
+fn ansi_segments(text: &str) -> Vec<(bool, &str)> {
+    let mut segments = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let (is_escape, length) = match rest.find('\u{1b}') {
+            Some(0) => (true, escape_sequence_length(rest)),
+            Some(at) => (false, at),
+            None => (false, rest.len()),
+        };
+        segments.push((is_escape, &rest[..length]));
+        rest = &rest[length..];
+    }
+    segments
+}
+
+/// The length in bytes of the escape sequence `text` starts with (at its `ESC`).
+fn escape_sequence_length(text: &str) -> usize {
+    let mut chars = text.char_indices().skip(1);
+    let end = match chars.next() {
+        // Parameters and intermediates, up to a final byte in `@`..=`~`.
+        Some((_, '[')) => chars
+            .find(|(_, c)| ('@'..='~').contains(c))
+            .map(|(at, c)| at + c.len_utf8()),
+        // Terminated by BEL or by the string terminator `ESC \`.
+        Some((_, ']')) => chars.find_map(|(at, c)| match c {
+            '\u{7}' => Some(at + 1),
+            '\u{1b}' if text[at + 1..].starts_with('\\') => Some(at + 2),
+            _ => None,
+        }),
+        Some((at, c)) => Some(at + c.len_utf8()),
+        None => Some(text.len()),
+    };
+    end.unwrap_or(text.len())
+}
+
+/// `text` with every path into a cargo home's registry sources or git checkouts written the
+/// same way on every machine: the cargo home as `$CARGO_HOME` and the registry index's or
+/// repository's hash as `-*`, so that
+/// `/home/ci/.cargo/registry/src/index.crates.io-6f17d22bba15001f/foo-1.2.3/src/lib.rs:10`
+/// becomes `$CARGO_HOME/registry/src/index.crates.io-*/foo-1.2.3/src/lib.rs:10`. Crate
+/// versions are kept. Paths are looked for between terminal escape sequences, which are
+/// left as they are.
+pub fn normalize_cargo_home_paths(text: &str) -> String {
+    ansi_segments(text)
+        .into_iter()
+        .map(|(is_escape, segment)| {
+            if is_escape {
+                segment.to_string()
+            } else {
+                normalize_plain_cargo_home_paths(segment)
+            }
+        })
+        .collect()
+}
+
+/// [`normalize_cargo_home_paths`] for text without escape sequences.
+fn normalize_plain_cargo_home_paths(text: &str) -> String {
+    const MARKERS: [&str; 4] = [
+        "/registry/src/",
+        "\\registry\\src\\",
+        "/git/checkouts/",
+        "\\git\\checkouts\\",
+    ];
+    text.split_inclusive(char::is_whitespace)
+        .map(|word| {
+            let Some((marker_at, marker)) = MARKERS
+                .iter()
+                .filter_map(|marker| word.find(marker).map(|at| (at, *marker)))
+                .min()
+            else {
+                return word.to_string();
+            };
+            // The path starts after any quote or bracket it is wrapped in.
+            let path_start = word[..marker_at]
+                .rfind(['`', '\'', '"', '(', '[', '<'])
+                .map_or(0, |at| at + 1);
+            let cargo_home = &word[path_start..marker_at];
+            let is_absolute =
+                cargo_home.starts_with(['/', '~']) || cargo_home.get(1..2) == Some(":");
+            if !is_absolute {
+                return word.to_string();
+            }
+            let rest = &word[marker_at + marker.len()..];
+            let hashed_dir_end = rest.find(['/', '\\']).map_or(rest.len(), |at| at + 1);
+            format!(
+                "{}$CARGO_HOME{}{}{}",
+                &word[..path_start],
+                marker,
+                strip_version(&rest[..hashed_dir_end]),
+                &rest[hashed_dir_end..]
+            )
+        })
+        .collect()
+}
+
+/// The root of the workspace the current directory belongs to: the nearest directory up
+/// from it whose `Cargo.toml` has a `[workspace]` table, else the nearest with a `Cargo.toml`.
+fn workspace_root() -> Option<PathBuf> {
+    let current_dir = fs::canonicalize(".").ok()?;
+    let mut package_root = None;
+    for dir in current_dir.ancestors() {
+        let Ok(manifest) = fs::read_to_string(dir.join("Cargo.toml")) else {
+            continue;
+        };
+        if manifest
+            .parse::<toml::Table>()
+            .is_ok_and(|table| table.contains_key("workspace"))
+        {
+            return Some(dir.to_path_buf());
+        }
+        package_root.get_or_insert_with(|| dir.to_path_buf());
+    }
+    package_root
+}
+
+/// `text` with its paths written the same way from any checkout, for diagnostic IDs: paths
+/// inside `workspace_root` relative to it, a build script's output directory
+/// (`.../build/<crate>-<hash>/out`) as `$OUT_DIR`, and cargo-home paths as by
+/// [`normalize_cargo_home_paths`].
+fn normalize_id_paths(text: &str, workspace_root: Option<&Path>) -> String {
+    let mut text = text.to_string();
+    if let Some(root) = workspace_root {
+        for separator in ['/', '\\'] {
+            text = text.replace(&format!("{}{}", root.display(), separator), "");
+        }
+    }
+    let text: String = text
+        .split_inclusive(char::is_whitespace)
+        .map(normalize_out_dir_path)
+        .collect();
+    normalize_cargo_home_paths(&text)
+}
+
+/// `word` with a leading path into a build script's output directory, such as
+/// `/work/target/debug/build/foo-0123456789abcdef/out/gen.rs`, written as `$OUT_DIR/gen.rs`.
+fn normalize_out_dir_path(word: &str) -> String {
+    let markers = word
+        .match_indices("/build/")
+        .chain(word.match_indices("\\build\\"));
+    for (marker_at, marker) in markers {
+        let rest = &word[marker_at + marker.len()..];
+        let Some(crate_dir_end) = rest.find(['/', '\\']) else {
+            continue;
+        };
+        // As in `build_script_output_crate`: `<crate>-<hash>/out`.
+        let Some(tail) = rest[crate_dir_end + 1..].strip_prefix("out") else {
+            continue;
+        };
+        let ends_path = tail.is_empty()
+            || tail.starts_with(['/', '\\', ':'])
+            || tail.starts_with(char::is_whitespace);
+        if !rest[..crate_dir_end].contains('-') || !ends_path {
+            continue;
+        }
+        // The path starts after any quote or bracket it is wrapped in.
+        let path_start = word[..marker_at]
+            .rfind(['`', '\'', '"', '(', '[', '<'])
+            .map_or(0, |at| at + 1);
+        return format!("{}$OUT_DIR{}", &word[..path_start], tail);
+    }
+    word.to_string()
+}
+
+/// A path component (with any trailing separator) with a `-<version>` or `-<hash>` suffix,
+/// as in `serde-1.0.200` or `index.crates.io-6f17d22bba15001f`, replaced by `-*`.
+pub(crate) fn strip_version(component: &str) -> String {
+    let (name, separator) = match component.strip_suffix(['/', '\\']) {
+        Some(name) => (name, &component[name.len()..]),
+        None => (component, ""),
+    };
+    let Some((stem, suffix)) = name.rsplit_once('-') else {
+        return component.to_string();
+    };
+    let is_version = suffix.starts_with(|c: char| c.is_ascii_digit()) && suffix.contains('.');
+    let is_hash = suffix.len() == 16 && suffix.chars().all(|c| c.is_ascii_hexdigit());
+    if is_version || is_hash {
+        format!("{}-*{}", stem, separator)
+    } else {
+        component.to_string()
+    }
+}
+
+/// A span's file as shown in the report: relative to the project when it lies inside it.
+fn span_display_path(span: &RustcSpan, current_dir: &Path) -> PathBuf {
+    let path_obj = PathBuf::from(&span.file_name);
+    if path_obj.is_absolute() {
+        path_obj
+            .strip_prefix(current_dir)
+            .unwrap_or(&path_obj)
+            .to_path_buf()
+    } else {
+        path_obj
+    }
+}
+
+/// Collects the fixes suggested anywhere in a diagnostic: rustc attaches them to the spans
+/// of its `help` children, each span replacing one piece of code.
+fn collect_suggestions(diag_data: &RustcDiagnosticData, current_dir: &Path) -> Vec<Suggestion> {
+    let mut suggestions = Vec::new();
+    for span in &diag_data.spans {
+        if let Some(replacement) = &span.suggested_replacement {
+            let display_path = span_display_path(span, current_dir);
+            let (original_code, fixed_code) = apply_to_span_text(span, replacement);
+            let suggestion = Suggestion {
+                message: diag_data.message.clone(),
+                location: format!(
+                    "{}:{}:{}",
+                    display_path.display(),
+                    span.line_start,
+                    span.column_start
+                ),
+                end_location: format!(
+                    "{}:{}:{}",
+                    display_path.display(),
+                    span.line_end,
+                    span.column_end
+                ),
+                replacement: replacement.clone(),
+                applicability: span.suggestion_applicability.unwrap_or_default(),
+                original_code,
+                fixed_code,
+            };
+            if !suggestions.contains(&suggestion) {
+                suggestions.push(suggestion);
+            }
+        }
+    }
+    for child in &diag_data.children {
+        for suggestion in collect_suggestions(child, current_dir) {
+            if !suggestions.contains(&suggestion) {
+                suggestions.push(suggestion);
+            }
+        }
+    }
+    suggestions
+}
+
+/// Returns the source lines a suggestion span covers, before and after putting `replacement`
+/// in place of the highlighted code. Both are empty when rustc sent no source text.
+fn apply_to_span_text(span: &RustcSpan, replacement: &str) -> (String, String) {
+    let (Some(first), Some(last)) = (span.text.first(), span.text.last()) else {
+        return (String::new(), String::new());
+    };
+    let original = span
+        .text
+        .iter()
+        .map(|line| line.text.as_str())
+        .collect::<Vec<&str>>()
+        .join("\n");
+    let prefix: String = first
+        .text
+        .chars()
+        .take(first.highlight_start.saturating_sub(1))
+        .collect();
+    let suffix: String = last
+        .text
+        .chars()
+        .skip(last.highlight_end.saturating_sub(1))
+        .collect();
+    (original, format!("{}{}{}", prefix, replacement, suffix))
+}
+
+/// For a file generated by a build script (`<target>/<profile>/build/<crate>-<hash>/out/...`),
+/// the name of the crate whose build script wrote it.
+pub(crate) fn build_script_output_crate(path: &Path) -> Option<String> {
+    let components: Vec<&std::ffi::OsStr> = path
+        .components()
+        .map(|component| component.as_os_str())
+        .collect();
+    components.windows(3).rev().find_map(|window| {
+        let [build, crate_dir, out] = window else {
+            return None;
+        };
+        if *build != "build" || *out != "out" {
+            return None;
+        }
+        let crate_dir = crate_dir.to_str()?;
+        let (crate_name, _hash) = crate_dir.rsplit_once('-')?;
+        Some(crate_name.to_string())
+    })
+}
+
+/// For a file in a registry's unpacked sources (`<cargo home>/registry/src/<index>/<crate>-<version>/...`),
+/// the crate's name and version.
+pub fn registry_crate_version(path: &Path) -> Option<(String, String)> {
+    let components: Vec<&std::ffi::OsStr> = path
+        .components()
+        .map(|component| component.as_os_str())
+        .collect();
+    components.windows(4).find_map(|window| {
+        let [registry, src, _index, crate_dir] = window else {
+            return None;
+        };
+        if *registry != "registry" || *src != "src" {
+            return None;
+        }
+        let crate_dir = crate_dir.to_str()?;
+        // Names may contain `-` too: the version starts at the first `-<major>.<minor>`.
+        crate_dir.match_indices('-').find_map(|(at, _)| {
+            let version = &crate_dir[at + 1..];
+            let (major, rest) = version.split_once('.')?;
+            let is_version = !major.is_empty()
+                && major.chars().all(|c| c.is_ascii_digit())
+                && rest.starts_with(|c: char| c.is_ascii_digit());
+            (is_version && at > 0).then(|| (crate_dir[..at].to_string(), version.to_string()))
+        })
+    })
+}
+
+// --- Error Code Explanations ---
+
+/// Normalizes a user-supplied error code: `e308`, `0308`, and `E0308` all become `E0308`.
+pub(crate) fn normalize_error_code(code: &str) -> String {
+    let code = code.trim();
+    let digits = code.trim_start_matches(['E', 'e']);
+    if (1..=4).contains(&digits.len()) && digits.chars().all(|c| c.is_ascii_digit()) {
+        format!("E{:0>4}", digits)
+    } else {
+        code.to_string()
+    }
+}
+
+/// Fetches the explanation of `code` from `rustc --explain`, using `$RUSTC` when set as
+/// cargo does.
+pub(crate) fn explain_error_code(code: &str) -> Result<String, GetdocError> {
+    let explain_error = |message: String| GetdocError::ExplainErrorCode {
+        code: code.to_string(),
+        message,
+    };
+    let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = Command::new(rustc)
+        .arg("--explain")
+        .arg(code)
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|e| explain_error(format!("could not run `rustc`: {}", e)))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(explain_error(
+            stderr.trim().trim_start_matches("error: ").to_string(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,465 @@
+// getdoc - diff.rs
+//
+// `getdoc diff OLD NEW`: what changed between two JSON reports (`--format json`), e.g. from
+// before and after a dependency bump. Diagnostics are matched by level, code, message, and
+// file; line numbers, dependency versions, and registry hashes in paths are ignored, so a
+// diagnostic that merely moved still matches. Differences in the feature-set matrices of the
+// two runs are reported separately, since they add and remove diagnostics on their own.
+
+// --- Standard Library Imports ---
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// --- External Crate Imports ---
+use serde::Deserialize;
+
+// --- Crate Imports ---
+use crate::diagnostics::strip_version;
+use crate::error::GetdocError;
+use crate::progress::info;
+use crate::report::write_file_atomically;
+use crate::util::quoted_list;
+
+/// Arguments of `getdoc diff`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct DiffArgs {
+    /// The earlier JSON report (written with `--format json`).
+    pub old: PathBuf,
+    /// The later JSON report.
+    pub new: PathBuf,
+    /// Write the comparison to this Markdown file instead of printing it.
+    #[clap(long, short, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+    /// Overwrite `--output` if it already exists.
+    #[clap(long)]
+    pub force: bool,
+}
+
+/// The parts of a saved JSON report that are compared.
+#[derive(Deserialize)]
+struct SavedReport {
+    diagnostics: Vec<SavedDiagnostic>,
+    #[serde(default)]
+    timings: Vec<SavedTiming>,
+    #[serde(default)]
+    equivalent_feature_sets: Vec<SavedEquivalentFeatureSets>,
+}
+
+#[derive(Deserialize)]
+struct SavedDiagnostic {
+    /// Missing from reports written before diagnostics had IDs.
+    #[serde(default)]
+    id: Option<String>,
+    level: String,
+    code: Option<String>,
+    message: String,
+    #[serde(default)]
+    primary_location: String,
+    #[serde(default)]
+    feature_set_descriptors: BTreeSet<String>,
+}
+
+#[derive(Deserialize)]
+struct SavedTiming {
+    feature_set: String,
+}
+
+#[derive(Deserialize)]
+struct SavedEquivalentFeatureSets {
+    aliases: Vec<String>,
+}
+
+/// A diagnostic of one of the compared reports.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DiffedDiagnostic {
+    /// Diagnostic level, e.g. `error`.
+    pub level: String,
+    /// Error code, e.g. `E0277`, if rustc gave one.
+    pub code: Option<String>,
+    /// rustc's one-line message, as in the report it comes from.
+    pub message: String,
+    /// Where it was reported, e.g. `src/lib.rs:42`.
+    pub location: String,
+    /// Feature sets it occurred under.
+    pub feature_sets: BTreeSet<String>,
+    /// The diagnostic's ID in the report, if the report has IDs.
+    pub id: Option<String>,
+}
+
+/// A diagnostic found in both reports, but under different feature sets.
+#[derive(Debug, Clone)]
+pub struct FeatureSetChange {
+    /// The diagnostic as the new report has it.
+    pub diagnostic: DiffedDiagnostic,
+    /// Feature sets it now also occurs under.
+    pub added: BTreeSet<String>,
+    /// Feature sets it no longer occurs under.
+    pub removed: BTreeSet<String>,
+}
+
+/// The differences between two reports.
+#[derive(Debug, Clone, Default)]
+pub struct ReportDiff {
+    /// Diagnostics only the new report has.
+    pub new: Vec<DiffedDiagnostic>,
+    /// Diagnostics only the old report has.
+    pub resolved: Vec<DiffedDiagnostic>,
+    /// Diagnostics in both reports whose feature sets differ.
+    pub changed_feature_sets: Vec<FeatureSetChange>,
+    /// Number of diagnostics the same in both reports.
+    pub unchanged: usize,
+    /// Feature sets only the new run checked (e.g. for a feature added since).
+    pub feature_sets_added: BTreeSet<String>,
+    /// Feature sets only the old run checked.
+    pub feature_sets_removed: BTreeSet<String>,
+}
+
+impl ReportDiff {
+    /// One line for a pull-request comment, e.g. `getdoc diff: 2 new, 1 resolved, 0 with
+    /// changed feature sets, 14 unchanged.`
+    pub fn summary_line(&self) -> String {
+        let mut line = format!(
+            "getdoc diff: {} new, {} resolved, {} with changed feature sets, {} unchanged",
+            self.new.len(),
+            self.resolved.len(),
+            self.changed_feature_sets.len(),
+            self.unchanged
+        );
+        if !self.feature_sets_added.is_empty() || !self.feature_sets_removed.is_empty() {
+            line.push_str(&format!(
+                " (feature matrix: {} set(s) added, {} removed)",
+                self.feature_sets_added.len(),
+                self.feature_sets_removed.len()
+            ));
+        }
+        line.push('.');
+        line
+    }
+}
+
+/// Compares the JSON reports at `old_path` and `new_path`.
+pub fn diff_reports(old_path: &Path, new_path: &Path) -> Result<ReportDiff, GetdocError> {
+    let old_report = read_saved_report(old_path)?;
+    let new_report = read_saved_report(new_path)?;
+    let old_matrix = checked_feature_sets(&old_report);
+    let new_matrix = checked_feature_sets(&new_report);
+    let old_diagnostics = diagnostics_by_key(old_report.diagnostics);
+    let mut new_diagnostics = diagnostics_by_key(new_report.diagnostics);
+
+    let mut diff = ReportDiff {
+        feature_sets_added: new_matrix.difference(&old_matrix).cloned().collect(),
+        feature_sets_removed: old_matrix.difference(&new_matrix).cloned().collect(),
+        ..ReportDiff::default()
+    };
+    for (key, mut old_instances) in old_diagnostics {
+        let mut new_instances = new_diagnostics.remove(&key).unwrap_or_default();
+        // Instances with the same ID are the same diagnostic, even where one message
+        // occurs at several places in a file.
+        old_instances.retain(|old_instance| {
+            let same_id = old_instance.id.as_ref().and_then(|id| {
+                new_instances
+                    .iter()
+                    .position(|new_instance| new_instance.id.as_ref() == Some(id))
+            });
+            if let Some(at) = same_id {
+                let new_instance = new_instances.remove(at);
+                if new_instance.feature_sets == old_instance.feature_sets {
+                    diff.unchanged += 1;
+                } else {
+                    diff.changed_feature_sets
+                        .push(feature_set_change(old_instance, new_instance));
+                }
+            }
+            same_id.is_none()
+        });
+        // Then instances under the same feature sets pair up; the rest in report order.
+        old_instances.retain(|old_instance| {
+            let same = new_instances
+                .iter()
+                .position(|new_instance| new_instance.feature_sets == old_instance.feature_sets);
+            if let Some(at) = same {
+                new_instances.remove(at);
+                diff.unchanged += 1;
+            }
+            same.is_none()
+        });
+        let mut new_instances = new_instances.into_iter();
+        for old_instance in old_instances {
+            let Some(new_instance) = new_instances.next() else {
+                diff.resolved.push(old_instance);
+                continue;
+            };
+            diff.changed_feature_sets
+                .push(feature_set_change(&old_instance, new_instance));
+        }
+        diff.new.extend(new_instances);
+    }
+    diff.new.extend(new_diagnostics.into_values().flatten());
+    Ok(diff)
+}
+
+/// `new_instance` of a diagnostic, with the feature sets it gained and lost since `old_instance`.
+fn feature_set_change(
+    old_instance: &DiffedDiagnostic,
+    new_instance: DiffedDiagnostic,
+) -> FeatureSetChange {
+    FeatureSetChange {
+        added: new_instance
+            .feature_sets
+            .difference(&old_instance.feature_sets)
+            .cloned()
+            .collect(),
+        removed: old_instance
+            .feature_sets
+            .difference(&new_instance.feature_sets)
+            .cloned()
+            .collect(),
+        diagnostic: new_instance,
+    }
+}
+
+/// Reads a report written with `--format json`.
+fn read_saved_report(path: &Path) -> Result<SavedReport, GetdocError> {
+    let read_error = |message: String| GetdocError::ReadReport {
+        path: path.to_path_buf(),
+        message,
+    };
+    let content = fs::read_to_string(path).map_err(|e| read_error(e.to_string()))?;
+    serde_json::from_str(&content)
+        .map_err(|e| read_error(format!("not a getdoc JSON report ({})", e)))
+}
+
+/// The feature sets a run checked, including those folded into an equivalent set.
+fn checked_feature_sets(report: &SavedReport) -> BTreeSet<String> {
+    report
+        .timings
+        .iter()
+        .map(|timing| timing.feature_set.clone())
+        .chain(
+            report
+                .equivalent_feature_sets
+                .iter()
+                .flat_map(|equivalent| equivalent.aliases.iter().cloned()),
+        )
+        .collect()
+}
+
+/// Groups diagnostics, in report order, by what identifies them across runs: level, code,
+/// and message and file with line numbers, versions, and hashes left out (see
+/// [`normalize`]). The same message in the same file can occur at several places.
+fn diagnostics_by_key(
+    diagnostics: Vec<SavedDiagnostic>,
+) -> BTreeMap<(String, Option<String>, String, String), Vec<DiffedDiagnostic>> {
+    let mut by_key: BTreeMap<_, Vec<DiffedDiagnostic>> = BTreeMap::new();
+    for diagnostic in diagnostics {
+        let key = (
+            diagnostic.level.clone(),
+            diagnostic.code.clone(),
+            normalize(&diagnostic.message),
+            normalize(&diagnostic.primary_location),
+        );
+        by_key.entry(key).or_default().push(DiffedDiagnostic {
+            level: diagnostic.level,
+            code: diagnostic.code,
+            message: diagnostic.message,
+            location: diagnostic.primary_location,
+            feature_sets: diagnostic.feature_set_descriptors,
+            id: diagnostic.id,
+        });
+    }
+    by_key
+}
+
+/// `text` without what differs between runs of unchanged code: line and column numbers
+/// after a `.rs` file name, ` (non-primary)` markers, and the version or hash at the end of
+/// a path component, so that
+/// `.../index.crates.io-6f17d22bba15001f/serde-1.0.200/src/de.rs:412:5` becomes
+/// `.../index.crates.io-*/serde-*/src/de.rs`.
+fn normalize(text: &str) -> String {
+    let text = text.replace(" (non-primary)", "");
+    text.split_inclusive(|c: char| c.is_whitespace() || c == '`')
+        .map(|word| {
+            let (word, delimiter) = match word.char_indices().last() {
+                Some((at, c)) if c.is_whitespace() || c == '`' => word.split_at(at),
+                _ => (word, ""),
+            };
+            let word = match word.find(".rs:") {
+                Some(at) => &word[..at + ".rs".len()],
+                None => word,
+            };
+            let word = if word.contains('/') || word.contains('\\') {
+                word.split_inclusive(['/', '\\'])
+                    .map(strip_version)
+                    .collect::<String>()
+            } else {
+                word.to_string()
+            };
+            word + delimiter
+        })
+        .collect()
+}
+
+/// Writes the comparison as Markdown: the summary line, the feature-matrix differences, and
+/// the new, resolved, and changed diagnostics.
+pub fn write_diff_markdown(
+    diff: &ReportDiff,
+    old_path: &Path,
+    new_path: &Path,
+    writer: &mut dyn Write,
+) -> std::io::Result<()> {
+    writeln!(writer, "# GetDoc Diff\n")?;
+    writeln!(
+        writer,
+        "`{}` → `{}`\n",
+        old_path.display(),
+        new_path.display()
+    )?;
+    writeln!(writer, "{}\n", diff.summary_line())?;
+
+    if !diff.feature_sets_added.is_empty() || !diff.feature_sets_removed.is_empty() {
+        writeln!(writer, "## Feature Matrix Changes\n")?;
+        if !diff.feature_sets_added.is_empty() {
+            writeln!(
+                writer,
+                "* Checked only in the new run: {}.",
+                quoted_list(&diff.feature_sets_added)
+            )?;
+        }
+        if !diff.feature_sets_removed.is_empty() {
+            writeln!(
+                writer,
+                "* Checked only in the old run: {}.",
+                quoted_list(&diff.feature_sets_removed)
+            )?;
+        }
+        writeln!(
+            writer,
+            "\nDiagnostics under these sets can be new or resolved because of the matrix alone.\n"
+        )?;
+    }
+
+    writeln!(writer, "## New Diagnostics ({})\n", diff.new.len())?;
+    for diagnostic in &diff.new {
+        write_diagnostic_line(writer, diagnostic, &diff.feature_sets_added, "the old run")?;
+    }
+    if diff.new.is_empty() {
+        writeln!(writer, "_None._")?;
+    }
+
+    writeln!(
+        writer,
+        "\n## Resolved Diagnostics ({})\n",
+        diff.resolved.len()
+    )?;
+    for diagnostic in &diff.resolved {
+        write_diagnostic_line(
+            writer,
+            diagnostic,
+            &diff.feature_sets_removed,
+            "the new run",
+        )?;
+    }
+    if diff.resolved.is_empty() {
+        writeln!(writer, "_None._")?;
+    }
+
+    writeln!(
+        writer,
+        "\n## Changed Feature Sets ({})\n",
+        diff.changed_feature_sets.len()
+    )?;
+    for change in &diff.changed_feature_sets {
+        let unmatched = |sets: &BTreeSet<String>, matrix_only: &BTreeSet<String>| {
+            sets.iter()
+                .map(|set| {
+                    if matrix_only.contains(set) {
+                        format!("`{}` (newly checked)", set)
+                    } else {
+                        format!("`{}`", set)
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let mut parts = Vec::new();
+        if !change.added.is_empty() {
+            parts.push(format!(
+                "now also under {}",
+                unmatched(&change.added, &diff.feature_sets_added)
+            ));
+        }
+        if !change.removed.is_empty() {
+            parts.push(format!(
+                "no longer under {}",
+                unmatched(&change.removed, &BTreeSet::new())
+            ));
+        }
+        writeln!(
+            writer,
+            "* {}: {}.",
+            diagnostic_heading(&change.diagnostic),
+            parts.join("; ")
+        )?;
+    }
+    if diff.changed_feature_sets.is_empty() {
+        writeln!(writer, "_None._")?;
+    }
+    Ok(())
+}
+
+/// `**error[E0061]** message (at `src/main.rs:2`)`.
+fn diagnostic_heading(diagnostic: &DiffedDiagnostic) -> String {
+    let level = match &diagnostic.code {
+        Some(code) => format!("{}[{}]", diagnostic.level, code),
+        None => diagnostic.level.clone(),
+    };
+    format!(
+        "**{}** {} (at `{}`)",
+        level, diagnostic.message, diagnostic.location
+    )
+}
+
+/// One new or resolved diagnostic, noting when all its feature sets were checked by one run
+/// only (`unmatched_sets`), so that the other run could not have seen it.
+fn write_diagnostic_line(
+    writer: &mut dyn Write,
+    diagnostic: &DiffedDiagnostic,
+    unmatched_sets: &BTreeSet<String>,
+    other_run: &str,
+) -> std::io::Result<()> {
+    write!(
+        writer,
+        "* {}, under {}",
+        diagnostic_heading(diagnostic),
+        quoted_list(&diagnostic.feature_sets)
+    )?;
+    if !diagnostic.feature_sets.is_empty() && diagnostic.feature_sets.is_subset(unmatched_sets) {
+        write!(
+            writer,
+            "; these feature sets were not checked in {}",
+            other_run
+        )?;
+    }
+    writeln!(writer, ".")
+}
+
+/// Runs `getdoc diff`: compares the reports and prints the comparison, or writes it to
+/// `--output` and prints the summary line.
+pub fn run_diff(args: &DiffArgs) -> Result<ReportDiff, GetdocError> {
+    let diff = diff_reports(&args.old, &args.new)?;
+    match &args.output {
+        Some(path) => {
+            write_file_atomically(path, args.force, |writer| {
+                write_diff_markdown(&diff, &args.old, &args.new, writer)
+            })?;
+            info!("[getdoc] Diff written to {}", path.display());
+            info!("[getdoc] {}", diff.summary_line());
+        }
+        None => {
+            let _ = write_diff_markdown(&diff, &args.old, &args.new, &mut std::io::stdout());
+        }
+    }
+    Ok(diff)
+}
@@ -0,0 +1,168 @@
+// getdoc - error.rs
+//
+// The error type returned by getdoc's fallible operations.
+
+// --- Standard Library Imports ---
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Everything that can stop getdoc, with the feature set or file involved.
+#[derive(Debug)]
+pub enum GetdocError {
+    /// The `cargo` executable could not be started (not installed or not on `PATH`).
+    CargoNotFound { source: io::Error },
+    /// `cargo check` could not be run, or its output not processed, for a feature set.
+    CargoCheck {
+        feature_set: String,
+        source: io::Error,
+    },
+    /// `cargo metadata` failed or printed output that could not be parsed.
+    CargoMetadata {
+        manifest_path: PathBuf,
+        message: String,
+    },
+    /// The manifest could not be read.
+    ReadManifest { path: PathBuf, source: io::Error },
+    /// The manifest is not valid TOML (or not a valid `Cargo.toml`).
+    ParseManifest {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    /// Pre-captured cargo output (`--input`) could not be read.
+    ReadInput { path: PathBuf, source: io::Error },
+    /// An implicated source file could not be read.
+    ReadSource { path: PathBuf, source: io::Error },
+    /// A report file could not be written.
+    WriteReport { path: PathBuf, source: io::Error },
+    /// A report file already exists and overwriting was not allowed (`--force`).
+    ReportExists { path: PathBuf },
+    /// `rustc --explain` could not be run or does not know the error code.
+    ExplainErrorCode { code: String, message: String },
+    /// Ctrl-C was pressed while a feature set was being checked; its check was stopped.
+    Interrupted { feature_set: String },
+    /// Another getdoc run in the same project holds the run lock (and `--wait` was not given).
+    RunLocked { path: PathBuf, holder: Option<u32> },
+    /// `--minimal-versions` could not swap in a lockfile resolved to minimal versions.
+    MinimalVersions { message: String },
+    /// The report viewer (`--serve`) could not listen on its address.
+    ServeReport { address: String, source: io::Error },
+    /// A `--features-from-file` list could not be read, is malformed, or names a feature
+    /// the manifest does not declare.
+    FeatureList { path: PathBuf, message: String },
+    /// A saved JSON report (`getdoc diff`) could not be read or is not a getdoc report.
+    ReadReport { path: PathBuf, message: String },
+}
+
+impl fmt::Display for GetdocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GetdocError::CargoNotFound { source } => write!(f, "could not run `cargo`: {}", source),
+            GetdocError::CargoCheck {
+                feature_set,
+                source,
+            } => write!(
+                f,
+                "could not run `cargo check` for feature set '{}': {}",
+                feature_set, source
+            ),
+            GetdocError::CargoMetadata {
+                manifest_path,
+                message,
+            } => write!(
+                f,
+                "`cargo metadata` failed for {}: {}",
+                manifest_path.display(),
+                message
+            ),
+            GetdocError::ReadManifest { path, source } => {
+                write!(f, "could not read {}: {}", path.display(), source)
+            }
+            GetdocError::ParseManifest { path, source } => {
+                write!(f, "could not parse {}: {}", path.display(), source)
+            }
+            GetdocError::ReadInput { path, source } => write!(
+                f,
+                "could not read cargo output from {}: {}",
+                path.display(),
+                source
+            ),
+            GetdocError::ReadSource { path, source } => {
+                write!(
+                    f,
+                    "could not read source file {}: {}",
+                    path.display(),
+                    source
+                )
+            }
+            GetdocError::WriteReport { path, source } => {
+                write!(f, "could not write {}: {}", path.display(), source)
+            }
+            GetdocError::ReportExists { path } => write!(
+                f,
+                "{} already exists; refusing to overwrite it",
+                path.display()
+            ),
+            GetdocError::ExplainErrorCode { code, message } => {
+                write!(f, "could not explain {}: {}", code, message)
+            }
+            GetdocError::Interrupted { feature_set } => {
+                write!(
+                    f,
+                    "interrupted while checking feature set '{}'",
+                    feature_set
+                )
+            }
+            GetdocError::RunLocked { path, holder } => {
+                write!(f, "another getdoc run")?;
+                if let Some(pid) = holder {
+                    write!(f, " (process {})", pid)?;
+                }
+                write!(
+                    f,
+                    " is working in this project (it holds {})",
+                    path.display()
+                )
+            }
+            GetdocError::MinimalVersions { message } => {
+                write!(
+                    f,
+                    "could not resolve minimal dependency versions: {}",
+                    message
+                )
+            }
+            GetdocError::ServeReport { address, source } => {
+                write!(f, "could not serve the report on {}: {}", address, source)
+            }
+            GetdocError::FeatureList { path, message } => {
+                write!(f, "feature list {}: {}", path.display(), message)
+            }
+            GetdocError::ReadReport { path, message } => {
+                write!(f, "could not read report {}: {}", path.display(), message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GetdocError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GetdocError::CargoNotFound { source }
+            | GetdocError::CargoCheck { source, .. }
+            | GetdocError::ReadManifest { source, .. }
+            | GetdocError::ReadInput { source, .. }
+            | GetdocError::ReadSource { source, .. }
+            | GetdocError::WriteReport { source, .. }
+            | GetdocError::ServeReport { source, .. } => Some(source),
+            GetdocError::ParseManifest { source, .. } => Some(source),
+            GetdocError::CargoMetadata { .. }
+            | GetdocError::ReportExists { .. }
+            | GetdocError::ExplainErrorCode { .. }
+            | GetdocError::Interrupted { .. }
+            | GetdocError::RunLocked { .. }
+            | GetdocError::MinimalVersions { .. }
+            | GetdocError::FeatureList { .. }
+            | GetdocError::ReadReport { .. } => None,
+        }
+    }
+}
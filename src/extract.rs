@@ -0,0 +1,1492 @@
+// getdoc - extract.rs
+//
+// Extracting item definitions and doc comments from implicated source files.
+
+// --- Standard Library Imports ---
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// --- External Crate Imports ---
+use quote::ToTokens;
+use serde::Serialize;
+
+// --- Crate Imports ---
+use crate::Options;
+use crate::error::GetdocError;
+use crate::progress::{Bar, info};
+use crate::timing::PhaseTimer;
+
+/// Options controlling which items `extract_items_from_file` keeps.
+#[derive(Debug, Clone, Copy)]
+pub struct ExtractionOptions {
+    /// Keep `#[doc(hidden)]` items and `#[cfg(test)]`-gated code instead of skipping them.
+    pub(crate) include_hidden: bool,
+    /// Lines of raw source to show around each implicated line when falling back to raw text.
+    pub(crate) context_lines: usize,
+    /// Size in bytes above which a file is summarized instead of parsed (0 = no limit).
+    pub(crate) max_file_size_bytes: u64,
+    /// How many inline modules deep items are extracted; deeper modules are listed with
+    /// their contents elided.
+    pub(crate) max_module_depth: usize,
+}
+
+impl From<&Options> for ExtractionOptions {
+    fn from(options: &Options) -> Self {
+        ExtractionOptions {
+            include_hidden: options.include_hidden,
+            context_lines: options.context_lines,
+            max_file_size_bytes: options.max_file_size,
+            max_module_depth: options.max_depth,
+        }
+    }
+}
+
+/// The items extracted from a single file, plus bookkeeping about what was left out.
+#[derive(Debug, Default)]
+pub(crate) struct FileExtraction {
+    pub(crate) items: Vec<ExtractedItem>,
+    pub(crate) hidden_items_skipped: usize, // Items dropped for `#[doc(hidden)]` or `#[cfg(test)]`
+    pub(crate) raw_fallback: Option<RawContextFallback>, // Set when the file was not (or could not be) parsed with `syn`
+    pub(crate) first_invalid_utf8_line: Option<usize>, // Set when the file is not valid UTF-8 and was read lossily
+}
+
+/// Raw source excerpts shown in place of extracted items when a file cannot be
+/// processed normally (e.g. `syn` fails to parse it, or it is huge or generated).
+#[derive(Debug, Serialize)]
+pub struct RawContextFallback {
+    /// Why the file was not parsed, e.g. a `syn` error or "file is generated".
+    pub reason: String,
+    /// Source excerpts around the implicated lines.
+    pub snippets: Vec<RawSnippet>,
+    /// File size, set for summarized (oversized or generated) files only.
+    pub file_size_bytes: Option<u64>,
+    /// The file's leading `//!` doc comment, for summarized files only.
+    pub leading_doc_comment: Vec<String>,
+}
+
+/// A contiguous range of raw source lines around one or more implicated lines.
+#[derive(Debug, Serialize)]
+pub struct RawSnippet {
+    /// First line shown (1-based, inclusive).
+    pub line_start: usize,
+    /// Last line shown (1-based, inclusive).
+    pub line_end: usize,
+    /// The source lines themselves.
+    pub lines: Vec<String>,
+}
+
+/// An impl block (with its sub-items) found in another file of the same crate
+/// as an implicated file, for a type that the implicated file defines.
+#[derive(Debug, Serialize)]
+pub struct RelatedImplBlock {
+    /// Path relative to the crate root, e.g. `src/methods.rs`.
+    pub source_file: PathBuf,
+    /// The impl block followed by its items.
+    pub items: Vec<ExtractedItem>,
+}
+
+/// One item definition extracted from a third-party source file.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtractedItem {
+    /// Kind of item, e.g. "Function", "Struct", "Impl Method", "Enum Variant".
+    pub item_kind: String,
+    /// The item's name (for impl blocks, the `impl ...` header).
+    pub name: String,
+    /// The item's signature, or its full definition for types and constants. A function's
+    /// `where` clause is put on lines of its own.
+    pub signature_or_definition: String,
+    /// For functions and methods, the bounds on their generic parameters, inline ones and
+    /// those from the `where` clause, e.g. `T : Clone + Send`.
+    pub trait_bounds: Vec<String>,
+    /// Doc comment lines, without the `///` markers.
+    pub doc_comments: Vec<String>,
+    /// Identifier unique within the file the item was extracted from.
+    pub id: usize,
+    /// `id` of the enclosing item (e.g. the impl block of a method, or the enum of a variant).
+    pub parent: Option<usize>,
+    /// Header of the enclosing impl block, e.g. `impl Display for Foo`.
+    pub parent_impl: Option<String>,
+    /// Other `file:line` locations of an identical item (re-exports).
+    pub also_found_in: Vec<String>,
+    /// Fully qualified path of the item, e.g. `serde::de::Deserialize`, when known from the
+    /// crate's rustdoc JSON (`--rustdoc-json`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item_path: Option<String>,
+    /// 1-based line of the item's first non-attribute token.
+    pub line_start: usize,
+    /// 1-based line of the item's last token.
+    pub line_end: usize,
+}
+
+/// Runs `extract_items_from_file` over `file_paths` on a pool of scoped worker threads.
+/// Parsing with `syn` is CPU-bound, so this matters when many files are implicated.
+/// The returned results are in the same order as `file_paths`. Each file's extraction
+/// is added to `timer` as an "extraction" run.
+pub(crate) fn extract_items_from_files(
+    file_paths: &[PathBuf],
+    implicated_lines_by_file: &HashMap<PathBuf, BTreeSet<usize>>,
+    options: &ExtractionOptions,
+    progress: &Bar,
+    timer: &PhaseTimer,
+) -> Vec<Result<FileExtraction, GetdocError>> {
+    let worker_count = std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(file_paths.len())
+        .max(1);
+    let next_index = AtomicUsize::new(0);
+
+    let mut indexed_results: Vec<(usize, Result<FileExtraction, GetdocError>)> =
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..worker_count)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut worker_results = Vec::new();
+                        loop {
+                            let index = next_index.fetch_add(1, Ordering::Relaxed);
+                            let Some(file_path) = file_paths.get(index) else {
+                                break;
+                            };
+                            let result = timer.time("extraction", || {
+                                extract_items_from_file(
+                                    file_path,
+                                    implicated_lines_by_file.get(file_path),
+                                    options,
+                                )
+                            });
+                            progress.inc();
+                            worker_results.push((index, result));
+                        }
+                        worker_results
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("extraction worker thread panicked"))
+                .collect()
+        });
+
+    indexed_results.sort_by_key(|(index, _)| *index);
+    indexed_results
+        .into_iter()
+        .map(|(_, result)| result)
+        .collect()
+}
+
+/// Upper bound on how many other source files of one crate `--related-impls` will parse.
+const RELATED_IMPLS_MAX_FILES: usize = 200;
+
+/// For every implicated file, finds impl blocks in *other* files of the same crate whose
+/// self type is a struct, enum, or type alias defined in the implicated file.
+/// Each crate's candidate files are parsed once and shared by all of its implicated files.
+pub fn find_related_impls(
+    extracted_data: &HashMap<PathBuf, Vec<ExtractedItem>>,
+    options: &ExtractionOptions,
+) -> HashMap<PathBuf, Vec<RelatedImplBlock>> {
+    let mut implicated_files_by_crate_root: BTreeMap<PathBuf, Vec<&PathBuf>> = BTreeMap::new();
+    for file_path in extracted_data.keys() {
+        if let Some(crate_root) = file_path
+            .ancestors()
+            .skip(1)
+            .find(|dir| dir.join("Cargo.toml").is_file())
+        {
+            implicated_files_by_crate_root
+                .entry(crate_root.to_path_buf())
+                .or_default()
+                .push(file_path);
+        }
+    }
+
+    let mut related_impls: HashMap<PathBuf, Vec<RelatedImplBlock>> = HashMap::new();
+    for (crate_root, implicated_files) in implicated_files_by_crate_root {
+        let mut candidate_files = Vec::new();
+        collect_rust_files(
+            &crate_root.join("src"),
+            RELATED_IMPLS_MAX_FILES + implicated_files.len(),
+            &mut candidate_files,
+        );
+        // Implicated files already have their own report sections.
+        candidate_files.retain(|path| !extracted_data.contains_key(path));
+        if candidate_files.len() > RELATED_IMPLS_MAX_FILES {
+            info!(
+                "[getdoc] Note: only scanning the first {} source files of {} for related impls.",
+                RELATED_IMPLS_MAX_FILES,
+                crate_root.display()
+            );
+            candidate_files.truncate(RELATED_IMPLS_MAX_FILES);
+        }
+
+        let candidate_extractions: Vec<(PathBuf, Vec<ExtractedItem>)> = candidate_files
+            .iter()
+            .zip(extract_items_from_files(
+                &candidate_files,
+                &HashMap::new(),
+                options,
+                &Bar::hidden(),
+                &PhaseTimer::default(),
+            ))
+            .filter_map(|(path, result)| {
+                let relative_path = path.strip_prefix(&crate_root).unwrap_or(path);
+                result
+                    .ok()
+                    .map(|extraction| (relative_path.to_path_buf(), extraction.items))
+            })
+            .collect();
+
+        for file_path in implicated_files {
+            let type_names: HashSet<&str> = extracted_data[file_path]
+                .iter()
+                .filter(|item| matches!(item.item_kind.as_str(), "Struct" | "Enum" | "Type Alias"))
+                .map(|item| item.name.as_str())
+                .collect();
+            if type_names.is_empty() {
+                continue;
+            }
+
+            let mut blocks = Vec::new();
+            for (source_file, items) in &candidate_extractions {
+                // Sub-items directly follow their impl block, so group them while a matching
+                // block is open. An impl block itself may have a parent (its inline module).
+                let mut current_block: Option<RelatedImplBlock> = None;
+                for item in items {
+                    if let Some(block) = current_block.as_mut()
+                        && item.parent.is_some_and(|parent| {
+                            block.items.iter().any(|member| member.id == parent)
+                        })
+                    {
+                        block.items.push(item.clone());
+                        continue;
+                    }
+                    blocks.extend(current_block.take());
+                    if item.item_kind.contains("Impl Block")
+                        && impl_self_type_name(item)
+                            .is_some_and(|name| type_names.contains(name.as_str()))
+                    {
+                        current_block = Some(RelatedImplBlock {
+                            source_file: source_file.clone(),
+                            items: vec![item.clone()],
+                        });
+                    }
+                }
+                blocks.extend(current_block);
+            }
+            if !blocks.is_empty() {
+                related_impls.insert(file_path.clone(), blocks);
+            }
+        }
+    }
+    related_impls
+}
+
+/// Removes items whose doc comments, definition, and parent exactly match an item already
+/// kept from an earlier file (in `sorted_file_paths` order), as happens with crates that
+/// re-export the same item from several places. The parent (an impl block's header, or the
+/// kind and name of an enclosing enum or module) keeps apart identical methods of different
+/// types, such as `fn new() -> Self` documented "Creates an empty value.". The kept item
+/// records the other locations in `also_found_in`. Only documented items without children
+/// are collapsed. Returns the number of items removed.
+pub fn collapse_duplicate_items(
+    sorted_file_paths: &[PathBuf],
+    extracted_data: &mut HashMap<PathBuf, Vec<ExtractedItem>>,
+) -> usize {
+    // (parent, doc comments, definition)
+    type DuplicateKey = (Option<String>, Vec<String>, String);
+    // key -> (file, index) of the first occurrence
+    let mut first_occurrences: HashMap<DuplicateKey, (PathBuf, usize)> = HashMap::new();
+    let mut duplicate_locations: Vec<(PathBuf, usize, String)> = Vec::new();
+    let mut collapsed_count = 0;
+
+    for file_path in sorted_file_paths {
+        let Some(items) = extracted_data.get_mut(file_path) else {
+            continue;
+        };
+        let parent_ids: HashSet<usize> = items.iter().filter_map(|item| item.parent).collect();
+        let parent_names: HashMap<usize, String> = items
+            .iter()
+            .filter(|item| parent_ids.contains(&item.id))
+            .map(|item| (item.id, format!("{} {}", item.item_kind, item.name)))
+            .collect();
+        let mut kept_items = Vec::with_capacity(items.len());
+        for item in items.drain(..) {
+            if item.doc_comments.is_empty() || parent_ids.contains(&item.id) {
+                kept_items.push(item);
+                continue;
+            }
+            let parent = item.parent_impl.clone().or_else(|| {
+                item.parent
+                    .and_then(|parent| parent_names.get(&parent).cloned())
+            });
+            let key = (
+                parent,
+                item.doc_comments.clone(),
+                item.signature_or_definition.clone(),
+            );
+            match first_occurrences.get(&key) {
+                Some((first_path, first_index)) if first_path != file_path => {
+                    duplicate_locations.push((
+                        first_path.clone(),
+                        *first_index,
+                        format!("{}:{}", file_path.display(), item.line_start),
+                    ));
+                    collapsed_count += 1;
+                }
+                _ => {
+                    first_occurrences
+                        .entry(key)
+                        .or_insert_with(|| (file_path.clone(), kept_items.len()));
+                    kept_items.push(item);
+                }
+            }
+        }
+        *items = kept_items;
+    }
+
+    for (first_path, first_index, location) in duplicate_locations {
+        if let Some(item) = extracted_data
+            .get_mut(&first_path)
+            .and_then(|items| items.get_mut(first_index))
+        {
+            item.also_found_in.push(location);
+        }
+    }
+    collapsed_count
+}
+
+/// Recursively collects `.rs` files under `dir` in sorted order, stopping at `limit` files.
+fn collect_rust_files(dir: &Path, limit: usize, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .collect();
+    paths.sort();
+    for path in paths {
+        if files.len() >= limit {
+            return;
+        }
+        if path.is_dir() {
+            collect_rust_files(&path, limit, files);
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+}
+
+/// Returns the bare name of an impl block's self type, e.g. `Foo` for `impl<T> Foo<T>`,
+/// `impl Display for crate::Foo`, and `impl<'a> Read for &'a mut Foo`.
+fn impl_self_type_name(item: &ExtractedItem) -> Option<String> {
+    // Inherent impls are named by their self type; trait impls are named "impl Trait for SelfTy".
+    let self_type = match item.name.rsplit_once(" for ") {
+        Some((_, self_type)) if item.name.starts_with("impl ") => self_type,
+        _ => item.name.as_str(),
+    };
+    let mut self_type = syn::parse_str::<syn::Type>(self_type).ok()?;
+    loop {
+        self_type = match self_type {
+            syn::Type::Reference(reference) => *reference.elem,
+            syn::Type::Paren(paren) => *paren.elem,
+            syn::Type::Group(group) => *group.elem,
+            syn::Type::Path(path) => {
+                return path.path.segments.last().map(|last| last.ident.to_string());
+            }
+            _ => return None,
+        };
+    }
+}
+
+/// The tokens of `tokens` written out with a space only between two words, e.g.
+/// `&'a mut Foo<T>` or `dyn Fn(u8)`, so that keywords and lifetimes stay apart from names.
+fn compact_tokens(tokens: &impl ToTokens) -> String {
+    let spaced = tokens.to_token_stream().to_string();
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    let mut compact = String::with_capacity(spaced.len());
+    let mut chars = spaced.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == ' ' {
+            let between_words = compact.ends_with(is_word_char)
+                && chars.peek().is_some_and(|&next| is_word_char(next));
+            if !between_words {
+                continue;
+            }
+        }
+        compact.push(c);
+    }
+    compact
+}
+
+pub(crate) fn extract_items_from_file(
+    file_path: &PathBuf,
+    implicated_lines: Option<&BTreeSet<usize>>,
+    options: &ExtractionOptions,
+) -> Result<FileExtraction, GetdocError> {
+    let bytes = fs::read(file_path).map_err(|source| GetdocError::ReadSource {
+        path: file_path.clone(),
+        source,
+    })?;
+    let mut extraction = FileExtraction {
+        first_invalid_utf8_line: first_invalid_utf8_line(&bytes),
+        ..FileExtraction::default()
+    };
+    // A stray non-UTF-8 byte (e.g. Latin-1 in a comment) shouldn't cost the whole file:
+    // it becomes U+FFFD, which leaves line numbers intact.
+    let content = String::from_utf8_lossy(&bytes);
+
+    // Huge or machine-generated files are slow to parse and their item dump is useless,
+    // so summarize them instead.
+    let file_size_bytes = content.len() as u64;
+    let summary_reason = if let Some(marker) = generated_file_marker(&content) {
+        Some(format!("the file is marked as generated (`{}`)", marker))
+    } else if options.max_file_size_bytes > 0 && file_size_bytes > options.max_file_size_bytes {
+        Some(format!(
+            "the file is too large ({} bytes, `--max-file-size` is {})",
+            file_size_bytes, options.max_file_size_bytes
+        ))
+    } else {
+        None
+    };
+    if let Some(reason) = summary_reason {
+        extraction.raw_fallback = Some(RawContextFallback {
+            reason,
+            snippets: raw_context_snippets(&content, implicated_lines, options.context_lines),
+            file_size_bytes: Some(file_size_bytes),
+            leading_doc_comment: leading_doc_comment(&content),
+        });
+        return Ok(extraction);
+    }
+
+    let ast = match syn::parse_file(&content) {
+        Ok(ast) => ast,
+        Err(parse_error) => {
+            // Unparseable files (unstable or edition-specific syntax) still get raw context.
+            extraction.raw_fallback = Some(RawContextFallback {
+                reason: format!("`syn` could not parse the file ({})", parse_error),
+                snippets: raw_context_snippets(&content, implicated_lines, options.context_lines),
+                file_size_bytes: None,
+                leading_doc_comment: Vec::new(),
+            });
+            return Ok(extraction);
+        }
+    };
+
+    process_items_syn(&ast.items, file_path, 0, options, &mut extraction);
+    Ok(extraction)
+}
+
+/// The 1-based line holding the first byte of `bytes` that is not valid UTF-8, if any.
+fn first_invalid_utf8_line(bytes: &[u8]) -> Option<usize> {
+    let valid_up_to = std::str::from_utf8(bytes).err()?.valid_up_to();
+    Some(1 + bytes[..valid_up_to].iter().filter(|&&b| b == b'\n').count())
+}
+
+/// The items getdoc run with `options` extracts from `file_path`; none when the file is
+/// summarized or cannot be parsed.
+pub fn extract_items(
+    file_path: &Path,
+    options: &Options,
+) -> Result<Vec<ExtractedItem>, GetdocError> {
+    extract_items_from_file(
+        &file_path.to_path_buf(),
+        None,
+        &ExtractionOptions::from(options),
+    )
+    .map(|extraction| extraction.items)
+}
+
+/// Processes the items of a file (`module_depth` 0) or of an inline module nested
+/// `module_depth` modules deep, skipping hidden ones unless `--include-hidden` is given.
+fn process_items_syn(
+    items_syn: &[syn::Item],
+    source_file: &Path,
+    module_depth: usize,
+    options: &ExtractionOptions,
+    extraction: &mut FileExtraction,
+) {
+    for item_syn in items_syn {
+        let attrs = item_attrs(item_syn);
+        if !options.include_hidden && is_hidden_or_test_only(attrs) {
+            extraction.hidden_items_skipped += 1;
+            continue;
+        }
+        let docs = extract_doc_comments(attrs, source_file);
+        process_item_syn(
+            item_syn,
+            docs,
+            source_file,
+            module_depth,
+            options,
+            extraction,
+        );
+    }
+}
+
+/// Number of leading lines searched for a "generated file" marker.
+const GENERATED_MARKER_SCAN_LINES: usize = 10;
+
+/// Returns the marker if one of the first lines flags the file as machine-generated.
+fn generated_file_marker(content: &str) -> Option<&'static str> {
+    content
+        .lines()
+        .take(GENERATED_MARKER_SCAN_LINES)
+        .find_map(|line| {
+            ["@generated", "DO NOT EDIT"]
+                .into_iter()
+                .find(|marker| line.contains(marker))
+        })
+}
+
+/// Returns the first block of consecutive `//!` or `///` comment lines, with the comment
+/// markers stripped.
+pub(crate) fn leading_doc_comment(content: &str) -> Vec<String> {
+    let mut block = Vec::new();
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        let doc_text = trimmed
+            .strip_prefix("//!")
+            .or_else(|| trimmed.strip_prefix("///"));
+        match doc_text {
+            Some(text) => block.push(text.strip_prefix(' ').unwrap_or(text).to_string()),
+            None if !block.is_empty() => break,
+            None => {}
+        }
+    }
+    block
+}
+
+/// Cuts the raw lines around each implicated line out of `content`, merging
+/// overlapping or adjacent windows into a single snippet.
+fn raw_context_snippets(
+    content: &str,
+    implicated_lines: Option<&BTreeSet<usize>>,
+    context_lines: usize,
+) -> Vec<RawSnippet> {
+    let source_lines: Vec<&str> = content.lines().collect();
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &line in implicated_lines.into_iter().flatten() {
+        if line == 0 || line > source_lines.len() {
+            continue;
+        }
+        let start = line.saturating_sub(context_lines).max(1);
+        let end = (line + context_lines).min(source_lines.len());
+        match ranges.last_mut() {
+            // Implicated lines are sorted, so only the previous window can overlap.
+            Some((_, prev_end)) if start <= *prev_end + 1 => *prev_end = (*prev_end).max(end),
+            _ => ranges.push((start, end)),
+        }
+    }
+    ranges
+        .into_iter()
+        .map(|(line_start, line_end)| RawSnippet {
+            line_start,
+            line_end,
+            lines: source_lines[line_start - 1..line_end]
+                .iter()
+                .map(|line| line.to_string())
+                .collect(),
+        })
+        .collect()
+}
+
+/// Returns the attributes of the item kinds `process_item_syn` handles.
+fn item_attrs(item_syn: &syn::Item) -> &[syn::Attribute] {
+    match item_syn {
+        syn::Item::Fn(i) => &i.attrs,
+        syn::Item::Struct(i) => &i.attrs,
+        syn::Item::Enum(i) => &i.attrs,
+        syn::Item::Trait(i) => &i.attrs,
+        syn::Item::Mod(i) => &i.attrs,
+        syn::Item::Impl(i) => &i.attrs,
+        syn::Item::Type(i) => &i.attrs,
+        syn::Item::Const(i) => &i.attrs,
+        syn::Item::Static(i) => &i.attrs,
+        syn::Item::Use(i) => &i.attrs,
+        syn::Item::ExternCrate(i) => &i.attrs,
+        _ => &[],
+    }
+}
+
+/// Checks whether an item is deliberately hidden from the public API, either via
+/// `#[doc(hidden)]` or by only existing in test builds (see [`cfg_requires_test`]).
+fn is_hidden_or_test_only(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        if attr.path().is_ident("doc") {
+            nested_metas(&attr.meta).is_some_and(|metas| {
+                metas
+                    .iter()
+                    .any(|meta| matches!(meta, syn::Meta::Path(path) if path.is_ident("hidden")))
+            })
+        } else if attr.path().is_ident("cfg") {
+            attr.parse_args::<syn::Meta>()
+                .is_ok_and(|predicate| cfg_requires_test(&predicate))
+        } else {
+            false
+        }
+    })
+}
+
+/// The comma-separated metas inside a list attribute or predicate, e.g. the three of
+/// `all(test, unix, feature = "x")`; `None` for anything else.
+fn nested_metas(meta: &syn::Meta) -> Option<Vec<syn::Meta>> {
+    let syn::Meta::List(list) = meta else {
+        return None;
+    };
+    list.parse_args_with(syn::punctuated::Punctuated::<syn::Meta, syn::Token![,]>::parse_terminated)
+        .ok()
+        .map(|metas| metas.into_iter().collect())
+}
+
+/// Whether a `cfg` predicate can only hold in test builds: `test` itself, `all(..)` with such
+/// a predicate among its parts, `any(..)` made only of such predicates, or `not(..)` of a
+/// predicate that never holds in them (see [`cfg_excludes_test`]). `not(test)` does not.
+fn cfg_requires_test(predicate: &syn::Meta) -> bool {
+    match predicate {
+        syn::Meta::Path(path) => path.is_ident("test"),
+        syn::Meta::List(list) => {
+            let Some(parts) = nested_metas(predicate) else {
+                return false;
+            };
+            if list.path.is_ident("all") {
+                parts.iter().any(cfg_requires_test)
+            } else if list.path.is_ident("any") {
+                !parts.is_empty() && parts.iter().all(cfg_requires_test)
+            } else if list.path.is_ident("not") {
+                parts.len() == 1 && cfg_excludes_test(&parts[0])
+            } else {
+                false
+            }
+        }
+        syn::Meta::NameValue(_) => false,
+    }
+}
+
+/// Whether a `cfg` predicate never holds in test builds, e.g. `not(test)`; the mirror image
+/// of [`cfg_requires_test`].
+fn cfg_excludes_test(predicate: &syn::Meta) -> bool {
+    let syn::Meta::List(list) = predicate else {
+        return false;
+    };
+    let Some(parts) = nested_metas(predicate) else {
+        return false;
+    };
+    if list.path.is_ident("all") {
+        parts.iter().any(cfg_excludes_test)
+    } else if list.path.is_ident("any") {
+        !parts.is_empty() && parts.iter().all(cfg_excludes_test)
+    } else if list.path.is_ident("not") {
+        parts.len() == 1 && cfg_requires_test(&parts[0])
+    } else {
+        false
+    }
+}
+
+fn process_item_syn(
+    item_syn: &syn::Item,
+    docs: Vec<String>,
+    source_file: &Path,
+    module_depth: usize,
+    options: &ExtractionOptions,
+    extraction: &mut FileExtraction,
+) {
+    let items = &mut extraction.items;
+    let (line_start, line_end) = item_line_range(item_syn, item_attrs(item_syn));
+    match item_syn {
+        syn::Item::Fn(item_fn) => {
+            let vis_string = item_fn.vis.to_token_stream().to_string();
+            let vis_prefix = if vis_string.is_empty() {
+                "".to_string()
+            } else {
+                format!("{} ", vis_string.trim_end())
+            };
+            let sig = format!(
+                "{}{}",
+                vis_prefix,
+                signature_with_where_lines(&item_fn.sig, "")
+            );
+            if let Some(entry_point) =
+                proc_macro_entry_point(&item_fn.attrs, &item_fn.sig.ident.to_string())
+            {
+                // Show how the macro is invoked, followed by the function implementing it.
+                items.push(ExtractedItem {
+                    item_kind: entry_point.kind.to_string(),
+                    name: entry_point.public_name,
+                    signature_or_definition: format!("{}\n{}", entry_point.usage, sig.trim()),
+                    trait_bounds: generic_bounds(&item_fn.sig.generics),
+                    doc_comments: docs,
+                    id: items.len(),
+                    parent: None,
+                    parent_impl: None,
+                    also_found_in: Vec::new(),
+                    item_path: None,
+                    line_start,
+                    line_end,
+                });
+                return;
+            }
+            items.push(ExtractedItem {
+                item_kind: "Function".to_string(),
+                name: item_fn.sig.ident.to_string(),
+                signature_or_definition: sig.trim().to_string(),
+                trait_bounds: generic_bounds(&item_fn.sig.generics),
+                doc_comments: docs,
+                id: items.len(),
+                parent: None,
+                parent_impl: None,
+                also_found_in: Vec::new(),
+                item_path: None,
+                line_start,
+                line_end,
+            });
+        }
+        syn::Item::Struct(item_struct) => {
+            let vis_string = item_struct.vis.to_token_stream().to_string();
+            let vis_prefix = if vis_string.is_empty() {
+                "".to_string()
+            } else {
+                format!("{} ", vis_string.trim_end())
+            };
+            let def = format!(
+                "{}struct {}{}",
+                vis_prefix,
+                item_struct.ident.to_token_stream(),
+                item_struct.generics.to_token_stream()
+            );
+            items.push(ExtractedItem {
+                item_kind: "Struct".to_string(),
+                name: item_struct.ident.to_string(),
+                signature_or_definition: def.trim().to_string(),
+                trait_bounds: Vec::new(),
+                doc_comments: docs,
+                id: items.len(),
+                parent: None,
+                parent_impl: None,
+                also_found_in: Vec::new(),
+                item_path: None,
+                line_start,
+                line_end,
+            });
+        }
+        syn::Item::Enum(item_enum) => {
+            let vis_string = item_enum.vis.to_token_stream().to_string();
+            let vis_prefix = if vis_string.is_empty() {
+                "".to_string()
+            } else {
+                format!("{} ", vis_string.trim_end())
+            };
+            let def = format!(
+                "{}enum {}{}",
+                vis_prefix,
+                item_enum.ident.to_token_stream(),
+                item_enum.generics.to_token_stream()
+            );
+            let enum_id = items.len();
+            items.push(ExtractedItem {
+                item_kind: "Enum".to_string(),
+                name: item_enum.ident.to_string(),
+                signature_or_definition: def.trim().to_string(),
+                trait_bounds: Vec::new(),
+                doc_comments: docs,
+                id: enum_id,
+                parent: None,
+                parent_impl: None,
+                also_found_in: Vec::new(),
+                item_path: None,
+                line_start,
+                line_end,
+            });
+
+            for variant in &item_enum.variants {
+                if !options.include_hidden && is_hidden_or_test_only(&variant.attrs) {
+                    extraction.hidden_items_skipped += 1;
+                    continue;
+                }
+                let (variant_line_start, variant_line_end) =
+                    item_line_range(variant, &variant.attrs);
+                let mut variant_tokens = variant.ident.to_token_stream();
+                variant.fields.to_tokens(&mut variant_tokens);
+                if let Some((eq_token, discriminant)) = &variant.discriminant {
+                    eq_token.to_tokens(&mut variant_tokens);
+                    discriminant.to_tokens(&mut variant_tokens);
+                }
+                items.push(ExtractedItem {
+                    item_kind: "Enum Variant".to_string(),
+                    name: variant.ident.to_string(),
+                    signature_or_definition: variant_tokens.to_string(),
+                    trait_bounds: Vec::new(),
+                    doc_comments: extract_doc_comments(&variant.attrs, source_file),
+                    id: items.len(),
+                    parent: Some(enum_id),
+                    parent_impl: None,
+                    also_found_in: Vec::new(),
+                    item_path: None,
+                    line_start: variant_line_start,
+                    line_end: variant_line_end,
+                });
+            }
+        }
+        syn::Item::Trait(item_trait) => {
+            let vis_string = item_trait.vis.to_token_stream().to_string();
+            let vis_prefix = if vis_string.is_empty() {
+                "".to_string()
+            } else {
+                format!("{} ", vis_string.trim_end())
+            };
+            let def = format!(
+                "{}trait {}{}{}",
+                vis_prefix,
+                item_trait.ident.to_token_stream(),
+                item_trait.generics.params.to_token_stream(),
+                item_trait
+                    .generics
+                    .where_clause
+                    .as_ref()
+                    .map_or("".to_string(), |wc| format!(" {}", wc.to_token_stream()))
+            );
+            items.push(ExtractedItem {
+                item_kind: "Trait".to_string(),
+                name: item_trait.ident.to_string(),
+                signature_or_definition: def.trim().to_string(),
+                trait_bounds: Vec::new(),
+                doc_comments: docs,
+                id: items.len(),
+                parent: None,
+                parent_impl: None,
+                also_found_in: Vec::new(),
+                item_path: None,
+                line_start,
+                line_end,
+            });
+        }
+        syn::Item::Mod(item_mod) => {
+            if item_mod.content.is_none() && docs.is_empty() {
+                return;
+            }
+            let vis_string = item_mod.vis.to_token_stream().to_string();
+            let vis_prefix = if vis_string.is_empty() {
+                "".to_string()
+            } else {
+                format!("{} ", vis_string.trim_end())
+            };
+            let mod_name_str = item_mod.ident.to_token_stream().to_string();
+            // The module's own items are listed under it, up to `--max-depth` modules deep.
+            let within_depth_limit = module_depth < options.max_module_depth;
+            let def = match &item_mod.content {
+                Some(_) if within_depth_limit => {
+                    format!("{}mod {} {{ /* ... */ }}", vis_prefix, mod_name_str)
+                }
+                Some(_) => format!(
+                    "{}mod {} {{ /* module elided (depth limit) */ }}",
+                    vis_prefix, mod_name_str
+                ),
+                None => format!("{}mod {};", vis_prefix, mod_name_str),
+            };
+            let mod_id = items.len();
+            items.push(ExtractedItem {
+                item_kind: "Module".to_string(),
+                name: mod_name_str,
+                signature_or_definition: def.trim().to_string(),
+                trait_bounds: Vec::new(),
+                doc_comments: docs,
+                id: mod_id,
+                parent: None,
+                parent_impl: None,
+                also_found_in: Vec::new(),
+                item_path: None,
+                line_start,
+                line_end,
+            });
+            if let Some((_, module_items)) = &item_mod.content
+                && within_depth_limit
+            {
+                let first_module_item = extraction.items.len();
+                process_items_syn(
+                    module_items,
+                    source_file,
+                    module_depth + 1,
+                    options,
+                    extraction,
+                );
+                // Items nested in one of the module's items keep that item as their parent.
+                for item in &mut extraction.items[first_module_item..] {
+                    item.parent.get_or_insert(mod_id);
+                }
+            }
+        }
+        syn::Item::Impl(item_impl) => {
+            let mut impl_line_tokens = quote::quote! {};
+            if let Some(defaultness) = &item_impl.defaultness {
+                defaultness.to_tokens(&mut impl_line_tokens);
+                impl_line_tokens.extend(quote::quote! {});
+            }
+            if let Some(unsafety) = &item_impl.unsafety {
+                unsafety.to_tokens(&mut impl_line_tokens);
+                impl_line_tokens.extend(quote::quote! {});
+            }
+            impl_line_tokens.extend(quote::quote! { impl });
+            item_impl.generics.params.to_tokens(&mut impl_line_tokens);
+            if !item_impl.generics.params.is_empty() {
+                impl_line_tokens.extend(quote::quote! {});
+            }
+
+            let mut name_parts: Vec<String> = Vec::new();
+            if let Some((opt_bang, trait_path, _for_keyword)) = &item_impl.trait_ {
+                if opt_bang.is_some() {
+                    impl_line_tokens.extend(quote::quote! { ! });
+                }
+                trait_path.to_tokens(&mut impl_line_tokens);
+                name_parts.push(compact_tokens(trait_path));
+                impl_line_tokens.extend(quote::quote! { for });
+                name_parts.push("for".to_string());
+                impl_line_tokens.extend(quote::quote! {});
+            }
+            item_impl.self_ty.to_tokens(&mut impl_line_tokens);
+            name_parts.push(compact_tokens(&item_impl.self_ty));
+
+            if let Some(where_clause) = &item_impl.generics.where_clause {
+                impl_line_tokens.extend(quote::quote! {});
+                where_clause.to_tokens(&mut impl_line_tokens);
+            }
+
+            let name = if item_impl.trait_.is_none() {
+                compact_tokens(&item_impl.self_ty)
+            } else {
+                format!("impl {}", name_parts.join(" "))
+            };
+            let item_kind_str = if item_impl.trait_.is_some() {
+                "Trait Impl Block".to_string()
+            } else {
+                "Inherent Impl Block".to_string()
+            };
+
+            // Sub-items carry the impl header, e.g. `impl Display for Foo`, alongside the id link.
+            let parent_impl = format!("impl {}", name_parts.join(" "));
+            let impl_block_id = items.len();
+            items.push(ExtractedItem {
+                item_kind: item_kind_str,
+                name,
+                signature_or_definition: impl_line_tokens.to_string().trim().to_string(),
+                trait_bounds: Vec::new(),
+                doc_comments: docs.clone(),
+                id: items.len(),
+                parent: None,
+                parent_impl: None,
+                also_found_in: Vec::new(),
+                item_path: None,
+                line_start,
+                line_end,
+            });
+
+            for impl_item_syn in &item_impl.items {
+                let sub_attrs: &[syn::Attribute] = match impl_item_syn {
+                    syn::ImplItem::Const(item) => &item.attrs,
+                    syn::ImplItem::Fn(item) => &item.attrs,
+                    syn::ImplItem::Type(item) => &item.attrs,
+                    syn::ImplItem::Macro(item) => &item.attrs,
+                    _ => &[],
+                };
+                if !options.include_hidden && is_hidden_or_test_only(sub_attrs) {
+                    extraction.hidden_items_skipped += 1;
+                    continue;
+                }
+                let sub_docs = extract_doc_comments(sub_attrs, source_file);
+                let (sub_line_start, sub_line_end) = item_line_range(impl_item_syn, sub_attrs);
+
+                match impl_item_syn {
+                    syn::ImplItem::Fn(impl_fn) => {
+                        let vis_string = impl_fn.vis.to_token_stream().to_string();
+                        let vis_prefix = if vis_string.is_empty() {
+                            "".to_string()
+                        } else {
+                            format!("{} ", vis_string.trim_end())
+                        };
+                        let sig_def_str = format!(
+                            "{}{}",
+                            vis_prefix,
+                            signature_with_where_lines(&impl_fn.sig, ";")
+                        );
+                        items.push(ExtractedItem {
+                            item_kind: "Impl Method".to_string(),
+                            name: impl_fn.sig.ident.to_string(),
+                            signature_or_definition: sig_def_str.trim().to_string(),
+                            trait_bounds: generic_bounds(&impl_fn.sig.generics),
+                            doc_comments: sub_docs,
+                            id: items.len(),
+                            parent: Some(impl_block_id),
+                            parent_impl: Some(parent_impl.clone()),
+                            also_found_in: Vec::new(),
+                            item_path: None,
+                            line_start: sub_line_start,
+                            line_end: sub_line_end,
+                        });
+                    }
+                    syn::ImplItem::Const(impl_const) => {
+                        let vis_string = impl_const.vis.to_token_stream().to_string();
+                        let vis_prefix = if vis_string.is_empty() {
+                            "".to_string()
+                        } else {
+                            format!("{} ", vis_string.trim_end())
+                        };
+                        let sig_def_str = format!(
+                            "{}const {}: {} = ...;",
+                            vis_prefix,
+                            impl_const.ident.to_token_stream(),
+                            impl_const.ty.to_token_stream()
+                        );
+                        items.push(ExtractedItem {
+                            item_kind: "Impl Associated Constant".to_string(),
+                            name: impl_const.ident.to_string(),
+                            signature_or_definition: sig_def_str.trim().to_string(),
+                            trait_bounds: Vec::new(),
+                            doc_comments: sub_docs,
+                            id: items.len(),
+                            parent: Some(impl_block_id),
+                            parent_impl: Some(parent_impl.clone()),
+                            also_found_in: Vec::new(),
+                            item_path: None,
+                            line_start: sub_line_start,
+                            line_end: sub_line_end,
+                        });
+                    }
+                    syn::ImplItem::Type(impl_type) => {
+                        let vis_string = impl_type.vis.to_token_stream().to_string();
+                        let vis_prefix = if vis_string.is_empty() {
+                            "".to_string()
+                        } else {
+                            format!("{} ", vis_string.trim_end())
+                        };
+                        let sig_def_str = format!(
+                            "{}type {}{} = {};",
+                            vis_prefix,
+                            impl_type.ident.to_token_stream(),
+                            impl_type.generics.to_token_stream(),
+                            impl_type.ty.to_token_stream()
+                        );
+                        items.push(ExtractedItem {
+                            item_kind: "Impl Associated Type".to_string(),
+                            name: impl_type.ident.to_string(),
+                            signature_or_definition: sig_def_str.trim().to_string(),
+                            trait_bounds: Vec::new(),
+                            doc_comments: sub_docs,
+                            id: items.len(),
+                            parent: Some(impl_block_id),
+                            parent_impl: Some(parent_impl.clone()),
+                            also_found_in: Vec::new(),
+                            item_path: None,
+                            line_start: sub_line_start,
+                            line_end: sub_line_end,
+                        });
+                    }
+                    syn::ImplItem::Macro(impl_macro) => {
+                        let sig_def_str = impl_macro.mac.to_token_stream().to_string();
+                        let name = impl_macro.mac.path.segments.last().map_or_else(
+                            || "unknown_macro".to_string(),
+                            |seg| seg.ident.to_string(),
+                        );
+                        items.push(ExtractedItem {
+                            item_kind: "Impl Macro Invocation".to_string(),
+                            name,
+                            signature_or_definition: sig_def_str.trim().to_string(),
+                            trait_bounds: Vec::new(),
+                            doc_comments: sub_docs,
+                            id: items.len(),
+                            parent: Some(impl_block_id),
+                            parent_impl: Some(parent_impl.clone()),
+                            also_found_in: Vec::new(),
+                            item_path: None,
+                            line_start: sub_line_start,
+                            line_end: sub_line_end,
+                        });
+                    }
+                    _ => { /* Verbatim or other unhandled impl items */ }
+                }
+            }
+        }
+        syn::Item::Type(item_type) => {
+            let vis_string = item_type.vis.to_token_stream().to_string();
+            let vis_prefix = if vis_string.is_empty() {
+                "".to_string()
+            } else {
+                format!("{} ", vis_string.trim_end())
+            };
+            let def = format!(
+                "{}type {}{} = {};",
+                vis_prefix,
+                item_type.ident.to_token_stream(),
+                item_type.generics.to_token_stream(),
+                item_type.ty.to_token_stream()
+            );
+            items.push(ExtractedItem {
+                item_kind: "Type Alias".to_string(),
+                name: item_type.ident.to_string(),
+                signature_or_definition: def.trim().to_string(),
+                trait_bounds: Vec::new(),
+                doc_comments: docs,
+                id: items.len(),
+                parent: None,
+                parent_impl: None,
+                also_found_in: Vec::new(),
+                item_path: None,
+                line_start,
+                line_end,
+            });
+        }
+        syn::Item::Const(item_const) => {
+            let vis_string = item_const.vis.to_token_stream().to_string();
+            let vis_prefix = if vis_string.is_empty() {
+                "".to_string()
+            } else {
+                format!("{} ", vis_string.trim_end())
+            };
+            let def = format!(
+                "{}const {}: {} = ...;",
+                vis_prefix,
+                item_const.ident.to_token_stream(),
+                item_const.ty.to_token_stream()
+            );
+            items.push(ExtractedItem {
+                item_kind: "Constant".to_string(),
+                name: item_const.ident.to_string(),
+                signature_or_definition: def.trim().to_string(),
+                trait_bounds: Vec::new(),
+                doc_comments: docs,
+                id: items.len(),
+                parent: None,
+                parent_impl: None,
+                also_found_in: Vec::new(),
+                item_path: None,
+                line_start,
+                line_end,
+            });
+        }
+        syn::Item::Static(item_static) => {
+            let vis_string = item_static.vis.to_token_stream().to_string();
+            let vis_prefix = if vis_string.is_empty() {
+                "".to_string()
+            } else {
+                format!("{} ", vis_string.trim_end())
+            };
+            let def = format!(
+                "{}static {}: {} = ...;",
+                vis_prefix,
+                item_static.ident.to_token_stream(),
+                item_static.ty.to_token_stream()
+            );
+            items.push(ExtractedItem {
+                item_kind: "Static".to_string(),
+                name: item_static.ident.to_string(),
+                signature_or_definition: def.trim().to_string(),
+                trait_bounds: Vec::new(),
+                doc_comments: docs,
+                id: items.len(),
+                parent: None,
+                parent_impl: None,
+                also_found_in: Vec::new(),
+                item_path: None,
+                line_start,
+                line_end,
+            });
+        }
+        syn::Item::ExternCrate(item_ec) => {
+            let def = item_ec.to_token_stream().to_string();
+            let name = if let Some(rename) = &item_ec.rename {
+                rename.1.to_string()
+            } else {
+                item_ec.ident.to_string()
+            };
+            items.push(ExtractedItem {
+                item_kind: "Extern Crate".to_string(),
+                name,
+                signature_or_definition: def.trim().to_string(),
+                trait_bounds: Vec::new(),
+                doc_comments: docs,
+                id: items.len(),
+                parent: None,
+                parent_impl: None,
+                also_found_in: Vec::new(),
+                item_path: None,
+                line_start,
+                line_end,
+            });
+        }
+        syn::Item::Use(item_use) => {
+            let is_public = matches!(item_use.vis, syn::Visibility::Public(_));
+            if docs.is_empty() && !is_public {
+                return;
+            }
+
+            let def = item_use.to_token_stream().to_string();
+            let name_str = item_use.tree.to_token_stream().to_string(); // Renamed from 'name' to avoid conflict
+            let display_name = if name_str.chars().count() > 70 {
+                name_str.chars().take(67).collect::<String>() + "..."
+            } else {
+                name_str
+            };
+            items.push(ExtractedItem {
+                item_kind: "Use Statement".to_string(),
+                name: display_name,
+                signature_or_definition: def.trim().to_string(),
+                trait_bounds: Vec::new(),
+                doc_comments: docs,
+                id: items.len(),
+                parent: None,
+                parent_impl: None,
+                also_found_in: Vec::new(),
+                item_path: None,
+                line_start,
+                line_end,
+            });
+        }
+        _ => { /* Other item types are not processed */ }
+    }
+}
+
+/// Renders a function signature with its `where` clause (if any) moved to lines of its own,
+/// one predicate per line, followed by `terminator` (e.g. `;` for a method declaration).
+fn signature_with_where_lines(sig: &syn::Signature, terminator: &str) -> String {
+    let mut head = sig.clone();
+    let Some(where_clause) = head.generics.where_clause.take() else {
+        return format!("{}{}", sig.to_token_stream(), terminator);
+    };
+    let predicates: Vec<String> = where_clause
+        .predicates
+        .iter()
+        .map(|predicate| format!("    {}", predicate.to_token_stream()))
+        .collect();
+    format!(
+        "{}\nwhere\n{}{}",
+        head.to_token_stream(),
+        predicates.join(",\n"),
+        terminator
+    )
+}
+
+/// The bounds a function puts on its generic parameters: inline ones (`<T: Clone>`) first,
+/// then the `where` clause's predicates.
+fn generic_bounds(generics: &syn::Generics) -> Vec<String> {
+    let inline = generics.params.iter().filter_map(|param| match param {
+        syn::GenericParam::Type(type_param) if !type_param.bounds.is_empty() => Some(format!(
+            "{} : {}",
+            type_param.ident,
+            type_param.bounds.to_token_stream()
+        )),
+        syn::GenericParam::Lifetime(lifetime_param) if !lifetime_param.bounds.is_empty() => {
+            Some(format!(
+                "{} : {}",
+                lifetime_param.lifetime,
+                lifetime_param.bounds.to_token_stream()
+            ))
+        }
+        _ => None,
+    });
+    let where_predicates = generics
+        .where_clause
+        .iter()
+        .flat_map(|where_clause| &where_clause.predicates)
+        .map(|predicate| predicate.to_token_stream().to_string());
+    inline.chain(where_predicates).collect()
+}
+
+/// Item kinds produced for `#[proc_macro]`, `#[proc_macro_derive]`, and `#[proc_macro_attribute]` functions.
+pub(crate) const PROC_MACRO_ITEM_KINDS: [&str; 3] =
+    ["Function-like Macro", "Derive Macro", "Attribute Macro"];
+
+/// Describes the macro a proc-macro entry-point function exposes to users.
+struct ProcMacroEntryPoint {
+    kind: &'static str,
+    public_name: String, // The derive name; other kinds are named after the function
+    usage: String,       // How users invoke it, as comment lines for the report
+}
+
+/// Recognizes proc-macro entry points by their attribute, returning the exposed macro's
+/// kind, public name, and invocation form (including a derive's helper attributes).
+fn proc_macro_entry_point(attrs: &[syn::Attribute], fn_name: &str) -> Option<ProcMacroEntryPoint> {
+    for attr in attrs {
+        if attr.path().is_ident("proc_macro") {
+            return Some(ProcMacroEntryPoint {
+                kind: PROC_MACRO_ITEM_KINDS[0],
+                public_name: fn_name.to_string(),
+                usage: format!("// Invoked as: {}!(...)", fn_name),
+            });
+        }
+        if attr.path().is_ident("proc_macro_attribute") {
+            return Some(ProcMacroEntryPoint {
+                kind: PROC_MACRO_ITEM_KINDS[2],
+                public_name: fn_name.to_string(),
+                usage: format!("// Invoked as: #[{}] or #[{}(...)]", fn_name, fn_name),
+            });
+        }
+        if attr.path().is_ident("proc_macro_derive") {
+            // `#[proc_macro_derive(Name, attributes(helper_a, helper_b))]`
+            let args = attr
+                .parse_args_with(
+                    syn::punctuated::Punctuated::<syn::Meta, syn::Token![,]>::parse_terminated,
+                )
+                .ok()?;
+            let mut derive_name = None;
+            let mut helper_attributes = Vec::new();
+            for arg in args {
+                match arg {
+                    syn::Meta::Path(path) => {
+                        derive_name = path.get_ident().map(|ident| ident.to_string());
+                    }
+                    syn::Meta::List(list) if list.path.is_ident("attributes") => {
+                        helper_attributes.extend(
+                            list.tokens
+                                .to_string()
+                                .split(',')
+                                .map(|helper| helper.trim().to_string())
+                                .filter(|helper| !helper.is_empty()),
+                        );
+                    }
+                    _ => {}
+                }
+            }
+            let derive_name = derive_name?;
+            let mut usage = format!("// Invoked as: #[derive({})]", derive_name);
+            if !helper_attributes.is_empty() {
+                let helpers: Vec<String> = helper_attributes
+                    .iter()
+                    .map(|h| format!("#[{}(...)]", h))
+                    .collect();
+                usage.push_str(&format!("\n// Helper attributes: {}", helpers.join(", ")));
+            }
+            return Some(ProcMacroEntryPoint {
+                kind: PROC_MACRO_ITEM_KINDS[1],
+                public_name: derive_name,
+                usage,
+            });
+        }
+    }
+    None
+}
+
+/// Computes the 1-based `(first, last)` source lines of an item, excluding its outer
+/// attributes (doc comments included) so the range starts at the item itself.
+/// Relies on proc-macro2's `span-locations` feature to report real line numbers.
+fn item_line_range(item: &impl ToTokens, attrs: &[syn::Attribute]) -> (usize, usize) {
+    // Each outer attribute is emitted as two tokens: `#` and the bracketed group.
+    let outer_attr_token_count = attrs
+        .iter()
+        .filter(|attr| matches!(attr.style, syn::AttrStyle::Outer))
+        .count()
+        * 2;
+    let mut tokens = item
+        .to_token_stream()
+        .into_iter()
+        .skip(outer_attr_token_count);
+    let Some(first_token) = tokens.next() else {
+        return (0, 0);
+    };
+    let line_start = first_token.span().start().line;
+    let line_end = tokens
+        .last()
+        .map_or(first_token.span().end().line, |token| {
+            token.span().end().line
+        });
+    (line_start, line_end)
+}
+
+/// Largest file `#[doc = include_str!(..)]` inlines in full; longer ones are cut off.
+const MAX_INCLUDED_DOC_BYTES: usize = 16 * 1024;
+
+/// The doc comment lines of an item, in attribute order: `///` comments and
+/// `#[doc = "..."]`, the text of `#[doc = include_str!("..")]` (read relative to
+/// `source_file`), and the docs of `#[cfg_attr(condition, doc = "...")]` after a line
+/// naming the condition.
+fn extract_doc_comments(attrs: &[syn::Attribute], source_file: &Path) -> Vec<String> {
+    let mut doc_lines = Vec::new();
+    for attr in attrs {
+        match &attr.meta {
+            syn::Meta::NameValue(meta_name_value) if attr.path().is_ident("doc") => {
+                doc_lines.extend(doc_value_lines(&meta_name_value.value, source_file));
+            }
+            syn::Meta::List(meta_list) if attr.path().is_ident("cfg_attr") => {
+                // `cfg_attr(condition, attr, ...)`; only the `doc = ...` attributes matter.
+                let Ok(arguments) = meta_list.parse_args_with(
+                    syn::punctuated::Punctuated::<syn::Meta, syn::Token![,]>::parse_terminated,
+                ) else {
+                    continue;
+                };
+                let mut arguments = arguments.into_iter();
+                let Some(condition) = arguments.next() else {
+                    continue;
+                };
+                let conditional_lines: Vec<String> = arguments
+                    .filter_map(|argument| match argument {
+                        syn::Meta::NameValue(meta_name_value)
+                            if meta_name_value.path.is_ident("doc") =>
+                        {
+                            Some(doc_value_lines(&meta_name_value.value, source_file))
+                        }
+                        _ => None,
+                    })
+                    .flatten()
+                    .collect();
+                if !conditional_lines.is_empty() {
+                    doc_lines.push(format!(
+                        "(Only documented with `cfg({})`:)",
+                        condition.to_token_stream()
+                    ));
+                    doc_lines.extend(conditional_lines);
+                }
+            }
+            _ => { /* Other attributes, and other meta forms for `doc` (like `#[doc(hidden)]`) */ }
+        }
+    }
+    doc_lines
+}
+
+/// The lines a `doc = ...` value contributes: a string literal as one line, or the lines of
+/// the file an `include_str!` names.
+fn doc_value_lines(value: &syn::Expr, source_file: &Path) -> Vec<String> {
+    match value {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(lit_str),
+            ..
+        }) => {
+            // Only the space after `///` goes; the rest of the indentation is Markdown.
+            let value = lit_str.value();
+            vec![
+                value
+                    .strip_prefix(' ')
+                    .unwrap_or(&value)
+                    .trim_end()
+                    .to_string(),
+            ]
+        }
+        syn::Expr::Macro(expr_macro) if expr_macro.mac.path.is_ident("include_str") => {
+            let Ok(included) = expr_macro.mac.parse_body::<syn::LitStr>() else {
+                return Vec::new();
+            };
+            let included_path = source_file
+                .parent()
+                .unwrap_or(Path::new("."))
+                .join(included.value());
+            let Ok(text) = fs::read_to_string(&included_path) else {
+                return vec![format!(
+                    "(Docs are included from `{}`, which could not be read.)",
+                    included.value()
+                )];
+            };
+            let mut end = text.len().min(MAX_INCLUDED_DOC_BYTES);
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            let mut lines: Vec<String> = text[..end]
+                .lines()
+                .map(|line| line.trim_end().to_string())
+                .collect();
+            if end < text.len() {
+                lines.push(format!(
+                    "(… cut off after {} of the {} bytes of `{}`.)",
+                    end,
+                    text.len(),
+                    included.value()
+                ));
+            }
+            lines
+        }
+        _ => Vec::new(),
+    }
+}
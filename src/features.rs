@@ -0,0 +1,1030 @@
+// getdoc - features.rs
+//
+// Planning which feature combinations `cargo check` is run with.
+
+// --- Standard Library Imports ---
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+// --- External Crate Imports ---
+use serde::{Deserialize, Serialize};
+
+// --- Crate Imports ---
+use crate::cargo::cargo_command;
+use crate::error::GetdocError;
+use crate::progress::{info, warning};
+use crate::util::quoted_list;
+
+// --- Public Types ---
+
+/// A package's `[features]` table: feature name -> features/dependencies it enables.
+/// Kept sorted by name so every plan built from it is deterministic.
+pub type FeatureTable = BTreeMap<String, Vec<String>>;
+
+/// One `cargo check` configuration: the feature arguments passed to cargo and a
+/// human-readable description of them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FeatureSet {
+    /// Cargo arguments, e.g. `["--no-default-features", "--features", "fancy"]`.
+    /// Sets planned for a workspace member start with `-p <member>`.
+    pub args: Vec<String>,
+    /// Description used in messages and reports, e.g. `default features`.
+    pub description: String,
+    /// Descriptions of other planned sets that activate exactly the same features and
+    /// were folded into this one.
+    pub equivalent_sets: Vec<String>,
+    /// The workspace member this set is checked for, when planned at a virtual workspace root.
+    pub package: Option<String>,
+    /// Targets cargo silently skips under this set because their `required-features`
+    /// are not all enabled, e.g. ``bin `cli` (requires: cli)``.
+    pub skipped_targets: Vec<String>,
+}
+
+impl FeatureSet {
+    /// Creates a feature set from cargo arguments; an empty list means default features.
+    pub fn new(args: Vec<String>) -> Self {
+        let description = if args.is_empty() {
+            "default features".to_string()
+        } else {
+            args.join(" ")
+        };
+        Self {
+            args,
+            description,
+            equivalent_sets: Vec::new(),
+            package: None,
+            skipped_targets: Vec::new(),
+        }
+    }
+
+    /// Restricts this set to the workspace member `package` (`cargo check -p <package> ...`).
+    pub fn for_package(mut self, package: &str) -> Self {
+        self.args
+            .splice(0..0, ["-p".to_string(), package.to_string()]);
+        self.description = format!("{}: {}", package, self.description);
+        for equivalent in &mut self.equivalent_sets {
+            *equivalent = format!("{}: {}", package, equivalent);
+        }
+        self.package = Some(package.to_string());
+        self
+    }
+}
+
+/// Options for [`plan_feature_sets`].
+#[derive(Debug, Clone, Default)]
+pub struct FeaturePlanOptions {
+    /// Features to focus on ("Targeted Mode"). `None` plans "Comprehensive Mode",
+    /// which covers default, no-default, each individual feature, and all features.
+    pub target_features: Option<Vec<String>>,
+    /// In Targeted Mode, plan exactly `--features <targets>` and nothing else.
+    pub manifest_features_only: bool,
+    /// Plan the matrix `cargo hack check --each-feature` would run instead (see
+    /// [`plan_feature_sets`]). Takes precedence over `target_features`.
+    pub each_feature: bool,
+    /// With `each_feature`, features that get no individual run.
+    pub exclude_features: Vec<String>,
+    /// With `each_feature`, also give the implicit features of optional dependencies
+    /// their own runs (cargo-hack's `--optional-deps`).
+    pub optional_deps: bool,
+    /// With `each_feature`, leave out the final `--all-features` run.
+    pub exclude_all_features: bool,
+    /// Add the `required-features` of any target a set would skip, so every target is
+    /// always built.
+    pub ensure_targets: bool,
+    /// Sets read from `--features-from-file`, planned exactly as listed. Takes precedence
+    /// over all of the above except `ensure_targets`.
+    pub feature_list: Option<FeatureList>,
+    /// Cargo configuration overrides, each passed to `cargo metadata` as `--config`.
+    pub config_overrides: Vec<String>,
+}
+
+/// A `--features-from-file` list: one feature set per line.
+#[derive(Debug, Clone)]
+pub struct FeatureList {
+    pub path: PathBuf,
+    pub sets: Vec<ListedFeatureSet>,
+}
+
+/// One line of a [`FeatureList`].
+#[derive(Debug, Clone)]
+pub struct ListedFeatureSet {
+    /// Line number in the file, for error messages.
+    pub line: usize,
+    /// The features the line names (without keywords).
+    pub features: Vec<String>,
+    /// Cargo arguments, e.g. `["--no-default-features", "--features", "a,b"]`.
+    pub args: Vec<String>,
+}
+
+// --- Manifest Structs ---
+
+#[derive(Deserialize, Debug)]
+struct CargoToml {
+    #[serde(default)]
+    features: FeatureTable,
+}
+
+/// The parts of `cargo metadata --format-version 1` output that getdoc uses.
+#[derive(Deserialize, Debug)]
+struct CargoMetadata {
+    packages: Vec<MetadataPackage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MetadataPackage {
+    name: String,
+    manifest_path: PathBuf,
+    // Resolved feature table, including implicit features for optional dependencies.
+    #[serde(default)]
+    features: FeatureTable,
+    #[serde(default)]
+    dependencies: Vec<MetadataDependency>,
+    #[serde(default)]
+    targets: Vec<MetadataTarget>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MetadataTarget {
+    name: String,
+    kind: Vec<String>,
+    #[serde(rename = "required-features", default)]
+    required_features: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MetadataDependency {
+    name: String,
+    rename: Option<String>,
+    #[serde(default)]
+    optional: bool,
+}
+
+/// A package's feature table plus which of its features only exist implicitly
+/// because of an optional dependency (`foo = ["dep:foo"]` generated by cargo).
+#[derive(Debug, Clone, Default)]
+pub struct PackageFeatures {
+    pub table: FeatureTable,
+    pub optional_dependency_features: BTreeSet<String>,
+    /// Binaries built by a plain `cargo check` that declare `required-features`.
+    pub gated_targets: Vec<GatedTarget>,
+    /// Names of the package's dependencies (renamed ones by their new name), or `None` when
+    /// the features were read from the manifest alone.
+    pub dependencies: Option<BTreeSet<String>>,
+}
+
+/// A target that cargo only builds when all of its `required-features` are enabled.
+#[derive(Debug, Clone)]
+pub struct GatedTarget {
+    /// Display label, e.g. ``bin `cli` ``.
+    pub label: String,
+    pub required_features: Vec<String>,
+}
+
+/// The features behind a manifest: a single package, or every member of a virtual
+/// workspace (a manifest with `[workspace]` but no `[package]`), in name order.
+#[derive(Debug, Clone)]
+pub enum ManifestFeatures {
+    Package(PackageFeatures),
+    VirtualWorkspace(Vec<(String, PackageFeatures)>),
+}
+
+// --- Feature Lists ---
+
+/// Reads a `--features-from-file` list. Each line is a comma-separated feature set, checked
+/// with `--no-default-features` unless it includes `*default*`; `*default*` alone means the
+/// default features, `*none*` no features, and `*all*` `--all-features`. Blank lines and
+/// lines starting with `#` are ignored.
+pub fn read_feature_list(path: &Path) -> Result<FeatureList, GetdocError> {
+    let list_error = |message: String| GetdocError::FeatureList {
+        path: path.to_path_buf(),
+        message,
+    };
+    let contents = fs::read_to_string(path).map_err(|e| list_error(e.to_string()))?;
+    let mut sets = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let entries: Vec<&str> = line
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .collect();
+        let (keywords, features): (Vec<&str>, Vec<&str>) = entries
+            .iter()
+            .partition(|entry| entry.starts_with('*') && entry.ends_with('*'));
+        let features: Vec<String> = features.into_iter().map(str::to_string).collect();
+        let args = match keywords.as_slice() {
+            [] => vec![
+                "--no-default-features".to_string(),
+                "--features".to_string(),
+                features.join(","),
+            ],
+            ["*default*"] if features.is_empty() => vec![],
+            ["*default*"] => vec!["--features".to_string(), features.join(",")],
+            ["*none*"] if features.is_empty() => vec!["--no-default-features".to_string()],
+            ["*all*"] if features.is_empty() => vec!["--all-features".to_string()],
+            ["*none*" | "*all*"] => {
+                return Err(list_error(format!(
+                    "line {}: `{}` cannot be combined with features",
+                    line_number, keywords[0]
+                )));
+            }
+            _ => {
+                return Err(list_error(format!(
+                    "line {}: expected features and at most one of `*default*`, `*none*`, `*all*`, found {}",
+                    line_number,
+                    quoted_list(&keywords)
+                )));
+            }
+        };
+        sets.push(ListedFeatureSet {
+            line: line_number,
+            features,
+            args,
+        });
+    }
+    if sets.is_empty() {
+        return Err(list_error("lists no feature sets".to_string()));
+    }
+    Ok(FeatureList {
+        path: path.to_path_buf(),
+        sets,
+    })
+}
+
+/// Plans the sets of a [`FeatureList`] in file order, after checking that the manifest
+/// declares every feature they name. At a virtual workspace root, each set is planned for
+/// every member declaring all of its features.
+fn plan_listed_feature_sets(
+    feature_list: &FeatureList,
+    manifest_features: &ManifestFeatures,
+    options: &FeaturePlanOptions,
+) -> Result<Vec<FeatureSet>, GetdocError> {
+    let packages: Vec<(Option<&str>, &PackageFeatures)> = match manifest_features {
+        ManifestFeatures::Package(package_features) => vec![(None, package_features)],
+        ManifestFeatures::VirtualWorkspace(members) => members
+            .iter()
+            .map(|(name, package_features)| (Some(name.as_str()), package_features))
+            .collect(),
+    };
+    // `dep/feature` entries name a dependency's feature, not one of the package's own.
+    let declares = |package_features: &PackageFeatures, feature: &String| {
+        feature.contains('/') || package_features.table.contains_key(feature)
+    };
+
+    let mut feature_sets = Vec::new();
+    for listed in &feature_list.sets {
+        let declaring_packages: Vec<_> = packages
+            .iter()
+            .filter(|(_, package_features)| {
+                listed
+                    .features
+                    .iter()
+                    .all(|feature| declares(package_features, feature))
+            })
+            .collect();
+        if declaring_packages.is_empty() {
+            let undeclared: Vec<&String> = listed
+                .features
+                .iter()
+                .filter(|feature| {
+                    !packages
+                        .iter()
+                        .any(|(_, package_features)| declares(package_features, feature))
+                })
+                .collect();
+            let message = if undeclared.is_empty() {
+                format!(
+                    "line {}: no workspace member declares all of {}",
+                    listed.line,
+                    quoted_list(&listed.features)
+                )
+            } else {
+                format!(
+                    "line {}: {} not declared in the manifest's `[features]`",
+                    listed.line,
+                    quoted_list(&undeclared)
+                )
+            };
+            return Err(GetdocError::FeatureList {
+                path: feature_list.path.clone(),
+                message,
+            });
+        }
+        for (package, package_features) in declaring_packages {
+            let mut args = listed.args.clone();
+            if options.ensure_targets {
+                add_required_target_features(&mut args, package_features);
+            }
+            let mut feature_set = FeatureSet::new(args);
+            feature_set.skipped_targets =
+                skipped_target_labels(&feature_set.args, package_features);
+            feature_sets.push(match package {
+                Some(package) => feature_set.for_package(package),
+                None => feature_set,
+            });
+        }
+    }
+    Ok(feature_sets)
+}
+
+// --- Planning ---
+
+/// Determines the feature sets to pass to `cargo check` for the package at `manifest_path`.
+/// Sets that activate exactly the same features are merged, with the later ones recorded
+/// in the kept set's `equivalent_sets`.
+///
+/// With `each_feature`, the sets follow `cargo hack --each-feature` exactly, in this order
+/// and without merging equivalent sets, so results line up run for run:
+/// 1. default features;
+/// 2. `--no-default-features`;
+/// 3. `--no-default-features --features <f>` for each feature `f` in name order, skipping
+///    `default`, `exclude_features`, and (unless `optional_deps`) the implicit features of
+///    optional dependencies;
+/// 4. `--all-features`, unless `exclude_all_features`.
+///
+/// Comprehensive Mode checks default features, `--no-default-features`, then
+/// `--no-default-features --features <f>` for each feature in name order, then
+/// `--all-features`. The order never depends on how the manifest lists its features, so
+/// reports and their "Occurred under feature set(s)" lists stay comparable across runs.
+///
+/// At a virtual workspace root, each member gets its own plan from its own features, run
+/// with `-p <member>`. In Targeted Mode only members declaring all targeted features are
+/// planned (all members if none do, so cargo reports the unknown features).
+pub fn plan_feature_sets(
+    manifest_path: &Path,
+    options: &FeaturePlanOptions,
+) -> Result<Vec<FeatureSet>, GetdocError> {
+    let manifest_features = load_manifest_features(manifest_path, &options.config_overrides);
+    plan_manifest_feature_sets(&manifest_features, options)
+}
+
+/// Plans the feature sets for features already loaded with [`load_manifest_features`]; see
+/// [`plan_feature_sets`].
+pub fn plan_manifest_feature_sets(
+    manifest_features: &ManifestFeatures,
+    options: &FeaturePlanOptions,
+) -> Result<Vec<FeatureSet>, GetdocError> {
+    if let Some(feature_list) = options.feature_list.as_ref() {
+        info!(
+            "[getdoc] Determining feature checks from the feature list {} ({} set(s)).",
+            feature_list.path.display(),
+            feature_list.sets.len()
+        );
+    } else if options.each_feature {
+        info!("[getdoc] Determining feature checks for Each-Feature Mode (cargo-hack compatible).");
+    } else if let Some(targets) = options.target_features.as_ref() {
+        info!(
+            "[getdoc] Determining feature checks for Targeted Mode (context: {:?})",
+            targets
+        );
+    } else {
+        info!("[getdoc] Determining feature checks for Comprehensive Mode.");
+    }
+
+    if let Some(feature_list) = options.feature_list.as_ref() {
+        return plan_listed_feature_sets(feature_list, manifest_features, options);
+    }
+    let members = match manifest_features {
+        ManifestFeatures::Package(package_features) => {
+            return Ok(plan_package_feature_sets(package_features, options));
+        }
+        ManifestFeatures::VirtualWorkspace(members) => members,
+    };
+    let member_names: Vec<&str> = members.iter().map(|(name, _)| name.as_str()).collect();
+    info!(
+        "[getdoc] Virtual workspace detected: planning feature sets for {} member(s): {}.",
+        members.len(),
+        member_names.join(", ")
+    );
+
+    let mut planned_members: Vec<&(String, PackageFeatures)> = members.iter().collect();
+    if !options.each_feature
+        && let Some(targets) = options.target_features.as_ref()
+    {
+        let declaring_members: Vec<_> = members
+            .iter()
+            .filter(|(_, package_features)| {
+                targets
+                    .iter()
+                    .all(|target| package_features.table.contains_key(target))
+            })
+            .collect();
+        if declaring_members.is_empty() {
+            info!(
+                "[getdoc] No workspace member declares all of {:?}; planning for every member.",
+                targets
+            );
+        } else {
+            planned_members = declaring_members;
+        }
+    }
+
+    Ok(planned_members
+        .into_iter()
+        .flat_map(|(name, package_features)| {
+            plan_package_feature_sets(package_features, options)
+                .into_iter()
+                .map(move |feature_set| feature_set.for_package(name))
+        })
+        .collect())
+}
+
+/// Plans the feature sets of a single package; see [`plan_feature_sets`].
+fn plan_package_feature_sets(
+    package_features: &PackageFeatures,
+    options: &FeaturePlanOptions,
+) -> Vec<FeatureSet> {
+    let feature_table = &package_features.table;
+    let mut sets: Vec<Vec<String>> = Vec::new();
+
+    if options.each_feature {
+        sets = each_feature_sets(package_features, options);
+    } else if let Some(targets) = options.target_features.as_ref() {
+        if targets.is_empty() {
+            info!(
+                "[getdoc] Targeted features list is empty. Checking with crate default features only."
+            );
+            sets.push(vec![]);
+        } else if options.manifest_features_only {
+            let features_arg_string = targets.join(",");
+            info!(
+                "[getdoc] --manifest-features-only: checking only `--features {}`.",
+                features_arg_string
+            );
+            sets.push(vec!["--features".to_string(), features_arg_string]);
+        } else {
+            let features_arg_string = targets.join(",");
+            // Always check the targeted feature(s) with --no-default-features for the project.
+            sets.push(vec![
+                "--no-default-features".to_string(),
+                "--features".to_string(),
+                features_arg_string.clone(),
+            ]);
+
+            // If more than one feature is specified by the user (e.g., "feat1,feat2"),
+            // then also check their combination together WITH the project's default features.
+            if targets.len() > 1 {
+                info!(
+                    "[getdoc] Multiple features targeted ('{}'): also checking their combination with project default features.",
+                    features_arg_string
+                );
+                sets.push(vec!["--features".to_string(), features_arg_string.clone()]);
+            } else {
+                // If only a SINGLE feature is targeted (e.g., `getdoc --features backend_mkl`),
+                // skip the check that combines this single targeted feature
+                // WITH the project's default features.
+                info!(
+                    "[getdoc] Single feature targeted ('{}'): skipping check that combines it with project default features to avoid potential conflicts. It is already checked with --no-default-features.",
+                    features_arg_string
+                );
+            }
+
+            // Always check the project's default features independently.
+            sets.push(vec![]);
+        }
+    } else {
+        sets.push(vec![]);
+        // Always planned so the minimal configuration is covered. When `default` is empty
+        // or absent it resolves to the default run and is folded into it below, which the
+        // report lists under "Equivalent Feature Sets".
+        sets.push(vec!["--no-default-features".to_string()]);
+
+        if !feature_table.is_empty() {
+            // The shortcut below would otherwise cost a full `cargo check` that repeats
+            // another run exactly. (Each-feature mode keeps it, matching cargo-hack.)
+            // `FeatureTable` is a `BTreeMap`: per-feature runs come in name order.
+            let mut named_features = feature_table.keys().filter(|name| *name != "default");
+            for feature_name in named_features.clone() {
+                sets.push(vec![
+                    "--no-default-features".to_string(),
+                    "--features".to_string(),
+                    feature_name.clone(),
+                ]);
+            }
+            if let (Some(only_feature), None) = (named_features.next(), named_features.next()) {
+                info!(
+                    "[getdoc] Skipping `--all-features`: `{}` is the crate's only feature and already has its own run.",
+                    only_feature
+                );
+            } else {
+                sets.push(vec!["--all-features".to_string()]);
+            }
+        }
+    }
+
+    if options.ensure_targets {
+        for set in &mut sets {
+            add_required_target_features(set, package_features);
+        }
+    }
+
+    let mut feature_sets = if options.each_feature {
+        sets.into_iter().map(FeatureSet::new).collect()
+    } else {
+        let mut unique_sets_str: HashSet<String> = HashSet::new();
+        let mut unique_sets_vec: Vec<Vec<String>> = Vec::new();
+        for set in sets {
+            let mut sorted_set_for_key = set.clone();
+            sorted_set_for_key.sort();
+            let set_key = sorted_set_for_key.join(" ");
+            if unique_sets_str.insert(set_key) {
+                unique_sets_vec.push(set);
+            }
+        }
+        collapse_equivalent_feature_sets(unique_sets_vec, feature_table)
+    };
+    for feature_set in &mut feature_sets {
+        feature_set.skipped_targets = skipped_target_labels(&feature_set.args, package_features);
+    }
+    feature_sets
+}
+
+/// Labels of the targets a set of feature arguments leaves unbuilt, with what they require.
+fn skipped_target_labels(
+    feature_args: &[String],
+    package_features: &PackageFeatures,
+) -> Vec<String> {
+    unbuilt_targets(feature_args, package_features)
+        .map(|target| {
+            format!(
+                "{} (requires: {})",
+                target.label,
+                target.required_features.join(", ")
+            )
+        })
+        .collect()
+}
+
+/// The gated targets whose `required-features` a set of feature arguments does not enable.
+fn unbuilt_targets<'a>(
+    feature_args: &[String],
+    package_features: &'a PackageFeatures,
+) -> impl Iterator<Item = &'a GatedTarget> {
+    let selection = resolved_feature_selection(feature_args, &package_features.table);
+    package_features.gated_targets.iter().filter(move |target| {
+        !target
+            .required_features
+            .iter()
+            .all(|feature| selection.contains(feature))
+    })
+}
+
+/// Extends `feature_args` with the missing `required-features` of every target they would
+/// leave unbuilt (`--ensure-targets`).
+fn add_required_target_features(
+    feature_args: &mut Vec<String>,
+    package_features: &PackageFeatures,
+) {
+    let missing: BTreeSet<&String> = unbuilt_targets(feature_args, package_features)
+        .flat_map(|target| &target.required_features)
+        .collect();
+    if missing.is_empty() {
+        return;
+    }
+    let missing_list = missing
+        .into_iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(",");
+    match feature_args.iter().position(|arg| arg == "--features") {
+        Some(index) if index + 1 < feature_args.len() => {
+            let list = &mut feature_args[index + 1];
+            list.push(',');
+            list.push_str(&missing_list);
+        }
+        _ => feature_args.extend(["--features".to_string(), missing_list]),
+    }
+}
+
+/// Builds the `--each-feature` matrix described on [`plan_feature_sets`].
+fn each_feature_sets(
+    package_features: &PackageFeatures,
+    options: &FeaturePlanOptions,
+) -> Vec<Vec<String>> {
+    let mut sets = vec![vec![], vec!["--no-default-features".to_string()]];
+    for feature_name in package_features.table.keys() {
+        let excluded = feature_name == "default"
+            || options.exclude_features.contains(feature_name)
+            || (!options.optional_deps
+                && package_features
+                    .optional_dependency_features
+                    .contains(feature_name));
+        if !excluded {
+            sets.push(vec![
+                "--no-default-features".to_string(),
+                "--features".to_string(),
+                feature_name.clone(),
+            ]);
+        }
+    }
+    if !options.exclude_all_features {
+        sets.push(vec!["--all-features".to_string()]);
+    }
+    sets
+}
+
+/// Computes the full set of features (and `dep:`/`dep/feature` entries) that a set of cargo
+/// feature arguments activates, following implications through `feature_table`.
+fn resolved_feature_selection(
+    feature_args: &[String],
+    feature_table: &FeatureTable,
+) -> BTreeSet<String> {
+    let mut pending: Vec<String> = Vec::new();
+    let mut default_features = true;
+    let mut args = feature_args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--no-default-features" => default_features = false,
+            "--all-features" => pending.extend(feature_table.keys().cloned()),
+            "--features" => {
+                if let Some(list) = args.next() {
+                    pending.extend(
+                        list.split([',', ' '])
+                            .filter(|name| !name.is_empty())
+                            .map(String::from),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+    if default_features && feature_table.contains_key("default") {
+        pending.push("default".to_string());
+    }
+
+    let mut resolved = BTreeSet::new();
+    while let Some(feature) = pending.pop() {
+        if !resolved.insert(feature.clone()) {
+            continue;
+        }
+        if let Some(enables) = feature_table.get(&feature) {
+            pending.extend(enables.iter().cloned());
+        }
+        // `foo/bar` also turns on the feature `foo` (unlike the weak `foo?/bar`).
+        if let Some((dependency, _)) = feature.split_once('/')
+            && feature_table.contains_key(dependency)
+        {
+            pending.push(dependency.to_string());
+        }
+    }
+    // `default` only matters through what it enables; code almost never tests for it directly.
+    resolved.remove("default");
+    resolved
+}
+
+/// Keeps the first of each group of feature sets that activate exactly the same features,
+/// noting the others in its `equivalent_sets`.
+fn collapse_equivalent_feature_sets(
+    feature_sets: Vec<Vec<String>>,
+    feature_table: &FeatureTable,
+) -> Vec<FeatureSet> {
+    let mut kept_sets: Vec<FeatureSet> = Vec::new();
+    let mut kept_selections: Vec<BTreeSet<String>> = Vec::new();
+    for feature_args in feature_sets {
+        let selection = resolved_feature_selection(&feature_args, feature_table);
+        let feature_set = FeatureSet::new(feature_args);
+        match kept_selections.iter().position(|kept| *kept == selection) {
+            Some(kept_index) => kept_sets[kept_index]
+                .equivalent_sets
+                .push(feature_set.description),
+            None => {
+                kept_sets.push(feature_set);
+                kept_selections.push(selection);
+            }
+        }
+    }
+    kept_sets
+}
+
+/// Cuts `feature_sets` down to at most `sample_size` sets (or just the mandatory ones, if
+/// those alone exceed it). The mandatory sets — default features, `--no-default-features`,
+/// and `--all-features`, per workspace member — are always kept; the rest are drawn at
+/// random from `seed`, so the same seed and plan always give the same sample. Order is kept.
+pub fn sample_feature_sets(
+    feature_sets: Vec<FeatureSet>,
+    sample_size: usize,
+    seed: u64,
+) -> Vec<FeatureSet> {
+    if feature_sets.len() <= sample_size {
+        return feature_sets;
+    }
+    let (mandatory, optional): (Vec<usize>, Vec<usize>) =
+        (0..feature_sets.len()).partition(|&index| is_mandatory_set(&feature_sets[index]));
+    let mut candidates = optional;
+    let draw_count = sample_size.saturating_sub(mandatory.len());
+    // Partial Fisher-Yates shuffle: the first `draw_count` candidates end up the sample.
+    let mut rng = SplitMix64(seed);
+    for position in 0..draw_count.min(candidates.len()) {
+        let remaining = (candidates.len() - position) as u64;
+        let pick = position + (rng.next() % remaining) as usize;
+        candidates.swap(position, pick);
+    }
+    candidates.truncate(draw_count);
+    let kept: BTreeSet<usize> = mandatory.into_iter().chain(candidates).collect();
+    feature_sets
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| kept.contains(index))
+        .map(|(_, feature_set)| feature_set)
+        .collect()
+}
+
+/// Default features, `--no-default-features`, and `--all-features` (ignoring `-p <member>`).
+fn is_mandatory_set(feature_set: &FeatureSet) -> bool {
+    let args = match feature_set.args.as_slice() {
+        [flag, _, rest @ ..] if flag == "-p" => rest,
+        args => args,
+    };
+    matches!(args, [] | [_])
+        && args
+            .iter()
+            .all(|arg| arg == "--no-default-features" || arg == "--all-features")
+}
+
+/// The SplitMix64 generator: tiny, and its output for a seed never changes between
+/// versions, which keeps `--seed` reproducible.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+/// Checks the `--features` of a feature set against the features loaded with
+/// [`load_manifest_features`], failing the way cargo would for a feature the package does not
+/// declare, a `dep/feature` naming something other than a dependency, or explicit `dep:`
+/// syntax. Features of dependencies are not known without resolving them and are left to
+/// cargo. Sets without `--features` are always accepted, as is everything when the features
+/// were read from the manifest alone. A workspace member's set is checked against that member.
+pub fn validate_feature_set(
+    manifest_features: &ManifestFeatures,
+    feature_set: &FeatureSet,
+) -> Result<(), String> {
+    let (member, feature_args) = match feature_set.args.as_slice() {
+        [flag, member, rest @ ..] if flag == "-p" => (Some(member.as_str()), rest),
+        args => (None, args),
+    };
+    let package_features = match (manifest_features, member) {
+        (ManifestFeatures::Package(package_features), _) => package_features,
+        (ManifestFeatures::VirtualWorkspace(members), Some(member)) => {
+            match members.iter().find(|(name, _)| name == member) {
+                Some((_, package_features)) => package_features,
+                None => return Ok(()),
+            }
+        }
+        (ManifestFeatures::VirtualWorkspace(_), None) => return Ok(()),
+    };
+    let Some(dependencies) = package_features.dependencies.as_ref() else {
+        return Ok(());
+    };
+    let package = member
+        .map(|member| format!("the package '{}'", member))
+        .unwrap_or_else(|| "the package".to_string());
+    let requested = feature_args
+        .windows(2)
+        .filter(|pair| pair[0] == "--features")
+        .flat_map(|pair| pair[1].split([',', ' ']))
+        .filter(|feature| !feature.is_empty());
+    for feature in requested {
+        if feature.starts_with("dep:") {
+            return Err(format!(
+                "feature `{}` is not allowed to use explicit `dep:` syntax",
+                feature
+            ));
+        }
+        let declared = match feature.split_once('/') {
+            Some((dependency, _)) => {
+                dependencies.contains(dependency.strip_suffix('?').unwrap_or(dependency))
+            }
+            None => package_features.table.contains_key(feature),
+        };
+        if !declared {
+            return Err(format!(
+                "{} does not contain this feature: {}",
+                package, feature
+            ));
+        }
+    }
+    Ok(())
+}
+
+// --- Feature Discovery ---
+
+/// Loads the features behind `manifest_path`, preferring `cargo metadata` and falling
+/// back to reading the manifest directly (which cannot see workspace members).
+/// `config_overrides` are passed to cargo as `--config` options.
+pub fn load_manifest_features(
+    manifest_path: &Path,
+    config_overrides: &[String],
+) -> ManifestFeatures {
+    features_from_cargo_metadata(manifest_path, config_overrides).unwrap_or_else(|e| {
+        warning!(
+            "[getdoc] Warning: {}. Falling back to reading {}.",
+            e,
+            manifest_path.display()
+        );
+        // A manifest on its own never declares implicit optional-dependency features.
+        let table = features_from_cargo_toml(manifest_path).unwrap_or_else(|e| {
+            warning!(
+                "[getdoc] Warning: {}. Proceeding with default features check only.",
+                e
+            );
+            FeatureTable::new()
+        });
+        ManifestFeatures::Package(PackageFeatures {
+            table,
+            ..PackageFeatures::default()
+        })
+    })
+}
+
+/// Reads the features of the package at `manifest_path` from `cargo metadata`, or of
+/// every workspace member when it is a virtual workspace manifest.
+fn features_from_cargo_metadata(
+    manifest_path: &Path,
+    config_overrides: &[String],
+) -> Result<ManifestFeatures, GetdocError> {
+    let metadata_error = |message: String| GetdocError::CargoMetadata {
+        manifest_path: manifest_path.to_path_buf(),
+        message,
+    };
+    let output = cargo_command(config_overrides)
+        .args([
+            "metadata",
+            "--format-version",
+            "1",
+            "--no-deps",
+            "--manifest-path",
+        ])
+        .arg(manifest_path)
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|source| match source.kind() {
+            std::io::ErrorKind::NotFound => GetdocError::CargoNotFound { source },
+            _ => metadata_error(source.to_string()),
+        })?;
+    if !output.status.success() {
+        return Err(metadata_error(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    let metadata: CargoMetadata = serde_json::from_slice(&output.stdout)
+        .map_err(|e| metadata_error(format!("unexpected output: {}", e)))?;
+
+    let current_manifest = fs::canonicalize(manifest_path).ok();
+    if let Some(package) = metadata
+        .packages
+        .iter()
+        .find(|package| Some(&package.manifest_path) == current_manifest.as_ref())
+    {
+        return Ok(ManifestFeatures::Package(package_features(package)));
+    }
+    // No package lives at the manifest itself: with `--no-deps`, the packages listed are
+    // exactly the workspace members.
+    let mut members: Vec<(String, PackageFeatures)> = metadata
+        .packages
+        .iter()
+        .map(|package| (package.name.clone(), package_features(package)))
+        .collect();
+    members.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(ManifestFeatures::VirtualWorkspace(members))
+}
+
+/// Extracts the feature table of one package from its `cargo metadata` entry.
+fn package_features(package: &MetadataPackage) -> PackageFeatures {
+    let mut package_features = PackageFeatures {
+        table: package.features.clone(),
+        optional_dependency_features: BTreeSet::new(),
+        dependencies: Some(
+            package
+                .dependencies
+                .iter()
+                .map(|dependency| {
+                    dependency
+                        .rename
+                        .as_ref()
+                        .unwrap_or(&dependency.name)
+                        .clone()
+                })
+                .collect(),
+        ),
+        // A plain `cargo check` builds the library and binaries; only binaries can be gated.
+        gated_targets: package
+            .targets
+            .iter()
+            .filter(|target| {
+                target.kind.iter().any(|kind| kind == "bin") && !target.required_features.is_empty()
+            })
+            .map(|target| GatedTarget {
+                label: format!("bin `{}`", target.name),
+                required_features: target.required_features.clone(),
+            })
+            .collect(),
+    };
+    for dependency in package.dependencies.iter().filter(|dep| dep.optional) {
+        let dependency_name = dependency.rename.as_ref().unwrap_or(&dependency.name);
+        let implicit_definition = [format!("dep:{}", dependency_name)];
+        // Cargo generates `<name> = ["dep:<name>"]` unless a feature refers to `dep:<name>`;
+        // an explicit feature spelled the same way is indistinguishable and treated alike.
+        if package.features.get(dependency_name).map(Vec::as_slice)
+            == Some(implicit_definition.as_slice())
+        {
+            package_features
+                .optional_dependency_features
+                .insert(dependency_name.clone());
+        }
+    }
+    package_features
+}
+
+/// Fallback feature discovery that reads the `[features]` table of the manifest directly.
+/// Misses implicit optional-dependency features and workspace-inherited tables.
+fn features_from_cargo_toml(manifest_path: &Path) -> Result<FeatureTable, GetdocError> {
+    if !manifest_path.exists() {
+        info!(
+            "[getdoc] Warning: {} not found. Only checking with default features.",
+            manifest_path.display()
+        );
+        return Ok(FeatureTable::new());
+    }
+    let cargo_toml_content =
+        fs::read_to_string(manifest_path).map_err(|source| GetdocError::ReadManifest {
+            path: manifest_path.to_path_buf(),
+            source,
+        })?;
+    let parsed_toml = toml::from_str::<CargoToml>(&cargo_toml_content).map_err(|source| {
+        GetdocError::ParseManifest {
+            path: manifest_path.to_path_buf(),
+            source,
+        }
+    })?;
+    Ok(parsed_toml.features)
+}
+
+// --- Listing ---
+
+/// The `--list-features` listing for the package at `manifest_path`, or for each member of a
+/// virtual workspace: every feature in name order with what it enables, marked when the
+/// default features turn it on (directly or through other features) and when it is the
+/// implicit feature of an optional dependency. `config_overrides` are passed to cargo as
+/// `--config` options.
+pub fn feature_list(manifest_path: &Path, config_overrides: &[String]) -> String {
+    let packages = match load_manifest_features(manifest_path, config_overrides) {
+        ManifestFeatures::Package(package) => vec![(None, package)],
+        ManifestFeatures::VirtualWorkspace(members) => members
+            .into_iter()
+            .map(|(name, package)| (Some(name), package))
+            .collect(),
+    };
+    let mut listing = String::new();
+    for (name, package) in packages {
+        match name {
+            Some(name) => listing.push_str(&format!("Features of `{}`:\n", name)),
+            None => listing.push_str("Features:\n"),
+        }
+        if package.table.is_empty() {
+            listing.push_str("  (none declared)\n");
+            continue;
+        }
+        let enabled_by_default = resolved_feature_selection(&[], &package.table);
+        let name_width = package.table.keys().map(String::len).max().unwrap_or(0);
+        for (feature, enables) in &package.table {
+            let enables = if enables.is_empty() {
+                "(nothing)".to_string()
+            } else {
+                enables.join(", ")
+            };
+            let mut notes = Vec::new();
+            if enabled_by_default.contains(feature) {
+                notes.push("default");
+            }
+            if package.optional_dependency_features.contains(feature) {
+                notes.push("optional dependency");
+            }
+            let notes = if notes.is_empty() {
+                String::new()
+            } else {
+                format!("  [{}]", notes.join(", "))
+            };
+            listing.push_str(&format!(
+                "  {:<width$}  -> {}{}\n",
+                feature,
+                enables,
+                notes,
+                width = name_width
+            ));
+        }
+    }
+    listing
+}
@@ -0,0 +1,105 @@
+// getdoc - fetch.rs
+//
+// Implicated registry files whose sources are not on disk: the check ran before
+// `~/.cargo/registry/src` was pruned, or its output (`--input`) came from another machine.
+// With `--fetch` their crates are downloaded with `cargo fetch` or unpacked from the
+// `.crate` archives cargo keeps in `registry/cache/`.
+
+// --- Standard Library Imports ---
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+// --- Crate Imports ---
+use crate::cargo::cargo_command;
+
+/// Unpacked crate directories (e.g. `~/.cargo/registry/src/<index>/serde-1.0.200`), keyed by
+/// their name, that implicated files point into but that do not exist.
+pub(crate) fn missing_crate_sources<'a>(
+    implicated_files: impl IntoIterator<Item = &'a PathBuf>,
+) -> BTreeMap<String, PathBuf> {
+    let Some(registry_src) = home::cargo_home()
+        .ok()
+        .map(|cargo_home| cargo_home.join("registry").join("src"))
+    else {
+        return BTreeMap::new();
+    };
+    let mut missing = BTreeMap::new();
+    for file_path in implicated_files {
+        if file_path.exists() {
+            continue;
+        }
+        // `<registry_src>/<index>/<crate>-<version>/...`
+        let Ok(relative_path) = file_path.strip_prefix(&registry_src) else {
+            continue;
+        };
+        let mut components = relative_path.components();
+        if let (Some(index), Some(crate_dir)) = (components.next(), components.next()) {
+            let crate_name = crate_dir.as_os_str().to_string_lossy().into_owned();
+            missing
+                .entry(crate_name)
+                .or_insert_with(|| registry_src.join(index).join(crate_dir));
+        }
+    }
+    missing
+}
+
+/// Tries to put the given crates' sources in place: first `cargo fetch` for the project
+/// (enough when they are in its `Cargo.lock`), then by unpacking a cached `.crate` archive
+/// for each crate still missing. Returns the names of the crates now on disk.
+/// `config_overrides` are passed to `cargo fetch` as `--config` options.
+pub(crate) fn fetch_crate_sources(
+    missing: &BTreeMap<String, PathBuf>,
+    config_overrides: &[String],
+) -> Vec<String> {
+    if Path::new("Cargo.toml").is_file() {
+        let _ = cargo_command(config_overrides)
+            .args(["fetch", "--quiet"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+    missing
+        .iter()
+        .filter(|(crate_name, crate_dir)| {
+            crate_dir.is_dir() || unpack_cached_archive(crate_name, crate_dir)
+        })
+        .map(|(crate_name, _)| crate_name.clone())
+        .collect()
+}
+
+/// Unpacks `registry/cache/<index>/<crate>.crate` into `registry/src/<index>/`, which is
+/// what cargo itself does before building a crate. The archive is a gzipped tarball whose
+/// single top-level directory is `<crate>/`. `tar` ships with Linux, macOS, and Windows
+/// 10 and later.
+fn unpack_cached_archive(crate_name: &str, crate_dir: &Path) -> bool {
+    let Some(index_dir) = crate_dir.parent() else {
+        return false;
+    };
+    // `<registry>/src/<index>/<crate>`
+    let (Some(index), Some(registry_dir)) = (
+        index_dir.file_name(),
+        index_dir.parent().and_then(Path::parent),
+    ) else {
+        return false;
+    };
+    let archive = registry_dir
+        .join("cache")
+        .join(index)
+        .join(format!("{}.crate", crate_name));
+    if !archive.is_file() {
+        return false;
+    }
+    Command::new("tar")
+        .arg("-xzf")
+        .arg(&archive)
+        .arg("-C")
+        .arg(index_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+        && crate_dir.is_dir()
+}
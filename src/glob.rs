@@ -0,0 +1,59 @@
+// getdoc - glob.rs
+//
+// Glob patterns for `--exclude-path`, matched against implicated files' canonical paths.
+// Both the pattern and the path use `/` as the separator, whatever the platform, so the
+// same pattern works on Windows paths.
+
+// --- Standard Library Imports ---
+use std::path::Path;
+
+// --- External Crate Imports ---
+use globset::{GlobBuilder, GlobMatcher};
+
+/// A glob pattern over paths: `*` and `?` stay within one path component, `**` spans any
+/// number of them, `[a-z]` / `[!a-z]` match one character of a set, and `{a,b}` either
+/// alternative. A pattern without `/` is matched against the file name alone, so `*.pb.rs`
+/// excludes such files anywhere.
+#[derive(Debug, Clone)]
+pub struct PathGlob {
+    pattern: String,
+    matcher: GlobMatcher,
+    file_name_only: bool,
+}
+
+impl PathGlob {
+    /// Parses `pattern`; `\` separators are read as `/`.
+    pub fn parse(pattern: &str) -> Result<PathGlob, String> {
+        let normalized = pattern.replace('\\', "/");
+        if normalized.is_empty() {
+            return Err("the pattern is empty".to_string());
+        }
+        let glob = GlobBuilder::new(&normalized)
+            .literal_separator(true)
+            .backslash_escape(false)
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(PathGlob {
+            file_name_only: !normalized.contains('/'),
+            pattern: pattern.to_string(),
+            matcher: glob.compile_matcher(),
+        })
+    }
+
+    /// The pattern as given.
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Whether `path` (or, for a pattern without `/`, its file name) matches the pattern.
+    pub fn matches(&self, path: &Path) -> bool {
+        let text = if self.file_name_only {
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        } else {
+            path.to_string_lossy().replace('\\', "/")
+        };
+        self.matcher.is_match(text)
+    }
+}
@@ -0,0 +1,71 @@
+// getdoc - interrupt.rs
+//
+// Ctrl-C handling: the first interrupt stops the feature-set matrix so the report can be
+// written for what already ran, a second one exits immediately.
+
+// --- Standard Library Imports ---
+use std::sync::Once;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// --- Crate Imports ---
+use crate::progress::warning;
+
+/// Exit status after an interrupt, as shells report for a process killed by SIGINT.
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+/// Set by the first Ctrl-C; checked before and while each `cargo check` runs.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+/// Set while `--serve` serves the finished report; Ctrl-C then stops the viewer.
+static SERVING: AtomicBool = AtomicBool::new(false);
+/// Set while getdoc keeps the `--copy` text on the clipboard; Ctrl-C then releases it.
+static HOLDING_CLIPBOARD: AtomicBool = AtomicBool::new(false);
+static INSTALL_HANDLER: Once = Once::new();
+
+/// Makes Ctrl-C stop the run gracefully: the in-flight `cargo check` is killed, no further
+/// feature sets are started, and [`crate::run`] goes on to write the report for the sets
+/// checked so far (marking the rest as not run). A second Ctrl-C exits at once with
+/// [`EXIT_INTERRUPTED`].
+///
+/// The `getdoc` binary installs this handler; library callers that have their own signal
+/// handling can leave it out. Installing it more than once has no further effect.
+pub fn install_interrupt_handler() {
+    INSTALL_HANDLER.call_once(|| {
+        let installed = ctrlc::set_handler(|| {
+            if INTERRUPTED.swap(true, Ordering::SeqCst) {
+                eprintln!("\n[getdoc] Interrupted again; exiting without a report.");
+                std::process::exit(EXIT_INTERRUPTED);
+            }
+            if SERVING.load(Ordering::SeqCst) {
+                warning!("\n[getdoc] Stopping the report viewer.");
+                return;
+            }
+            if HOLDING_CLIPBOARD.load(Ordering::SeqCst) {
+                warning!("\n[getdoc] Releasing the clipboard.");
+                return;
+            }
+            warning!(
+                "\n[getdoc] Interrupted: stopping the current check and writing the report for the feature sets checked so far. Press Ctrl-C again to exit immediately."
+            );
+        });
+        if let Err(e) = installed {
+            warning!("[getdoc] Warning: could not install the Ctrl-C handler: {}", e);
+        }
+    });
+}
+
+/// Switches Ctrl-C over to stopping the report viewer (`--serve`), once the run is over.
+pub(crate) fn begin_serving() {
+    SERVING.store(true, Ordering::SeqCst);
+    INTERRUPTED.store(false, Ordering::SeqCst);
+}
+
+/// Switches Ctrl-C over to releasing the clipboard (`--copy`), once the run is over.
+pub(crate) fn begin_holding_clipboard() {
+    HOLDING_CLIPBOARD.store(true, Ordering::SeqCst);
+    INTERRUPTED.store(false, Ordering::SeqCst);
+}
+
+/// Whether Ctrl-C has been pressed during this run.
+pub(crate) fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,380 @@
+// getdoc - issue.rs
+//
+// `--emit-issue <crate>`: a Markdown draft of an upstream bug report for one implicated
+// crate, assembled from the run's report: the diagnostics pointing into the crate, the
+// smallest feature sets that reproduce them, how the project depends on it, the implicated
+// items, and the toolchain.
+
+// --- Standard Library Imports ---
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// --- Crate Imports ---
+use crate::diagnostics::{
+    AggregatedDiagnosticInstance, build_script_output_crate, display_location,
+    normalize_cargo_home_paths,
+};
+use crate::error::GetdocError;
+use crate::features::FeatureSet;
+use crate::report::{Report, write_file_atomically};
+use crate::state::toolchain_version;
+use crate::util::quoted_list;
+
+/// Where the draft for `crate_name` goes: `issue-<crate>.md` next to the report.
+pub fn issue_path(output_path: &Path, crate_name: &str) -> PathBuf {
+    output_path.with_file_name(format!("issue-{}.md", crate_name))
+}
+
+/// One version of an implicated crate, as found on disk.
+struct ImplicatedCrate {
+    // The name as the crate's manifest spells it.
+    name: String,
+    version: String,
+    repository: Option<String>,
+    // Implicated files, each with its path relative to the crate root.
+    files: Vec<(PathBuf, PathBuf)>,
+}
+
+/// What `--emit-issue` did.
+pub enum IssueOutcome {
+    /// No diagnostic of the run implicates the crate; nothing was written.
+    NotImplicated,
+    /// The draft was written. `issues_url` is where to file it, when the crate's manifest
+    /// names its repository.
+    Written { issues_url: Option<String> },
+}
+
+/// Writes the issue draft for `crate_name` to `path`, unless the crate is not implicated.
+pub fn write_issue(
+    report: &Report,
+    crate_name: &str,
+    feature_sets: &[FeatureSet],
+    path: &Path,
+    overwrite: bool,
+) -> Result<IssueOutcome, GetdocError> {
+    let implicated_crates = find_implicated_crates(report, crate_name);
+    let implicated_paths: BTreeSet<&PathBuf> = implicated_crates
+        .iter()
+        .flat_map(|implicated_crate| &implicated_crate.files)
+        .map(|(path, _)| path)
+        .collect();
+    // Spans in a diagnostic's `note` children (e.g. "function defined here") are not among
+    // its own implicated files, but rustc renders them with their full paths.
+    let diagnostics: Vec<&AggregatedDiagnosticInstance> = report
+        .consolidated_diagnostics
+        .iter()
+        .filter(|diagnostic| {
+            diagnostic
+                .implicated_third_party_files_details
+                .iter()
+                .any(|(path, _)| implicated_paths.contains(path))
+                || implicated_paths.iter().any(|path| {
+                    diagnostic
+                        .rendered_message
+                        .contains(&normalize_cargo_home_paths(&path.to_string_lossy()))
+                })
+        })
+        .collect();
+    if diagnostics.is_empty() {
+        return Ok(IssueOutcome::NotImplicated);
+    }
+    let crate_name = implicated_crates[0].name.as_str();
+    let versions: Vec<&str> = implicated_crates
+        .iter()
+        .map(|implicated_crate| implicated_crate.version.as_str())
+        .collect();
+    let issues_url = implicated_crates
+        .iter()
+        .find_map(|implicated_crate| implicated_crate.repository.as_deref())
+        .map(|repository| {
+            format!(
+                "{}/issues/new",
+                repository.trim_end_matches('/').trim_end_matches(".git")
+            )
+        });
+
+    write_file_atomically(path, overwrite, |writer| {
+        writeln!(
+            writer,
+            "# `{}` {}: {}\n",
+            crate_name,
+            versions.join(", "),
+            diagnostics[0].message
+        )?;
+        if let Some(url) = &issues_url {
+            writeln!(writer, "<!-- File at {} -->\n", url)?;
+        }
+        writeln!(
+            writer,
+            "Compiling a crate that depends on {} produces the diagnostic(s) below, which point into `{}`'s own source.\n",
+            quoted_list(
+                versions
+                    .iter()
+                    .map(|version| format!("{} {}", crate_name, version))
+            ),
+            crate_name
+        )?;
+
+        writeln!(writer, "## Diagnostics\n")?;
+        for diagnostic in &diagnostics {
+            writeln!(writer, "```text\n{}\n```\n", diagnostic.rendered_message)?;
+            writeln!(
+                writer,
+                "At `{}` in the dependent crate, under: {}.\n",
+                display_location(diagnostic.primary_location.as_ref()),
+                quoted_list(&diagnostic.feature_set_descriptors)
+            )?;
+        }
+
+        writeln!(writer, "## Reproducing Feature Sets\n")?;
+        let reproducing: BTreeSet<&String> = diagnostics
+            .iter()
+            .flat_map(|diagnostic| &diagnostic.feature_set_descriptors)
+            .collect();
+        let (smallest, others) = smallest_feature_sets(&reproducing, feature_sets);
+        writeln!(
+            writer,
+            "Smallest feature set(s) that reproduce it: {}.",
+            quoted_list(&smallest)
+        )?;
+        if !others.is_empty() {
+            writeln!(writer, "Also reproduced under: {}.", quoted_list(&others))?;
+        }
+        writeln!(writer)?;
+
+        writeln!(writer, "## Dependency\n")?;
+        match dependency_declaration(crate_name) {
+            Some(declaration) => writeln!(writer, "```toml\n{}\n```\n", declaration)?,
+            None => writeln!(
+                writer,
+                "Not a direct dependency; it is pulled in through another crate.\n"
+            )?,
+        }
+        let locked = locked_versions(crate_name);
+        if !locked.is_empty() {
+            writeln!(
+                writer,
+                "Locked in `Cargo.lock`: {}.\n",
+                quoted_list(&locked)
+            )?;
+        }
+
+        writeln!(writer, "## Implicated Items\n")?;
+        for implicated_crate in &implicated_crates {
+            // Each version's files are listed apart when several are implicated.
+            if implicated_crates.len() > 1 {
+                writeln!(
+                    writer,
+                    "### `{} {}`\n",
+                    crate_name, implicated_crate.version
+                )?;
+            }
+            for (path, relative_path) in &implicated_crate.files {
+                let implicated_lines = report.implicated_lines_by_file.get(path);
+                let lines: Vec<String> = implicated_lines
+                    .into_iter()
+                    .flatten()
+                    .map(|line| line.to_string())
+                    .collect();
+                writeln!(
+                    writer,
+                    "`{}` (line(s) {}):\n",
+                    relative_path.display(),
+                    lines.join(", ")
+                )?;
+                let items = report.extracted_data.get(path).into_iter().flatten();
+                let signatures: Vec<&str> = items
+                    .filter(|item| {
+                        implicated_lines.is_some_and(|lines| {
+                            lines
+                                .range(item.line_start..=item.line_end)
+                                .next()
+                                .is_some()
+                        })
+                    })
+                    .map(|item| item.signature_or_definition.as_str())
+                    .collect();
+                if signatures.is_empty() {
+                    writeln!(writer, "_No item definitions were extracted here._\n")?;
+                } else {
+                    writeln!(writer, "```rust\n{}\n```\n", signatures.join("\n\n"))?;
+                }
+            }
+        }
+
+        writeln!(writer, "## Environment\n")?;
+        writeln!(
+            writer,
+            "* OS: {} ({})",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        )?;
+        writeln!(
+            writer,
+            "* Found with getdoc {}\n",
+            env!("CARGO_PKG_VERSION")
+        )?;
+        let toolchain = toolchain_version();
+        if !toolchain.trim().is_empty() {
+            writeln!(writer, "```text\n{}\n```", toolchain.trim())?;
+        }
+        Ok(())
+    })?;
+    Ok(IssueOutcome::Written { issues_url })
+}
+
+/// The implicated files belonging to the crate named `crate_name` (by its manifest's
+/// `[package] name`; `-` and `_` are interchangeable), grouped by the crate's version, each
+/// with its repository. Versions are in the order their first file appears in the report.
+/// Build-script output is not part of the crate's sources and is left out.
+fn find_implicated_crates(report: &Report, crate_name: &str) -> Vec<ImplicatedCrate> {
+    let normalize = |name: &str| name.replace('-', "_");
+    let mut manifests: BTreeMap<PathBuf, Option<toml::Table>> = BTreeMap::new();
+    let mut implicated_crates: Vec<ImplicatedCrate> = Vec::new();
+    for path in &report.sorted_file_paths {
+        if build_script_output_crate(path).is_some() {
+            continue;
+        }
+        let Some(crate_root) = path
+            .ancestors()
+            .skip(1)
+            .find(|dir| dir.join("Cargo.toml").is_file())
+        else {
+            continue;
+        };
+        let manifest = manifests
+            .entry(crate_root.to_path_buf())
+            .or_insert_with(|| {
+                fs::read_to_string(crate_root.join("Cargo.toml"))
+                    .ok()
+                    .and_then(|content| content.parse().ok())
+            });
+        let Some(package) = manifest
+            .as_ref()
+            .and_then(|manifest| manifest.get("package")?.as_table())
+        else {
+            continue;
+        };
+        let Some(name) = package.get("name").and_then(|name| name.as_str()) else {
+            continue;
+        };
+        if normalize(name) != normalize(crate_name) {
+            continue;
+        }
+        let version = package
+            .get("version")
+            .and_then(|version| version.as_str())
+            .unwrap_or("(unknown version)");
+        let implicated_crate = match implicated_crates
+            .iter()
+            .position(|implicated_crate| implicated_crate.version == version)
+        {
+            Some(index) => &mut implicated_crates[index],
+            None => {
+                implicated_crates.push(ImplicatedCrate {
+                    name: name.to_string(),
+                    version: version.to_string(),
+                    repository: package
+                        .get("repository")
+                        .and_then(|repository| repository.as_str())
+                        .map(str::to_string),
+                    files: Vec::new(),
+                });
+                implicated_crates.last_mut().unwrap()
+            }
+        };
+        let relative_path = path.strip_prefix(crate_root).unwrap_or(path);
+        implicated_crate
+            .files
+            .push((path.clone(), relative_path.to_path_buf()));
+    }
+    implicated_crates
+}
+
+/// Of the feature sets in `reproducing`, the ones enabling the fewest features, and the
+/// rest. A set's size is the number of features it names, plus one when it leaves default
+/// features on; `--all-features` is larger than any other.
+fn smallest_feature_sets<'a>(
+    reproducing: &BTreeSet<&'a String>,
+    feature_sets: &[FeatureSet],
+) -> (Vec<&'a String>, Vec<&'a String>) {
+    let size = |description: &str| -> usize {
+        let Some(feature_set) = feature_sets
+            .iter()
+            .find(|feature_set| feature_set.description == description)
+        else {
+            return usize::MAX;
+        };
+        let args = &feature_set.args;
+        if args.iter().any(|arg| arg == "--all-features") {
+            return usize::MAX - 1;
+        }
+        let named_features = args
+            .iter()
+            .skip_while(|arg| *arg != "--features")
+            .nth(1)
+            .map_or(0, |features| features.split(',').count());
+        named_features + usize::from(!args.iter().any(|arg| arg == "--no-default-features"))
+    };
+    let smallest_size = reproducing
+        .iter()
+        .map(|description| size(description))
+        .min()
+        .unwrap_or(0);
+    reproducing
+        .iter()
+        .partition(|description| size(description) == smallest_size)
+}
+
+/// How the project's `Cargo.toml` declares `crate_name`, e.g.
+/// `[dependencies]\nserde = { version = "1", features = ["derive"] }`, looking through the
+/// dependency tables, including `[target.*]` ones and renamed (`package = ...`) entries.
+fn dependency_declaration(crate_name: &str) -> Option<String> {
+    const DEPENDENCY_TABLES: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+    let manifest: toml::Table = fs::read_to_string("Cargo.toml").ok()?.parse().ok()?;
+    let mut tables: Vec<(String, &toml::Table)> = DEPENDENCY_TABLES
+        .iter()
+        .filter_map(|name| Some((name.to_string(), manifest.get(*name)?.as_table()?)))
+        .collect();
+    if let Some(targets) = manifest.get("target").and_then(toml::Value::as_table) {
+        for (target, target_table) in targets {
+            for name in DEPENDENCY_TABLES {
+                if let Some(table) = target_table.get(name).and_then(toml::Value::as_table) {
+                    tables.push((format!("target.'{}'.{}", target, name), table));
+                }
+            }
+        }
+    }
+    tables.into_iter().find_map(|(table_name, table)| {
+        table.iter().find_map(|(key, value)| {
+            let package = value
+                .get("package")
+                .and_then(toml::Value::as_str)
+                .unwrap_or(key);
+            (package == crate_name).then(|| format!("[{}]\n{} = {}", table_name, key, value))
+        })
+    })
+}
+
+/// `<name> <version>` for every `Cargo.lock` entry of `crate_name`.
+fn locked_versions(crate_name: &str) -> Vec<String> {
+    let Some(lockfile) = std::env::current_dir().ok().and_then(|dir| {
+        dir.ancestors()
+            .map(|ancestor| ancestor.join("Cargo.lock"))
+            .find(|path| path.is_file())
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| content.parse::<toml::Table>().ok())
+    }) else {
+        return Vec::new();
+    };
+    lockfile
+        .get("package")
+        .and_then(toml::Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|package| package.get("name").and_then(toml::Value::as_str) == Some(crate_name))
+        .filter_map(|package| package.get("version")?.as_str())
+        .map(|version| format!("{} {}", crate_name, version))
+        .collect()
+}
@@ -0,0 +1,1912 @@
+// getdoc - lib.rs
+//
+// Library surface for driving getdoc's analysis from other tools. The `getdoc`
+// binary is a thin wrapper around [`run`].
+
+// --- Standard Library Imports ---
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// --- External Crate Imports ---
+use chrono::format::{Item, StrftimeItems};
+use clap::{CommandFactory, FromArgMatches, Parser};
+use serde::{Deserialize, Serialize};
+
+// --- Modules ---
+pub mod capture;
+pub mod cargo;
+mod cargo_config;
+pub mod diagnostics;
+pub mod diff;
+pub mod error;
+pub mod extract;
+pub mod features;
+mod fetch;
+pub mod glob;
+mod interrupt;
+pub mod issue;
+mod lock;
+pub mod markdown;
+mod minimal_versions;
+mod progress;
+pub mod report;
+pub mod rustdoc_json;
+mod serve;
+pub mod state;
+mod timing;
+mod util;
+
+pub use capture::RawJsonDir;
+pub use cargo::{
+    CargoCli, CargoCommand, DiagnosticsSource, JsonFile, RawRunOutput, check_feature_set,
+};
+pub use diagnostics::{
+    AggregatedDiagnosticInstance, Applicability, DiagnosticCounts, DiagnosticOriginInfo,
+    DiagnosticSort, DisplayableDiagnostic, FlakyDiagnostic, Location, Suggestion,
+    consolidate_diagnostics, merge_repeated_runs, record_absent_feature_sets, sort_diagnostics,
+    suppress_diagnostics,
+};
+pub use error::GetdocError;
+pub use extract::{ExtractedItem, extract_items};
+pub use features::{
+    FeatureList, FeaturePlanOptions, FeatureSet, ListedFeatureSet, feature_list, plan_feature_sets,
+    plan_manifest_feature_sets, read_feature_list, sample_feature_sets,
+};
+pub use interrupt::{EXIT_INTERRUPTED, install_interrupt_handler};
+pub use report::{OutputFormat, Report, hold_copied_report};
+pub use serve::serve_report;
+
+use cargo::{
+    CheckOptions, check_feature_set_timed, inherited_rustflags, split_rustflags,
+    target_selection_args,
+};
+use diagnostics::{explain_error_code, manifest_suppressed_ids, normalize_error_code};
+use extract::{
+    ExtractionOptions, FileExtraction, RawContextFallback, collapse_duplicate_items,
+    extract_items_from_files, find_related_impls,
+};
+use features::{ManifestFeatures, load_manifest_features, validate_feature_set};
+use glob::PathGlob;
+use issue::IssueOutcome;
+use lock::RunLock;
+use minimal_versions::MinimalVersionsLockfile;
+use progress::{Bar, info, warning};
+use report::{
+    EquivalentFeatureSets, FeatureSetTiming, MarkdownOptions, PackageFeatureTable,
+    SkippedFeatureSet, copy_report, display_paths, format_duration, open_report, print_explanation,
+    report_paths, write_reports, write_summary_json,
+};
+use state::{CachedSource, RecordingSource, ReusedRun, RunCache, RunOutcome, getdoc_dir};
+use timing::PhaseTimer;
+
+// --- Options ---
+
+/// A Rust developer tool to provide source code context with compiler errors,
+/// especially from third-party crates, across various feature flag combinations.
+///
+/// These are the `getdoc` command-line options; library callers can start from
+/// `Options::default()` (the CLI defaults) and set fields directly.
+#[derive(clap::Parser, Debug, Clone)]
+#[clap(
+    name = "getdoc",
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true
+)]
+pub struct Options {
+    /// Instead of an analysis, run one of getdoc's other commands.
+    #[clap(subcommand)]
+    pub subcommand: Option<GetdocCommand>,
+
+    /// Comma-separated list of specific crate features to focus the analysis on.
+    /// If provided, `getdoc` runs in "Targeted Mode", checking combinations
+    /// relevant to these features within the current environment.
+    /// If omitted, `getdoc` runs in "Comprehensive Mode", checking a broader
+    /// set of feature combinations (default, no-default, all-features, etc.).
+    #[clap(long, value_parser, value_delimiter = ',')]
+    pub features: Option<Vec<String>>,
+
+    /// In Targeted Mode, check exactly `--features <FEATURES>` and nothing else,
+    /// skipping the extra default-only and `--no-default-features` permutations.
+    #[clap(long, requires = "features")]
+    pub manifest_features_only: bool,
+
+    /// Check the same matrix as `cargo hack check --each-feature`: default features,
+    /// `--no-default-features`, each feature alone with `--no-default-features`, and
+    /// `--all-features`, so results can be compared run for run.
+    #[clap(long, conflicts_with_all = ["features", "input"])]
+    pub each_feature: bool,
+
+    /// Check exactly the feature sets listed in a file, one per line, instead of computing
+    /// them: comma-separated features (checked with `--no-default-features` unless the line
+    /// includes `*default*`), or `*default*`, `*none*`, `*all*` on their own. Every feature
+    /// must be declared in the manifest. `#` starts a comment line.
+    #[clap(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["features", "each_feature", "input"]
+    )]
+    pub features_from_file: Option<PathBuf>,
+
+    /// With `--each-feature`, features that get no individual run.
+    #[clap(
+        long,
+        value_name = "FEATURES",
+        value_delimiter = ',',
+        requires = "each_feature"
+    )]
+    pub exclude_features: Vec<String>,
+
+    /// With `--each-feature`, also give the implicit features of optional dependencies
+    /// their own runs.
+    #[clap(long, requires = "each_feature")]
+    pub optional_deps: bool,
+
+    /// With `--each-feature`, skip the final `--all-features` run.
+    #[clap(long, requires = "each_feature")]
+    pub exclude_all_features: bool,
+
+    /// Add the `required-features` of binaries a feature set would otherwise skip, so
+    /// every target is built under every set.
+    #[clap(long, conflicts_with = "input")]
+    pub ensure_targets: bool,
+
+    /// When more than N valid feature sets are planned, check only N of them: default
+    /// features, `--no-default-features`, and `--all-features` always, plus a random sample
+    /// of the rest. The report header records the seed and the fraction checked.
+    #[clap(long, value_name = "N", conflicts_with = "input")]
+    pub sample: Option<usize>,
+
+    /// Seed for `--sample`, so repeated runs check the same sets. A random seed is picked
+    /// (and printed) when omitted.
+    #[clap(long, value_name = "S", requires = "sample")]
+    pub seed: Option<u64>,
+
+    /// Read pre-captured `cargo check --message-format=json` output from FILE
+    /// instead of invoking cargo (e.g. from a CI log). Use `-` to read from stdin.
+    /// Feature-set selection is skipped in this mode, since the captured output
+    /// already reflects whatever configuration produced it. A directory written by
+    /// `--emit-raw-json` is replayed as the feature-set runs it holds.
+    #[clap(
+        long,
+        visible_alias = "from-json",
+        value_name = "FILE",
+        conflicts_with = "features"
+    )]
+    pub input: Option<PathBuf>,
+
+    /// Save the untouched `--message-format=json` output of every cargo run in DIR, as
+    /// `<feature set>-<hash>.json`, each next to a `<feature set>-<hash>.meta.json` with
+    /// cargo's stderr and exit status. Captures left in DIR by an earlier run are replaced.
+    /// Pass DIR to `--input` to regenerate the report offline. Every feature set is checked
+    /// afresh rather than replayed from the cache; with `--repeat`, the last run of each set
+    /// is kept.
+    #[clap(long, value_name = "DIR", conflicts_with = "input")]
+    pub emit_raw_json: Option<PathBuf>,
+
+    /// Skip items marked `#[doc(hidden)]` and modules/items gated behind `#[cfg(test)]`
+    /// during extraction. This is the default behavior.
+    #[clap(long, overrides_with = "include_hidden")]
+    pub skip_hidden: bool,
+
+    /// Include `#[doc(hidden)]` items and `#[cfg(test)]`-gated code in the extracted output.
+    #[clap(long, overrides_with = "skip_hidden")]
+    pub include_hidden: bool,
+
+    /// Extra flags to pass to rustc for every `cargo check` run (e.g. `--cfg tokio_unstable`),
+    /// added to the rustflags cargo already uses (from `RUSTFLAGS` or `.cargo/config.toml`,
+    /// including `[target.<triple>]` and `[target.'cfg(...)']` tables).
+    /// Multiple flags are separated by spaces; single or double quotes keep a flag containing
+    /// spaces together.
+    #[clap(
+        long,
+        value_name = "FLAGS",
+        allow_hyphen_values = true,
+        conflicts_with = "input"
+    )]
+    pub rustflags: Option<String>,
+
+    /// A cargo configuration override for every cargo run, passed on as `--config`: a
+    /// `KEY=VALUE` pair in TOML (e.g. `net.git-fetch-with-cli=true`) or the path of an extra
+    /// config file. Can be given more than once.
+    #[clap(
+        long,
+        value_name = "KEY=VALUE",
+        value_parser = parse_cargo_config,
+        conflicts_with = "input"
+    )]
+    pub cargo_config: Vec<String>,
+
+    /// The cargo subcommand to run for each feature set. `build` takes much longer than
+    /// `check` but also catches what only codegen and linking reveal: post-monomorphization
+    /// and const-evaluation errors, and linker failures (missing native libraries of `-sys`
+    /// crates are named in the report). `test-compile` runs `cargo test --no-run`, which
+    /// also compiles test code and its dev-dependencies. `doc` runs `cargo doc --no-deps`
+    /// for rustdoc's lints, such as broken intra-doc links.
+    #[clap(
+        long,
+        value_enum,
+        value_name = "COMMAND",
+        default_value = "check",
+        conflicts_with = "input"
+    )]
+    pub command: CargoCommand,
+
+    /// With `--command doc`, document private items too, so rustdoc also checks their doc
+    /// comments (`cargo doc --document-private-items`).
+    #[clap(long)]
+    pub document_private_items: bool,
+
+    /// Check only the named binary target (repeatable). Combines with `--lib` and
+    /// `--example`; without any of them, `cargo check` checks its default targets.
+    #[clap(long, value_name = "NAME", conflicts_with = "input")]
+    pub bin: Vec<String>,
+
+    /// Check only the library target.
+    #[clap(long, conflicts_with = "input")]
+    pub lib: bool,
+
+    /// Check only the named example target (repeatable).
+    #[clap(long, value_name = "NAME", conflicts_with = "input")]
+    pub example: Vec<String>,
+
+    /// For each struct, enum, or type alias extracted from an implicated file, also include
+    /// `impl` blocks for that type found in other source files of the same crate.
+    #[clap(long)]
+    pub related_impls: bool,
+
+    /// Build the rustdoc JSON of each implicated crate (needs a nightly toolchain, run as
+    /// `cargo +nightly`) and use it for the report: items get their full paths and docs as
+    /// rustdoc sees them, and items generated by macros are added where they are implicated.
+    /// Crates whose JSON cannot be built keep the usual source extraction.
+    #[clap(long)]
+    pub rustdoc_json: bool,
+
+    /// Check against the oldest dependency versions the manifests allow, as resolved by
+    /// `cargo +nightly generate-lockfile -Z minimal-versions` (needs a nightly toolchain).
+    /// `Cargo.lock` is replaced for the run and restored afterwards.
+    #[clap(long, conflicts_with = "input")]
+    pub minimal_versions: bool,
+
+    /// When implicated registry crates are not on disk (e.g. `~/.cargo` was pruned, or
+    /// `--input` came from another machine), download them with `cargo fetch`, or unpack
+    /// them from cargo's cached `.crate` archives, before extracting. Without it, such
+    /// crates are listed in the report as missing.
+    #[clap(long)]
+    pub fetch: bool,
+
+    /// Number of source lines to show on each side of an implicated line when a file
+    /// cannot be parsed and its raw text is shown instead.
+    #[clap(long, value_name = "N", default_value_t = 5)]
+    pub context_lines: usize,
+
+    /// Files larger than BYTES (suffixes `K`/`KiB` and `M`/`MiB` accepted) are not parsed;
+    /// the report shows their size, leading doc comment, and raw context around the
+    /// implicated lines instead (0 = no limit). Files marked `@generated` or `DO NOT EDIT`
+    /// near the top are always summarized this way.
+    #[clap(long, value_name = "BYTES", default_value = "512KiB", value_parser = parse_byte_size)]
+    pub max_file_size: u64,
+
+    /// Extract the items of inline modules (`mod name { ... }`) nested up to N modules
+    /// deep; a module nested deeper is listed with its contents elided (0 = list inline
+    /// modules without their items).
+    #[clap(long, value_name = "N", default_value_t = 3)]
+    pub max_depth: usize,
+
+    /// Report format(s) to write. Several formats can be requested at once as a
+    /// comma-separated list (e.g. `markdown,json`); each is written next to `--output`
+    /// with the format's file extension.
+    #[clap(long, value_enum, value_delimiter = ',', default_value = "markdown")]
+    pub format: Vec<OutputFormat>,
+
+    /// Order of the diagnostics in the report: by primary `location` (then code and
+    /// message), by error `code`, by `level` (errors first), or by `count` of feature sets
+    /// affected (most first). Ties keep the location order.
+    #[clap(long, value_enum, value_name = "KEY", default_value = "location")]
+    pub sort: DiagnosticSort,
+
+    /// Leave out the diagnostics with these IDs (comma-separated, as shown next to each
+    /// diagnostic in the report), e.g. known issues. The IDs listed as `ids` under
+    /// `[package.metadata.getdoc.suppress]` in `Cargo.toml` are left out as well.
+    #[clap(long, value_name = "IDS", value_delimiter = ',')]
+    pub suppress_ids: Vec<String>,
+
+    /// Leave out implicated third-party files whose canonical path matches this glob, e.g.
+    /// `**/generated/**` or `**/tests/**`: they get no report section and nothing is
+    /// extracted from them. `*` and `?` stay within one path component, `**` spans any
+    /// number; a pattern without `/` is matched against the file name. Can be given more
+    /// than once.
+    #[clap(long, value_name = "GLOB", value_parser = PathGlob::parse)]
+    pub exclude_path: Vec<PathGlob>,
+
+    /// Path of the report to write. For multiple formats, the extension is replaced
+    /// per format (e.g. `report.md` and `report.json`).
+    #[clap(long, value_name = "PATH", default_value = "report.md")]
+    pub output: PathBuf,
+
+    /// Overwrite existing report files. Without it, getdoc refuses to start when a report it
+    /// would write already exists, so an annotated report is not silently replaced.
+    #[clap(long)]
+    pub force: bool,
+
+    /// Also write a compact JSON object with just the run's counts (errors, warnings,
+    /// implicated crates, feature sets checked, duration) to PATH, whatever `--format` is.
+    #[clap(long, value_name = "PATH")]
+    pub summary_json: Option<PathBuf>,
+
+    /// Also write `issue-<CRATE>.md` next to the report: a draft bug report for the
+    /// implicated crate CRATE, with the diagnostics pointing into it, the smallest feature
+    /// sets that reproduce them, how the project depends on it (and the locked version),
+    /// the implicated items' signatures, and the toolchain. Where to file it is printed when
+    /// the crate's manifest names its repository.
+    #[clap(long, value_name = "CRATE")]
+    pub emit_issue: Option<String>,
+
+    /// Maximum number of doc-comment lines to show per item in the Markdown report
+    /// (0 = unlimited). Longer docs are cut at a paragraph boundary, always keeping
+    /// the first paragraph and never splitting a fenced or indented code example.
+    #[clap(long, value_name = "N", default_value_t = 40)]
+    pub max_doc_lines: usize,
+
+    /// Render doc comments as Markdown instead of quoting them, so their lists, code
+    /// blocks, and `# Examples` sections display properly. Headings inside doc comments
+    /// are turned into bold lines so they don't collide with the report's own headings.
+    #[clap(long)]
+    pub raw_docs: bool,
+
+    /// In the Markdown report's "Implicates" line, list a file implicated at several lines
+    /// of one diagnostic once, with all its lines (e.g. `de.rs` (at `de.rs:412-414, 418, 430`)).
+    #[clap(long)]
+    pub collapse_implicated_lines: bool,
+
+    /// Leave Appendix A (rustc's explanations of the error codes that occurred) and the
+    /// pointers to it out of the Markdown report. The JSON report still has them.
+    #[clap(long, conflicts_with = "short_explanations")]
+    pub no_explanations: bool,
+
+    /// In Appendix A, show only the first paragraph of each error code's explanation, with
+    /// a link to the full text on doc.rust-lang.org. The JSON report still has them in full.
+    #[clap(long)]
+    pub short_explanations: bool,
+
+    /// Soft-wrap the compiler messages in the Markdown report at WIDTH characters (0 = off).
+    /// Lines of rustc's source snippets, whose `^^^` markers line up by column, are never wrapped.
+    #[clap(long, value_name = "WIDTH", default_value_t = 0)]
+    pub wrap: usize,
+
+    /// In the Markdown report, list at most N diagnostics under each third-party file's
+    /// "Referenced by" (0 = all). The JSON report always lists every reference.
+    #[clap(long, value_name = "N", default_value_t = 10)]
+    pub max_referencers: usize,
+
+    /// Keep terminal color codes in rendered compiler messages. By default they are removed,
+    /// so output captured with `--message-format=json-diagnostic-rendered-ansi` reads cleanly
+    /// in the report; keep them when the report is meant for a terminal.
+    #[clap(long)]
+    pub keep_ansi: bool,
+
+    /// Also report top-level `note` messages that point into third-party files (such as
+    /// "this error originates in the macro ..."), which are otherwise dropped.
+    #[clap(long)]
+    pub include_notes_as_diagnostics: bool,
+
+    /// Print every line of cargo's output that looks like a JSON message (starts with `{`)
+    /// but could not be parsed as one, with the parse error, to stderr. Such lines are
+    /// otherwise skipped silently; use this when a diagnostic is missing from the report,
+    /// e.g. after a cargo update changed the message format.
+    #[clap(long)]
+    pub debug_json: bool,
+
+    /// Add a "Suggested Fixes" section to the Markdown report listing every machine-applicable
+    /// fix rustc suggested, grouped by file, with each line before and after the fix. Nothing
+    /// is changed on disk; `cargo fix --broken-code` would apply them.
+    #[clap(long)]
+    pub fix_preview: bool,
+
+    /// Show the time in the Markdown report's header in UTC instead of local time, so that
+    /// reports from machines in different time zones compare directly.
+    #[clap(long)]
+    pub utc: bool,
+
+    /// Format of the time in the Markdown report's header: `rfc3339` (sortable, e.g.
+    /// `2024-05-01T14:03:12+02:00`), `rfc2822` (e.g. `Wed, 1 May 2024 14:03:12 +0200`, as
+    /// earlier versions wrote), or a chrono format string such as `%Y-%m-%d %H:%M %Z`.
+    #[clap(long, value_name = "FMT", default_value = "rfc3339", value_parser = parse_date_format)]
+    pub date_format: String,
+
+    /// Add a table of the crate's declared features (from `cargo metadata`) near the top of
+    /// the report, mapping each feature to what it enables: the features Comprehensive Mode
+    /// permutes over.
+    #[clap(long)]
+    pub include_features_table: bool,
+
+    /// Add a "Phase Timings" section to the report breaking the run's time down by phase:
+    /// feature-set discovery, cargo runs, JSON processing, extraction, and so on. A one-line
+    /// version is always printed at the end of the run.
+    #[clap(long)]
+    pub timings: bool,
+
+    /// Open the generated report in the default application once it is written, like
+    /// `cargo doc --open`. Skipped with a warning when no opener is available (e.g. in CI).
+    #[clap(long)]
+    pub open: bool,
+
+    /// Put the report's text on the clipboard once it is written, ready to paste (the first
+    /// `--format` written; with `--format short` alone, its one-line-per-diagnostic text).
+    /// Skipped with a warning when no clipboard is available (e.g. over SSH or in CI). On
+    /// Linux, unless a clipboard manager takes the text over, getdoc stays running after the
+    /// run until something else is copied or Ctrl-C is pressed, as the text would be gone
+    /// once it exits.
+    #[clap(long)]
+    pub copy: bool,
+
+    /// With `--copy`, warn when the copied text is longer than this many characters, as
+    /// chat inputs and model context windows cap what can be pasted.
+    #[clap(
+        long,
+        value_name = "CHARS",
+        default_value_t = 100_000,
+        requires = "copy"
+    )]
+    pub copy_warn_above: usize,
+
+    /// After the run, serve an interactive view of the report on `127.0.0.1` until Ctrl-C:
+    /// filters by error code, crate, and feature set, a search over extracted items, and
+    /// collapsible file sections. With `--format json`, the page follows the JSON report, so
+    /// it updates when a later run replaces it.
+    #[clap(long, conflicts_with = "explain_only")]
+    pub serve: bool,
+
+    /// Port for `--serve`; 0 picks a free one.
+    #[clap(long, value_name = "PORT", default_value_t = 0, requires = "serve")]
+    pub serve_port: u16,
+
+    /// Stop launching further feature-set checks as soon as one produces errors.
+    /// The report still covers the sets that ran.
+    #[clap(long)]
+    pub fail_fast: bool,
+
+    /// Re-check only the feature sets whose last check had errors or failed to run, and
+    /// reuse the cached results of the others (kept in `target/getdoc/state/`), even if the
+    /// sources changed since. The cache is dropped when `Cargo.lock` or the planned feature
+    /// sets change.
+    #[clap(long)]
+    pub resume: bool,
+
+    /// Ignore cached results and check every feature set. Without it, a set is replayed from
+    /// the cache when its sources, `Cargo.lock`, arguments, and toolchain are unchanged since
+    /// it was last checked; with `--resume`, more sets are. Results are still cached.
+    #[clap(long)]
+    pub no_cache: bool,
+
+    /// Run each feature set N times and list the diagnostics that did not come out in every
+    /// run in a "Flaky Diagnostics" section, to pin down intermittent failures (e.g. from a
+    /// nondeterministic proc macro). Cached results are not reused when N is above 1.
+    #[clap(
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with = "input"
+    )]
+    pub repeat: u32,
+
+    /// Wall-clock budget for the whole analysis. Once a feature-set check finishes past
+    /// it, the remaining sets are skipped and getdoc exits with status 3 after writing
+    /// the report, so pipelines can tell coverage was partial.
+    #[clap(long, value_name = "SECONDS")]
+    pub max_total_time: Option<u64>,
+
+    /// When another getdoc run is already working in this project, wait for it to finish
+    /// instead of exiting with status 7. Runs take a lock on `target/getdoc/.lock` so they
+    /// don't overwrite each other's reports and cached results.
+    #[clap(long)]
+    pub wait: bool,
+
+    /// Only print warnings and errors: no progress bars and no informational lines.
+    /// Progress bars are also left out whenever stdout or stderr is not a terminal.
+    #[clap(long)]
+    pub quiet: bool,
+
+    /// Print the explanation of a single rustc error code (e.g. `E0308`) to stdout, as it
+    /// appears in the report, and exit without running any checks or writing a report.
+    #[clap(long, value_name = "CODE")]
+    pub explain_only: Option<String>,
+
+    /// Print the crate's features (from `cargo metadata`) with what each enables, marking
+    /// the ones enabled by default, and exit without running any checks or writing a report.
+    #[clap(long, conflicts_with_all = ["explain_only", "input", "serve"])]
+    pub list_features: bool,
+}
+
+/// getdoc's commands besides the analysis itself.
+#[derive(clap::Subcommand, Debug, Clone)]
+pub enum GetdocCommand {
+    /// Compare two JSON reports (`--format json`), e.g. from before and after a dependency
+    /// bump: new, resolved, and changed diagnostics, and changes to the feature matrix.
+    Diff(diff::DiffArgs),
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options::parse_from(["getdoc"])
+    }
+}
+
+impl Options {
+    /// Parses command-line arguments (program name first), exiting with clap's message on
+    /// invalid ones. Run as `cargo getdoc ...`, cargo starts `cargo-getdoc getdoc ...`; that
+    /// extra `getdoc` is skipped, so both ways of invoking getdoc take the same arguments.
+    pub fn from_args<I, T>(args: I) -> Options
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>,
+    {
+        let mut args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+        let mut command = Options::command();
+        if args.get(1).is_some_and(|arg| arg == "getdoc") {
+            args.remove(1);
+            command = command.bin_name("cargo getdoc");
+        }
+        let matches = command.get_matches_from(args);
+        Options::from_arg_matches(&matches).unwrap_or_else(|e| e.exit())
+    }
+}
+
+/// Parses a `--max-file-size` value: a byte count with an optional `K`/`KiB` or `M`/`MiB` suffix.
+fn parse_byte_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let digits_end = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (digits, suffix) = value.split_at(digits_end);
+    let multiplier = match suffix.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1024,
+        "m" | "mb" | "mib" => 1024 * 1024,
+        other => return Err(format!("unknown size suffix `{}`", other)),
+    };
+    digits
+        .parse::<u64>()
+        .map_err(|e| format!("invalid size `{}`: {}", value, e))
+        .map(|count| count.saturating_mul(multiplier))
+}
+
+/// Parses a `--date-format` value: `rfc3339`, `rfc2822`, or a chrono format string. The
+/// latter is checked up front, since formatting with an invalid one fails.
+fn parse_date_format(value: &str) -> Result<String, String> {
+    if StrftimeItems::new(value).any(|item| matches!(item, Item::Error)) {
+        return Err(format!(
+            "invalid format string `{}` (see chrono's strftime specifiers)",
+            value
+        ));
+    }
+    Ok(value.to_string())
+}
+
+/// Checks a `--cargo-config` value the way cargo reads `--config`: a `KEY=VALUE` pair must
+/// parse as a line of TOML; anything without `=` must name an existing config file.
+fn parse_cargo_config(value: &str) -> Result<String, String> {
+    let Some((key, _)) = value.split_once('=') else {
+        return if Path::new(value).is_file() {
+            Ok(value.to_string())
+        } else {
+            Err(format!(
+                "`{}` is neither a `KEY=VALUE` pair nor an existing config file",
+                value
+            ))
+        };
+    };
+    if key.trim().is_empty() {
+        return Err(format!("`{}` has no key before `=`", value));
+    }
+    let table = value
+        .parse::<toml::Table>()
+        .map_err(|e| format!("`{}` is not valid TOML: {}", value, e.message()))?;
+    if table.len() != 1 {
+        return Err(format!("`{}` must set exactly one key", value));
+    }
+    Ok(value.to_string())
+}
+
+// --- Running an Analysis ---
+
+/// Runs a full analysis as configured by `options`: plans the feature sets, checks each one,
+/// extracts context from the implicated third-party files, and writes the requested reports.
+///
+/// Progress is printed to stdout/stderr as the CLI does. The returned [`Report`] holds
+/// everything the reports were generated from. A feature set whose check fails is recorded
+/// in the report and the run goes on; missing `cargo`, unreadable `--input`, and unwritable
+/// reports end the run with the corresponding [`GetdocError`].
+pub fn run(options: &Options) -> Result<Report, GetdocError> {
+    let analysis_started = Instant::now();
+    let phase_timer = PhaseTimer::default();
+    let check_options = CheckOptions::from(options);
+    progress::configure(options.quiet);
+
+    if let Some(code) = options.explain_only.as_deref() {
+        let code = normalize_error_code(code);
+        let explanation = explain_error_code(&code)?;
+        print_explanation(&code, &explanation);
+        return Ok(Report {
+            mode_description: "Explain-only Mode".to_string(),
+            unique_explanations: HashMap::from([(code, explanation)]),
+            total_duration: analysis_started.elapsed(),
+            ..Default::default()
+        });
+    }
+
+    if options.list_features {
+        print!(
+            "{}",
+            feature_list(Path::new("Cargo.toml"), &options.cargo_config)
+        );
+        return Ok(Report {
+            mode_description: "List-Features Mode".to_string(),
+            total_duration: analysis_started.elapsed(),
+            ..Default::default()
+        });
+    }
+
+    // Held until `run` returns, so a concurrent run cannot interleave report or cache writes.
+    let _run_lock = RunLock::acquire(options.wait)?;
+    // A `--minimal-versions` run that was killed left its lockfile in place.
+    minimal_versions::restore_original_lockfile();
+
+    // Refuse up front rather than after a long matrix; writing checks again at the end.
+    if !options.force
+        && let Some(path) = report_paths(&options.format, &options.output)
+            .into_iter()
+            .filter_map(|(_, path)| path)
+            .chain(
+                options
+                    .emit_issue
+                    .as_deref()
+                    .map(|crate_name| issue::issue_path(&options.output, crate_name)),
+            )
+            .find(|path| path.exists())
+    {
+        return Err(GetdocError::ReportExists { path });
+    }
+
+    // Determine the mode of operation based on CLI arguments
+    if let Some(input_path) = options.input.as_ref() {
+        info!(
+            "[getdoc] Starting analysis of pre-captured cargo output from {}...",
+            input_path.display()
+        );
+    } else if let Some(list_path) = options.features_from_file.as_ref() {
+        info!(
+            "[getdoc] Starting analysis in Feature-List Mode for the sets in {}...",
+            list_path.display()
+        );
+    } else if options.each_feature {
+        info!("[getdoc] Starting analysis in Each-Feature Mode (cargo-hack compatible)...");
+    } else if options.features.is_some() {
+        info!("[getdoc] Starting analysis in Targeted Mode for specified features...");
+    } else {
+        info!("[getdoc] Starting analysis in Comprehensive Mode for multiple feature sets...");
+    }
+
+    let rustflags = options
+        .rustflags
+        .as_deref()
+        .map(split_rustflags)
+        .unwrap_or_default();
+    if !rustflags.is_empty() {
+        info!("[getdoc] Passing rustc flags to cargo: {:?}", rustflags);
+    }
+    // Without the target tables' flags, cargo would drop ours for them; such flags are not
+    // claimed in the report.
+    let rustflags_applied = rustflags.is_empty()
+        || match inherited_rustflags(&options.cargo_config) {
+            Ok(_) => true,
+            Err(reason) => {
+                warning!(
+                    "[getdoc] Warning: Could not read the rustflags of cargo's `[target]` configuration ({}); `--rustflags` are ignored if any apply.",
+                    reason
+                );
+                false
+            }
+        };
+    if !options.cargo_config.is_empty() {
+        info!(
+            "[getdoc] Passing config overrides to cargo: {:?}",
+            options.cargo_config
+        );
+    }
+    if options.document_private_items && options.command != CargoCommand::Doc {
+        warning!(
+            "[getdoc] Warning: --document-private-items only applies to --command doc; ignoring it."
+        );
+    }
+    let rustdoc_json = options.rustdoc_json && cargo::nightly_available();
+    if options.rustdoc_json && !rustdoc_json {
+        warning!(
+            "[getdoc] Warning: --rustdoc-json needs a nightly toolchain (`cargo +nightly`), which was not found; extracting from source only."
+        );
+    }
+    // Held until `run` returns; the original lockfile is restored when it is dropped.
+    let _minimal_versions_lockfile = if options.minimal_versions {
+        Some(MinimalVersionsLockfile::generate(&options.cargo_config)?)
+    } else {
+        None
+    };
+    let target_args = target_selection_args(options);
+    if !target_args.is_empty() {
+        info!(
+            "[getdoc] Checking selected target(s) only: {}",
+            target_args.join(" ")
+        );
+    }
+
+    if let Some(dir) = options.emit_raw_json.as_ref() {
+        let write_error = |source| GetdocError::WriteReport {
+            path: dir.clone(),
+            source,
+        };
+        fs::create_dir_all(dir).map_err(write_error)?;
+        let cleared = capture::clear_captures(dir).map_err(write_error)?;
+        info!(
+            "[getdoc] Saving cargo's raw output of each run to {}{}",
+            dir.display(),
+            if cleared > 0 {
+                format!(" (replacing {} earlier capture(s)).", cleared)
+            } else {
+                ".".to_string()
+            }
+        );
+    }
+
+    let markdown_options = MarkdownOptions::from(options);
+
+    // Pre-captured input is processed as a single synthetic "run", unless it is a directory
+    // of `--emit-raw-json` captures; no feature sets are derived.
+    let discovery_started = Instant::now();
+    let manifest_path = Path::new("Cargo.toml");
+    let raw_json_input = match options.input.as_ref() {
+        Some(input_path) if input_path.is_dir() => Some(RawJsonDir::open(input_path)?),
+        _ => None,
+    };
+    // Read once with `cargo metadata`, for planning, validating, and the features table.
+    let manifest_features = options
+        .input
+        .is_none()
+        .then(|| load_manifest_features(manifest_path, &options.cargo_config));
+    let feature_sets_to_check = if let Some(raw_json_input) = raw_json_input.as_ref() {
+        raw_json_input.feature_sets()
+    } else if let Some(manifest_features) = manifest_features.as_ref() {
+        let plan_options = FeaturePlanOptions {
+            target_features: options.features.clone(),
+            manifest_features_only: options.manifest_features_only,
+            each_feature: options.each_feature,
+            exclude_features: options.exclude_features.clone(),
+            optional_deps: options.optional_deps,
+            exclude_all_features: options.exclude_all_features,
+            ensure_targets: options.ensure_targets,
+            feature_list: options
+                .features_from_file
+                .as_deref()
+                .map(read_feature_list)
+                .transpose()?,
+            config_overrides: options.cargo_config.clone(),
+        };
+        match plan_manifest_feature_sets(manifest_features, &plan_options) {
+            Ok(feature_sets) => feature_sets,
+            // A curated list is checked as written or not at all.
+            Err(e @ GetdocError::FeatureList { .. }) => return Err(e),
+            Err(e) => {
+                warning!(
+                    "[getdoc] Warning: Could not determine feature sets: {}. Proceeding with a minimal check.",
+                    e
+                );
+                match options.features.as_ref() {
+                    Some(target_feats) if !target_feats.is_empty() => vec![FeatureSet::new(vec![
+                        "--features".to_string(),
+                        target_feats.join(","),
+                    ])],
+                    _ => vec![FeatureSet::new(vec![])],
+                }
+            }
+        }
+    } else {
+        vec![FeatureSet::new(vec![])]
+    };
+    // Name the selected targets in every description, so the report attributes each
+    // diagnostic to a target as well as a feature set.
+    let mut feature_sets_to_check = feature_sets_to_check;
+    if !target_args.is_empty() {
+        let target_suffix = format!(" [{}]", target_args.join(" "));
+        for feature_set in &mut feature_sets_to_check {
+            feature_set.description.push_str(&target_suffix);
+            for equivalent in &mut feature_set.equivalent_sets {
+                equivalent.push_str(&target_suffix);
+            }
+        }
+    }
+    let equivalent_feature_sets: Vec<EquivalentFeatureSets> = feature_sets_to_check
+        .iter()
+        .filter(|feature_set| !feature_set.equivalent_sets.is_empty())
+        .map(|feature_set| EquivalentFeatureSets {
+            checked_as: feature_set.description.clone(),
+            aliases: feature_set.equivalent_sets.clone(),
+        })
+        .collect();
+    for equivalence in &equivalent_feature_sets {
+        info!(
+            "[getdoc] Feature set '{}' resolves to the same features as '{}'; checking it once.",
+            equivalence.aliases.join("', '"),
+            equivalence.checked_as
+        );
+    }
+    let collapsed_set_count: usize = equivalent_feature_sets
+        .iter()
+        .map(|equivalence| equivalence.aliases.len())
+        .sum();
+    if collapsed_set_count > 0 {
+        info!(
+            "[getdoc] Collapsed {} of {} planned feature set(s) with identical resolved features into {} check(s).",
+            collapsed_set_count,
+            feature_sets_to_check.len() + collapsed_set_count,
+            feature_sets_to_check.len()
+        );
+    }
+
+    // Drop feature selections cargo would reject before spending a full check on each.
+    let mut skipped_feature_sets: Vec<SkippedFeatureSet> = Vec::new();
+    let mut feature_sets_to_check: Vec<FeatureSet> = match manifest_features.as_ref() {
+        None => feature_sets_to_check,
+        Some(manifest_features) => feature_sets_to_check
+            .into_iter()
+            .filter(
+                |feature_set| match validate_feature_set(manifest_features, feature_set) {
+                    Ok(()) => true,
+                    Err(reason) => {
+                        warning!(
+                            "[getdoc] Skipping feature set '{}' (invalid feature selection): {}",
+                            feature_set.description,
+                            reason
+                        );
+                        skipped_feature_sets.push(SkippedFeatureSet {
+                            feature_set_desc: feature_set.description.clone(),
+                            reason: format!("invalid feature selection: {}", reason),
+                        });
+                        false
+                    }
+                },
+            )
+            .collect(),
+    };
+
+    // Sample among the valid sets only, so the sample size and coverage hold.
+    let mut sampling_note: Option<String> = None;
+    if let Some(sample_size) = options.sample
+        && feature_sets_to_check.len() > sample_size
+    {
+        let seed = options.seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+        });
+        let planned_count = feature_sets_to_check.len();
+        feature_sets_to_check = sample_feature_sets(feature_sets_to_check, sample_size, seed);
+        let note = format!(
+            "sampled {} of {} feature sets ({:.0}%), seed {}",
+            feature_sets_to_check.len(),
+            planned_count,
+            100.0 * feature_sets_to_check.len() as f64 / planned_count as f64,
+            seed
+        );
+        info!(
+            "[getdoc] --sample: {}; rerun with `--seed {}` to check the same sets.",
+            note, seed
+        );
+        sampling_note = Some(note);
+    }
+
+    if options.input.is_none() {
+        phase_timer.record("feature-set discovery", discovery_started.elapsed());
+    }
+
+    // Sets that failed last time go first, so likely offenders are reported (or, with
+    // --fail-fast, stop the run) early. Each-feature mode keeps cargo-hack's order.
+    let previous_run_state = load_run_state();
+    if options.input.is_none() && !options.each_feature {
+        feature_sets_to_check.sort_by_key(|feature_set| {
+            !previous_run_state
+                .failed_feature_sets
+                .contains(&feature_set.description)
+        });
+    }
+
+    let mut all_displayable_diagnostics: Vec<(String, Vec<DisplayableDiagnostic>)> = Vec::new();
+    let mut all_implicated_files_globally: HashMap<PathBuf, BTreeSet<usize>> = HashMap::new();
+    let mut global_file_referencers: HashMap<PathBuf, HashSet<DiagnosticOriginInfo>> =
+        HashMap::new();
+
+    // Per-outcome counts of feature-set runs, for the end-of-run summary line.
+    let (mut sets_with_errors, mut sets_with_warnings_only, mut clean_sets, mut failed_sets) =
+        (0usize, 0usize, 0usize, 0usize);
+    let total_feature_sets = feature_sets_to_check.len();
+    let mut failed_feature_sets_this_run: Vec<String> = Vec::new();
+    // Sets whose check completed, so a diagnostic missing from one is known to be absent there.
+    let mut checked_feature_sets: Vec<String> = Vec::new();
+    let mut feature_set_timings: Vec<FeatureSetTiming> = Vec::new();
+    let time_budget = options.max_total_time.map(Duration::from_secs);
+    let cargo_cli = CargoCli {
+        command: options.command,
+        document_private_items: options.document_private_items,
+        target_args: target_args.clone(),
+        rustflags: rustflags.clone(),
+        config_overrides: options.cargo_config.clone(),
+        raw_json_dir: options.emit_raw_json.clone(),
+    };
+    let source: Box<dyn DiagnosticsSource> = match options.input.as_ref() {
+        Some(_) if let Some(raw_json_input) = raw_json_input => Box::new(raw_json_input),
+        Some(input_path) => Box::new(JsonFile {
+            path: input_path.clone(),
+        }),
+        None => Box::new(cargo_cli.clone()),
+    };
+    let mut time_budget_exceeded = false;
+    let mut run_interrupted = false;
+    let mut flaky_diagnostics: Vec<FlakyDiagnostic> = Vec::new();
+
+    // Every fresh check is cached. A set whose fingerprint is unchanged is replayed from
+    // the cache; `--resume` also replays the current sets that did not fail.
+    let cache_lookup_started = Instant::now();
+    let run_cache = options
+        .input
+        .is_none()
+        .then(|| RunCache::new(&feature_sets_to_check, &cargo_cli, &output_paths(options)));
+    let mut reused_runs: Vec<Option<ReusedRun>> = feature_sets_to_check
+        .iter()
+        .map(|feature_set| match &run_cache {
+            Some(run_cache)
+                if !options.no_cache && options.repeat == 1 && options.emit_raw_json.is_none() =>
+            {
+                run_cache.reusable(feature_set, options.resume)
+            }
+            _ => None,
+        })
+        .collect();
+    if run_cache.is_some() {
+        phase_timer.record("cache lookup", cache_lookup_started.elapsed());
+    }
+    let reused_run_count = reused_runs.iter().flatten().count();
+    if options.resume && run_cache.is_some() {
+        info!(
+            "[getdoc] --resume: reusing {} cached result(s) from {}; checking {} feature set(s) again.",
+            reused_run_count,
+            getdoc_dir().join("state").display(),
+            feature_sets_to_check.len() - reused_run_count
+        );
+    } else if reused_run_count > 0 {
+        info!(
+            "[getdoc] Reusing {} cached result(s) of feature sets whose sources, lockfile, and toolchain are unchanged (--no-cache checks them again).",
+            reused_run_count
+        );
+    }
+    let recording_source = RecordingSource::new(source.as_ref());
+    let run_phase = if options.input.is_some() {
+        "reading input".to_string()
+    } else {
+        cargo_cli.display_name()
+    };
+
+    let feature_set_bar = Bar::new(total_feature_sets, "feature sets");
+    for (set_index, feature_set) in feature_sets_to_check.iter().enumerate() {
+        let progress_prefix = format!("[{}/{}]", set_index + 1, total_feature_sets);
+        let run_started = Instant::now();
+        let reused_run = reused_runs[set_index].take();
+        let feature_desc = if let Some(input_path) = options.input.as_ref()
+            && input_path.is_dir()
+        {
+            info!(
+                "[getdoc] {} Replaying the capture of '{}' from {}...",
+                progress_prefix,
+                feature_set.description,
+                input_path.display()
+            );
+            feature_set.description.clone()
+        } else if let Some(input_path) = options.input.as_ref() {
+            info!(
+                "[getdoc] Reading cargo JSON messages from {}...",
+                input_path.display()
+            );
+            format!("pre-captured input ({})", input_path.display())
+        } else {
+            let feature_desc = feature_set.description.clone();
+            // Show the real cargo arguments; descriptions may carry a member or target label.
+            let cargo_args: Vec<&str> = feature_set
+                .args
+                .iter()
+                .chain(&target_args)
+                .map(String::as_str)
+                .collect();
+            if feature_set_bar.is_visible() {
+                feature_set_bar.set_message(&feature_desc);
+            } else if let Some(reused_run) = &reused_run {
+                info!(
+                    "[getdoc] {} Reusing the result of '{}' cached from {}...",
+                    progress_prefix, feature_desc, reused_run.checked_at
+                );
+            } else {
+                info!(
+                    "[getdoc] {} Running `{} --message-format=json {}`...",
+                    progress_prefix,
+                    cargo_cli.display_name(),
+                    if cargo_args.is_empty() {
+                        feature_desc.clone()
+                    } else {
+                        cargo_args.join(" ")
+                    }
+                );
+            }
+            feature_desc
+        };
+        let cached_from = reused_run.as_ref().map(|run| run.checked_at.clone());
+        let run_result = match reused_run {
+            Some(run) => check_feature_set_timed(
+                &CachedSource(run.output),
+                &feature_set.args,
+                &feature_desc,
+                &phase_timer,
+                "cache replay",
+                check_options,
+            ),
+            None => check_feature_set_timed(
+                &recording_source,
+                &feature_set.args,
+                &feature_desc,
+                &phase_timer,
+                &run_phase,
+                check_options,
+            ),
+        };
+        // The cache keeps the first run; `--repeat` runs are compared, not cached.
+        let fresh_output = recording_source.take_last_output();
+        let run_result = match run_result {
+            Ok((diagnostics, mut implicated_files, mut referencers)) if options.repeat > 1 => {
+                let mut runs = vec![diagnostics];
+                for repetition in 2..=options.repeat {
+                    if interrupt::interrupted() {
+                        break;
+                    }
+                    if !feature_set_bar.is_visible() {
+                        info!(
+                            "[getdoc] {} Repeating '{}' (run {} of {})...",
+                            progress_prefix, feature_desc, repetition, options.repeat
+                        );
+                    }
+                    match check_feature_set_timed(
+                        &recording_source,
+                        &feature_set.args,
+                        &feature_desc,
+                        &phase_timer,
+                        &run_phase,
+                        check_options,
+                    ) {
+                        Ok((diagnostics, files, origins)) => {
+                            runs.push(diagnostics);
+                            for (file, lines) in files {
+                                implicated_files.entry(file).or_default().extend(lines);
+                            }
+                            for (file, file_origins) in origins {
+                                referencers.entry(file).or_default().extend(file_origins);
+                            }
+                        }
+                        Err(GetdocError::Interrupted { .. }) => break,
+                        Err(e) => {
+                            warning!(
+                                "[getdoc] Warning: Run {} of '{}' failed ({}); comparing the {} run(s) that completed.",
+                                repetition,
+                                feature_desc,
+                                e,
+                                runs.len()
+                            );
+                            break;
+                        }
+                    }
+                }
+                let (diagnostics, flaky) = merge_repeated_runs(runs, &feature_desc);
+                flaky_diagnostics.extend(flaky);
+                Ok((diagnostics, implicated_files, referencers))
+            }
+            other => other,
+        };
+        feature_set_bar.inc();
+        if let Err(GetdocError::Interrupted { .. }) = run_result {
+            // The stopped check counts as not run, like everything after it.
+            run_interrupted = true;
+            skipped_feature_sets.extend(feature_sets_to_check[set_index..].iter().map(
+                |feature_set| SkippedFeatureSet {
+                    feature_set_desc: feature_set.description.clone(),
+                    reason: "not run (interrupted)".to_string(),
+                },
+            ));
+            break;
+        }
+
+        // An explicit target selection builds exactly those targets (or fails loudly).
+        let skipped_targets = if options.input.is_none() && target_args.is_empty() {
+            feature_set.skipped_targets.clone()
+        } else {
+            Vec::new()
+        };
+        if !skipped_targets.is_empty() {
+            info!(
+                "[getdoc] Note: cargo did not build {} under '{}' (missing required features; see --ensure-targets).",
+                skipped_targets.join(", "),
+                feature_desc
+            );
+        }
+        feature_set_timings.push(FeatureSetTiming {
+            feature_set_desc: feature_desc.clone(),
+            duration: run_started.elapsed(),
+            skipped_targets,
+            cached_from: cached_from.clone(),
+            command: options
+                .input
+                .is_none()
+                .then(|| cargo_cli.reproduction_command(&feature_set.args)),
+        });
+
+        let mut run_had_errors = false;
+        let outcome = match run_result {
+            Ok((diagnostics_for_run, implicated_files_for_run, referencers_for_run)) => {
+                // A set whose build failed short of compiling everything cannot show that
+                // a diagnostic is absent under it.
+                if !diagnostics_for_run.iter().any(|d| d.level == "TOOL_ERROR") {
+                    checked_feature_sets.push(feature_desc.clone());
+                }
+                // `TOOL_ERROR`s here are build-script and native-library failures.
+                let outcome = if diagnostics_for_run
+                    .iter()
+                    .any(|d| d.level == "error" || d.level == "TOOL_ERROR")
+                {
+                    sets_with_errors += 1;
+                    run_had_errors = true;
+                    failed_feature_sets_this_run.push(feature_desc.clone());
+                    RunOutcome::Errors
+                } else if diagnostics_for_run.is_empty() {
+                    clean_sets += 1;
+                    RunOutcome::Clean
+                } else {
+                    sets_with_warnings_only += 1;
+                    RunOutcome::Warnings
+                };
+                if !diagnostics_for_run.is_empty() {
+                    all_displayable_diagnostics.push((feature_desc.clone(), diagnostics_for_run));
+                }
+                for (file, lines) in implicated_files_for_run {
+                    all_implicated_files_globally
+                        .entry(file)
+                        .or_default()
+                        .extend(lines);
+                }
+                for (file, origins) in referencers_for_run {
+                    global_file_referencers
+                        .entry(file)
+                        .or_default()
+                        .extend(origins);
+                }
+                outcome
+            }
+            // Every other set would fail the same way.
+            Err(e @ (GetdocError::CargoNotFound { .. } | GetdocError::ReadInput { .. })) => {
+                return Err(e);
+            }
+            Err(e) => {
+                failed_sets += 1;
+                let error_message = format!("Error: {}", e);
+                warning!("[getdoc] {}", error_message);
+                all_displayable_diagnostics.push((
+                    feature_desc.clone(),
+                    vec![DisplayableDiagnostic {
+                        level: "TOOL_ERROR".to_string(),
+                        code: None,
+                        code_explanation: None,
+                        message: error_message.clone(),
+                        rendered: error_message,
+                        primary_location_of_diagnostic: None,
+                        implicated_third_party_files_details: vec![],
+                        suggestions: vec![],
+                        producing_crate: None,
+                    }],
+                ));
+                RunOutcome::ToolError
+            }
+        };
+        if let Some(run_cache) = &run_cache
+            && cached_from.is_none()
+        {
+            run_cache.store(feature_set, outcome, fresh_output);
+        }
+
+        let remaining_sets = &feature_sets_to_check[set_index + 1..];
+        if remaining_sets.is_empty() {
+            continue;
+        }
+        let stop_reason = if interrupt::interrupted() {
+            run_interrupted = true;
+            Some("not run (interrupted)".to_string())
+        } else if options.fail_fast && run_had_errors {
+            info!(
+                "[getdoc] --fail-fast: stopping after errors under '{}'; {} feature set(s) not checked.",
+                feature_desc,
+                remaining_sets.len()
+            );
+            Some(format!(
+                "not checked (`--fail-fast` stopped after errors under `{}`)",
+                feature_desc
+            ))
+        } else if let Some(budget) = time_budget
+            && analysis_started.elapsed() >= budget
+        {
+            info!(
+                "[getdoc] --max-total-time: {} budget used up; {} feature set(s) not run.",
+                format_duration(budget),
+                remaining_sets.len()
+            );
+            time_budget_exceeded = true;
+            Some("not run (time budget exceeded)".to_string())
+        } else {
+            None
+        };
+        if let Some(reason) = stop_reason {
+            skipped_feature_sets.extend(remaining_sets.iter().map(|feature_set| {
+                SkippedFeatureSet {
+                    feature_set_desc: feature_set.description.clone(),
+                    reason: reason.clone(),
+                }
+            }));
+            break;
+        }
+    }
+    feature_set_bar.finish();
+
+    if options.input.is_none() {
+        // Sets that were not checked this time keep their previous status.
+        let checked: HashSet<&String> = feature_set_timings
+            .iter()
+            .map(|timing| &timing.feature_set_desc)
+            .collect();
+        let mut failed_feature_sets = failed_feature_sets_this_run;
+        failed_feature_sets.extend(
+            previous_run_state
+                .failed_feature_sets
+                .into_iter()
+                .filter(|desc| !checked.contains(desc)),
+        );
+        save_run_state(&RunState {
+            failed_feature_sets,
+        });
+    }
+
+    info!(
+        "[getdoc] Checked {} feature set(s) in {}: {} with errors, {} with warnings only, {} clean{}.",
+        feature_set_timings.len(),
+        format_duration(
+            feature_set_timings
+                .iter()
+                .map(|timing| timing.duration)
+                .sum()
+        ),
+        sets_with_errors,
+        sets_with_warnings_only,
+        clean_sets,
+        if failed_sets > 0 {
+            format!(", {} failed to run", failed_sets)
+        } else {
+            String::new()
+        }
+    );
+    if !flaky_diagnostics.is_empty() {
+        warning!(
+            "[getdoc] Warning: {} diagnostic(s) did not reproduce in every run of their feature set; see \"Flaky Diagnostics\" in the report.",
+            flaky_diagnostics.len()
+        );
+    }
+    if !skipped_feature_sets.is_empty() {
+        info!(
+            "[getdoc] Skipped {} feature set(s); see the report for reasons.",
+            skipped_feature_sets.len()
+        );
+    }
+
+    // Determine mode description once; it is shared by the minimal and full reports
+    let mode_description_for_report = match options.features.as_ref() {
+        _ if options.input.is_some() => "Pre-captured Input Mode".to_string(),
+        _ if let Some(list_path) = options.features_from_file.as_ref() => {
+            format!("Feature-List Mode (`{}`)", list_path.display())
+        }
+        _ if options.each_feature => "Each-Feature Mode (cargo-hack compatible)".to_string(),
+        Some(features_vec) if !features_vec.is_empty() => {
+            format!("Targeted Mode for Features: `{}`", features_vec.join(", "))
+        }
+        Some(_) => "Targeted Mode (Context specified, using crate defaults)".to_string(),
+        None => "Comprehensive Mode".to_string(),
+    };
+    let mode_description_for_report = if options.command == CargoCommand::Check {
+        mode_description_for_report
+    } else {
+        format!(
+            "{} (`{}`)",
+            mode_description_for_report,
+            cargo_cli.display_name()
+        )
+    };
+    let mode_description_for_report = if rustflags.is_empty() || !rustflags_applied {
+        mode_description_for_report
+    } else {
+        format!(
+            "{} (RUSTFLAGS: `{}`)",
+            mode_description_for_report,
+            rustflags.join(" ")
+        )
+    };
+    let mode_description_for_report = if options.cargo_config.is_empty() {
+        mode_description_for_report
+    } else {
+        format!(
+            "{} (cargo config: `{}`)",
+            mode_description_for_report,
+            options.cargo_config.join("`, `")
+        )
+    };
+    let mode_description_for_report = if options.minimal_versions {
+        format!(
+            "{} (minimal dependency versions)",
+            mode_description_for_report
+        )
+    } else {
+        mode_description_for_report
+    };
+    let mode_description_for_report = match sampling_note {
+        Some(note) => format!("{} ({})", mode_description_for_report, note),
+        None => mode_description_for_report,
+    };
+    let reused_set_count = feature_set_timings
+        .iter()
+        .filter(|timing| timing.cached_from.is_some())
+        .count();
+    let mode_description_for_report = if reused_set_count > 0 {
+        format!(
+            "{} ({} cached and {} fresh feature-set result(s))",
+            mode_description_for_report,
+            reused_set_count,
+            feature_set_timings.len() - reused_set_count
+        )
+    } else {
+        mode_description_for_report
+    };
+
+    let feature_tables = match manifest_features.as_ref() {
+        Some(manifest_features) if options.include_features_table => {
+            declared_feature_tables(manifest_features)
+        }
+        _ => Vec::new(),
+    };
+
+    let diagnostic_counts = DiagnosticCounts::of(&all_displayable_diagnostics);
+    if diagnostic_counts.reported > 0 && all_implicated_files_globally.is_empty() {
+        info!(
+            "[getdoc] {} diagnostic(s) reported, none implicating third-party files; the report lists them without third-party sources.",
+            diagnostic_counts.reported
+        );
+    }
+
+    let excluded_files = exclude_implicated_files(
+        &options.exclude_path,
+        &mut all_implicated_files_globally,
+        &mut global_file_referencers,
+    );
+
+    if all_displayable_diagnostics
+        .iter()
+        .all(|(_, diags)| diags.is_empty())
+        && all_implicated_files_globally.is_empty()
+    {
+        info!(
+            "[getdoc] No compiler messages under any of the {} checked feature set(s), and no third-party crates implicated.",
+            feature_set_timings.len()
+        );
+        let report = Report {
+            mode_description: mode_description_for_report,
+            feature_tables,
+            skipped_feature_sets,
+            equivalent_feature_sets,
+            feature_set_timings,
+            total_duration: analysis_started.elapsed(),
+            time_budget_exceeded,
+            interrupted: run_interrupted,
+            phase_timings: if options.timings {
+                phase_timer.phases()
+            } else {
+                Vec::new()
+            },
+            diagnostic_counts,
+            raw_json_dir: options.emit_raw_json.clone(),
+            ..Default::default()
+        };
+        let written_paths = phase_timer.time("report writing", || {
+            write_reports(
+                &report,
+                &options.format,
+                &options.output,
+                &markdown_options,
+                true,
+                options.force,
+            )
+        })?;
+        if !written_paths.is_empty() {
+            info!(
+                "[getdoc] Minimal report generated: {}",
+                display_paths(&written_paths)
+            );
+        }
+        if let Some(crate_name) = &options.emit_issue {
+            warning!(
+                "[getdoc] Warning: No diagnostics implicate `{}`; no issue draft written.",
+                crate_name
+            );
+        }
+        if let Some(summary_path) = options.summary_json.as_ref() {
+            phase_timer.time("report writing", || {
+                write_summary_json(&report, summary_path)
+            })?;
+        }
+        info!("[getdoc] Time by phase: {}.", phase_timer.summary());
+        if options.open {
+            open_report(&written_paths);
+        }
+        if options.copy {
+            copy_report(&report, &written_paths, options.copy_warn_above);
+        }
+        return Ok(Report {
+            written_paths,
+            ..report
+        });
+    }
+
+    // --- Consolidate Diagnostics and Collect Explanations ---
+    let (mut sorted_consolidated_diagnostics, unique_explanations) =
+        consolidate_diagnostics(&all_displayable_diagnostics);
+    record_absent_feature_sets(&mut sorted_consolidated_diagnostics, &checked_feature_sets);
+    sort_diagnostics(&mut sorted_consolidated_diagnostics, options.sort);
+
+    let mut suppressed_ids = options.suppress_ids.clone();
+    suppressed_ids.extend(manifest_suppressed_ids(manifest_path));
+    let (suppressed_diagnostics, unmatched_suppressed_ids) = suppress_diagnostics(
+        &mut sorted_consolidated_diagnostics,
+        &mut global_file_referencers,
+        &mut all_implicated_files_globally,
+        &suppressed_ids,
+    );
+    if suppressed_diagnostics > 0 {
+        info!(
+            "[getdoc] Suppressed {} diagnostic(s) by ID.",
+            suppressed_diagnostics
+        );
+    }
+    for id in unmatched_suppressed_ids {
+        warning!(
+            "[getdoc] Warning: No diagnostic has the suppressed ID `{}`; it may have been fixed, or changed and been given a new ID.",
+            id
+        );
+    }
+
+    // `--format short` alone needs only the diagnostics; skip the extraction work (which
+    // an issue draft does need).
+    if options.emit_issue.is_none()
+        && options
+            .format
+            .iter()
+            .all(|format| format.extension().is_none())
+    {
+        let report = Report {
+            mode_description: mode_description_for_report,
+            consolidated_diagnostics: sorted_consolidated_diagnostics,
+            suppressed_diagnostics,
+            skipped_feature_sets,
+            equivalent_feature_sets,
+            feature_set_timings,
+            total_duration: analysis_started.elapsed(),
+            time_budget_exceeded,
+            interrupted: run_interrupted,
+            phase_timings: if options.timings {
+                phase_timer.phases()
+            } else {
+                Vec::new()
+            },
+            diagnostic_counts,
+            raw_json_dir: options.emit_raw_json.clone(),
+            ..Default::default()
+        };
+        let written_paths = phase_timer.time("report writing", || {
+            write_reports(
+                &report,
+                &options.format,
+                &options.output,
+                &markdown_options,
+                false,
+                options.force,
+            )
+        })?;
+        if let Some(summary_path) = options.summary_json.as_ref() {
+            phase_timer.time("report writing", || {
+                write_summary_json(&report, summary_path)
+            })?;
+        }
+        info!("[getdoc] Time by phase: {}.", phase_timer.summary());
+        if options.copy {
+            copy_report(&report, &written_paths, options.copy_warn_above);
+        }
+        return Ok(Report {
+            written_paths,
+            ..report
+        });
+    }
+
+    let extraction_options = ExtractionOptions::from(options);
+    let mut extracted_data: HashMap<PathBuf, Vec<ExtractedItem>> = HashMap::new();
+    let mut hidden_items_skipped: HashMap<PathBuf, usize> = HashMap::new();
+    let mut lossily_read_files: HashMap<PathBuf, usize> = HashMap::new();
+    let mut raw_fallbacks: HashMap<PathBuf, RawContextFallback> = HashMap::new();
+    let mut sorted_file_paths: Vec<PathBuf> =
+        all_implicated_files_globally.keys().cloned().collect();
+    sorted_file_paths.sort();
+
+    let mut missing_sources = fetch::missing_crate_sources(&sorted_file_paths);
+    if !missing_sources.is_empty() && options.fetch {
+        info!(
+            "[getdoc] Fetching the sources of {} implicated crate(s) not on disk...",
+            missing_sources.len()
+        );
+        let fetched = fetch::fetch_crate_sources(&missing_sources, &options.cargo_config);
+        if !fetched.is_empty() {
+            info!("[getdoc] Fetched: {}", fetched.join(", "));
+        }
+        missing_sources.retain(|crate_name, _| !fetched.contains(crate_name));
+    }
+    let missing_crate_sources: Vec<String> = missing_sources.into_keys().collect();
+    if !missing_crate_sources.is_empty() {
+        warning!(
+            "[getdoc] Warning: The sources of {} implicated crate(s) are not on disk, so nothing was extracted from them: {}{}",
+            missing_crate_sources.len(),
+            missing_crate_sources.join(", "),
+            if options.fetch {
+                ""
+            } else {
+                " (pass --fetch to download them)"
+            }
+        );
+    }
+    let extractable_file_paths: Vec<PathBuf> = sorted_file_paths
+        .iter()
+        .filter(|path| path.exists())
+        .cloned()
+        .collect();
+
+    info!(
+        "[getdoc] Inspecting {} implicated third-party file(s)...",
+        extractable_file_paths.len()
+    );
+    let extraction_bar = Bar::new(extractable_file_paths.len(), "files");
+    let extraction_results = extract_items_from_files(
+        &extractable_file_paths,
+        &all_implicated_files_globally,
+        &extraction_options,
+        &extraction_bar,
+        &phase_timer,
+    );
+    extraction_bar.finish();
+
+    // Results come back in path order, so messages below stay deterministic.
+    for (file_path, extraction_result) in extractable_file_paths.iter().zip(extraction_results) {
+        match extraction_result {
+            Ok(FileExtraction {
+                items,
+                hidden_items_skipped: skipped_count,
+                raw_fallback,
+                first_invalid_utf8_line,
+            }) => {
+                if let Some(line) = first_invalid_utf8_line {
+                    warning!(
+                        "[getdoc] Warning: {} is not valid UTF-8 (first invalid byte on line {}); read it with invalid bytes replaced, so its excerpts may be imperfect.",
+                        file_path.display(),
+                        line
+                    );
+                    lossily_read_files.insert(file_path.clone(), line);
+                }
+                if skipped_count > 0 {
+                    hidden_items_skipped.insert(file_path.clone(), skipped_count);
+                }
+                if let Some(fallback) = raw_fallback {
+                    warning!(
+                        "[getdoc] Warning: {} in {}; including raw source around implicated lines instead.",
+                        fallback.reason,
+                        file_path.display()
+                    );
+                    raw_fallbacks.insert(file_path.clone(), fallback);
+                } else if !items.is_empty() {
+                    extracted_data.insert(file_path.clone(), items);
+                } else {
+                    info!(
+                        "[getdoc] No extractable items (meeting criteria) found in: {}",
+                        file_path.display()
+                    );
+                }
+            }
+            Err(e) => warning!("[getdoc] Warning: {}", e),
+        }
+    }
+
+    if rustdoc_json && !extracted_data.is_empty() {
+        // Each file is documented under the first feature set of the matrix implicating it.
+        let feature_args_by_file: HashMap<PathBuf, Vec<String>> = global_file_referencers
+            .iter()
+            .filter_map(|(file, origins)| {
+                let feature_set = feature_sets_to_check.iter().find(|feature_set| {
+                    origins
+                        .iter()
+                        .any(|origin| origin.feature_set_desc == feature_set.description)
+                })?;
+                Some((file.clone(), feature_set.args.clone()))
+            })
+            .collect();
+        let enriched_count = phase_timer.time("rustdoc JSON", || {
+            rustdoc_json::enrich_with_rustdoc_json(
+                &mut extracted_data,
+                &all_implicated_files_globally,
+                &feature_args_by_file,
+                &options.cargo_config,
+            )
+        });
+        info!(
+            "[getdoc] Used rustdoc JSON for {} implicated crate(s); any others keep their source extraction.",
+            enriched_count
+        );
+    }
+
+    let collapsed_count = collapse_duplicate_items(&sorted_file_paths, &mut extracted_data);
+    if collapsed_count > 0 {
+        info!(
+            "[getdoc] Collapsed {} item(s) identical to items already extracted from another file.",
+            collapsed_count
+        );
+    }
+
+    let related_impls = if options.related_impls {
+        info!("[getdoc] Scanning implicated crates for related impl blocks...");
+        phase_timer.time("related impls", || {
+            find_related_impls(&extracted_data, &extraction_options)
+        })
+    } else {
+        HashMap::new()
+    };
+
+    let report = Report {
+        mode_description: mode_description_for_report,
+        feature_tables,
+        consolidated_diagnostics: sorted_consolidated_diagnostics,
+        unique_explanations,
+        extracted_data,
+        hidden_items_skipped,
+        lossily_read_files,
+        sorted_file_paths,
+        implicated_lines_by_file: all_implicated_files_globally,
+        raw_fallbacks,
+        related_impls,
+        file_referencers: global_file_referencers,
+        skipped_feature_sets,
+        equivalent_feature_sets,
+        feature_set_timings,
+        total_duration: analysis_started.elapsed(),
+        written_paths: Vec::new(),
+        time_budget_exceeded,
+        interrupted: run_interrupted,
+        phase_timings: if options.timings {
+            phase_timer.phases()
+        } else {
+            Vec::new()
+        },
+        missing_crate_sources,
+        flaky_diagnostics,
+        diagnostic_counts,
+        suppressed_diagnostics,
+        excluded_files,
+        raw_json_dir: options.emit_raw_json.clone(),
+    };
+    let written_paths = phase_timer.time("report writing", || {
+        write_reports(
+            &report,
+            &options.format,
+            &options.output,
+            &markdown_options,
+            false,
+            options.force,
+        )
+    })?;
+
+    info!(
+        "[getdoc] Analysis complete. Report generated: {}",
+        display_paths(&written_paths)
+    );
+    let total_items: usize = report.extracted_data.values().map(Vec::len).sum::<usize>()
+        + report
+            .related_impls
+            .values()
+            .flatten()
+            .map(|block| block.items.len())
+            .sum::<usize>();
+    let report_sizes: Vec<String> = written_paths
+        .iter()
+        .filter_map(|path| {
+            fs::metadata(path)
+                .ok()
+                .map(|metadata| format!("{} = {} bytes", path.display(), metadata.len()))
+        })
+        .collect();
+    info!(
+        "[getdoc] Report size: {}; {} extracted item(s).",
+        report_sizes.join(", "),
+        total_items
+    );
+    if let Some(crate_name) = &options.emit_issue {
+        let issue_path = issue::issue_path(&options.output, crate_name);
+        match issue::write_issue(
+            &report,
+            crate_name,
+            &feature_sets_to_check,
+            &issue_path,
+            options.force,
+        )? {
+            IssueOutcome::Written { issues_url } => info!(
+                "[getdoc] Issue draft for `{}` written to {}{}",
+                crate_name,
+                issue_path.display(),
+                issues_url
+                    .map(|url| format!("; file it at {}", url))
+                    .unwrap_or_default()
+            ),
+            IssueOutcome::NotImplicated => warning!(
+                "[getdoc] Warning: No diagnostics implicate `{}`; no issue draft written.",
+                crate_name
+            ),
+        }
+    }
+    if let Some(summary_path) = options.summary_json.as_ref() {
+        phase_timer.time("report writing", || {
+            write_summary_json(&report, summary_path)
+        })?;
+    }
+    info!("[getdoc] Time by phase: {}.", phase_timer.summary());
+    if options.open {
+        open_report(&written_paths);
+    }
+    if options.copy {
+        copy_report(&report, &written_paths, options.copy_warn_above);
+    }
+    Ok(Report {
+        written_paths,
+        ..report
+    })
+}
+
+/// Every file or directory this run writes into the project: the reports, the issue draft,
+/// the summary, and the raw captures. They are left out of the cache's source fingerprint,
+/// since they change on every run.
+fn output_paths(options: &Options) -> Vec<PathBuf> {
+    report_paths(&options.format, &options.output)
+        .into_iter()
+        .filter_map(|(_, path)| path)
+        .chain(
+            options
+                .emit_issue
+                .as_deref()
+                .map(|crate_name| issue::issue_path(&options.output, crate_name)),
+        )
+        .chain(options.summary_json.clone())
+        .chain(options.emit_raw_json.clone())
+        .collect()
+}
+
+// --- Run State ---
+
+/// What getdoc remembers between runs, stored best-effort under `target/getdoc/`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RunState {
+    // Descriptions of the feature sets whose last check produced errors.
+    #[serde(default)]
+    failed_feature_sets: Vec<String>,
+}
+
+/// Location of the run-state file: `$CARGO_TARGET_DIR/getdoc/last-run.json`, or under
+/// `./target` when `CARGO_TARGET_DIR` is unset.
+fn run_state_path() -> PathBuf {
+    getdoc_dir().join("last-run.json")
+}
+
+/// Reads the previous run's state; a missing or unreadable file just means no history.
+fn load_run_state() -> RunState {
+    fs::read_to_string(run_state_path())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Drops the implicated files matching any of `globs` (`--exclude-path`), along with the
+/// diagnostics' references to them. Returns how many files were dropped.
+fn exclude_implicated_files(
+    globs: &[PathGlob],
+    implicated_files: &mut HashMap<PathBuf, BTreeSet<usize>>,
+    file_referencers: &mut HashMap<PathBuf, HashSet<DiagnosticOriginInfo>>,
+) -> usize {
+    if globs.is_empty() {
+        return 0;
+    }
+    let mut excluded: Vec<PathBuf> = implicated_files
+        .keys()
+        .filter(|path| globs.iter().any(|glob| glob.matches(path)))
+        .cloned()
+        .collect();
+    excluded.sort();
+    for path in &excluded {
+        implicated_files.remove(path);
+        file_referencers.remove(path);
+        info!(
+            "[getdoc] Excluding {} (matches --exclude-path).",
+            path.display()
+        );
+    }
+    for glob in globs {
+        if !excluded.iter().any(|path| glob.matches(path)) {
+            info!(
+                "[getdoc] Note: --exclude-path `{}` matched no implicated file.",
+                glob.as_str()
+            );
+        }
+    }
+    excluded.len()
+}
+
+/// The declared features of the package, or of each member of a virtual workspace, for
+/// `--include-features-table`.
+fn declared_feature_tables(manifest_features: &ManifestFeatures) -> Vec<PackageFeatureTable> {
+    match manifest_features {
+        ManifestFeatures::Package(package) => vec![PackageFeatureTable {
+            package: None,
+            features: package.table.clone(),
+        }],
+        ManifestFeatures::VirtualWorkspace(members) => members
+            .iter()
+            .map(|(name, package)| PackageFeatureTable {
+                package: Some(name.clone()),
+                features: package.table.clone(),
+            })
+            .collect(),
+    }
+}
+
+/// Saves the run state for the next run. Failures are reported but never fail the run.
+fn save_run_state(state: &RunState) {
+    let path = run_state_path();
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(&path, serde_json::to_string_pretty(state)?));
+    if let Err(e) = result {
+        warning!(
+            "[getdoc] Warning: Could not save run state to {}: {}",
+            path.display(),
+            e
+        );
+    }
+}
@@ -0,0 +1,98 @@
+// getdoc - lock.rs
+//
+// Keeps two getdoc runs in the same project from interleaving their reports and cache
+// writes: each run holds an advisory lock on `target/getdoc/.lock` while it works.
+
+// --- Standard Library Imports ---
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, Write};
+use std::time::Duration;
+
+// --- Crate Imports ---
+use crate::error::GetdocError;
+use crate::interrupt::interrupted;
+use crate::progress::{info, warning};
+use crate::state::getdoc_dir;
+
+/// How often a waiting run retries the lock (and notices Ctrl-C).
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Held for the duration of a run. The lock is released when this is dropped, including
+/// while unwinding from a panic, and by the operating system when the process exits in
+/// any other way (a second Ctrl-C, `std::process::exit`, or a crash).
+pub(crate) struct RunLock {
+    _file: Option<File>,
+}
+
+impl RunLock {
+    /// Takes the project's run lock. If another run holds it, waits for it to be released
+    /// when `wait` is set, and otherwise fails with [`GetdocError::RunLocked`]. A lock file
+    /// that cannot be created (e.g. a read-only target directory) only earns a warning.
+    pub(crate) fn acquire(wait: bool) -> Result<RunLock, GetdocError> {
+        let path = getdoc_dir().join(".lock");
+        let mut file = match fs::create_dir_all(getdoc_dir()).and_then(|()| {
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&path)
+        }) {
+            Ok(file) => file,
+            Err(e) => {
+                warning!(
+                    "[getdoc] Warning: Could not create the lock file {} ({}); not guarding against concurrent runs.",
+                    path.display(),
+                    e
+                );
+                return Ok(RunLock { _file: None });
+            }
+        };
+
+        let mut announced_wait = false;
+        loop {
+            match file.try_lock() {
+                Ok(()) => break,
+                Err(TryLockError::WouldBlock) => {
+                    let holder = lock_holder(&file);
+                    if !wait || interrupted() {
+                        return Err(GetdocError::RunLocked { path, holder });
+                    }
+                    if !announced_wait {
+                        info!(
+                            "[getdoc] Another getdoc run{} is working in this project; waiting for it to finish...",
+                            holder
+                                .map(|pid| format!(" (process {})", pid))
+                                .unwrap_or_default()
+                        );
+                        announced_wait = true;
+                    }
+                    std::thread::sleep(LOCK_RETRY_INTERVAL);
+                }
+                Err(TryLockError::Error(e)) => {
+                    warning!(
+                        "[getdoc] Warning: Could not lock {} ({}); not guarding against concurrent runs.",
+                        path.display(),
+                        e
+                    );
+                    return Ok(RunLock { _file: None });
+                }
+            }
+        }
+
+        // Leave our process ID behind so a blocked run can say who it is waiting for.
+        let _ = file
+            .set_len(0)
+            .and_then(|()| file.rewind())
+            .and_then(|()| write!(file, "{}", std::process::id()));
+        Ok(RunLock { _file: Some(file) })
+    }
+}
+
+/// The process ID the current holder wrote into the lock file, if readable.
+fn lock_holder(mut file: &File) -> Option<u32> {
+    let mut content = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut content).ok()?;
+    content.trim().parse().ok()
+}
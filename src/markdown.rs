@@ -0,0 +1,73 @@
+// getdoc - markdown.rs
+//
+// Escaping for the Markdown report. Item names and impl headers are full of `<`, `>`, `|`,
+// `*`, and backticks (e.g. ``impl<T: Deref<Target = [u8]>> Foo for Bar<T>``), so they only
+// go into the report as code spans built here, and headings are kept unique so that their
+// anchors are.
+
+// --- Standard Library Imports ---
+use std::collections::HashSet;
+
+/// `text` as an inline code span. Text containing backticks is delimited by a longer run
+/// of backticks than any inside it, with a space of padding when it starts or ends with a
+/// backtick (or a space), as CommonMark requires. Line breaks become spaces.
+pub fn inline_code(text: &str) -> String {
+    let text = text.replace("\r\n", " ").replace(['\n', '\r'], " ");
+    if text.is_empty() {
+        return "` `".to_string();
+    }
+    let longest_backtick_run = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_backtick_run + 1);
+    // CommonMark strips one space from each end of a span that has a space at both, so
+    // padding is needed whenever an end is a backtick or the text already has such spaces.
+    let needs_padding = text.starts_with('`')
+        || text.ends_with('`')
+        || (text.starts_with(' ') && text.ends_with(' ') && !text.chars().all(|c| c == ' '));
+    if needs_padding {
+        format!("{} {} {}", fence, text, fence)
+    } else {
+        format!("{}{}{}", fence, text, fence)
+    }
+}
+
+/// [`inline_code`] for a table cell. GFM splits rows at `|` before it sees code spans, so
+/// pipes are escaped even inside the span; the backslash does not show.
+pub fn table_code(text: &str) -> String {
+    inline_code(text).replace('|', "\\|")
+}
+
+/// The anchor GitHub gives a heading: its text lowercased, without punctuation (including
+/// Markdown syntax such as backticks and `**`), spaces turned into hyphens.
+pub fn heading_anchor(heading: &str) -> String {
+    heading
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            '-' | '_' => Some(c),
+            c if c.is_alphanumeric() => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The headings a report has written so far, so that each new one gets an anchor of its own.
+#[derive(Debug, Default)]
+pub struct HeadingAnchors {
+    seen: HashSet<String>,
+}
+
+impl HeadingAnchors {
+    /// `heading` as is when its anchor is new, otherwise with the first of ` (2)`, ` (3)`, …
+    /// that makes it new.
+    pub fn unique(&mut self, heading: String) -> String {
+        if self.seen.insert(heading_anchor(&heading)) {
+            return heading;
+        }
+        (2..)
+            .map(|suffix| format!("{} ({})", heading, suffix))
+            .find(|candidate| self.seen.insert(heading_anchor(candidate)))
+            .expect("some suffix gives an unused anchor")
+    }
+}
@@ -0,0 +1,141 @@
+// getdoc - minimal_versions.rs
+//
+// `--minimal-versions`: checking against the oldest dependency versions the manifests allow,
+// as `cargo +nightly generate-lockfile -Z minimal-versions` resolves them. The project's
+// `Cargo.lock` is swapped out for the run and put back afterwards; a copy is kept under
+// `target/getdoc/minimal-versions/` so that a run that was killed is undone by the next one.
+
+// --- Standard Library Imports ---
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+// --- Crate Imports ---
+use crate::cargo::{cargo_command, nightly_available, nightly_cargo_command};
+use crate::error::GetdocError;
+use crate::progress::{info, warning};
+use crate::state::getdoc_dir;
+
+/// Where the original lockfile and its location are kept while the minimal one is in use.
+fn backup_dir() -> PathBuf {
+    getdoc_dir().join("minimal-versions")
+}
+
+/// The minimal-versions `Cargo.lock`, in place until this is dropped; then the original
+/// lockfile (or its absence) is restored.
+pub(crate) struct MinimalVersionsLockfile {
+    _private: (),
+}
+
+impl MinimalVersionsLockfile {
+    /// Saves the project's `Cargo.lock` and replaces it with one resolved to minimal versions.
+    /// `config_overrides` are passed to cargo as `--config` options.
+    pub(crate) fn generate(
+        config_overrides: &[String],
+    ) -> Result<MinimalVersionsLockfile, GetdocError> {
+        let minimal_versions_error = |message: String| GetdocError::MinimalVersions { message };
+        if !nightly_available() {
+            return Err(minimal_versions_error(
+                "no nightly toolchain found (`cargo +nightly` failed)".to_string(),
+            ));
+        }
+        let lockfile = workspace_lockfile(config_overrides).map_err(minimal_versions_error)?;
+        let backup_dir = backup_dir();
+        let save = || -> std::io::Result<()> {
+            fs::create_dir_all(&backup_dir)?;
+            if lockfile.is_file() {
+                fs::copy(&lockfile, backup_dir.join("Cargo.lock"))?;
+            }
+            // Written last: its presence means the backup is complete.
+            fs::write(
+                backup_dir.join("lockfile-path"),
+                lockfile.to_string_lossy().as_bytes(),
+            )
+        };
+        save().map_err(|e| {
+            minimal_versions_error(format!(
+                "could not save {} to {}: {}",
+                lockfile.display(),
+                backup_dir.display(),
+                e
+            ))
+        })?;
+        let guard = MinimalVersionsLockfile { _private: () };
+
+        info!("[getdoc] Resolving dependencies to their minimal versions...");
+        let output = nightly_cargo_command(config_overrides)
+            .args(["generate-lockfile", "-Z", "minimal-versions"])
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| minimal_versions_error(e.to_string()))?;
+        if !output.status.success() {
+            // Dropping `guard` puts the original lockfile back.
+            return Err(minimal_versions_error(format!(
+                "`cargo +nightly generate-lockfile -Z minimal-versions` failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(guard)
+    }
+}
+
+impl Drop for MinimalVersionsLockfile {
+    fn drop(&mut self) {
+        restore_original_lockfile();
+    }
+}
+
+/// Puts back the lockfile saved by [`MinimalVersionsLockfile::generate`], if there is a
+/// saved one: the run that saved it is over, or was killed before it could restore it.
+pub(crate) fn restore_original_lockfile() {
+    let backup_dir = backup_dir();
+    let Ok(lockfile) = fs::read_to_string(backup_dir.join("lockfile-path")) else {
+        return;
+    };
+    let lockfile = Path::new(&lockfile);
+    let backup = backup_dir.join("Cargo.lock");
+    let had_lockfile = backup.is_file();
+    let result = if had_lockfile {
+        fs::copy(&backup, lockfile).map(|_| ())
+    } else {
+        // There was no lockfile before; remove the generated one.
+        fs::remove_file(lockfile).or_else(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => Ok(()),
+            _ => Err(e),
+        })
+    };
+    match result {
+        Ok(()) => {
+            let _ = fs::remove_dir_all(&backup_dir);
+            if had_lockfile {
+                info!("[getdoc] Restored the original {}.", lockfile.display());
+            } else {
+                info!(
+                    "[getdoc] Removed the minimal-versions {}; the project had none.",
+                    lockfile.display()
+                );
+            }
+        }
+        Err(e) => warning!(
+            "[getdoc] Warning: Could not restore {} ({}); the original is saved in {}.",
+            lockfile.display(),
+            e,
+            backup_dir.display()
+        ),
+    }
+}
+
+/// The `Cargo.lock` of the workspace the current directory belongs to (which need not
+/// exist yet).
+fn workspace_lockfile(config_overrides: &[String]) -> Result<PathBuf, String> {
+    let output = cargo_command(config_overrides)
+        .args(["locate-project", "--workspace", "--message-format", "plain"])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("could not run `cargo locate-project`: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    let manifest = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    Ok(manifest.with_file_name("Cargo.lock"))
+}
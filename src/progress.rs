@@ -0,0 +1,120 @@
+// getdoc - progress.rs
+//
+// Progress bars for feature-set runs and file extraction, and the console output layer
+// that keeps informational lines from tearing through them.
+
+// --- Standard Library Imports ---
+use std::fmt;
+use std::io::IsTerminal;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+// --- External Crate Imports ---
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+
+/// The bars on screen, or `None` when progress is reported as plain log lines.
+static DISPLAY: Mutex<Option<MultiProgress>> = Mutex::new(None);
+/// Set by `--quiet`: informational lines are dropped, warnings and errors still shown.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Chooses how progress is shown for the rest of the run: bars when both stdout and stderr
+/// are terminals and `quiet` is off, plain log lines otherwise.
+pub(crate) fn configure(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+    let interactive = std::io::stdout().is_terminal() && std::io::stderr().is_terminal();
+    *DISPLAY.lock().unwrap_or_else(|e| e.into_inner()) =
+        (interactive && !quiet).then(MultiProgress::new);
+}
+
+/// Prints an informational line to stdout, above the bars while they are shown.
+pub(crate) fn print_info(args: fmt::Arguments) {
+    if QUIET.load(Ordering::Relaxed) {
+        return;
+    }
+    match DISPLAY.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        Some(display) => {
+            let _ = display.println(args.to_string());
+        }
+        None => println!("{}", args),
+    }
+}
+
+/// Prints a warning or error line to stderr, above the bars while they are shown.
+pub(crate) fn print_warning(args: fmt::Arguments) {
+    match DISPLAY.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        Some(display) => display.suspend(|| eprintln!("{}", args)),
+        None => eprintln!("{}", args),
+    }
+}
+
+/// `println!` for getdoc's informational output; see [`print_info`].
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::progress::print_info(format_args!($($arg)*))
+    };
+}
+
+/// `eprintln!` for getdoc's warnings and errors; see [`print_warning`].
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        $crate::progress::print_warning(format_args!($($arg)*))
+    };
+}
+
+pub(crate) use {info, warning};
+
+/// A progress bar over `total` steps, or nothing when progress is shown as log lines.
+pub(crate) struct Bar(Option<ProgressBar>);
+
+impl Bar {
+    /// Adds a bar counting `unit` (e.g. "feature sets") to the display, if there is one.
+    pub(crate) fn new(total: usize, unit: &str) -> Bar {
+        let display = DISPLAY.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(display) = display.as_ref() else {
+            return Bar(None);
+        };
+        let template = format!(
+            "{{spinner}} [{{elapsed_precise}}] {{bar:30}} {{pos}}/{{len}} {} {{wide_msg}}",
+            unit
+        );
+        let bar = display.add(ProgressBar::new(total as u64));
+        bar.set_style(
+            ProgressStyle::with_template(&template)
+                .unwrap_or_else(|_| ProgressStyle::default_bar()),
+        );
+        bar.enable_steady_tick(Duration::from_millis(120));
+        Bar(Some(bar))
+    }
+
+    /// A bar that is never shown, for work that is not worth reporting on its own.
+    pub(crate) fn hidden() -> Bar {
+        Bar(None)
+    }
+
+    /// Whether the bar is on screen; when it is not, callers log each step instead.
+    pub(crate) fn is_visible(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// Shows what the current step is working on.
+    pub(crate) fn set_message(&self, message: &str) {
+        if let Some(bar) = &self.0 {
+            bar.set_message(message.to_string());
+        }
+    }
+
+    /// Marks one step as done.
+    pub(crate) fn inc(&self) {
+        if let Some(bar) = &self.0 {
+            bar.inc(1);
+        }
+    }
+
+    /// Removes the bar from the display.
+    pub(crate) fn finish(&self) {
+        if let Some(bar) = &self.0 {
+            bar.finish_and_clear();
+        }
+    }
+}
@@ -0,0 +1,2468 @@
+// getdoc - report.rs
+//
+// Writing the Markdown, JSON, and short reports.
+
+// --- Standard Library Imports ---
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+// --- External Crate Imports ---
+use chrono::{DateTime, Local, SecondsFormat, TimeZone, Utc};
+use serde::Serialize;
+
+// --- Crate Imports ---
+use crate::Options;
+use crate::diagnostics::{
+    AggregatedDiagnosticInstance, Applicability, DiagnosticCounts, DiagnosticOriginInfo,
+    FlakyDiagnostic, Suggestion, build_script_output_crate, display_location,
+    registry_crate_version,
+};
+use crate::error::GetdocError;
+use crate::extract::{ExtractedItem, PROC_MACRO_ITEM_KINDS, RawContextFallback, RelatedImplBlock};
+use crate::features::FeatureTable;
+use crate::interrupt::{begin_holding_clipboard, interrupted};
+use crate::markdown::{HeadingAnchors, inline_code, table_code};
+use crate::progress::{info, warning};
+
+/// Output formats the report can be written in.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable Markdown report.
+    #[clap(alias = "md")]
+    Markdown,
+    /// Machine-readable JSON with the same content as the Markdown report.
+    Json,
+    /// One line per diagnostic, printed to stdout instead of written to a file.
+    Short,
+}
+
+impl OutputFormat {
+    /// File extension used when deriving this format's output path from `--output`,
+    /// or `None` for formats that are printed rather than written to a file.
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            OutputFormat::Markdown => Some("md"),
+            OutputFormat::Json => Some("json"),
+            OutputFormat::Short => None,
+        }
+    }
+}
+
+/// Presentation options for the Markdown report.
+#[derive(Debug, Clone)]
+pub(crate) struct MarkdownOptions {
+    /// Per-item doc-comment line limit (0 = unlimited).
+    pub(crate) max_doc_lines: usize,
+    /// Emit doc comments as Markdown rather than as a `> ` blockquote.
+    pub(crate) raw_docs: bool,
+    /// List each implicated file once per diagnostic, with all of its implicated lines.
+    pub(crate) collapse_implicated_lines: bool,
+    /// Soft-wrap rendered diagnostics at this many characters (0 = off).
+    pub(crate) wrap: usize,
+    /// Most "Referenced by" bullets listed per file (0 = unlimited).
+    pub(crate) max_referencers: usize,
+    /// A JSON report is written alongside, so cut lists can point to it.
+    pub(crate) json_report: bool,
+    /// Add the "Suggested Fixes" section aggregating machine-applicable suggestions.
+    pub(crate) fix_preview: bool,
+    /// Show the header's time in UTC rather than local time.
+    pub(crate) utc: bool,
+    /// Format of the header's time: `rfc3339`, `rfc2822`, or a chrono format string.
+    pub(crate) date_format: String,
+    /// How much of each error code's explanation Appendix A shows.
+    pub(crate) explanations: ExplanationStyle,
+}
+
+/// How Appendix A presents error-code explanations (the JSON report always has them in full).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum ExplanationStyle {
+    /// rustc's whole explanation.
+    #[default]
+    Full,
+    /// The first paragraph, with a link to the full text (`--short-explanations`).
+    Short,
+    /// No appendix, and no pointers to it (`--no-explanations`).
+    Omitted,
+}
+
+impl From<&Options> for MarkdownOptions {
+    fn from(options: &Options) -> Self {
+        MarkdownOptions {
+            max_doc_lines: options.max_doc_lines,
+            raw_docs: options.raw_docs,
+            collapse_implicated_lines: options.collapse_implicated_lines,
+            max_referencers: options.max_referencers,
+            json_report: options.format.contains(&OutputFormat::Json),
+            wrap: options.wrap,
+            fix_preview: options.fix_preview,
+            utc: options.utc,
+            date_format: options.date_format.clone(),
+            explanations: if options.no_explanations {
+                ExplanationStyle::Omitted
+            } else if options.short_explanations {
+                ExplanationStyle::Short
+            } else {
+                ExplanationStyle::Full
+            },
+        }
+    }
+}
+
+/// Everything an analysis run gathers, in the form the report writers consume.
+/// Returned by [`run`](crate::run).
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Description of the analysis mode (Comprehensive, Targeted, Pre-captured Input), used for the report header.
+    pub mode_description: String,
+    /// Consolidated and sorted diagnostic instances. Each instance represents a unique error/warning.
+    pub consolidated_diagnostics: Vec<AggregatedDiagnosticInstance>,
+    /// Explanation texts of the error codes that occurred, keyed by error code.
+    pub unique_explanations: HashMap<String, String>,
+    /// Items extracted from each implicated third-party file.
+    pub extracted_data: HashMap<PathBuf, Vec<ExtractedItem>>,
+    /// Number of `#[doc(hidden)]` / `#[cfg(test)]` items skipped per file during extraction.
+    pub hidden_items_skipped: HashMap<PathBuf, usize>,
+    /// Implicated files that are not valid UTF-8, with the line of their first invalid byte.
+    /// They were read with invalid bytes replaced by U+FFFD.
+    pub lossily_read_files: HashMap<PathBuf, usize>,
+    /// Sorted list of paths to all implicated third-party files.
+    pub sorted_file_paths: Vec<PathBuf>,
+    /// Line numbers within each implicated file that diagnostics pointed at.
+    pub implicated_lines_by_file: HashMap<PathBuf, BTreeSet<usize>>,
+    /// Raw source excerpts for implicated files that were summarized or could not be parsed.
+    pub raw_fallbacks: HashMap<PathBuf, RawContextFallback>,
+    /// Impl blocks from other files of the crate for types defined in each implicated file (`--related-impls`).
+    pub related_impls: HashMap<PathBuf, Vec<RelatedImplBlock>>,
+    /// Which diagnostics referenced each implicated third-party file.
+    pub file_referencers: HashMap<PathBuf, HashSet<DiagnosticOriginInfo>>,
+    /// Planned feature sets that were not checked, with the reason.
+    pub skipped_feature_sets: Vec<SkippedFeatureSet>,
+    /// Feature sets folded into an earlier set that activates exactly the same features.
+    pub equivalent_feature_sets: Vec<EquivalentFeatureSets>,
+    /// Wall-clock time of each feature-set check, in the order they ran.
+    pub feature_set_timings: Vec<FeatureSetTiming>,
+    /// Wall-clock time of the whole analysis, up to report generation.
+    pub total_duration: Duration,
+    /// Report files written for the requested formats.
+    pub written_paths: Vec<PathBuf>,
+    /// Whether `max_total_time` ran out before every planned feature set was checked.
+    pub time_budget_exceeded: bool,
+    /// Whether Ctrl-C stopped the run before every planned feature set was checked.
+    pub interrupted: bool,
+    /// The analyzed crate's declared features (`--include-features-table`), one table per
+    /// workspace member at a virtual workspace root.
+    pub feature_tables: Vec<PackageFeatureTable>,
+    /// Time spent in each phase of the run (`--timings`), up to report generation.
+    pub phase_timings: Vec<PhaseTiming>,
+    /// Implicated registry crates whose sources were not on disk (and were not fetched),
+    /// e.g. `serde-1.0.200`.
+    pub missing_crate_sources: Vec<String>,
+    /// Diagnostics that did not appear in every repeated run of their feature set (`--repeat`).
+    pub flaky_diagnostics: Vec<FlakyDiagnostic>,
+    /// Diagnostics reported across all checked feature sets, before consolidation, and how
+    /// many of them implicate third-party files.
+    pub diagnostic_counts: DiagnosticCounts,
+    /// Consolidated diagnostics left out by ID (`--suppress-ids` and the manifest's
+    /// `[package.metadata.getdoc.suppress]`).
+    pub suppressed_diagnostics: usize,
+    /// Implicated files left out because their path matches an `--exclude-path` glob.
+    pub excluded_files: usize,
+    /// Where cargo's raw output of each run was saved (`--emit-raw-json`).
+    pub raw_json_dir: Option<PathBuf>,
+}
+
+/// A package's `[features]` table as resolved by `cargo metadata`, shown in the report.
+#[derive(Debug, Serialize)]
+pub struct PackageFeatureTable {
+    /// The workspace member, when the analyzed manifest is a virtual workspace.
+    pub package: Option<String>,
+    /// Each feature and the features and dependencies it enables.
+    pub features: FeatureTable,
+}
+
+/// Headline numbers of a run, shown in the report's summary and written by `--summary-json`.
+/// Fields are only ever added (bumping `schema_version`), never renamed or removed.
+#[derive(Debug, Serialize)]
+struct ReportSummary {
+    schema_version: u32,
+    errors: usize,
+    warnings: usize,
+    crates_implicated: usize,
+    files_implicated: usize,
+    feature_sets_checked: usize,
+    feature_sets_skipped: usize,
+    duration_seconds: f64,
+    feature_sets_collapsed: usize,
+    diagnostics_reported: usize,
+    diagnostics_implicating_third_party: usize,
+    diagnostics_suppressed: usize,
+}
+
+/// Version of the `--summary-json` object layout.
+const SUMMARY_SCHEMA_VERSION: u32 = 4;
+
+/// A planned feature set that was not checked (cargo cannot resolve it, or `--fail-fast` stopped early).
+#[derive(Debug, Serialize)]
+pub struct SkippedFeatureSet {
+    /// Description of the feature set, as used elsewhere in the report.
+    pub feature_set_desc: String,
+    /// Why it was not checked.
+    pub reason: String,
+}
+
+/// Feature sets that resolve to the same final feature selection, so only one was checked.
+#[derive(Debug, Serialize)]
+pub struct EquivalentFeatureSets {
+    /// Description of the feature set that was checked.
+    pub checked_as: String,
+    /// Descriptions of the sets that were folded into it.
+    pub aliases: Vec<String>,
+}
+
+/// How long the `cargo check` for one feature set took, and which targets it left unbuilt.
+#[derive(Debug)]
+pub struct FeatureSetTiming {
+    /// Description of the feature set.
+    pub feature_set_desc: String,
+    /// Wall-clock time of its `cargo check`.
+    pub duration: Duration,
+    /// Targets cargo skipped under this set for lack of their `required-features`.
+    pub skipped_targets: Vec<String>,
+    /// When the result was reused from the cache rather than checked now, the local time
+    /// the cached check ran (e.g. `2024-05-01 14:03:12`).
+    pub cached_from: Option<String>,
+    /// Shell command line that reruns this check by hand from the project directory (see
+    /// [`CargoCli::reproduction_command`](crate::CargoCli::reproduction_command)); `None`
+    /// for pre-captured input.
+    pub command: Option<String>,
+}
+
+/// Time spent in one phase of a run, such as `cargo check` or extraction, over all the
+/// times it ran.
+#[derive(Debug, Clone)]
+pub struct PhaseTiming {
+    /// Name of the phase.
+    pub phase: String,
+    /// How many times it ran (e.g. once per feature set or per file).
+    pub count: usize,
+    /// Wall-clock time of all runs together. Runs on parallel workers are added up, so
+    /// this can exceed the time the run took.
+    pub total: Duration,
+    /// Wall-clock time of the longest single run.
+    pub longest: Duration,
+}
+
+fn item_header_name_logic(item: &ExtractedItem) -> String {
+    if item.item_kind.contains("Impl Block") && item.name.starts_with("impl ") {
+        // For impl blocks, the signature_or_definition usually contains the full impl line,
+        // so take up to the first '{' or the whole name if no brace (should not happen for valid impls).
+        item.signature_or_definition
+            .split('{')
+            .next()
+            .unwrap_or(&item.name)
+            .trim()
+            .to_string()
+    } else if item.item_kind == "Module" && item.name.is_empty() {
+        "Unnamed Module".to_string() // Should be rare with syn parsing actual mods
+    } else {
+        item.name.clone()
+    }
+}
+
+/// Formats the heading suffix giving an item's line range, e.g. ` (lines 198–236)`.
+/// Items whose range covers a line implicated by a diagnostic are called out in bold.
+fn item_location_suffix(
+    item: &ExtractedItem,
+    implicated_lines: Option<&BTreeSet<usize>>,
+) -> String {
+    if item.line_start == 0 {
+        return String::new(); // No span information available
+    }
+    let mut suffix = if item.line_start == item.line_end {
+        format!(" (line {})", item.line_start)
+    } else {
+        format!(" (lines {}–{})", item.line_start, item.line_end)
+    };
+    if let Some(lines) = implicated_lines {
+        let covered: Vec<String> = lines
+            .range(item.line_start..=item.line_end)
+            .map(|line| line.to_string())
+            .collect();
+        if !covered.is_empty() {
+            suffix.push_str(&format!(
+                " — **implicated at {} {}**",
+                if covered.len() == 1 { "line" } else { "lines" },
+                covered.join(", ")
+            ));
+        }
+    }
+    suffix
+}
+
+/// The requested formats without repeats, each with the file it is written to (`None` for
+/// `short`, which is printed). With a single file format, `output_path` is used as given;
+/// with several, its extension is replaced by each format's extension.
+pub(crate) fn report_paths(
+    formats: &[OutputFormat],
+    output_path: &Path,
+) -> Vec<(OutputFormat, Option<PathBuf>)> {
+    let mut unique_formats: Vec<OutputFormat> = Vec::new();
+    for format in formats {
+        if !unique_formats.contains(format) {
+            unique_formats.push(*format);
+        }
+    }
+
+    let file_format_count = unique_formats
+        .iter()
+        .filter(|format| format.extension().is_some())
+        .count();
+    unique_formats
+        .into_iter()
+        .map(|format| {
+            let path = format.extension().map(|extension| {
+                if file_format_count == 1 {
+                    output_path.to_path_buf()
+                } else {
+                    output_path.with_extension(extension)
+                }
+            });
+            (format, path)
+        })
+        .collect()
+}
+
+/// Writes the report once per requested format and returns the paths written; see
+/// [`report_paths`]. Existing files are only replaced when `overwrite` is set.
+pub(crate) fn write_reports(
+    report: &Report,
+    formats: &[OutputFormat],
+    output_path: &Path,
+    markdown_options: &MarkdownOptions,
+    minimal: bool,
+    overwrite: bool,
+) -> Result<Vec<PathBuf>, GetdocError> {
+    let mut written_paths = Vec::new();
+    for (format, path) in report_paths(formats, output_path) {
+        let Some(path) = path else {
+            print_short_report(report);
+            continue;
+        };
+        write_file_atomically(&path, overwrite, |mut writer| match format {
+            OutputFormat::Markdown if minimal => {
+                generate_minimal_markdown_report(report, &mut writer, markdown_options)
+            }
+            OutputFormat::Markdown => {
+                generate_markdown_report(report, &mut writer, markdown_options)
+            }
+            OutputFormat::Json => generate_json_report(report, &mut writer),
+            OutputFormat::Short => unreachable!("has no file"),
+        })?;
+        written_paths.push(path);
+    }
+    Ok(written_paths)
+}
+
+/// Writes `path` through `write` without ever leaving a truncated file behind: the content
+/// goes to a temporary file in the same directory, which is renamed over `path` only once
+/// `write` has succeeded. On failure the temporary file is removed and any previous `path`
+/// is left untouched. Unless `overwrite` is set, an existing `path` is an error
+/// ([`GetdocError::ReportExists`]).
+pub fn write_file_atomically(
+    path: &Path,
+    overwrite: bool,
+    write: impl FnOnce(&mut dyn Write) -> std::io::Result<()>,
+) -> Result<(), GetdocError> {
+    if !overwrite && path.exists() {
+        return Err(GetdocError::ReportExists {
+            path: path.to_path_buf(),
+        });
+    }
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path =
+        path.with_file_name(format!(".{}.getdoc-{}.tmp", file_name, std::process::id()));
+    let write_temp = || -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        write(&mut writer)?;
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+        fs::rename(&temp_path, path)
+    };
+    write_temp().map_err(|source| {
+        let _ = fs::remove_file(&temp_path);
+        GetdocError::WriteReport {
+            path: path.to_path_buf(),
+            source,
+        }
+    })
+}
+
+/// The rustdoc book's entry for a rustdoc lint code such as `rustdoc::broken_intra_doc_links`,
+/// or `None` for other codes.
+fn rustdoc_lint_url(code: &str) -> Option<String> {
+    code.strip_prefix("rustdoc::")
+        .map(|lint| format!("https://doc.rust-lang.org/rustdoc/lints.html#{}", lint))
+}
+
+/// Prints `--format short`: one line per consolidated diagnostic, e.g.
+/// `error[E0277] src/lib.rs:42: the trait bound ... is not satisfied (features: default features)`.
+fn print_short_report(report: &Report) {
+    print!("{}", short_report_text(report));
+}
+
+/// The `--format short` report: one line per diagnostic.
+fn short_report_text(report: &Report) -> String {
+    let mut text = String::new();
+    for diagnostic in &report.consolidated_diagnostics {
+        let level = match &diagnostic.code {
+            Some(code) => format!("{}[{}]", diagnostic.level, code),
+            None => diagnostic.level.clone(),
+        };
+        // Older captured output may lack `message`; the first rendered line stands in.
+        let message = if diagnostic.message.is_empty() {
+            diagnostic
+                .rendered_message
+                .lines()
+                .next()
+                .unwrap_or_default()
+        } else {
+            diagnostic.message.lines().next().unwrap_or_default()
+        };
+        let feature_sets: Vec<&str> = diagnostic
+            .feature_set_descriptors
+            .iter()
+            .map(String::as_str)
+            .collect();
+        text.push_str(&format!(
+            "{} {}: {} (features: {})\n",
+            level,
+            display_location(diagnostic.primary_location.as_ref()),
+            message,
+            feature_sets.join(", ")
+        ));
+    }
+    text
+}
+
+/// Computes the headline numbers of a report. Errors and warnings count unique diagnostics,
+/// and a crate is any directory above an implicated file that holds a `Cargo.toml`.
+fn report_summary(report: &Report) -> ReportSummary {
+    let count_level = |level: &str| {
+        report
+            .consolidated_diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.level == level)
+            .count()
+    };
+    let crate_roots: HashSet<&Path> = report
+        .sorted_file_paths
+        .iter()
+        .filter_map(|file_path| {
+            file_path
+                .ancestors()
+                .skip(1)
+                .find(|dir| dir.join("Cargo.toml").is_file())
+        })
+        .collect();
+    ReportSummary {
+        schema_version: SUMMARY_SCHEMA_VERSION,
+        errors: count_level("error"),
+        warnings: count_level("warning"),
+        crates_implicated: crate_roots.len(),
+        files_implicated: report.sorted_file_paths.len(),
+        feature_sets_checked: report.feature_set_timings.len(),
+        feature_sets_skipped: report.skipped_feature_sets.len(),
+        duration_seconds: report.total_duration.as_secs_f64(),
+        feature_sets_collapsed: report
+            .equivalent_feature_sets
+            .iter()
+            .map(|equivalence| equivalence.aliases.len())
+            .sum(),
+        diagnostics_reported: report.diagnostic_counts.reported,
+        diagnostics_implicating_third_party: report.diagnostic_counts.implicating_third_party,
+        diagnostics_suppressed: report.suppressed_diagnostics,
+    }
+}
+
+/// Writes the `--summary-json` file.
+pub(crate) fn write_summary_json(report: &Report, output_path: &Path) -> Result<(), GetdocError> {
+    // A CI artifact regenerated on every run, so it is always replaced.
+    write_file_atomically(output_path, true, |writer| {
+        serde_json::to_writer_pretty(&mut *writer, &report_summary(report))?;
+        writeln!(writer)
+    })?;
+    info!("[getdoc] Summary written to {}", output_path.display());
+    Ok(())
+}
+
+/// Joins report paths for console messages, e.g. `report.md, report.json`.
+pub(crate) fn display_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The time shown in the Markdown report's header: now, in UTC or local time, formatted as
+/// `--date-format` says.
+fn header_timestamp(markdown_options: &MarkdownOptions) -> String {
+    fn format_time<Tz: TimeZone>(time: DateTime<Tz>, date_format: &str) -> String
+    where
+        Tz::Offset: std::fmt::Display,
+    {
+        match date_format {
+            "rfc3339" => time.to_rfc3339_opts(SecondsFormat::Secs, true),
+            "rfc2822" => time.to_rfc2822(),
+            custom => time.format(custom).to_string(),
+        }
+    }
+    if markdown_options.utc {
+        format_time(Utc::now(), &markdown_options.date_format)
+    } else {
+        format_time(Local::now(), &markdown_options.date_format)
+    }
+}
+
+/// Writes the short Markdown report used when nothing relevant was found.
+fn generate_minimal_markdown_report(
+    report: &Report,
+    report_writer: &mut impl Write,
+    markdown_options: &MarkdownOptions,
+) -> std::io::Result<()> {
+    writeln!(
+        report_writer,
+        "# GetDoc Report - {} - {}",
+        report.mode_description,
+        header_timestamp(markdown_options)
+    )?;
+    write_raw_json_note(report_writer, report)?;
+    write_feature_tables_section(report_writer, &report.feature_tables)?;
+    writeln!(
+        report_writer,
+        "\n## Compiler Output (Errors and Warnings)\n"
+    )?;
+    // Say what was covered, so a clean report is distinguishable from an empty run.
+    let checked_sets = &report.feature_set_timings;
+    if checked_sets.is_empty() {
+        writeln!(
+            report_writer,
+            "No feature set was checked; see \"Skipped Feature Sets\" below."
+        )?;
+    } else {
+        writeln!(
+            report_writer,
+            "The compiler reported no errors or warnings under any of the {} checked feature set(s):\n",
+            checked_sets.len()
+        )?;
+        for timing in checked_sets {
+            writeln!(report_writer, "* {}", inline_code(&timing.feature_set_desc))?;
+        }
+    }
+    writeln!(
+        report_writer,
+        "\nNo third-party crates or files were implicated."
+    )?;
+    write_summary_section(report_writer, &report_summary(report))?;
+    write_skipped_feature_sets_section(report_writer, &report.skipped_feature_sets)?;
+    write_equivalent_feature_sets_section(report_writer, &report.equivalent_feature_sets)?;
+    write_timings_section(
+        report_writer,
+        &report.feature_set_timings,
+        report.total_duration,
+    )?;
+    write_phase_timings_section(report_writer, &report.phase_timings)?;
+    Ok(())
+}
+
+/// Says where `--emit-raw-json` saved cargo's raw output, for replaying with `--input`.
+fn write_raw_json_note(writer: &mut impl Write, report: &Report) -> std::io::Result<()> {
+    match &report.raw_json_dir {
+        Some(dir) => writeln!(
+            writer,
+            "\n_Cargo's raw output of each run was saved in {}; pass it to `--input` to regenerate this report offline._",
+            inline_code(&dir.display().to_string())
+        ),
+        None => Ok(()),
+    }
+}
+
+/// Opens the first written report with the system's default application. In CI, or on
+/// Linux without a display, this only prints a warning.
+pub(crate) fn open_report(written_paths: &[PathBuf]) {
+    let Some(path) = written_paths.first() else {
+        return;
+    };
+    let headless = std::env::var_os("CI").is_some()
+        || (cfg!(target_os = "linux")
+            && std::env::var_os("DISPLAY").is_none()
+            && std::env::var_os("WAYLAND_DISPLAY").is_none());
+    if headless {
+        warning!(
+            "[getdoc] Warning: --open ignored; no display is available to open {}.",
+            path.display()
+        );
+        return;
+    }
+    info!("[getdoc] Opening {}...", path.display());
+    if let Err(e) = open::that_detached(path) {
+        warning!("[getdoc] Warning: Could not open {}: {}", path.display(), e);
+    }
+}
+
+/// The thread serving the copied report on Linux, where the clipboard only offers what a
+/// running process serves; see [`hold_copied_report`].
+static CLIPBOARD_HOLDER: Mutex<Option<thread::JoinHandle<()>>> = Mutex::new(None);
+
+/// How long a clipboard manager gets to take the copied report over before getdoc waits.
+const CLIPBOARD_MANAGER_GRACE: Duration = Duration::from_millis(200);
+
+/// Puts the first written report's text on the clipboard (the one-line-per-diagnostic text
+/// when only `--format short` was written), warning when it is longer than
+/// `warn_above_chars`. Without a clipboard (headless systems, CI) this only prints a warning.
+pub(crate) fn copy_report(report: &Report, written_paths: &[PathBuf], warn_above_chars: usize) {
+    let (text, source) = match written_paths.first() {
+        Some(path) => match fs::read_to_string(path) {
+            Ok(text) => (text, path.display().to_string()),
+            Err(e) => {
+                warning!(
+                    "[getdoc] Warning: --copy ignored; could not read {}: {}",
+                    path.display(),
+                    e
+                );
+                return;
+            }
+        },
+        None => (short_report_text(report), "the short report".to_string()),
+    };
+    if let Err(e) = set_clipboard_text(text.clone()) {
+        warning!(
+            "[getdoc] Warning: --copy ignored; no clipboard is available ({}).",
+            e
+        );
+        return;
+    }
+    let chars = text.chars().count();
+    info!(
+        "[getdoc] Copied {} characters of {} to the clipboard.",
+        chars, source
+    );
+    if chars > warn_above_chars {
+        warning!(
+            "[getdoc] Warning: The copied report is {} characters, more than {} (--copy-warn-above); it may be too long to paste into a chat.",
+            chars,
+            warn_above_chars
+        );
+    }
+}
+
+/// Puts `text` on the clipboard. On Linux a thread keeps serving it until another
+/// application takes the clipboard over, as it would be gone once getdoc exits otherwise.
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+))]
+fn set_clipboard_text(text: String) -> Result<(), arboard::Error> {
+    use arboard::SetExtLinux;
+    let mut clipboard = arboard::Clipboard::new()?;
+    let holder = thread::spawn(move || {
+        let _ = clipboard.set().wait().text(text);
+    });
+    *CLIPBOARD_HOLDER.lock().unwrap_or_else(|e| e.into_inner()) = Some(holder);
+    Ok(())
+}
+
+/// Puts `text` on the clipboard, where it stays after getdoc exits.
+#[cfg(not(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+)))]
+fn set_clipboard_text(text: String) -> Result<(), arboard::Error> {
+    arboard::Clipboard::new()?.set_text(text)
+}
+
+/// Waits until the report `--copy` put on the clipboard no longer needs getdoc: on Linux,
+/// until another application has taken the clipboard over (at once when a clipboard manager
+/// runs; otherwise when something else is copied) or Ctrl-C is pressed. Returns at once
+/// elsewhere and without `--copy`.
+pub fn hold_copied_report() {
+    let Some(holder) = CLIPBOARD_HOLDER
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+    else {
+        return;
+    };
+    thread::sleep(CLIPBOARD_MANAGER_GRACE);
+    if holder.is_finished() || interrupted() {
+        return;
+    }
+    begin_holding_clipboard();
+    info!(
+        "[getdoc] Keeping the report on the clipboard until something else is copied (press Ctrl-C to release it)..."
+    );
+    while !holder.is_finished() && !interrupted() {
+        thread::sleep(CLIPBOARD_MANAGER_GRACE);
+    }
+}
+
+/// Formats a duration for humans, e.g. `850ms`, `12.3s`, or `2m 05.1s`.
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let total_secs = duration.as_secs_f64();
+    if total_secs < 1.0 {
+        format!("{}ms", duration.as_millis())
+    } else if total_secs < 60.0 {
+        format!("{:.1}s", total_secs)
+    } else {
+        let minutes = (total_secs / 60.0).floor();
+        format!("{}m {:04.1}s", minutes, total_secs - minutes * 60.0)
+    }
+}
+
+/// Writes the headline numbers of the run as a short list.
+fn write_summary_section(writer: &mut impl Write, summary: &ReportSummary) -> std::io::Result<()> {
+    writeln!(writer, "\n## Summary\n")?;
+    writeln!(
+        writer,
+        "* {} error(s) and {} warning(s) (unique diagnostics)",
+        summary.errors, summary.warnings
+    )?;
+    if summary.diagnostics_reported > 0 {
+        writeln!(
+            writer,
+            "* {} diagnostic(s) reported across the checked feature sets: {} implicating third-party files, {} only in the project's own code",
+            summary.diagnostics_reported,
+            summary.diagnostics_implicating_third_party,
+            summary.diagnostics_reported - summary.diagnostics_implicating_third_party
+        )?;
+    }
+    if summary.diagnostics_suppressed > 0 {
+        writeln!(
+            writer,
+            "* {} diagnostic(s) suppressed by ID and left out of this report",
+            summary.diagnostics_suppressed
+        )?;
+    }
+    writeln!(
+        writer,
+        "* {} third-party file(s) implicated across {} crate(s)",
+        summary.files_implicated, summary.crates_implicated
+    )?;
+    writeln!(
+        writer,
+        "* {} feature set(s) checked, {} skipped, in {}",
+        summary.feature_sets_checked,
+        summary.feature_sets_skipped,
+        format_duration(Duration::from_secs_f64(summary.duration_seconds))
+    )?;
+    if summary.feature_sets_collapsed > 0 {
+        writeln!(
+            writer,
+            "* {} feature set(s) not checked separately because they resolve to the same features as another",
+            summary.feature_sets_collapsed
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes the "Declared Features" section: each feature and what it enables.
+fn write_feature_tables_section(
+    writer: &mut impl Write,
+    feature_tables: &[PackageFeatureTable],
+) -> std::io::Result<()> {
+    if feature_tables.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer, "\n## Declared Features")?;
+    for table in feature_tables {
+        if let Some(package) = &table.package {
+            writeln!(writer, "\n### {}", inline_code(package))?;
+        }
+        if table.features.is_empty() {
+            writeln!(writer, "\nNo features declared.")?;
+            continue;
+        }
+        writeln!(writer, "\n| Feature | Enables |")?;
+        writeln!(writer, "|---|---|")?;
+        for (feature, enables) in &table.features {
+            let enables = if enables.is_empty() {
+                "—".to_string()
+            } else {
+                enables
+                    .iter()
+                    .map(|entry| table_code(entry))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            writeln!(writer, "| {} | {} |", table_code(feature), enables)?;
+        }
+    }
+    Ok(())
+}
+
+/// Lists the diagnostics that did not reproduce in every run of their feature set, under
+/// the diagnostics themselves (which include them).
+fn write_flaky_diagnostics_section(
+    writer: &mut impl Write,
+    flaky_diagnostics: &[FlakyDiagnostic],
+) -> std::io::Result<()> {
+    if flaky_diagnostics.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer, "### Flaky Diagnostics\n")?;
+    writeln!(
+        writer,
+        "These diagnostics did not come out in every run of their feature set (`--repeat`), so they may be nondeterministic:\n"
+    )?;
+    for flaky in flaky_diagnostics {
+        writeln!(
+            writer,
+            "* {}{} at {} under {}: {} — in {} of {} runs",
+            flaky.level.to_uppercase(),
+            flaky
+                .code
+                .as_ref()
+                .map_or_else(String::new, |code| format!(" {}", code)),
+            inline_code(&display_location(flaky.primary_location.as_ref())),
+            inline_code(&flaky.feature_set_desc),
+            flaky.message,
+            flaky.runs_seen,
+            flaky.runs
+        )?;
+    }
+    writeln!(writer)
+}
+
+/// Lists the implicated registry crates whose sources are not on disk, so the report has
+/// nothing extracted from them.
+fn write_missing_sources_section(
+    writer: &mut impl Write,
+    crate_names: &[String],
+) -> std::io::Result<()> {
+    writeln!(writer, "\n## Missing Third-Party Sources\n")?;
+    writeln!(
+        writer,
+        "Diagnostics implicate these crates, but their sources are not under `~/.cargo/registry/src`, so nothing could be extracted from them. Rerun with `--fetch` (or run `cargo fetch`) to download them:\n"
+    )?;
+    for crate_name in crate_names {
+        writeln!(writer, "* {}", inline_code(crate_name))?;
+    }
+    Ok(())
+}
+
+/// Lists the feature sets that were not checked, with the reason for each.
+fn write_skipped_feature_sets_section(
+    writer: &mut impl Write,
+    skipped_feature_sets: &[SkippedFeatureSet],
+) -> std::io::Result<()> {
+    if skipped_feature_sets.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer, "\n## Skipped Feature Sets\n")?;
+    for skipped in skipped_feature_sets {
+        writeln!(
+            writer,
+            "* {} — skipped: {}",
+            inline_code(&skipped.feature_set_desc),
+            skipped.reason
+        )?;
+    }
+    Ok(())
+}
+
+/// Notes which feature sets were checked only once because they activate the same features.
+fn write_equivalent_feature_sets_section(
+    writer: &mut impl Write,
+    equivalent_feature_sets: &[EquivalentFeatureSets],
+) -> std::io::Result<()> {
+    if equivalent_feature_sets.is_empty() {
+        return Ok(());
+    }
+    let collapsed_count: usize = equivalent_feature_sets
+        .iter()
+        .map(|equivalence| equivalence.aliases.len())
+        .sum();
+    writeln!(writer, "\n## Equivalent Feature Sets\n")?;
+    writeln!(
+        writer,
+        "These feature sets activate exactly the same features once implications are followed, so each group was checked once ({} redundant check(s) saved):\n",
+        collapsed_count
+    )?;
+    for equivalence in equivalent_feature_sets {
+        let aliases: Vec<String> = equivalence
+            .aliases
+            .iter()
+            .map(|alias| inline_code(alias))
+            .collect();
+        writeln!(
+            writer,
+            "* {} ≡ {}",
+            inline_code(&equivalence.checked_as),
+            aliases.join(" ≡ ")
+        )?;
+    }
+    Ok(())
+}
+
+/// The command reproducing a diagnostic seen under `feature_sets` with the least setup:
+/// default features when they are among them, otherwise the shortest command line.
+fn simplest_reproduction_command<'a>(
+    feature_sets: &BTreeSet<String>,
+    feature_set_timings: &'a [FeatureSetTiming],
+) -> Option<&'a str> {
+    feature_set_timings
+        .iter()
+        .filter(|timing| feature_sets.contains(&timing.feature_set_desc))
+        .filter_map(|timing| Some((timing.feature_set_desc.as_str(), timing.command.as_deref()?)))
+        .min_by_key(|(feature_set, command)| {
+            (
+                !feature_set.contains("default features"),
+                command.split_whitespace().count(),
+                command.len(),
+            )
+        })
+        .map(|(_, command)| command)
+}
+
+/// Crates that a diagnostic points into at more than one version, with those versions in
+/// ascending order: the usual cause of "expected `Foo`, found `Foo`". Besides its implicated
+/// files, the registry paths in its rendered text count, as notes such as "struct defined
+/// here" point at the other version.
+pub fn duplicate_crate_versions(
+    diagnostic: &AggregatedDiagnosticInstance,
+) -> Vec<(String, Vec<String>)> {
+    let rendered_paths = diagnostic
+        .rendered_message
+        .split_whitespace()
+        .filter(|word| word.contains("registry"))
+        .map(Path::new);
+    let mut versions_by_crate: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for path in diagnostic
+        .implicated_third_party_files_details
+        .iter()
+        .map(|(path, _)| path.as_path())
+        .chain(rendered_paths)
+    {
+        if let Some((crate_name, version)) = registry_crate_version(path) {
+            versions_by_crate
+                .entry(crate_name)
+                .or_default()
+                .insert(version);
+        }
+    }
+    versions_by_crate
+        .into_iter()
+        .filter(|(_, versions)| versions.len() > 1)
+        .map(|(crate_name, versions)| {
+            let mut versions: Vec<String> = versions.into_iter().collect();
+            versions.sort_by_key(|version| {
+                version
+                    .split(['.', '-', '+'])
+                    .map(|part| part.parse::<u64>().unwrap_or(0))
+                    .collect::<Vec<_>>()
+            });
+            (crate_name, versions)
+        })
+        .collect()
+}
+
+/// Splits a line of a rendered diagnostic into lines of at most `width` characters (0 = no
+/// wrapping) at spaces, continuing under the text after a leading `error[E0308]: ` or
+/// `= note: ` label. Lines of rustc's source snippet (`12 |     code`, the `|` lines with
+/// `^^^` markers, `--> file:line`) are never wrapped, as their columns line up.
+pub fn wrap_rendered_line(line: &str, width: usize) -> Vec<String> {
+    let trimmed = line.trim_start();
+    let is_snippet_line = trimmed.starts_with("-->")
+        || trimmed.starts_with(":::")
+        || trimmed.split_once('|').is_some_and(|(gutter, _)| {
+            gutter
+                .trim_end()
+                .chars()
+                .all(|c| c.is_ascii_digit() || c == '.')
+        });
+    if width == 0 || line.chars().count() <= width || is_snippet_line {
+        return vec![line.to_string()];
+    }
+    let indent = line.len() - trimmed.len();
+    // Labels are short; a `: ` further in is part of the message.
+    let hang = indent
+        + trimmed
+            .char_indices()
+            .take(32)
+            .find(|&(at, _)| trimmed[at..].starts_with(": "))
+            .map_or(0, |(at, _)| at + 2);
+    let continuation = " ".repeat(hang.min(width / 2));
+
+    let mut wrapped: Vec<String> = Vec::new();
+    let mut current = line[..indent].to_string();
+    let mut current_width = indent;
+    let mut has_words = false;
+    for word in trimmed.split(' ') {
+        let word_width = word.chars().count();
+        if has_words && current_width + 1 + word_width > width {
+            wrapped.push(std::mem::replace(&mut current, continuation.clone()));
+            current_width = continuation.len();
+            has_words = false;
+        }
+        if has_words {
+            current.push(' ');
+            current_width += 1;
+        }
+        current.push_str(word);
+        current_width += word_width;
+        has_words = true;
+    }
+    wrapped.push(current);
+    wrapped
+}
+
+/// `a`, `a and b`, or `a, b and c`.
+fn and_list(items: &[String]) -> String {
+    match items {
+        [] => String::new(),
+        [item] => item.clone(),
+        [init @ .., last] => format!("{} and {}", init.join(", "), last),
+    }
+}
+
+/// The ID of the consolidated diagnostic `origin` stands for: same level, code, and
+/// location, under `origin`'s feature set.
+fn referencing_diagnostic_id<'a>(
+    origin: &DiagnosticOriginInfo,
+    consolidated_diagnostics: &'a [AggregatedDiagnosticInstance],
+) -> Option<&'a str> {
+    consolidated_diagnostics
+        .iter()
+        .find(|diagnostic| {
+            diagnostic.level == origin.level
+                && diagnostic.code == origin.code
+                && diagnostic.primary_location == origin.originating_diagnostic_span_location
+                && diagnostic
+                    .feature_set_descriptors
+                    .contains(&origin.feature_set_desc)
+        })
+        .map(|diagnostic| diagnostic.id.as_str())
+}
+
+/// Most feature sets named in an "Absent under" line; longer lists are only counted.
+const MAX_ABSENT_FEATURE_SETS_SHOWN: usize = 3;
+
+/// What a diagnostic's feature sets leave out, e.g. `Absent under: default features,
+/// --all-features`, or that it is feature-independent. `None` when there is nothing to
+/// contrast: a single checked set, or a check that failed to run.
+fn feature_set_contrast(diagnostic: &AggregatedDiagnosticInstance) -> Option<String> {
+    let absent = &diagnostic.absent_feature_set_descriptors;
+    if diagnostic.level == "TOOL_ERROR" {
+        None
+    } else if diagnostic.is_feature_independent() {
+        Some(format!(
+            "Feature-independent: occurred under all {} checked feature sets",
+            diagnostic.feature_set_descriptors.len()
+        ))
+    } else if absent.is_empty() {
+        None
+    } else if absent.len() > MAX_ABSENT_FEATURE_SETS_SHOWN {
+        Some(format!("Absent under: all other {} sets", absent.len()))
+    } else {
+        let absent: Vec<&str> = absent.iter().map(String::as_str).collect();
+        Some(format!("Absent under: {}", absent.join(", ")))
+    }
+}
+
+/// Most feature sets named in one "Referenced by" bullet; the count covers the rest.
+const MAX_REFERENCING_FEATURE_SETS_SHOWN: usize = 3;
+
+/// The feature sets a "Referenced by" bullet occurred under, e.g. ``from configuration:
+/// `default features` `` or ``under 6 feature sets: `--all-features`, `default features`, …``.
+fn referencing_feature_sets(feature_sets: &[&str]) -> String {
+    match feature_sets {
+        [feature_set] => format!("from configuration: {}", inline_code(feature_set)),
+        _ => {
+            let mut shown: Vec<String> = feature_sets
+                .iter()
+                .take(MAX_REFERENCING_FEATURE_SETS_SHOWN)
+                .map(|feature_set| inline_code(feature_set))
+                .collect();
+            if feature_sets.len() > MAX_REFERENCING_FEATURE_SETS_SHOWN {
+                shown.push("…".to_string());
+            }
+            format!(
+                "under {} feature sets: {}",
+                feature_sets.len(),
+                shown.join(", ")
+            )
+        }
+    }
+}
+
+/// Writes the per-feature-set timing table and the total analysis time.
+fn write_timings_section(
+    writer: &mut impl Write,
+    feature_set_timings: &[FeatureSetTiming],
+    total_duration: Duration,
+) -> std::io::Result<()> {
+    writeln!(writer, "\n## Feature-Set Timings\n")?;
+    // The targets column only appears when some set left a `required-features` target
+    // unbuilt; the command column whenever cargo was run (not for `--input`).
+    let any_skipped_targets = feature_set_timings
+        .iter()
+        .any(|timing| !timing.skipped_targets.is_empty());
+    let any_commands = feature_set_timings
+        .iter()
+        .any(|timing| timing.command.is_some());
+    if !feature_set_timings.is_empty() {
+        write!(writer, "| Feature set | Check time |")?;
+        if any_skipped_targets {
+            write!(writer, " Targets not built |")?;
+        }
+        if any_commands {
+            write!(writer, " Reproduce with |")?;
+        }
+        writeln!(
+            writer,
+            "\n|---|---|{}{}",
+            if any_skipped_targets { "---|" } else { "" },
+            if any_commands { "---|" } else { "" }
+        )?;
+        for timing in feature_set_timings {
+            let check_time = match &timing.cached_from {
+                Some(checked_at) => format!("cached from {}", checked_at),
+                None => format_duration(timing.duration),
+            };
+            write!(
+                writer,
+                "| {} | {} |",
+                table_code(&timing.feature_set_desc),
+                check_time
+            )?;
+            if any_skipped_targets {
+                let skipped = if timing.skipped_targets.is_empty() {
+                    "—".to_string()
+                } else {
+                    format!("⚠ {}", timing.skipped_targets.join("; "))
+                };
+                write!(writer, " {} |", skipped)?;
+            }
+            if any_commands {
+                match &timing.command {
+                    Some(command) => write!(writer, " {} |", table_code(command))?,
+                    None => write!(writer, " — |")?,
+                }
+            }
+            writeln!(writer)?;
+        }
+        writeln!(writer)?;
+        if any_skipped_targets {
+            writeln!(
+                writer,
+                "Sets marked ⚠ did not compile every target, so a clean result there says nothing about the targets listed. Rerun with `--ensure-targets` to include them.\n"
+            )?;
+        }
+    }
+    writeln!(
+        writer,
+        "Total analysis time: {}",
+        format_duration(total_duration)
+    )?;
+    Ok(())
+}
+
+/// Writes the `--timings` breakdown of the run by phase; nothing when no phases were timed.
+fn write_phase_timings_section(
+    writer: &mut impl Write,
+    phase_timings: &[PhaseTiming],
+) -> std::io::Result<()> {
+    if phase_timings.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer, "\n## Phase Timings\n")?;
+    writeln!(writer, "| Phase | Runs | Total | Longest run |")?;
+    writeln!(writer, "|---|---|---|---|")?;
+    for timing in phase_timings {
+        writeln!(
+            writer,
+            "| {} | {} | {} | {} |",
+            timing.phase,
+            timing.count,
+            format_duration(timing.total),
+            format_duration(timing.longest)
+        )?;
+    }
+    writeln!(
+        writer,
+        "\nExtraction runs once per file on parallel workers, so its total can exceed the time it took. Report writing is not included."
+    )?;
+    Ok(())
+}
+
+/// An extracted item in the JSON report, with its sub-items (impl members, enum variants)
+/// nested under it instead of following it in a flat list.
+#[derive(Serialize)]
+struct JsonItem<'a> {
+    #[serde(flatten)]
+    item: &'a ExtractedItem,
+    children: Vec<JsonItem<'a>>,
+}
+
+/// Related impl blocks from one other file, with their items nested like [`JsonItem`].
+#[derive(Serialize)]
+struct JsonRelatedImplBlock<'a> {
+    source_file: &'a Path,
+    items: Vec<JsonItem<'a>>,
+}
+
+/// Builds the item tree for the JSON report. Items whose parent is not in `items` (e.g. it
+/// was collapsed as a duplicate) become roots, as in the Markdown report.
+fn json_item_tree(items: &[ExtractedItem]) -> Vec<JsonItem<'_>> {
+    let ids: HashSet<usize> = items.iter().map(|item| item.id).collect();
+    let mut children: HashMap<usize, Vec<&ExtractedItem>> = HashMap::new();
+    let mut roots = Vec::new();
+    for item in items {
+        match item.parent {
+            Some(parent_id) if ids.contains(&parent_id) => {
+                children.entry(parent_id).or_default().push(item)
+            }
+            _ => roots.push(item),
+        }
+    }
+    fn build<'a>(
+        item: &'a ExtractedItem,
+        children: &HashMap<usize, Vec<&'a ExtractedItem>>,
+    ) -> JsonItem<'a> {
+        JsonItem {
+            item,
+            children: children
+                .get(&item.id)
+                .map(|kids| kids.iter().map(|kid| build(kid, children)).collect())
+                .unwrap_or_default(),
+        }
+    }
+    roots
+        .into_iter()
+        .map(|item| build(item, &children))
+        .collect()
+}
+
+/// One implicated file in the JSON report, gathering everything known about it.
+#[derive(Serialize)]
+struct JsonFileSection<'a> {
+    path: &'a Path,
+    // Crate whose build script generated this file, for files under `build/*/out`.
+    build_script_output_of: Option<String>,
+    implicated_lines: Option<&'a BTreeSet<usize>>,
+    referenced_by: Vec<&'a DiagnosticOriginInfo>,
+    hidden_items_skipped: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    first_invalid_utf8_line: Option<usize>,
+    items: Vec<JsonItem<'a>>,
+    raw_fallback: Option<&'a RawContextFallback>,
+    related_impls: Vec<JsonRelatedImplBlock<'a>>,
+}
+
+/// Top-level shape of the JSON report. Collections are sorted so output is stable.
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    generated_at: String,
+    mode_description: &'a str,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    feature_tables: &'a [PackageFeatureTable],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raw_json_dir: Option<&'a Path>,
+    diagnostics: &'a [AggregatedDiagnosticInstance],
+    explanations: BTreeMap<&'a String, &'a String>,
+    files: Vec<JsonFileSection<'a>>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    flaky_diagnostics: &'a [FlakyDiagnostic],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    missing_crate_sources: &'a [String],
+    summary: ReportSummary,
+    skipped_feature_sets: &'a [SkippedFeatureSet],
+    equivalent_feature_sets: &'a [EquivalentFeatureSets],
+    timings: Vec<JsonTiming<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    phase_timings: Vec<JsonPhaseTiming<'a>>,
+    total_seconds: f64,
+}
+
+#[derive(Serialize)]
+struct JsonTiming<'a> {
+    feature_set: &'a str,
+    seconds: f64,
+    skipped_targets: &'a [String],
+    cached_from: Option<&'a str>,
+    command: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct JsonPhaseTiming<'a> {
+    phase: &'a str,
+    count: usize,
+    seconds: f64,
+    longest_seconds: f64,
+}
+
+/// Writes one error-code explanation as a heading and a blockquote, as in the report's appendix.
+fn write_explanation(
+    writer: &mut impl Write,
+    code: &str,
+    explanation: &str,
+) -> std::io::Result<()> {
+    writeln!(writer, "### Explanation for {}\n", code)?;
+    // Properly format multi-line explanations as blockquotes
+    for line in explanation.trim().lines() {
+        writeln!(writer, "> {}", line)?;
+    }
+    writeln!(writer) // Add a blank line after each explanation block
+}
+
+/// The first paragraph of an explanation, e.g. "A trait bound was not satisfied.", followed
+/// by a link to the full text in the Rust error codes index.
+fn short_explanation(code: &str, explanation: &str) -> String {
+    let first_paragraph: Vec<&str> = explanation
+        .trim()
+        .lines()
+        .take_while(|line| !line.trim().is_empty())
+        .collect();
+    format!(
+        "{}\n\n[Full explanation of {}](https://doc.rust-lang.org/error_codes/{}.html)",
+        first_paragraph.join("\n"),
+        code,
+        code
+    )
+}
+
+/// Writes Appendix A: each explanation once, in code order. A code whose explanation is word
+/// for word that of an earlier one refers to it instead of repeating it.
+fn write_explanations_appendix(
+    writer: &mut impl Write,
+    explanations: &HashMap<String, String>,
+    style: ExplanationStyle,
+) -> std::io::Result<()> {
+    writeln!(writer, "\n## Appendix A: Error Code Explanations\n")?;
+    let mut sorted_explanations: Vec<(&String, &String)> = explanations.iter().collect();
+    sorted_explanations.sort_by_key(|(code, _)| *code);
+
+    let mut first_code_by_text: HashMap<&str, &str> = HashMap::new();
+    for (code, explanation_text) in sorted_explanations {
+        let text = explanation_text.trim();
+        if let Some(first_code) = first_code_by_text.get(text) {
+            writeln!(
+                writer,
+                "### Explanation for {}\n\n> Same as the explanation for {} above.\n",
+                code, first_code
+            )?;
+            continue;
+        }
+        first_code_by_text.insert(text, code);
+        match style {
+            ExplanationStyle::Short => {
+                write_explanation(writer, code, &short_explanation(code, text))?
+            }
+            _ => write_explanation(writer, code, text)?,
+        }
+    }
+    Ok(())
+}
+
+/// Prints `--explain-only`: a single explanation, formatted as in the report's appendix.
+pub(crate) fn print_explanation(code: &str, explanation: &str) {
+    let mut stdout = std::io::stdout().lock();
+    let _ = write_explanation(&mut stdout, code, explanation);
+}
+
+/// Generates a JSON report with the same content as the Markdown report.
+pub(crate) fn generate_json_report(
+    report: &Report,
+    writer: &mut impl Write,
+) -> std::io::Result<()> {
+    let files = report
+        .sorted_file_paths
+        .iter()
+        .map(|path| {
+            let mut referenced_by: Vec<&DiagnosticOriginInfo> = report
+                .file_referencers
+                .get(path)
+                .map(|origins| origins.iter().collect())
+                .unwrap_or_default();
+            referenced_by.sort();
+            JsonFileSection {
+                path,
+                build_script_output_of: build_script_output_crate(path),
+                implicated_lines: report.implicated_lines_by_file.get(path),
+                referenced_by,
+                hidden_items_skipped: report.hidden_items_skipped.get(path).copied().unwrap_or(0),
+                first_invalid_utf8_line: report.lossily_read_files.get(path).copied(),
+                items: report
+                    .extracted_data
+                    .get(path)
+                    .map(|items| json_item_tree(items))
+                    .unwrap_or_default(),
+                raw_fallback: report.raw_fallbacks.get(path),
+                related_impls: report
+                    .related_impls
+                    .get(path)
+                    .into_iter()
+                    .flatten()
+                    .map(|block| JsonRelatedImplBlock {
+                        source_file: &block.source_file,
+                        items: json_item_tree(&block.items),
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+    let json_report = JsonReport {
+        generated_at: Local::now().to_rfc3339(),
+        mode_description: &report.mode_description,
+        raw_json_dir: report.raw_json_dir.as_deref(),
+        feature_tables: &report.feature_tables,
+        diagnostics: &report.consolidated_diagnostics,
+        explanations: report.unique_explanations.iter().collect(),
+        files,
+        flaky_diagnostics: &report.flaky_diagnostics,
+        missing_crate_sources: &report.missing_crate_sources,
+        summary: report_summary(report),
+        skipped_feature_sets: &report.skipped_feature_sets,
+        equivalent_feature_sets: &report.equivalent_feature_sets,
+        timings: report
+            .feature_set_timings
+            .iter()
+            .map(|timing| JsonTiming {
+                feature_set: &timing.feature_set_desc,
+                seconds: timing.duration.as_secs_f64(),
+                skipped_targets: &timing.skipped_targets,
+                cached_from: timing.cached_from.as_deref(),
+                command: timing.command.as_deref(),
+            })
+            .collect(),
+        phase_timings: report
+            .phase_timings
+            .iter()
+            .map(|timing| JsonPhaseTiming {
+                phase: &timing.phase,
+                count: timing.count,
+                seconds: timing.total.as_secs_f64(),
+                longest_seconds: timing.longest.as_secs_f64(),
+            })
+            .collect(),
+        total_seconds: report.total_duration.as_secs_f64(),
+    };
+    serde_json::to_writer_pretty(&mut *writer, &json_report)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// Writes the headings, doc comments, and definitions for a file's extracted items.
+/// Items are nested under their `parent`, so an impl block's methods follow it one heading
+/// level deeper. `source_label`, when given, is appended to top-level headings to say which
+/// file the items came from.
+fn write_extracted_items(
+    writer: &mut impl Write,
+    items: &[ExtractedItem],
+    implicated_lines: Option<&BTreeSet<usize>>,
+    source_label: Option<&str>,
+    markdown_options: &MarkdownOptions,
+    anchors: &mut HeadingAnchors,
+) -> std::io::Result<()> {
+    // Items whose parent is not in this slice (e.g. filtered out) are shown at the top level.
+    let ids: HashSet<usize> = items.iter().map(|item| item.id).collect();
+    let mut children: HashMap<usize, Vec<&ExtractedItem>> = HashMap::new();
+    let mut roots = Vec::new();
+    for item in items {
+        match item.parent {
+            Some(parent_id) if ids.contains(&parent_id) => {
+                children.entry(parent_id).or_default().push(item)
+            }
+            _ => roots.push(item),
+        }
+    }
+    for item in roots {
+        write_extracted_item_tree(
+            writer,
+            item,
+            0,
+            &children,
+            implicated_lines,
+            source_label,
+            markdown_options,
+            anchors,
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes one item and, recursively, its children. Top-level items (`depth` 0) are H4 since
+/// H3 is "From File: ..."; each nesting level goes one heading deeper, down to H6.
+#[allow(clippy::too_many_arguments)]
+fn write_extracted_item_tree(
+    writer: &mut impl Write,
+    item: &ExtractedItem,
+    depth: usize,
+    children: &HashMap<usize, Vec<&ExtractedItem>>,
+    implicated_lines: Option<&BTreeSet<usize>>,
+    source_label: Option<&str>,
+    markdown_options: &MarkdownOptions,
+    anchors: &mut HeadingAnchors,
+) -> std::io::Result<()> {
+    let location_suffix = item_location_suffix(item, implicated_lines);
+    let heading_level = "#".repeat((4 + depth).min(6));
+    let heading = if depth == 0 {
+        let source_suffix = source_label.map_or_else(String::new, |label| {
+            format!(" — from {}", inline_code(label))
+        });
+        format!(
+            "{} {}{}{}",
+            item.item_kind,
+            inline_code(&item_header_name_logic(item)),
+            location_suffix,
+            source_suffix
+        )
+    } else {
+        format!(
+            "{} {}{}",
+            item.item_kind,
+            inline_code(&item.name),
+            location_suffix
+        )
+    };
+    writeln!(writer, "{} {}\n", heading_level, anchors.unique(heading))?;
+    // A sub-item shown without its impl block (e.g. the block was filtered out) still says where it lives.
+    if depth == 0
+        && let Some(parent_impl) = &item.parent_impl
+    {
+        writeln!(writer, "_Defined in {}._\n", inline_code(parent_impl))?;
+    }
+    if let Some(item_path) = &item.item_path {
+        writeln!(writer, "_Path: {}._\n", inline_code(item_path))?;
+    }
+    if !item.also_found_in.is_empty() {
+        let locations: Vec<String> = item
+            .also_found_in
+            .iter()
+            .map(|location| inline_code(location))
+            .collect();
+        writeln!(
+            writer,
+            "_Identical item (re-exported) also found at: {}._\n",
+            locations.join(", ")
+        )?;
+    }
+
+    if !item.doc_comments.is_empty() {
+        let shown_line_count =
+            truncated_doc_line_count(&item.doc_comments, markdown_options.max_doc_lines);
+        let shown_lines = &item.doc_comments[..shown_line_count];
+        let omitted_line_count = item.doc_comments.len() - shown_line_count;
+        if markdown_options.raw_docs {
+            write_raw_doc_lines(writer, shown_lines)?;
+            if omitted_line_count > 0 {
+                writeln!(writer, "\n_… ({} more lines)_", omitted_line_count)?;
+            }
+        } else {
+            for doc_line in shown_lines {
+                // So empty doc lines are still quoted to maintain blockquote continuity
+                writeln!(
+                    writer,
+                    "> {}",
+                    if doc_line.is_empty() { "" } else { doc_line }
+                )?;
+            }
+            if omitted_line_count > 0 {
+                writeln!(writer, ">\n> _… ({} more lines)_", omitted_line_count)?;
+            }
+        }
+        writeln!(writer)?;
+    }
+    writeln!(writer, "```rust\n{}\n```\n", item.signature_or_definition)?;
+    if !item.trait_bounds.is_empty() {
+        // Listed separately so a failing bound is easy to spot next to an E0277.
+        writeln!(writer, "Trait bounds:")?;
+        for bound in &item.trait_bounds {
+            writeln!(writer, "* {}", inline_code(bound))?;
+        }
+        writeln!(writer)?;
+    }
+
+    for child in children.get(&item.id).into_iter().flatten() {
+        write_extracted_item_tree(
+            writer,
+            child,
+            depth + 1,
+            children,
+            implicated_lines,
+            source_label,
+            markdown_options,
+            anchors,
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes doc-comment lines as plain Markdown for `--raw-docs`. Headings outside code fences
+/// become bold lines so they can't outrank the report's item headings, and bare code fences
+/// are tagged `rust`, matching how rustdoc treats them.
+fn write_raw_doc_lines(writer: &mut impl Write, doc_lines: &[String]) -> std::io::Result<()> {
+    let mut in_code_fence = false;
+    for line in doc_lines {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            let opens_bare_fence = !in_code_fence && (trimmed == "```" || trimmed == "~~~");
+            in_code_fence = !in_code_fence;
+            if opens_bare_fence {
+                writeln!(writer, "{}rust", line)?;
+                continue;
+            }
+        } else if !in_code_fence && trimmed.starts_with('#') {
+            let heading_text = trimmed.trim_start_matches('#');
+            if heading_text.is_empty() || heading_text.starts_with(' ') {
+                writeln!(writer, "**{}**", heading_text.trim())?;
+                continue;
+            }
+        }
+        writeln!(writer, "{}", line)?;
+    }
+    Ok(())
+}
+
+/// Decides how many leading doc-comment lines to show under a `max_lines` limit (0 = no limit).
+/// Cuts happen only at blank lines between paragraphs, never inside a fenced or indented code
+/// block, and never before the end of the first paragraph, so the result may exceed
+/// `max_lines` when the first paragraph is itself long.
+fn truncated_doc_line_count(doc_lines: &[String], max_lines: usize) -> usize {
+    if max_lines == 0 || doc_lines.len() <= max_lines {
+        return doc_lines.len();
+    }
+
+    // Indices of blank lines outside code blocks; cutting there drops that blank line and all after it.
+    let is_indented = |line: &str| line.starts_with("    ") || line.starts_with('\t');
+    let mut paragraph_boundaries = Vec::new();
+    let mut in_code_fence = false;
+    // An indented code block starts after a blank line and runs on across blank lines for as
+    // long as indented lines follow them.
+    let mut in_indented_block = false;
+    let mut previous_blank = true;
+    for (index, line) in doc_lines.iter().enumerate() {
+        let trimmed = line.trim_start();
+        if in_code_fence {
+            in_code_fence = !(trimmed.starts_with("```") || trimmed.starts_with("~~~"));
+        } else if trimmed.is_empty() {
+            let block_continues = in_indented_block
+                && doc_lines[index + 1..]
+                    .iter()
+                    .find(|later| !later.trim().is_empty())
+                    .is_some_and(|later| is_indented(later));
+            if !block_continues {
+                in_indented_block = false;
+                paragraph_boundaries.push(index);
+            }
+        } else if is_indented(line) && (previous_blank || in_indented_block) {
+            in_indented_block = true;
+        } else {
+            in_indented_block = false;
+            in_code_fence = trimmed.starts_with("```") || trimmed.starts_with("~~~");
+        }
+        previous_blank = trimmed.is_empty();
+    }
+
+    let Some(&first_paragraph_end) = paragraph_boundaries.first() else {
+        return doc_lines.len(); // A single paragraph is always kept intact
+    };
+    paragraph_boundaries
+        .iter()
+        .copied()
+        .filter(|&boundary| boundary <= max_lines)
+        .max()
+        .unwrap_or(first_paragraph_end)
+        .max(first_paragraph_end)
+}
+
+/// Writes the raw source excerpts shown for a file whose items could not be extracted.
+/// Implicated lines are marked with `>` in the gutter.
+fn write_raw_fallback(
+    writer: &mut impl Write,
+    fallback: &RawContextFallback,
+    implicated_lines: Option<&BTreeSet<usize>>,
+    anchors: &mut HeadingAnchors,
+) -> std::io::Result<()> {
+    writeln!(
+        writer,
+        "_Items could not be extracted: {}. Showing raw source around the implicated lines instead._\n",
+        fallback.reason
+    )?;
+    if let Some(size) = fallback.file_size_bytes {
+        writeln!(writer, "**File size:** {} bytes\n", size)?;
+    }
+    if !fallback.leading_doc_comment.is_empty() {
+        writeln!(writer, "**Leading doc comment:**\n")?;
+        for line in &fallback.leading_doc_comment {
+            writeln!(writer, "> {}", line)?;
+        }
+        writeln!(writer)?;
+    }
+    if fallback.snippets.is_empty() {
+        writeln!(
+            writer,
+            "_No implicated line numbers were available for this file._\n"
+        )?;
+    }
+    let line_number_width = fallback
+        .snippets
+        .last()
+        .map_or(1, |snippet| snippet.line_end.to_string().len());
+    for snippet in &fallback.snippets {
+        let heading = format!(
+            "Raw source (lines {}–{})",
+            snippet.line_start, snippet.line_end
+        );
+        writeln!(writer, "#### {}\n\n```text", anchors.unique(heading))?;
+        for (offset, line) in snippet.lines.iter().enumerate() {
+            let line_number = snippet.line_start + offset;
+            let marker = if implicated_lines.is_some_and(|lines| lines.contains(&line_number)) {
+                '>'
+            } else {
+                ' '
+            };
+            writeln!(
+                writer,
+                "{} {:>width$} | {}",
+                marker,
+                line_number,
+                line,
+                width = line_number_width
+            )?;
+        }
+        writeln!(writer, "```\n")?;
+    }
+    Ok(())
+}
+
+/// One line describing a suggested fix, labeled by whether it is safe to apply blindly.
+fn suggestion_line(suggestion: &Suggestion) -> String {
+    let label = match suggestion.applicability {
+        Applicability::MachineApplicable => "auto-applicable".to_string(),
+        other => format!("needs review: {}", other.as_str()),
+    };
+    let replacement = suggestion.replacement.replace('\n', "\\n");
+    let change = if suggestion.replacement.is_empty() {
+        format!("remove the code up to {}", suggestion.end_location)
+    } else if suggestion.location == suggestion.end_location {
+        format!("insert {}", inline_code(&replacement))
+    } else {
+        format!(
+            "replace up to {} with {}",
+            suggestion.end_location,
+            inline_code(&replacement)
+        )
+    };
+    format!(
+        "Suggested fix [{}]: {}: {} at {}",
+        label, suggestion.message, change, suggestion.location
+    )
+}
+
+/// Writes the "Suggested Fixes" section: every machine-applicable suggestion across all
+/// diagnostics, grouped by file and ordered by position, each shown as a before/after diff
+/// of the lines it touches. Returns the number of fixes listed.
+fn write_fix_preview<W: Write>(
+    writer: &mut W,
+    diagnostics: &[AggregatedDiagnosticInstance],
+    anchors: &mut HeadingAnchors,
+) -> std::io::Result<usize> {
+    // The location is "file:line:column"; the same fix can be attached to several diagnostics.
+    let mut fixes_by_file: BTreeMap<&str, BTreeMap<(usize, usize), Vec<&Suggestion>>> =
+        BTreeMap::new();
+    for suggestion in diagnostics.iter().flat_map(|d| &d.suggestions) {
+        if suggestion.applicability != Applicability::MachineApplicable {
+            continue;
+        }
+        let mut parts = suggestion.location.rsplitn(3, ':');
+        let column = parts
+            .next()
+            .and_then(|c| c.parse().ok())
+            .unwrap_or_default();
+        let line = parts
+            .next()
+            .and_then(|l| l.parse().ok())
+            .unwrap_or_default();
+        let file = parts.next().unwrap_or(&suggestion.location);
+        let at_position = fixes_by_file
+            .entry(file)
+            .or_default()
+            .entry((line, column))
+            .or_default();
+        if !at_position.iter().any(|existing| {
+            existing.end_location == suggestion.end_location
+                && existing.replacement == suggestion.replacement
+        }) {
+            at_position.push(suggestion);
+        }
+    }
+
+    writeln!(writer, "\n## Suggested Fixes (Preview)\n")?;
+    if fixes_by_file.is_empty() {
+        writeln!(
+            writer,
+            "rustc did not suggest any machine-applicable fixes for the checked feature sets."
+        )?;
+        return Ok(0);
+    }
+    writeln!(
+        writer,
+        "Machine-applicable fixes suggested by rustc, grouped by file. Nothing has been changed; `cargo fix --broken-code` would apply these."
+    )?;
+    let mut fix_count = 0;
+    for (file, fixes) in &fixes_by_file {
+        writeln!(writer, "\n### {}\n", anchors.unique(inline_code(file)))?;
+        for ((line, _), suggestions) in fixes {
+            for suggestion in suggestions {
+                fix_count += 1;
+                writeln!(
+                    writer,
+                    "- Line {} ({}): {}",
+                    line,
+                    inline_code(&suggestion.location),
+                    suggestion.message
+                )?;
+                if suggestion.original_code.is_empty() {
+                    // Older or replayed output without source text: fall back to the summary.
+                    writeln!(writer, "  {}", suggestion_line(suggestion))?;
+                    continue;
+                }
+                writeln!(writer, "  ```diff")?;
+                for before in suggestion.original_code.lines() {
+                    writeln!(writer, "  -{}", before)?;
+                }
+                for after in suggestion.fixed_code.lines() {
+                    writeln!(writer, "  +{}", after)?;
+                }
+                writeln!(writer, "  ```")?;
+            }
+        }
+    }
+    Ok(fix_count)
+}
+
+/// Most locations of one file listed in a diagnostic's "Implicates" line; the rest are
+/// only counted.
+const MAX_IMPLICATED_LOCATIONS_SHOWN: usize = 5;
+
+/// Formats the implicated locations of one diagnostic for its "Implicates" line, at most
+/// [`MAX_IMPLICATED_LOCATIONS_SHOWN`] per file: each as `` `de.rs` (at `de.rs:412-414`) ``
+/// or, with `collapse`, each file once with its locations joined, e.g.
+/// `` `de.rs` (at `de.rs:412-414, 418, 430`) ``.
+fn implicated_file_list(details: &[(PathBuf, String)], collapse: bool) -> Vec<String> {
+    // Details come grouped by file, in line order.
+    let mut locations_by_file: Vec<(&PathBuf, Vec<&str>)> = Vec::new();
+    for (path, detail_loc) in details {
+        // The detail_loc is "filename:line" or "filename:first-last"
+        let location = detail_loc
+            .rsplit_once(':')
+            .map_or(detail_loc.as_str(), |(_, location)| location);
+        match locations_by_file.last_mut() {
+            Some((last_path, locations)) if *last_path == path => locations.push(location),
+            _ => locations_by_file.push((path, vec![location])),
+        }
+    }
+    let mut entries = Vec::new();
+    for (path, locations) in locations_by_file {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let shown = &locations[..locations.len().min(MAX_IMPLICATED_LOCATIONS_SHOWN)];
+        let more = match locations.len() - shown.len() {
+            0 => None,
+            1 => Some("1 more location".to_string()),
+            hidden => Some(format!("{} more locations", hidden)),
+        };
+        let at = |locations: &[&str]| {
+            format!(
+                "{} (at {}",
+                inline_code(&file_name),
+                inline_code(&format!("{}:{}", file_name, locations.join(", ")))
+            )
+        };
+        if collapse {
+            entries.push(match more {
+                Some(more) => format!("{} and {})", at(shown), more),
+                None => format!("{})", at(shown)),
+            });
+        } else {
+            entries.extend(shown.iter().map(|location| format!("{})", at(&[location]))));
+            if let Some(more) = more {
+                entries.push(format!("and {} in {}", more, inline_code(&file_name)));
+            }
+        }
+    }
+    entries
+}
+
+/// Writes the full Markdown report for `report`, presented as `options` ask (`--wrap`,
+/// `--max-doc-lines`, ...).
+pub fn write_markdown_report(
+    report: &Report,
+    options: &Options,
+    writer: &mut impl Write,
+) -> std::io::Result<()> {
+    generate_markdown_report(report, writer, &MarkdownOptions::from(options))
+}
+
+/// Generates a Markdown report from the analyzed diagnostics and extracted source code items.
+/// Diagnostics are presented in a consolidated format, and error code explanations are globalized.
+fn generate_markdown_report(
+    report: &Report,
+    output: &mut impl Write,
+    markdown_options: &MarkdownOptions,
+) -> std::io::Result<()> {
+    let Report {
+        mode_description,
+        consolidated_diagnostics,
+        unique_explanations,
+        extracted_data,
+        hidden_items_skipped,
+        sorted_file_paths,
+        implicated_lines_by_file,
+        raw_fallbacks,
+        related_impls,
+        file_referencers,
+        skipped_feature_sets,
+        equivalent_feature_sets,
+        feature_set_timings,
+        total_duration,
+        phase_timings,
+        ..
+    } = report;
+    let mut writer = CountingWriter::new(output);
+    let mut composition: Vec<CompositionRow> = Vec::new();
+    let mut anchors = HeadingAnchors::default();
+
+    // --- Report Header ---
+    writeln!(
+        writer,
+        "# GetDoc Report - {} - {}",
+        mode_description,
+        header_timestamp(markdown_options)
+    )?;
+    if markdown_options.explanations == ExplanationStyle::Omitted {
+        writeln!(
+            writer,
+            "\nThis report consolidates identical diagnostic messages."
+        )?;
+    } else {
+        writeln!(
+            writer,
+            "\nThis report consolidates identical diagnostic messages and centralizes error code explanations in an appendix."
+        )?;
+    }
+    write_raw_json_note(&mut writer, report)?;
+    write_feature_tables_section(&mut writer, &report.feature_tables)?;
+
+    // --- Section B: Consolidated Compiler Diagnostics ---
+    writeln!(
+        writer,
+        "\n## Consolidated Compiler Diagnostics (Errors and Warnings)\n"
+    )?;
+    if consolidated_diagnostics.is_empty() && report.suppressed_diagnostics > 0 {
+        writeln!(
+            writer,
+            "```text\nAll {} diagnostic(s) were suppressed by ID.\n```\n",
+            report.suppressed_diagnostics
+        )?;
+    } else if consolidated_diagnostics.is_empty() {
+        writeln!(
+            writer,
+            "```text\nNo errors or warnings reported by the compiler across checked feature configurations.\n```\n"
+        )?;
+    } else {
+        writeln!(writer, "```text")?;
+        // Naming the crate being compiled only helps once diagnostics come from several.
+        let several_producing_crates = consolidated_diagnostics
+            .iter()
+            .flat_map(|d| &d.producing_crates)
+            .collect::<HashSet<&String>>()
+            .len()
+            > 1;
+        for agg_diag in consolidated_diagnostics {
+            // Print the core diagnostic message (level, code, rendered text). rustdoc lints
+            // are marked as such, so they don't read as compile errors.
+            let rustdoc_lint_docs = agg_diag.code.as_deref().and_then(rustdoc_lint_url);
+            let level = if rustdoc_lint_docs.is_some() {
+                format!("RUSTDOC {}", agg_diag.level.to_uppercase())
+            } else {
+                agg_diag.level.to_uppercase()
+            };
+            // The header on its own line, then rustc's rendering indented under it.
+            writeln!(
+                writer,
+                "{}",
+                agg_diag
+                    .code
+                    .as_ref()
+                    .map_or_else(|| level.clone(), |c| format!("{}: {}", level, c)),
+            )?;
+            for line in agg_diag.rendered_message.lines() {
+                for wrapped_line in wrap_rendered_line(line, markdown_options.wrap) {
+                    if wrapped_line.is_empty() {
+                        writeln!(writer)?;
+                    } else {
+                        writeln!(writer, "    {}", wrapped_line)?;
+                    }
+                }
+            }
+
+            // Print primary location
+            writeln!(
+                writer,
+                "    (Diagnostic primary location: {}; ID: {})",
+                display_location(agg_diag.primary_location.as_ref()),
+                agg_diag.id
+            )?;
+            if several_producing_crates && !agg_diag.producing_crates.is_empty() {
+                let producing_crates: Vec<&str> = agg_diag
+                    .producing_crates
+                    .iter()
+                    .map(String::as_str)
+                    .collect();
+                writeln!(
+                    writer,
+                    "    (Emitted while compiling {})",
+                    producing_crates.join("; ")
+                )?;
+            }
+            if let Some(url) = rustdoc_lint_docs {
+                writeln!(
+                    writer,
+                    "    (rustdoc lint, not a compile error; see {})",
+                    url
+                )?;
+            }
+
+            // Reference to global explanation, if applicable
+            if let Some(code) = &agg_diag.code
+                && unique_explanations.contains_key(code)
+                && markdown_options.explanations != ExplanationStyle::Omitted
+            {
+                writeln!(
+                    writer,
+                    "    (For generic explanation of {}, see Appendix A)",
+                    code
+                )?;
+            }
+
+            // List feature sets (the BTreeSet keeps them in a consistent order)
+            let sorted_features: Vec<&str> = agg_diag
+                .feature_set_descriptors
+                .iter()
+                .map(String::as_str)
+                .collect();
+            writeln!(
+                writer,
+                "    Occurred under feature set(s): {}",
+                sorted_features.join(", ")
+            )?;
+            if let Some(contrast) = feature_set_contrast(agg_diag) {
+                writeln!(writer, "    {}", contrast)?;
+            }
+            // The others are in the "Feature-Set Timings" table.
+            if let Some(command) = simplest_reproduction_command(
+                &agg_diag.feature_set_descriptors,
+                feature_set_timings,
+            ) {
+                writeln!(writer, "    Reproduce with: {}", command)?;
+            }
+
+            // List implicated third-party files for this specific instance
+            if !agg_diag.implicated_third_party_files_details.is_empty() {
+                let file_list = implicated_file_list(
+                    &agg_diag.implicated_third_party_files_details,
+                    markdown_options.collapse_implicated_lines,
+                )
+                .join(", ");
+                writeln!(
+                    writer,
+                    "    (Implicates: {} - see details below if extracted)",
+                    file_list
+                )?;
+            }
+            for (crate_name, versions) in duplicate_crate_versions(agg_diag) {
+                writeln!(
+                    writer,
+                    "    (This {} likely stems from duplicate versions of `{}`: {}; see `cargo tree --duplicates`)",
+                    agg_diag.level.to_lowercase(),
+                    crate_name,
+                    and_list(
+                        &versions
+                            .iter()
+                            .map(|v| format!("v{}", v))
+                            .collect::<Vec<_>>()
+                    )
+                )?;
+            }
+            for suggestion in &agg_diag.suggestions {
+                writeln!(writer, "    {}", suggestion_line(suggestion))?;
+            }
+            writeln!(writer)?; // Add a blank line for readability between diagnostics
+        }
+        writeln!(writer, "```\n")?;
+    }
+    write_flaky_diagnostics_section(&mut writer, &report.flaky_diagnostics)?;
+    composition.push(CompositionRow {
+        label: "Header and diagnostics".to_string(),
+        items: None,
+        bytes: writer.bytes_written,
+    });
+
+    // --- Section B2: Suggested Fixes (opt-in) ---
+    if markdown_options.fix_preview {
+        let section_start = writer.bytes_written;
+        let fix_count = write_fix_preview(&mut writer, consolidated_diagnostics, &mut anchors)?;
+        composition.push(CompositionRow {
+            label: "Suggested fixes".to_string(),
+            items: Some(fix_count),
+            bytes: writer.bytes_written - section_start,
+        });
+    }
+
+    // --- Section C: Extracted Third-Party Source Code ---
+    writeln!(writer, "\n## Extracted Third-Party Source Code\n")?;
+    if report.excluded_files > 0 && !sorted_file_paths.is_empty() {
+        writeln!(
+            writer,
+            "_{} more implicated file(s) matching `--exclude-path` are left out._\n",
+            report.excluded_files
+        )?;
+    }
+    if extracted_data.is_empty() && raw_fallbacks.is_empty() && !sorted_file_paths.is_empty() {
+        writeln!(
+            writer,
+            "Third-party files were implicated by diagnostics, but no source code items (functions, structs, etc. meeting criteria) were extracted from them, or an error occurred during extraction."
+        )?;
+    } else if extracted_data.is_empty() && raw_fallbacks.is_empty() {
+        // No third-party files were implicated at all.
+        let counts = report.diagnostic_counts;
+        if report.excluded_files > 0 {
+            writeln!(
+                writer,
+                "Every implicated third-party file matched `--exclude-path`, so there is no third-party source to extract."
+            )?;
+        } else if report.suppressed_diagnostics > 0 {
+            writeln!(
+                writer,
+                "No third-party files are implicated by the diagnostics left after suppressing {} by ID.",
+                report.suppressed_diagnostics
+            )?;
+        } else if counts.reported > 0 {
+            writeln!(
+                writer,
+                "None of the {} diagnostic(s) reported across the checked feature sets implicate third-party files; they are all in the project's own code, so there is no third-party source to extract.",
+                counts.reported
+            )?;
+        } else {
+            writeln!(
+                writer,
+                "No third-party files were implicated, and the compiler reported no errors or warnings."
+            )?;
+        }
+    } else {
+        // We have extracted data for some files
+
+        for file_path in sorted_file_paths {
+            // Only create a section for files that were actually implicated and processed.
+            // A file might be in sorted_file_paths but not in extracted_data if extraction failed or yielded no items.
+            // It should, however, be in file_referencers if it was implicated.
+            if extracted_data.contains_key(file_path) || file_referencers.contains_key(file_path) {
+                let section_start = writer.bytes_written;
+                let path_code = inline_code(&file_path.display().to_string());
+                let heading = match build_script_output_crate(file_path) {
+                    Some(crate_name) => format!(
+                        "From Build-Script Output of {}: {}",
+                        inline_code(&crate_name),
+                        path_code
+                    ),
+                    None => format!("From File: {}", path_code),
+                };
+                writeln!(writer, "---\n### {}\n", anchors.unique(heading))?;
+
+                if let Some(origins) = file_referencers.get(file_path)
+                    && !origins.is_empty()
+                {
+                    writeln!(writer, "**Referenced by:**")?;
+                    let mut sorted_origins: Vec<_> = origins.iter().collect();
+                    sorted_origins.sort();
+                    // One bullet per diagnostic: origins sort by everything but the feature
+                    // set first, so those differing only in the feature set are adjacent.
+                    let mut merged_origins: Vec<(&DiagnosticOriginInfo, Vec<&str>)> = Vec::new();
+                    for origin in sorted_origins {
+                        match merged_origins.last_mut() {
+                            Some((first, feature_sets))
+                                if first.level == origin.level
+                                    && first.code == origin.code
+                                    && first.originating_diagnostic_span_location
+                                        == origin.originating_diagnostic_span_location =>
+                            {
+                                feature_sets.push(&origin.feature_set_desc)
+                            }
+                            _ => merged_origins.push((origin, vec![&origin.feature_set_desc])),
+                        }
+                    }
+                    let shown_count = match markdown_options.max_referencers {
+                        0 => merged_origins.len(),
+                        max => merged_origins.len().min(max),
+                    };
+                    for (origin, feature_sets) in &merged_origins[..shown_count] {
+                        let level_str = origin.level.to_uppercase();
+                        // A diagnostic listed above is named by its ID; notes and help
+                        // messages, which are part of one, by where they point.
+                        if let Some(id) =
+                            referencing_diagnostic_id(origin, consolidated_diagnostics)
+                        {
+                            writeln!(
+                                writer,
+                                "* {} `{}` ({})",
+                                level_str,
+                                id,
+                                referencing_feature_sets(feature_sets)
+                            )?;
+                            continue;
+                        }
+                        let label = if level_str == "NOTE" || level_str == "HELP" {
+                            level_str
+                        } else {
+                            format!("{} {}", level_str, origin.code.as_deref().unwrap_or("N/A"))
+                        };
+                        writeln!(
+                            writer,
+                            "* {} (originating at `{}` {})",
+                            label,
+                            display_location(origin.originating_diagnostic_span_location.as_ref()),
+                            referencing_feature_sets(feature_sets)
+                        )?;
+                    }
+                    if merged_origins.len() > shown_count {
+                        let where_all = if markdown_options.json_report {
+                            "see the JSON report for all"
+                        } else {
+                            "`--max-referencers 0` lists all"
+                        };
+                        writeln!(
+                            writer,
+                            "* … and {} more referencing diagnostic(s) ({})",
+                            merged_origins.len() - shown_count,
+                            where_all
+                        )?;
+                    }
+                    writeln!(writer)?;
+                }
+
+                if let Some(line) = report.lossily_read_files.get(file_path) {
+                    writeln!(
+                        writer,
+                        "_This file is not valid UTF-8 (the first invalid byte is on line {}). Invalid bytes are shown as `�`, so the source below may be imperfect._\n",
+                        line
+                    )?;
+                }
+
+                if let Some(skipped_count) = hidden_items_skipped.get(file_path) {
+                    writeln!(
+                        writer,
+                        "_Skipped {} item(s) marked `#[doc(hidden)]` or gated behind `#[cfg(test)]` (use `--include-hidden` to show them)._\n",
+                        skipped_count
+                    )?;
+                }
+
+                if let Some(items) = extracted_data.get(file_path) {
+                    if items.is_empty() {
+                        // This message is printed if the file was processed but no items met extraction criteria.
+                        writeln!(
+                            writer,
+                            "_No extractable items (functions, structs, etc. meeting criteria) found or processed in this file, or all of them are identical to items shown for another file._\n"
+                        )?;
+                    } else {
+                        // Proc-macro crates: lead with the macros they expose, then everything else.
+                        let (exposed_macros, other_items): (
+                            Vec<ExtractedItem>,
+                            Vec<ExtractedItem>,
+                        ) = items.iter().cloned().partition(|item| {
+                            PROC_MACRO_ITEM_KINDS.contains(&item.item_kind.as_str())
+                        });
+                        if !exposed_macros.is_empty() {
+                            writeln!(writer, "**Exposed macros:**\n")?;
+                            write_extracted_items(
+                                &mut writer,
+                                &exposed_macros,
+                                implicated_lines_by_file.get(file_path),
+                                None,
+                                markdown_options,
+                                &mut anchors,
+                            )?;
+                            if !other_items.is_empty() {
+                                writeln!(writer, "**Other items:**\n")?;
+                            }
+                        }
+                        write_extracted_items(
+                            &mut writer,
+                            &other_items,
+                            implicated_lines_by_file.get(file_path),
+                            None,
+                            markdown_options,
+                            &mut anchors,
+                        )?;
+                    }
+
+                    if let Some(related_blocks) = related_impls.get(file_path) {
+                        writeln!(
+                            writer,
+                            "**Related impl blocks from other files of this crate:**\n"
+                        )?;
+                        for block in related_blocks {
+                            let source_label = block.source_file.display().to_string();
+                            write_extracted_items(
+                                &mut writer,
+                                &block.items,
+                                None,
+                                Some(&source_label),
+                                markdown_options,
+                                &mut anchors,
+                            )?;
+                        }
+                    }
+                } else if let Some(fallback) = raw_fallbacks.get(file_path) {
+                    write_raw_fallback(
+                        &mut writer,
+                        fallback,
+                        implicated_lines_by_file.get(file_path),
+                        &mut anchors,
+                    )?;
+                } else if file_referencers.contains_key(file_path) {
+                    // This case covers when a file was implicated by a diagnostic (so it's in file_referencers)
+                    // but yielded no extractable items (e.g., due to parsing error of that file by `syn`,
+                    // or the file contained no items matching the extraction criteria).
+                    writeln!(
+                        writer,
+                        "_This file was referenced by diagnostics, but no source code items were extracted (possibly due to a parsing issue or no matching items)._\n"
+                    )?;
+                }
+
+                let item_count = extracted_data.get(file_path).map_or(0, Vec::len)
+                    + related_impls.get(file_path).map_or(0, |blocks| {
+                        blocks.iter().map(|block| block.items.len()).sum()
+                    });
+                composition.push(CompositionRow {
+                    label: table_code(&file_path.display().to_string()),
+                    items: Some(item_count),
+                    bytes: writer.bytes_written - section_start,
+                });
+            }
+        }
+    }
+
+    if !report.missing_crate_sources.is_empty() {
+        let section_start = writer.bytes_written;
+        write_missing_sources_section(&mut writer, &report.missing_crate_sources)?;
+        composition.push(CompositionRow {
+            label: "Missing sources".to_string(),
+            items: Some(report.missing_crate_sources.len()),
+            bytes: writer.bytes_written - section_start,
+        });
+    }
+
+    let section_start = writer.bytes_written;
+    write_summary_section(&mut writer, &report_summary(report))?;
+    write_skipped_feature_sets_section(&mut writer, skipped_feature_sets)?;
+    write_equivalent_feature_sets_section(&mut writer, equivalent_feature_sets)?;
+    write_timings_section(&mut writer, feature_set_timings, *total_duration)?;
+    write_phase_timings_section(&mut writer, phase_timings)?;
+    composition.push(CompositionRow {
+        label: "Feature-set summary and timings".to_string(),
+        items: None,
+        bytes: writer.bytes_written - section_start,
+    });
+
+    // --- Section D: Appendix A: Error Code Explanations ---
+    if !unique_explanations.is_empty() && markdown_options.explanations != ExplanationStyle::Omitted
+    {
+        let section_start = writer.bytes_written;
+        write_explanations_appendix(
+            &mut writer,
+            unique_explanations,
+            markdown_options.explanations,
+        )?;
+        composition.push(CompositionRow {
+            label: "Appendix A".to_string(),
+            items: None,
+            bytes: writer.bytes_written - section_start,
+        });
+    }
+
+    write_composition_section(&mut writer, &composition)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// One row of the "Report composition" table: a report section and its rendered size.
+struct CompositionRow {
+    label: String,
+    // Number of extracted items, for file sections only.
+    items: Option<usize>,
+    bytes: usize,
+}
+
+/// Writes the "Report composition" table. Percentages are relative to everything written
+/// before the table itself.
+fn write_composition_section(
+    writer: &mut impl Write,
+    composition: &[CompositionRow],
+) -> std::io::Result<()> {
+    let total_bytes: usize = composition.iter().map(|row| row.bytes).sum();
+    writeln!(writer, "\n## Report Composition\n")?;
+    writeln!(writer, "| Section | Items | Bytes | % of report |")?;
+    writeln!(writer, "|---|---:|---:|---:|")?;
+    for row in composition {
+        let percent = if total_bytes == 0 {
+            0.0
+        } else {
+            row.bytes as f64 * 100.0 / total_bytes as f64
+        };
+        writeln!(
+            writer,
+            "| {} | {} | {} | {:.1}% |",
+            row.label,
+            row.items
+                .map_or_else(|| "–".to_string(), |count| count.to_string()),
+            row.bytes,
+            percent
+        )?;
+    }
+    Ok(())
+}
+
+/// A `Write` adapter that counts the bytes passed through it, used to measure report sections.
+struct CountingWriter<W: Write> {
+    inner: W,
+    bytes_written: usize,
+}
+
+impl<W: Write> CountingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            bytes_written: 0,
+        }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.bytes_written += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,438 @@
+// getdoc - rustdoc_json.rs
+//
+// Enriching extracted items with rustdoc's JSON output (`--rustdoc-json`): resolved item
+// paths, docs as rustdoc sees them (including `#[doc = include_str!(..)]` and docs written
+// by macros), and items generated by macros, which `syn` cannot see. The JSON format is
+// nightly-only and changes between releases, so it is read loosely and any crate whose
+// JSON cannot be built or understood keeps its `syn` extraction.
+
+// --- Standard Library Imports ---
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+// --- External Crate Imports ---
+use serde_json::Value;
+
+// --- Crate Imports ---
+use crate::cargo::nightly_cargo_command;
+use crate::extract::ExtractedItem;
+use crate::progress::info;
+use crate::state::getdoc_dir;
+
+/// Builds the rustdoc JSON of every crate with extracted items and uses it to fill in the
+/// items of that crate's files (see [`enrich_file_items`]). Crates inside the toolchain's
+/// own sources are left alone. Returns how many crates were enriched. A crate is built
+/// under the feature arguments `feature_args_by_file` gives its first file, and
+/// `config_overrides` are passed to cargo as `--config` options.
+pub(crate) fn enrich_with_rustdoc_json(
+    extracted_data: &mut HashMap<PathBuf, Vec<ExtractedItem>>,
+    implicated_lines_by_file: &HashMap<PathBuf, BTreeSet<usize>>,
+    feature_args_by_file: &HashMap<PathBuf, Vec<String>>,
+    config_overrides: &[String],
+) -> usize {
+    let mut files_by_crate_root: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+    for file_path in extracted_data.keys() {
+        if file_path
+            .components()
+            .any(|part| part.as_os_str() == "rustlib")
+        {
+            continue;
+        }
+        if let Some(crate_root) = file_path
+            .ancestors()
+            .skip(1)
+            .find(|dir| dir.join("Cargo.toml").is_file())
+        {
+            files_by_crate_root
+                .entry(crate_root.to_path_buf())
+                .or_default()
+                .push(file_path.clone());
+        }
+    }
+
+    let mut enriched_crates = 0;
+    for (crate_root, file_paths) in files_by_crate_root {
+        info!(
+            "[getdoc] Building rustdoc JSON for {}...",
+            crate_root.display()
+        );
+        let feature_args = file_paths
+            .iter()
+            .find_map(|file_path| feature_args_by_file.get(file_path))
+            .map_or(&[][..], Vec::as_slice);
+        let Some(index) = build_rustdoc_json(&crate_root, feature_args, config_overrides) else {
+            continue;
+        };
+        for file_path in file_paths {
+            let no_lines = BTreeSet::new();
+            let implicated_lines = implicated_lines_by_file
+                .get(&file_path)
+                .unwrap_or(&no_lines);
+            if let Some(items) = extracted_data.get_mut(&file_path) {
+                enrich_file_items(&index, &crate_root, &file_path, implicated_lines, items);
+            }
+        }
+        enriched_crates += 1;
+    }
+    enriched_crates
+}
+
+/// Runs `cargo +nightly rustdoc --output-format json` on the library of the crate at
+/// `crate_root`, writing under `target/getdoc/rustdoc-json/`, and reads the result.
+/// `None` when the crate has no library, fails to document, or the output is unreadable.
+///
+/// The crate is built from the project as `-p <name>@<version>`, so it resolves exactly
+/// as in the checks (the project's lockfile, dependencies, and cargo configuration). A
+/// workspace member also gets `feature_args`; cargo refuses feature arguments for any other
+/// package, which is built with the features the project's dependency graph enables.
+fn build_rustdoc_json(
+    crate_root: &Path,
+    feature_args: &[String],
+    config_overrides: &[String],
+) -> Option<Value> {
+    let manifest: toml::Table = fs::read_to_string(crate_root.join("Cargo.toml"))
+        .ok()?
+        .parse()
+        .ok()?;
+    let package = manifest.get("package")?;
+    let package_name = package.get("name")?.as_str()?;
+    let library_name = manifest
+        .get("lib")
+        .and_then(|lib| lib.get("name"))
+        .and_then(|name| name.as_str())
+        .unwrap_or(package_name)
+        .replace('-', "_");
+    let package_spec = match package.get("version").and_then(|version| version.as_str()) {
+        Some(version) => format!("{}@{}", package_name, version),
+        None => package_name.to_string(),
+    };
+    let is_workspace_member = fs::canonicalize(".")
+        .is_ok_and(|project_dir| crate_root.starts_with(project_dir))
+        && !crate_root
+            .components()
+            .any(|part| part.as_os_str() == "target");
+    // A workspace member's set may already select it with `-p <member>`.
+    let feature_args = match feature_args {
+        [flag, _, rest @ ..] if flag == "-p" => rest,
+        args => args,
+    };
+    let target_dir = getdoc_dir().join("rustdoc-json");
+    let mut command = nightly_cargo_command(config_overrides);
+    command
+        .args(["rustdoc", "--lib", "--quiet", "-p", &package_spec])
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .args(["-Z", "unstable-options", "--output-format", "json"]);
+    if is_workspace_member {
+        command.args(feature_args);
+    }
+    let status = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .ok()?;
+    if !status.success() {
+        return None;
+    }
+    let content = fs::read_to_string(
+        target_dir
+            .join("doc")
+            .join(format!("{}.json", library_name)),
+    )
+    .ok()?;
+    let index: Value = serde_json::from_str(&content).ok()?;
+    index.get("index")?.is_object().then_some(index)
+}
+
+/// Fills in the items of one file from its crate's rustdoc JSON: an item rustdoc also
+/// documents at the same place gets rustdoc's docs (when it has any) and its full path.
+/// Items rustdoc documents at an implicated line that `syn` did not find, such as those
+/// written by a macro invocation (rustdoc places them at the invocation), are added.
+pub fn enrich_file_items(
+    rustdoc_json: &Value,
+    crate_root: &Path,
+    file_path: &Path,
+    implicated_lines: &BTreeSet<usize>,
+    items: &mut Vec<ExtractedItem>,
+) {
+    let Some(index) = rustdoc_json.get("index").and_then(Value::as_object) else {
+        return;
+    };
+    let paths = rustdoc_json.get("paths").and_then(Value::as_object);
+    let mut next_id = items.iter().map(|item| item.id + 1).max().unwrap_or(0);
+
+    for (id, documented) in index {
+        let Some((name, line_start, line_end)) =
+            documented_location(documented, crate_root, file_path)
+        else {
+            continue;
+        };
+        let item_path = paths
+            .and_then(|paths| paths.get(id))
+            .and_then(|summary| summary.get("path"))
+            .and_then(Value::as_array)
+            .map(|segments| {
+                segments
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join("::")
+            });
+        let docs: Vec<String> = documented
+            .get("docs")
+            .and_then(Value::as_str)
+            .map(|docs| docs.lines().map(str::to_string).collect())
+            .unwrap_or_default();
+
+        let matching = items.iter_mut().find(|item| {
+            item.name == name && item.line_start <= line_end && line_start <= item.line_end
+        });
+        if let Some(item) = matching {
+            if !docs.is_empty() {
+                item.doc_comments = docs;
+            }
+            if item_path.is_some() {
+                item.item_path = item_path;
+            }
+            continue;
+        }
+
+        if !implicated_lines.range(line_start..=line_end).any(|_| true) {
+            continue;
+        }
+        let Some((item_kind, signature_or_definition)) = describe_item(documented, name) else {
+            continue;
+        };
+        items.push(ExtractedItem {
+            item_kind: item_kind.to_string(),
+            name: name.to_string(),
+            signature_or_definition,
+            trait_bounds: Vec::new(),
+            doc_comments: docs,
+            id: next_id,
+            parent: None,
+            parent_impl: None,
+            also_found_in: Vec::new(),
+            item_path,
+            line_start,
+            line_end,
+        });
+        next_id += 1;
+    }
+    items.sort_by_key(|item| item.line_start);
+}
+
+/// The name and line range of a documented item whose span lies in `file_path`.
+fn documented_location<'a>(
+    documented: &'a Value,
+    crate_root: &Path,
+    file_path: &Path,
+) -> Option<(&'a str, usize, usize)> {
+    if documented.get("crate_id").and_then(Value::as_u64) != Some(0) {
+        return None;
+    }
+    let name = documented.get("name")?.as_str()?;
+    let span = documented.get("span")?;
+    let filename = Path::new(span.get("filename")?.as_str()?);
+    if crate_root.join(filename) != file_path && !file_path.ends_with(filename) {
+        return None;
+    }
+    let line = |key: &str| -> Option<usize> { span.get(key)?.get(0)?.as_u64()?.try_into().ok() };
+    Some((name, line("begin")?, line("end")?))
+}
+
+/// The report's kind for a documented item added from rustdoc JSON, and a definition
+/// rebuilt from its JSON. Only item kinds that appear at module level are added.
+fn describe_item(documented: &Value, name: &str) -> Option<(&'static str, String)> {
+    let (kind, inner) = documented.get("inner")?.as_object()?.iter().next()?;
+    Some(match kind.as_str() {
+        "function" => {
+            let signature = inner.get("sig").or_else(|| inner.get("decl"))?;
+            let inputs: Vec<String> = signature
+                .get("inputs")?
+                .as_array()?
+                .iter()
+                .filter_map(|input| {
+                    let (param, ty) = (input.get(0)?.as_str()?, input.get(1)?);
+                    Some(render_parameter(param, ty))
+                })
+                .collect();
+            let output = signature
+                .get("output")
+                .filter(|output| !output.is_null())
+                .map(|output| format!(" -> {}", render_type(output)))
+                .unwrap_or_default();
+            (
+                "Function",
+                format!("fn {}({}){}", name, inputs.join(", "), output),
+            )
+        }
+        "struct" => ("Struct", format!("struct {}", name)),
+        "enum" => ("Enum", format!("enum {}", name)),
+        "trait" => ("Trait", format!("trait {}", name)),
+        "type_alias" | "typedef" => (
+            "Type Alias",
+            format!("type {} = {}", name, render_type(inner.get("type")?)),
+        ),
+        "constant" => (
+            "Constant",
+            format!("const {}: {}", name, render_type(inner.get("type")?)),
+        ),
+        "static" => (
+            "Static",
+            format!("static {}: {}", name, render_type(inner.get("type")?)),
+        ),
+        _ => return None,
+    })
+}
+
+/// A function parameter, with `self` receivers written the usual way (`&self`, `&mut self`).
+fn render_parameter(param: &str, ty: &Value) -> String {
+    if param == "self" {
+        return match render_type(ty).as_str() {
+            "Self" => "self".to_string(),
+            rendered if rendered.ends_with(" Self") || rendered.ends_with("&Self") => {
+                rendered.replace("Self", "self")
+            }
+            rendered => format!("self: {}", rendered),
+        };
+    }
+    format!("{}: {}", param, render_type(ty))
+}
+
+/// Renders a rustdoc JSON type back into Rust syntax. Unrecognized shapes become `_`.
+fn render_type(ty: &Value) -> String {
+    let Some((kind, inner)) = ty.as_object().and_then(|object| object.iter().next()) else {
+        // Includes `"infer"`, rustdoc's `_`.
+        return "_".to_string();
+    };
+    let flag = |keys: [&str; 2]| {
+        keys.iter()
+            .any(|key| inner.get(*key) == Some(&Value::Bool(true)))
+    };
+    match kind.as_str() {
+        "generic" | "primitive" => inner.as_str().unwrap_or("_").to_string(),
+        "resolved_path" => render_path(inner),
+        "borrowed_ref" => format!(
+            "&{}{}{}",
+            inner
+                .get("lifetime")
+                .and_then(Value::as_str)
+                .map(|lifetime| format!("{} ", lifetime))
+                .unwrap_or_default(),
+            if flag(["is_mutable", "mutable"]) {
+                "mut "
+            } else {
+                ""
+            },
+            render_type(&inner["type"])
+        ),
+        "raw_pointer" => format!(
+            "*{} {}",
+            if flag(["is_mutable", "mutable"]) {
+                "mut"
+            } else {
+                "const"
+            },
+            render_type(&inner["type"])
+        ),
+        "slice" => format!("[{}]", render_type(inner)),
+        "array" => format!(
+            "[{}; {}]",
+            render_type(&inner["type"]),
+            inner.get("len").and_then(Value::as_str).unwrap_or("_")
+        ),
+        "tuple" => {
+            let elements: Vec<String> = inner
+                .as_array()
+                .map(|elements| elements.iter().map(render_type).collect())
+                .unwrap_or_default();
+            if elements.len() == 1 {
+                format!("({},)", elements[0])
+            } else {
+                format!("({})", elements.join(", "))
+            }
+        }
+        "impl_trait" => format!("impl {}", render_bounds(inner)),
+        "dyn_trait" => format!(
+            "dyn {}",
+            inner
+                .get("traits")
+                .and_then(Value::as_array)
+                .map(|traits| {
+                    traits
+                        .iter()
+                        .filter_map(|poly| poly.get("trait"))
+                        .map(render_path)
+                        .collect::<Vec<_>>()
+                        .join(" + ")
+                })
+                .unwrap_or_default()
+        ),
+        "qualified_path" => {
+            let self_type = render_type(&inner["self_type"]);
+            let name = inner.get("name").and_then(Value::as_str).unwrap_or("_");
+            match inner.get("trait").filter(|path| !path.is_null()) {
+                Some(path) => format!("<{} as {}>::{}", self_type, render_path(path), name),
+                None => format!("{}::{}", self_type, name),
+            }
+        }
+        _ => "_".to_string(),
+    }
+}
+
+/// A path with its generic arguments, e.g. `Result<T, Error>`.
+fn render_path(path: &Value) -> String {
+    let name = path
+        .get("path")
+        .or_else(|| path.get("name"))
+        .and_then(Value::as_str)
+        .unwrap_or("_");
+    let arguments: Vec<String> = path
+        .get("args")
+        .and_then(|args| args.get("angle_bracketed"))
+        .and_then(|angle_bracketed| angle_bracketed.get("args"))
+        .and_then(Value::as_array)
+        .map(|args| {
+            args.iter()
+                .filter_map(|arg| match arg.as_object()?.iter().next()? {
+                    (kind, value) if kind == "lifetime" => value.as_str().map(str::to_string),
+                    (kind, value) if kind == "type" => Some(render_type(value)),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    if arguments.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}<{}>", name, arguments.join(", "))
+    }
+}
+
+/// Trait bounds joined with `+`, e.g. `Iterator + Send` (associated-type constraints are
+/// left out).
+fn render_bounds(bounds: &Value) -> String {
+    bounds
+        .as_array()
+        .map(|bounds| {
+            bounds
+                .iter()
+                .filter_map(|bound| {
+                    if let Some(trait_bound) = bound.get("trait_bound") {
+                        Some(render_path(trait_bound.get("trait")?))
+                    } else {
+                        bound
+                            .get("outlives")
+                            .and_then(Value::as_str)
+                            .map(str::to_string)
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" + ")
+        })
+        .unwrap_or_default()
+}
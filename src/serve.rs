@@ -0,0 +1,188 @@
+// getdoc - serve.rs
+//
+// `--serve`: a local viewer for the report. One self-contained page (`viewer.html`, no
+// external assets) fetches the JSON report and renders it with filters by error code,
+// crate, and feature set, a search over extracted items, and collapsible file sections.
+// When the run wrote a JSON report, the page follows that file, so a later run replacing it
+// updates the page.
+
+// --- Standard Library Imports ---
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+
+// --- Crate Imports ---
+use crate::Options;
+use crate::error::GetdocError;
+use crate::interrupt::{begin_serving, interrupted};
+use crate::progress::info;
+use crate::report::{OutputFormat, Report, generate_json_report, report_paths};
+
+/// The viewer page.
+const VIEWER_HTML: &str = include_str!("viewer.html");
+
+/// How often the accept loop looks for connections and Ctrl-C.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Longest request head read; the viewer only sends short `GET`s.
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+
+/// Where the served JSON comes from.
+enum ReportData {
+    /// The JSON report file this run wrote, read again for every request.
+    File(PathBuf),
+    /// This run's report, rendered once.
+    InMemory(Vec<u8>),
+}
+
+impl ReportData {
+    fn json(&self) -> std::io::Result<Vec<u8>> {
+        match self {
+            ReportData::File(path) => fs::read(path),
+            ReportData::InMemory(json) => Ok(json.clone()),
+        }
+    }
+
+    /// Changes whenever the data does: the file's modification time.
+    fn version(&self) -> String {
+        match self {
+            ReportData::File(path) => fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map_or_else(|| "missing".to_string(), |age| age.as_nanos().to_string()),
+            ReportData::InMemory(_) => "0".to_string(),
+        }
+    }
+}
+
+/// Serves the viewer for `report` on `127.0.0.1` (port `--serve-port`) until Ctrl-C.
+pub fn serve_report(report: &Report, options: &Options) -> Result<(), GetdocError> {
+    let json_path = report_paths(&options.format, &options.output)
+        .into_iter()
+        .find_map(|(format, path)| (format == OutputFormat::Json).then_some(path).flatten());
+    let address = format!("127.0.0.1:{}", options.serve_port);
+    let serve_error = |source| GetdocError::ServeReport {
+        address: address.clone(),
+        source,
+    };
+    let data = match json_path {
+        Some(path) => ReportData::File(path),
+        None => {
+            let mut json = Vec::new();
+            generate_json_report(report, &mut json).map_err(serve_error)?;
+            ReportData::InMemory(json)
+        }
+    };
+    let listener = TcpListener::bind(&address).map_err(serve_error)?;
+    listener.set_nonblocking(true).map_err(serve_error)?;
+    let local_address = listener.local_addr().map_err(serve_error)?;
+
+    begin_serving();
+    info!(
+        "[getdoc] Serving the report at http://{}/ (press Ctrl-C to stop)",
+        local_address
+    );
+    while !interrupted() {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                // A failed request only affects that request.
+                let _ = handle_request(stream, &data);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                thread::sleep(ACCEPT_POLL_INTERVAL)
+            }
+            Err(e) => return Err(serve_error(e)),
+        }
+    }
+    info!("[getdoc] Report viewer stopped.");
+    Ok(())
+}
+
+/// Whether the `Host` header names this machine. Pages from other sites can make a browser
+/// send requests here under their own host name (DNS rebinding), and must not get the
+/// report.
+fn is_local_host(head: &str) -> bool {
+    let Some(host) = head.lines().skip(1).find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("host")
+            .then(|| value.trim())
+    }) else {
+        return false;
+    };
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    };
+    name.eq_ignore_ascii_case("localhost") || name == "127.0.0.1"
+}
+
+/// Answers one request: the page at `/`, the report at `/report.json`, and the data's
+/// version (polled by the page) at `/version`. Requests naming any host but `localhost` or
+/// `127.0.0.1` are refused.
+fn handle_request(mut stream: TcpStream, data: &ReportData) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+    let mut head = Vec::new();
+    let mut buffer = [0; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") && head.len() < MAX_REQUEST_HEAD {
+        let read = stream.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&buffer[..read]);
+    }
+    let head = String::from_utf8_lossy(&head);
+    let mut request_line = head.lines().next().unwrap_or_default().split_whitespace();
+    let (method, target) = (request_line.next(), request_line.next());
+    let path = target.unwrap_or("/").split('?').next().unwrap_or("/");
+
+    let (status, content_type, body) = match (method, path) {
+        _ if !is_local_host(&head) => (
+            "403 Forbidden",
+            "text/plain; charset=utf-8",
+            b"Only requests to localhost or 127.0.0.1 are served".to_vec(),
+        ),
+        (Some("GET"), "/" | "/index.html") => (
+            "200 OK",
+            "text/html; charset=utf-8",
+            VIEWER_HTML.as_bytes().to_vec(),
+        ),
+        (Some("GET"), "/report.json") => match data.json() {
+            Ok(json) => ("200 OK", "application/json", json),
+            Err(e) => (
+                "503 Service Unavailable",
+                "text/plain; charset=utf-8",
+                format!("The report could not be read: {}", e).into_bytes(),
+            ),
+        },
+        (Some("GET"), "/version") => (
+            "200 OK",
+            "text/plain; charset=utf-8",
+            data.version().into_bytes(),
+        ),
+        (Some("GET"), _) => (
+            "404 Not Found",
+            "text/plain; charset=utf-8",
+            b"Not found".to_vec(),
+        ),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain; charset=utf-8",
+            b"Only GET is supported".to_vec(),
+        ),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(&body)?;
+    stream.flush()
+}
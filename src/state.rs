@@ -0,0 +1,389 @@
+// getdoc - state.rs
+//
+// Results of individual feature-set checks, kept under `target/getdoc/state/` so that
+// later runs can reuse them: always when nothing that affects the check has changed
+// (its fingerprint matches), and with `--resume` for every set that did not fail.
+
+// --- Standard Library Imports ---
+use std::cell::RefCell;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// --- External Crate Imports ---
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+// --- Crate Imports ---
+use crate::cargo::{CargoCli, DiagnosticsSource, RawRunOutput};
+use crate::cargo_config::cargo_config_files;
+use crate::error::GetdocError;
+use crate::features::FeatureSet;
+use crate::progress::warning;
+use crate::util::{StableHasher, stable_hash};
+
+/// getdoc's own directory under the target directory: `$CARGO_TARGET_DIR/getdoc`, or
+/// `./target/getdoc` when `CARGO_TARGET_DIR` is unset.
+pub(crate) fn getdoc_dir() -> PathBuf {
+    std::env::var_os("CARGO_TARGET_DIR")
+        .map_or_else(|| PathBuf::from("target"), PathBuf::from)
+        .join("getdoc")
+}
+
+/// How a feature-set check turned out.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RunOutcome {
+    Clean,
+    Warnings,
+    Errors,
+    /// `cargo check` could not be run or its output not processed.
+    ToolError,
+}
+
+/// One cached check, stored as `state/<hash of the cargo arguments>.json`.
+#[derive(Serialize, Deserialize)]
+struct CachedRun {
+    // Must match the current `RunCache::key`, or the entry is stale.
+    cache_key: String,
+    // `RunCache::fingerprint` when the check ran; a match means it would come out the same.
+    #[serde(default)]
+    fingerprint: String,
+    // Local time of the check, e.g. `2024-05-01 14:03:12`.
+    #[serde(default)]
+    checked_at: String,
+    feature_set: String,
+    outcome: RunOutcome,
+    json_messages: String,
+    stderr: String,
+}
+
+/// The cached results for one planned feature-set matrix.
+pub struct RunCache {
+    dir: PathBuf,
+    key: String,
+    // Hash of everything but the feature arguments that goes into a set's fingerprint.
+    inputs_hash: u64,
+}
+
+/// A cached check that can stand in for running it again.
+pub struct ReusedRun {
+    pub output: RawRunOutput,
+    /// When the cached check ran, e.g. `2024-05-01 14:03:12`.
+    pub checked_at: String,
+}
+
+impl RunCache {
+    /// Opens the cache for `feature_sets` run by `cargo`. Entries written under a different
+    /// `Cargo.lock`, matrix, cargo subcommand, target selection, set of rustc flags, or set
+    /// of cargo config overrides are treated as absent. Fingerprints additionally cover the
+    /// toolchain, cargo's configuration files and rustflags variables, and every file of the
+    /// project as it is now, except getdoc's own `outputs` (reports are rewritten each run).
+    pub fn new(feature_sets: &[FeatureSet], cargo: &CargoCli, outputs: &[PathBuf]) -> RunCache {
+        let command = cargo.subcommand_args();
+        let (target_args, rustflags, config_overrides) = (
+            &cargo.target_args,
+            &cargo.rustflags,
+            &cargo.config_overrides,
+        );
+        let lockfile = std::env::current_dir().ok().and_then(|dir| {
+            dir.ancestors()
+                .map(|ancestor| ancestor.join("Cargo.lock"))
+                .find(|path| path.is_file())
+                .and_then(|path| fs::read(path).ok())
+        });
+        // The matrix is compared as a set: `--resume` reorders it (failed sets first).
+        let mut matrix: Vec<&[String]> = feature_sets
+            .iter()
+            .map(|feature_set| feature_set.args.as_slice())
+            .collect();
+        matrix.sort();
+        let outputs: Vec<PathBuf> = outputs.iter().map(|path| absolute_path(path)).collect();
+        // A new toolchain may hash these differently, which only costs a cache miss: its
+        // version is part of the fingerprint anyway.
+        let inputs_hash = stable_hash(&(
+            &lockfile,
+            &command,
+            target_args,
+            rustflags,
+            config_overrides,
+            toolchain_version(),
+            rustflags_variables(),
+            cargo_config_files()
+                .into_iter()
+                .map(|path| {
+                    let content = fs::read(&path).unwrap_or_default();
+                    (path, content)
+                })
+                .collect::<Vec<_>>(),
+            local_source_dirs()
+                .iter()
+                .map(|dir| source_tree_hash(dir, &outputs))
+                .collect::<Vec<u64>>(),
+        ));
+        RunCache {
+            dir: getdoc_dir().join("state"),
+            key: format!(
+                "{:016x}",
+                stable_hash(&(
+                    lockfile,
+                    matrix,
+                    &command,
+                    target_args,
+                    rustflags,
+                    config_overrides
+                ))
+            ),
+            inputs_hash,
+        }
+    }
+
+    /// The cached result of `feature_set`'s last check, if running it again would give the
+    /// same result (its fingerprint is unchanged, whatever the outcome), or, with `resume`,
+    /// if it is current and came out clean or with warnings only.
+    pub fn reusable(&self, feature_set: &FeatureSet, resume: bool) -> Option<ReusedRun> {
+        let content = fs::read_to_string(self.entry_path(feature_set)).ok()?;
+        let cached: CachedRun = serde_json::from_str(&content).ok()?;
+        let unchanged = cached.fingerprint == self.fingerprint(feature_set);
+        let resumable = resume
+            && cached.cache_key == self.key
+            && matches!(cached.outcome, RunOutcome::Clean | RunOutcome::Warnings);
+        (unchanged || resumable).then(|| ReusedRun {
+            output: RawRunOutput {
+                json_messages: cached.json_messages,
+                stderr: cached.stderr,
+            },
+            checked_at: if cached.checked_at.is_empty() {
+                "an earlier run".to_string()
+            } else {
+                cached.checked_at
+            },
+        })
+    }
+
+    /// Records the outcome and output of a fresh check. Failures are reported but never
+    /// fail the run.
+    pub fn store(&self, feature_set: &FeatureSet, outcome: RunOutcome, output: RawRunOutput) {
+        let path = self.entry_path(feature_set);
+        let cached = CachedRun {
+            cache_key: self.key.clone(),
+            fingerprint: self.fingerprint(feature_set),
+            checked_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            feature_set: feature_set.description.clone(),
+            outcome,
+            json_messages: output.json_messages,
+            stderr: output.stderr,
+        };
+        let result = fs::create_dir_all(&self.dir)
+            .and_then(|()| fs::write(&path, serde_json::to_string(&cached)?));
+        if let Err(e) = result {
+            warning!(
+                "[getdoc] Warning: Could not cache the result of '{}' in {}: {}",
+                feature_set.description,
+                path.display(),
+                e
+            );
+        }
+    }
+
+    /// Identifies everything a check of `feature_set` depends on: `Cargo.lock`, the feature
+    /// and target arguments, rustc flags, cargo's configuration, the toolchain version, and
+    /// the project's files.
+    fn fingerprint(&self, feature_set: &FeatureSet) -> String {
+        format!(
+            "{:016x}",
+            stable_hash(&(self.inputs_hash, &feature_set.args))
+        )
+    }
+
+    /// Where this feature set's result is cached: one file per distinct cargo argument list.
+    fn entry_path(&self, feature_set: &FeatureSet) -> PathBuf {
+        self.dir
+            .join(format!("{:016x}.json", stable_hash(&feature_set.args)))
+    }
+}
+
+/// `rustc -vV` (using `$RUSTC` when set, as cargo does), or empty if it cannot be run.
+pub(crate) fn toolchain_version() -> String {
+    let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    Command::new(rustc)
+        .arg("-vV")
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+        .unwrap_or_default()
+}
+
+/// The project directory followed by every local path dependency outside it, found by
+/// following `path = "..."` entries from `Cargo.toml` to `Cargo.toml`. Their sources are
+/// part of what a check depends on, just as the project's own are.
+fn local_source_dirs() -> Vec<PathBuf> {
+    let Ok(project_dir) = fs::canonicalize(".") else {
+        return vec![PathBuf::from(".")];
+    };
+    let mut dirs = vec![project_dir.clone()];
+    let mut pending = vec![project_dir.clone()];
+    while let Some(dir) = pending.pop() {
+        let Some(manifest) = fs::read_to_string(dir.join("Cargo.toml"))
+            .ok()
+            .and_then(|content| content.parse::<toml::Table>().ok())
+        else {
+            continue;
+        };
+        for dependency_dir in path_dependencies(&manifest) {
+            let Ok(dependency_dir) = fs::canonicalize(dir.join(dependency_dir)) else {
+                continue;
+            };
+            if dependency_dir.starts_with(&project_dir) || dirs.contains(&dependency_dir) {
+                continue;
+            }
+            dirs.push(dependency_dir.clone());
+            pending.push(dependency_dir);
+        }
+    }
+    dirs
+}
+
+/// The `path` of every dependency a manifest declares, in any dependency table (including
+/// `[target.*]` and `[workspace.dependencies]`).
+fn path_dependencies(manifest: &toml::Table) -> Vec<&str> {
+    const DEPENDENCY_TABLES: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+    let mut parents: Vec<&toml::Table> = vec![manifest];
+    if let Some(targets) = manifest.get("target").and_then(toml::Value::as_table) {
+        parents.extend(targets.values().filter_map(toml::Value::as_table));
+    }
+    if let Some(workspace) = manifest.get("workspace").and_then(toml::Value::as_table) {
+        parents.push(workspace);
+    }
+    parents
+        .into_iter()
+        .flat_map(|parent| {
+            DEPENDENCY_TABLES
+                .iter()
+                .filter_map(|name| parent.get(*name)?.as_table())
+        })
+        .flat_map(|table| table.values())
+        .filter_map(|dependency| dependency.get("path")?.as_str())
+        .collect()
+}
+
+/// Environment variables through which cargo picks up rustc and rustdoc flags.
+const RUSTFLAGS_VARIABLES: [&str; 4] = [
+    "RUSTFLAGS",
+    "CARGO_ENCODED_RUSTFLAGS",
+    "CARGO_BUILD_RUSTFLAGS",
+    "RUSTDOCFLAGS",
+];
+
+/// The value of each of [`RUSTFLAGS_VARIABLES`], `None` when unset.
+fn rustflags_variables() -> Vec<Option<String>> {
+    RUSTFLAGS_VARIABLES
+        .iter()
+        .map(|name| std::env::var(name).ok())
+        .collect()
+}
+
+/// `path` made absolute and canonical as far as it exists, so it compares equal to the
+/// same file found by walking a canonical directory.
+fn absolute_path(path: &Path) -> PathBuf {
+    if let Ok(path) = fs::canonicalize(path) {
+        return path;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            fs::canonicalize(parent).map_or_else(|_| path.to_path_buf(), |dir| dir.join(name))
+        }
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Hashes the path and contents of every file under `dir` (a build script or `include_str!`
+/// can read any of them), in path order. `target` and `.git` directories and the files
+/// and directories in `skip` are left out. Symbolic links are hashed as the path they
+/// point to and never followed, so a link cycle cannot send the walk around forever.
+fn source_tree_hash(dir: &Path, skip: &[PathBuf]) -> u64 {
+    fn collect(dir: &Path, skip: &[PathBuf], files: &mut Vec<PathBuf>) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if skip.contains(&path) {
+                continue;
+            }
+            if file_type.is_dir() {
+                let name = entry.file_name();
+                if name != "target" && name != ".git" {
+                    collect(&path, skip, files);
+                }
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    let mut files = Vec::new();
+    collect(dir, skip, &mut files);
+    files.sort();
+    let mut hasher = StableHasher::default();
+    for file in files {
+        file.hash(&mut hasher);
+        match fs::read_link(&file) {
+            Ok(link_target) => link_target.hash(&mut hasher),
+            Err(_) => fs::read(&file).unwrap_or_default().hash(&mut hasher),
+        }
+    }
+    hasher.finish()
+}
+
+/// Passes runs through to another source, keeping the last output for [`RunCache::store`].
+pub(crate) struct RecordingSource<'a> {
+    source: &'a dyn DiagnosticsSource,
+    last_output: RefCell<Option<RawRunOutput>>,
+}
+
+impl<'a> RecordingSource<'a> {
+    pub(crate) fn new(source: &'a dyn DiagnosticsSource) -> RecordingSource<'a> {
+        RecordingSource {
+            source,
+            last_output: RefCell::new(None),
+        }
+    }
+
+    /// The output of the most recent run, or empty output when it failed to produce any.
+    pub(crate) fn take_last_output(&self) -> RawRunOutput {
+        self.last_output.take().unwrap_or_default()
+    }
+}
+
+impl DiagnosticsSource for RecordingSource<'_> {
+    fn run(
+        &self,
+        feature_args: &[String],
+        feature_desc: &str,
+    ) -> Result<RawRunOutput, GetdocError> {
+        let output = self.source.run(feature_args, feature_desc)?;
+        *self.last_output.borrow_mut() = Some(output.clone());
+        Ok(output)
+    }
+}
+
+/// Serves a cached output as if cargo had just produced it.
+pub(crate) struct CachedSource(pub(crate) RawRunOutput);
+
+impl DiagnosticsSource for CachedSource {
+    fn run(
+        &self,
+        _feature_args: &[String],
+        _feature_desc: &str,
+    ) -> Result<RawRunOutput, GetdocError> {
+        Ok(self.0.clone())
+    }
+}
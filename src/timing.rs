@@ -0,0 +1,74 @@
+// getdoc - timing.rs
+//
+// Wall-clock time spent in each phase of a run (planning, cargo runs, JSON processing,
+// extraction, report writing), for the end-of-run summary and `--timings`.
+
+// --- Standard Library Imports ---
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// --- Crate Imports ---
+use crate::report::{PhaseTiming, format_duration};
+
+/// Adds up the time spent in each named phase. Recording takes `&self`, so extraction
+/// workers can share one timer; phases are listed in the order first recorded.
+#[derive(Default)]
+pub(crate) struct PhaseTimer {
+    phases: Mutex<Vec<PhaseTiming>>,
+}
+
+impl PhaseTimer {
+    /// Runs `f` and adds its wall-clock time to `phase`.
+    pub(crate) fn time<T>(&self, phase: &str, f: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = f();
+        self.record(phase, started.elapsed());
+        result
+    }
+
+    /// Adds one occurrence of `phase` that took `duration`.
+    pub(crate) fn record(&self, phase: &str, duration: Duration) {
+        let mut phases = self.phases.lock().unwrap_or_else(|e| e.into_inner());
+        match phases.iter_mut().find(|timing| timing.phase == phase) {
+            Some(timing) => {
+                timing.count += 1;
+                timing.total += duration;
+                timing.longest = timing.longest.max(duration);
+            }
+            None => phases.push(PhaseTiming {
+                phase: phase.to_string(),
+                count: 1,
+                total: duration,
+                longest: duration,
+            }),
+        }
+    }
+
+    /// The phases recorded so far.
+    pub(crate) fn phases(&self) -> Vec<PhaseTiming> {
+        self.phases
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// One line for the terminal, e.g. `cargo check 3× 4.2s, JSON processing 12ms`.
+    pub(crate) fn summary(&self) -> String {
+        self.phases()
+            .iter()
+            .map(|timing| {
+                if timing.count > 1 {
+                    format!(
+                        "{} {}× {}",
+                        timing.phase,
+                        timing.count,
+                        format_duration(timing.total)
+                    )
+                } else {
+                    format!("{} {}", timing.phase, format_duration(timing.total))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
@@ -0,0 +1,57 @@
+// getdoc - util.rs
+//
+// Small helpers shared by several modules.
+
+// --- Standard Library Imports ---
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+/// A 64-bit FNV-1a hasher. Unlike `DefaultHasher`, its algorithm is fixed, so the hash of
+/// bytes written to it directly never changes between Rust releases or platforms. Values
+/// hashed through their `Hash` impls (as by [`stable_hash`]) are only stable under one
+/// toolchain and platform, since std does not fix how those impls encode a value as bytes.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> StableHasher {
+        StableHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Hashes `value` with a [`StableHasher`]. Only compare the result with hashes made by the
+/// same toolchain; see [`stable_bytes_hash`] for hashes kept beyond that.
+pub(crate) fn stable_hash(value: &impl Hash) -> u64 {
+    let mut hasher = StableHasher::default();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Hashes `bytes` alone with a [`StableHasher`], which gives the same result everywhere.
+pub(crate) fn stable_bytes_hash(bytes: &[u8]) -> u64 {
+    let mut hasher = StableHasher::default();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+/// `a`, `b`, `c` with each item in backticks.
+pub(crate) fn quoted_list(items: impl IntoIterator<Item = impl fmt::Display>) -> String {
+    items
+        .into_iter()
+        .map(|item| format!("`{}`", item))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
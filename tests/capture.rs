@@ -0,0 +1,16 @@
+// `--emit-raw-json` captures are named after their feature set plus a hash of its bytes,
+// which stays the same under every toolchain, so archived captures keep their names.
+
+use getdoc::capture::capture_stem;
+
+#[test]
+fn capture_names_are_fixed_across_toolchains() {
+    assert_eq!(
+        capture_stem("--features serde/std"),
+        "features_serde_std-edcd5bf7"
+    );
+    assert_eq!(
+        capture_stem("--features serde_std"),
+        "features_serde_std-7a859d47"
+    );
+}
@@ -0,0 +1,99 @@
+// Command-line parsing, as `getdoc` and as the cargo subcommand `cargo getdoc`, and what
+// `--debug-json` shows of cargo output that cannot be parsed.
+
+use clap::Parser;
+use getdoc::Options;
+
+#[test]
+fn cargo_subcommand_arguments_parse_like_direct_ones() {
+    let direct = Options::from_args(["getdoc", "--features", "foo,bar", "--force"]);
+    // `cargo getdoc --features foo,bar --force` runs this:
+    let subcommand =
+        Options::from_args(["cargo-getdoc", "getdoc", "--features", "foo,bar", "--force"]);
+
+    assert_eq!(
+        direct.features,
+        Some(vec!["foo".to_string(), "bar".to_string()])
+    );
+    assert_eq!(format!("{:?}", subcommand), format!("{:?}", direct));
+}
+
+#[test]
+fn cargo_config_overrides_are_collected_and_validated() {
+    let options = Options::from_args([
+        "getdoc",
+        "--cargo-config",
+        "net.git-fetch-with-cli=true",
+        "--cargo-config",
+        "build.jobs = 2",
+    ]);
+    assert_eq!(
+        options.cargo_config,
+        vec!["net.git-fetch-with-cli=true", "build.jobs = 2"]
+    );
+
+    for invalid in [
+        "no-equals-sign",
+        "=true",
+        "net.offline=",
+        "profile.dev.opt-level=fast",
+    ] {
+        assert!(
+            Options::try_parse_from(["getdoc", "--cargo-config", invalid]).is_err(),
+            "`{}` was accepted",
+            invalid
+        );
+    }
+}
+
+#[test]
+fn malformed_json_lines_are_skipped_and_shown_with_debug_json() {
+    let dir = std::env::temp_dir().join(format!("getdoc-debug-json-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let messages = std::fs::read_to_string("tests/fixtures/diagnostics/no-default.json").unwrap();
+    // A `package_id` of the wrong type, as a changed message format would produce.
+    let malformed = r#"{"reason": "compiler-message", "package_id": 7}"#;
+    let input = dir.join("messages.json");
+    std::fs::write(&input, format!("{}\n{}", malformed, messages)).unwrap();
+    let getdoc = |extra_args: &[&str]| {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_getdoc"))
+            .arg("--input")
+            .arg(&input)
+            .args(["--format", "short"])
+            .args(extra_args)
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        (
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+
+    let (quiet_stdout, quiet_stderr) = getdoc(&[]);
+    let (stdout, stderr) = getdoc(&["--debug-json"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // The rest of the output is read either way.
+    for output in [&quiet_stdout, &stdout] {
+        assert!(
+            output.contains("error[E0308] src/lib.rs:3: mismatched types"),
+            "{}",
+            output
+        );
+        assert!(output.contains("error[E0425] src/lib.rs:7"), "{}", output);
+    }
+    assert!(!quiet_stderr.contains(malformed), "{}", quiet_stderr);
+    assert!(
+        stderr.contains("--debug-json: Could not parse line 1 of the output"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("invalid type: integer `7`, expected a string"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains(malformed), "{}", stderr);
+}
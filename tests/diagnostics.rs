@@ -0,0 +1,1172 @@
+// Replays saved `cargo check --message-format=json` output through the diagnostics pipeline.
+// The fixtures implicate a build-script output file under `tests/fixtures/diagnostics/build/`.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use getdoc::cargo::CheckRunResult;
+use getdoc::diagnostics::normalize_cargo_home_paths;
+use getdoc::report::write_markdown_report;
+use getdoc::{
+    Applicability, DiagnosticCounts, DiagnosticSort, DiagnosticsSource, DisplayableDiagnostic,
+    GetdocError, JsonFile, Location, Options, RawJsonDir, RawRunOutput, Report, check_feature_set,
+    consolidate_diagnostics, merge_repeated_runs, record_absent_feature_sets, sort_diagnostics,
+    suppress_diagnostics,
+};
+
+const FIXTURE_DIR: &str = "tests/fixtures/diagnostics";
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(FIXTURE_DIR).join(name)
+}
+
+/// Serves a different saved run per feature selection, like a matrix of CI logs.
+struct FixtureMatrix;
+
+impl DiagnosticsSource for FixtureMatrix {
+    fn run(
+        &self,
+        feature_args: &[String],
+        feature_desc: &str,
+    ) -> Result<RawRunOutput, GetdocError> {
+        let name = match feature_args {
+            [] => "default.json",
+            [flag] if flag == "--no-default-features" => "no-default.json",
+            _ => panic!("no fixture for feature set '{}'", feature_desc),
+        };
+        JsonFile {
+            path: fixture(name),
+        }
+        .run(feature_args, feature_desc)
+    }
+}
+
+/// Checks both feature sets of [`FixtureMatrix`], default features first.
+fn run_fixture_matrix() -> Vec<(String, CheckRunResult)> {
+    [
+        (vec![], "default features"),
+        (
+            vec!["--no-default-features".to_string()],
+            "--no-default-features",
+        ),
+    ]
+    .into_iter()
+    .map(|(args, desc)| {
+        let result = check_feature_set(&FixtureMatrix, &args, desc).expect("replay failed");
+        (desc.to_string(), result)
+    })
+    .collect()
+}
+
+#[test]
+fn replayed_run_collects_displayable_diagnostics() {
+    let source = JsonFile {
+        path: fixture("default.json"),
+    };
+    let (diagnostics, implicated_files, referencers) =
+        check_feature_set(&source, &[], "default features").expect("replay failed");
+
+    // The note child is not displayable, and non-diagnostic or malformed lines are skipped.
+    let levels: Vec<&str> = diagnostics.iter().map(|d| d.level.as_str()).collect();
+    assert_eq!(levels, ["error", "warning"]);
+
+    let mismatch = &diagnostics[0];
+    assert_eq!(mismatch.code.as_deref(), Some("E0308"));
+    assert_eq!(mismatch.message, "mismatched types");
+    assert_eq!(
+        mismatch.primary_location_of_diagnostic,
+        Some(Location {
+            file: PathBuf::from("src/lib.rs"),
+            line: 3,
+            column: None,
+            is_primary: true,
+        })
+    );
+    assert!(mismatch.code_explanation.is_some());
+    // Two labels on the same line of the generated file are listed once.
+    let details: Vec<&str> = mismatch
+        .implicated_third_party_files_details
+        .iter()
+        .map(|(_, detail)| detail.as_str())
+        .collect();
+    assert_eq!(details, ["gen.rs:2"]);
+    // The `help` child's fix is carried along with its applicability.
+    let suggestions: Vec<(&str, &str, Applicability)> = mismatch
+        .suggestions
+        .iter()
+        .map(|s| (s.location.as_str(), s.replacement.as_str(), s.applicability))
+        .collect();
+    assert_eq!(
+        suggestions,
+        [("src/lib.rs:3:11", "&", Applicability::MaybeIncorrect)]
+    );
+    assert_eq!(mismatch.suggestions[0].original_code, "    takes(s);");
+    assert_eq!(mismatch.suggestions[0].fixed_code, "    takes(&s);");
+    assert!(
+        diagnostics[1]
+            .implicated_third_party_files_details
+            .is_empty()
+    );
+
+    let generated_file = std::fs::canonicalize(fixture("build/gen-0123abcd/out/gen.rs")).unwrap();
+    assert_eq!(
+        implicated_files
+            .get(&generated_file)
+            .map(|lines| lines.iter().copied().collect::<Vec<_>>()),
+        Some(vec![2])
+    );
+    let origins = &referencers[&generated_file];
+    assert!(
+        origins
+            .iter()
+            .all(|origin| origin.feature_set_desc == "default features")
+    );
+}
+
+#[test]
+fn identical_diagnostics_are_consolidated_across_feature_sets() {
+    let runs: Vec<_> = run_fixture_matrix()
+        .into_iter()
+        .map(|(desc, (diagnostics, _, _))| (desc, diagnostics))
+        .collect();
+
+    let (consolidated, explanations) = consolidate_diagnostics(&runs);
+
+    // Sorted by location: the shared E0308, the default-only warning, the no-default E0425.
+    let summary: Vec<(String, Option<&str>, Vec<&str>)> = consolidated
+        .iter()
+        .map(|d| {
+            (
+                d.primary_location.as_ref().unwrap().to_string(),
+                d.code.as_deref(),
+                d.feature_set_descriptors
+                    .iter()
+                    .map(String::as_str)
+                    .collect(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (
+                "src/lib.rs:3".to_string(),
+                Some("E0308"),
+                vec!["--no-default-features", "default features"]
+            ),
+            ("src/lib.rs:5".to_string(), None, vec!["default features"]),
+            (
+                "src/lib.rs:7".to_string(),
+                Some("E0425"),
+                vec!["--no-default-features"]
+            ),
+        ]
+    );
+
+    let mut explained_codes: Vec<&str> = explanations.keys().map(String::as_str).collect();
+    explained_codes.sort();
+    assert_eq!(explained_codes, ["E0308", "E0425"]);
+
+    // Every diagnostic has its own ID, and replaying the same output gives the same IDs.
+    let ids: BTreeSet<&str> = consolidated.iter().map(|d| d.id.as_str()).collect();
+    assert_eq!(ids.len(), 3);
+    assert!(
+        ids.iter()
+            .all(|id| id.len() == 16 && id.chars().all(|c| c.is_ascii_hexdigit()))
+    );
+    let (replayed, _) = consolidate_diagnostics(&runs);
+    assert_eq!(replayed[0].id, consolidated[0].id);
+}
+
+#[test]
+fn diagnostics_know_the_checked_feature_sets_they_are_absent_under() {
+    let runs: Vec<_> = run_fixture_matrix()
+        .into_iter()
+        .map(|(desc, (diagnostics, _, _))| (desc, diagnostics))
+        .collect();
+    let (mut consolidated, _) = consolidate_diagnostics(&runs);
+    // `--all-features` was checked too and came out clean.
+    let checked = [
+        "default features",
+        "--no-default-features",
+        "--all-features",
+    ]
+    .map(String::from);
+    record_absent_feature_sets(&mut consolidated, &checked);
+
+    let absent: Vec<(Option<&str>, Vec<&str>)> = consolidated
+        .iter()
+        .map(|d| {
+            (
+                d.code.as_deref(),
+                d.absent_feature_set_descriptors
+                    .iter()
+                    .map(String::as_str)
+                    .collect(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        absent,
+        [
+            (Some("E0308"), vec!["--all-features"]),
+            (None, vec!["--all-features", "--no-default-features"]),
+            (Some("E0425"), vec!["--all-features", "default features"]),
+        ]
+    );
+    assert!(consolidated.iter().all(|d| !d.is_feature_independent()));
+
+    // Without the clean set, the shared E0308 occurred under everything that was checked.
+    record_absent_feature_sets(&mut consolidated, &checked[..2]);
+    let independent: Vec<Option<&str>> = consolidated
+        .iter()
+        .filter(|d| d.is_feature_independent())
+        .map(|d| d.code.as_deref())
+        .collect();
+    assert_eq!(independent, [Some("E0308")]);
+}
+
+#[test]
+fn diagnostic_counts_separate_third_party_from_first_party_diagnostics() {
+    let mut runs: Vec<_> = run_fixture_matrix()
+        .into_iter()
+        .map(|(desc, (diagnostics, _, _))| (desc, diagnostics))
+        .collect();
+
+    // Counted per feature set: the E0308 into the generated file under both, the warning
+    // and the E0425 in `src/lib.rs` under one each.
+    assert_eq!(
+        DiagnosticCounts::of(&runs),
+        DiagnosticCounts {
+            reported: 4,
+            implicating_third_party: 2,
+        }
+    );
+
+    // A failed build script is a `TOOL_ERROR`, not a compiler diagnostic.
+    let (failure, _, _) =
+        check_feature_set(&FailedBuildScript, &[], "default features").expect("check failed");
+    runs.push(("--all-features".to_string(), failure));
+    assert_eq!(DiagnosticCounts::of(&runs).reported, 4);
+}
+
+#[test]
+fn consolidated_diagnostics_can_be_sorted_by_level_and_count() {
+    let runs: Vec<_> = run_fixture_matrix()
+        .into_iter()
+        .map(|(desc, (diagnostics, _, _))| (desc, diagnostics))
+        .collect();
+    let (consolidated, _) = consolidate_diagnostics(&runs);
+    let sorted_locations = |sort| -> Vec<String> {
+        let mut diagnostics = consolidated.clone();
+        sort_diagnostics(&mut diagnostics, sort);
+        diagnostics
+            .iter()
+            .map(|d| d.primary_location.as_ref().unwrap().to_string())
+            .collect()
+    };
+
+    // Consolidation sorts by location: E0308, the warning, E0425.
+    assert_eq!(
+        sorted_locations(DiagnosticSort::Location),
+        ["src/lib.rs:3", "src/lib.rs:5", "src/lib.rs:7"]
+    );
+    // Errors first; the two errors keep their location order.
+    assert_eq!(
+        sorted_locations(DiagnosticSort::Level),
+        ["src/lib.rs:3", "src/lib.rs:7", "src/lib.rs:5"]
+    );
+    // Only the E0308 occurs under both feature sets; the tie keeps the location order.
+    assert_eq!(
+        sorted_locations(DiagnosticSort::Count),
+        ["src/lib.rs:3", "src/lib.rs:5", "src/lib.rs:7"]
+    );
+    // The warning has no code and goes last.
+    assert_eq!(
+        sorted_locations(DiagnosticSort::Code),
+        ["src/lib.rs:3", "src/lib.rs:7", "src/lib.rs:5"]
+    );
+}
+
+#[test]
+fn missing_replay_file_is_an_input_error() {
+    let source = JsonFile {
+        path: fixture("missing.json"),
+    };
+    match check_feature_set(&source, &[], "pre-captured input") {
+        Err(GetdocError::ReadInput { path, .. }) => assert_eq!(path, fixture("missing.json")),
+        other => panic!("expected a ReadInput error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn emitted_raw_json_directories_replay_each_captured_run() {
+    let captures = RawJsonDir::open(Path::new("tests/fixtures/raw-json")).expect("open failed");
+
+    // Replayed in the order they ran, which is not the order of their file names.
+    let feature_sets = captures.feature_sets();
+    let descriptions: Vec<&str> = feature_sets
+        .iter()
+        .map(|set| set.description.as_str())
+        .collect();
+    assert_eq!(descriptions, ["--no-default-features", "default features"]);
+    assert_eq!(feature_sets[0].args, ["--no-default-features"]);
+
+    for feature_set in &feature_sets {
+        let replayed = captures
+            .run(&feature_set.args, &feature_set.description)
+            .expect("replay failed");
+        let original = FixtureMatrix
+            .run(&feature_set.args, &feature_set.description)
+            .unwrap();
+        assert_eq!(replayed.json_messages, original.json_messages);
+    }
+    let stderr = captures.run(&[], "default features").unwrap().stderr;
+    assert!(stderr.contains("could not compile `fixture`"), "{}", stderr);
+    assert!(matches!(
+        captures.run(&[], "--all-features"),
+        Err(GetdocError::ReadInput { .. })
+    ));
+
+    let empty = std::env::temp_dir().join(format!("getdoc-raw-json-{}", std::process::id()));
+    std::fs::create_dir_all(&empty).unwrap();
+    let opened = RawJsonDir::open(&empty);
+    std::fs::remove_dir_all(&empty).unwrap();
+    assert!(matches!(opened, Err(GetdocError::ReadInput { path, .. }) if path == empty));
+}
+
+#[test]
+fn clearing_captures_only_removes_getdoc_sidecars_and_their_own_messages() {
+    let base = std::env::temp_dir().join(format!("getdoc-clear-captures-{}", std::process::id()));
+    let dir = base.join("captures");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(&dir).unwrap();
+    let sidecar = |feature_set: &str, messages_file: &str| {
+        format!(
+            r#"{{"feature_set":"{}","feature_args":[],"messages_file":"{}","started_at":"2026-01-05T10:00:00Z","exit_code":0,"stderr":""}}"#,
+            feature_set, messages_file
+        )
+    };
+    std::fs::write(dir.join("run.json"), "").unwrap();
+    std::fs::write(
+        dir.join("run.meta.json"),
+        sidecar("default features", "run.json"),
+    )
+    .unwrap();
+    std::fs::write(base.join("outside.json"), "keep").unwrap();
+    std::fs::write(
+        dir.join("escape.meta.json"),
+        sidecar("escape", "../outside.json"),
+    )
+    .unwrap();
+
+    // A crafted sidecar cannot make a replay read a file outside the directory.
+    let replay = RawJsonDir::open(&dir).unwrap();
+    assert!(matches!(
+        replay.run(&[], "escape"),
+        Err(GetdocError::ReadInput { .. })
+    ));
+
+    // Nor can clearing the captures delete it, or a `.meta.json` that is not a sidecar.
+    std::fs::write(dir.join("unrelated.meta.json"), "not a capture").unwrap();
+    let removed = getdoc::capture::clear_captures(&dir).unwrap();
+    let mut left: Vec<String> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    left.sort();
+    let outside = std::fs::read_to_string(base.join("outside.json"));
+    std::fs::remove_dir_all(&base).unwrap();
+    assert_eq!(removed, 2);
+    assert_eq!(left, ["unrelated.meta.json"]);
+    assert_eq!(outside.unwrap(), "keep");
+}
+
+/// A run whose build script failed: no JSON diagnostics, only cargo's plain-text stderr.
+struct FailedBuildScript;
+
+impl DiagnosticsSource for FailedBuildScript {
+    fn run(
+        &self,
+        _feature_args: &[String],
+        _feature_desc: &str,
+    ) -> Result<RawRunOutput, GetdocError> {
+        Ok(RawRunOutput {
+            json_messages: String::new(),
+            stderr: "   Compiling openssl-sys v0.9.102\n\
+                     error: failed to run custom build command for `openssl-sys v0.9.102`\n\
+                     \n\
+                     Caused by:\n\
+                     \x20 --- stderr\n\
+                     \x20 The system library `openssl` required by crate `openssl-sys` was not found.\n\
+                     \x20 /usr/bin/ld: cannot find -lcrypto: No such file or directory\n"
+                .to_string(),
+        })
+    }
+}
+
+#[test]
+fn build_script_failure_names_missing_native_libraries() {
+    let (diagnostics, _, _) =
+        check_feature_set(&FailedBuildScript, &[], "default features").expect("check failed");
+
+    assert_eq!(diagnostics.len(), 1);
+    let failure = &diagnostics[0];
+    assert_eq!(failure.level, "TOOL_ERROR");
+    assert_eq!(
+        failure.message,
+        "native libraries not found: openssl, crypto"
+    );
+    assert_eq!(
+        failure.rendered.lines().collect::<Vec<_>>(),
+        [
+            ">>> native libraries not found: openssl, crypto",
+            "error: failed to run custom build command for `openssl-sys v0.9.102`",
+            "  The system library `openssl` required by crate `openssl-sys` was not found.",
+            "  /usr/bin/ld: cannot find -lcrypto: No such file or directory",
+        ]
+    );
+}
+
+#[test]
+fn doctest_errors_are_attributed_to_the_containing_file() {
+    let source = JsonFile {
+        path: fixture("doctest.json"),
+    };
+    let (diagnostics, _, _) =
+        check_feature_set(&source, &[], "default features").expect("replay failed");
+
+    // Line 3 of the doctest whose fence is on line 42 of `src/lib.rs`.
+    assert_eq!(
+        diagnostics[0]
+            .primary_location_of_diagnostic
+            .as_ref()
+            .unwrap()
+            .to_string(),
+        "src/lib.rs:45"
+    );
+    assert_eq!(diagnostics[0].suggestions[0].location, "src/lib.rs:45:1");
+}
+
+#[test]
+fn color_codes_are_stripped_from_rendered_messages() {
+    let source = JsonFile {
+        path: fixture("ansi.json"),
+    };
+    let (diagnostics, _, _) =
+        check_feature_set(&source, &[], "default features").expect("replay failed");
+
+    // Captured with `--message-format=json-diagnostic-rendered-ansi`: colors and a hyperlink.
+    assert_eq!(
+        diagnostics[0].rendered,
+        "error[E0425]: cannot find value `y` in this scope\n --> src/lib.rs:7:5"
+    );
+}
+
+/// A colored message whose note points into a registry crate, the path right after a color
+/// code as rustc writes it.
+struct ColoredRegistryNote;
+
+impl ColoredRegistryNote {
+    const RENDERED: &str = "\u{1b}[1m\u{1b}[38;5;9merror[E0061]\u{1b}[0m: this function takes 0 arguments\n\u{1b}[1m\u{1b}[38;5;10mnote\u{1b}[0m: defined here\n  \u{1b}[1m\u{1b}[38;5;12m--> \u{1b}[0m/home/ci/.cargo/registry/src/index.crates.io-6f17d22bba15001f/home-0.5.12/src/lib.rs:65:8\n";
+}
+
+impl DiagnosticsSource for ColoredRegistryNote {
+    fn run(&self, _: &[String], _: &str) -> Result<RawRunOutput, GetdocError> {
+        let message = serde_json::json!({
+            "reason": "compiler-message",
+            "message": {
+                "code": {"code": "E0061", "explanation": null},
+                "level": "error",
+                "message": "this function takes 0 arguments",
+                "spans": [
+                    {"file_name": "src/main.rs", "is_primary": true, "line_start": 2, "line_end": 2, "column_start": 5, "column_end": 20}
+                ],
+                "children": [],
+                "rendered": ColoredRegistryNote::RENDERED
+            }
+        });
+        Ok(RawRunOutput {
+            json_messages: message.to_string(),
+            stderr: String::new(),
+        })
+    }
+}
+
+#[test]
+fn cargo_home_paths_are_normalized_next_to_color_codes() {
+    let (diagnostics, _, _) =
+        check_feature_set(&ColoredRegistryNote, &[], "default features").expect("check failed");
+    assert_eq!(
+        diagnostics[0].rendered,
+        "error[E0061]: this function takes 0 arguments\nnote: defined here\n  --> $CARGO_HOME/registry/src/index.crates.io-*/home-0.5.12/src/lib.rs:65:8"
+    );
+
+    // With `--keep-ansi` the color codes stay, and the path is normalized all the same.
+    let kept = normalize_cargo_home_paths(ColoredRegistryNote::RENDERED);
+    assert!(
+        kept.ends_with(
+            "--> \u{1b}[0m$CARGO_HOME/registry/src/index.crates.io-*/home-0.5.12/src/lib.rs:65:8\n"
+        ),
+        "{:?}",
+        kept
+    );
+    assert!(kept.starts_with("\u{1b}[1m\u{1b}[38;5;9merror[E0061]\u{1b}[0m: "));
+}
+
+/// A run implicating a registry crate whose unpacked sources have since been pruned.
+struct PrunedRegistry;
+
+impl PrunedRegistry {
+    fn missing_file() -> PathBuf {
+        home::cargo_home()
+            .unwrap()
+            .join("registry/src/index.example-0000000000000000/pruned-crate-0.1.0/src/lib.rs")
+    }
+}
+
+impl DiagnosticsSource for PrunedRegistry {
+    fn run(
+        &self,
+        _feature_args: &[String],
+        _feature_desc: &str,
+    ) -> Result<RawRunOutput, GetdocError> {
+        let message = serde_json::json!({
+            "reason": "compiler-message",
+            "message": {
+                "code": null,
+                "level": "error",
+                "message": "this function takes 0 arguments but 1 argument was supplied",
+                "spans": [
+                    {"file_name": "src/main.rs", "is_primary": true, "line_start": 2, "line_end": 2, "column_start": 5, "column_end": 20},
+                    {"file_name": PrunedRegistry::missing_file(), "is_primary": false, "line_start": 65, "line_end": 65, "column_start": 8, "column_end": 16}
+                ],
+                "children": [],
+                "rendered": "error[E0061]: this function takes 0 arguments but 1 argument was supplied\n"
+            }
+        });
+        Ok(RawRunOutput {
+            json_messages: message.to_string(),
+            stderr: String::new(),
+        })
+    }
+}
+
+#[test]
+fn registry_files_missing_from_disk_are_still_implicated() {
+    let (diagnostics, implicated_files, _) =
+        check_feature_set(&PrunedRegistry, &[], "default features").expect("check failed");
+
+    let missing_file = PrunedRegistry::missing_file();
+    assert!(!missing_file.exists());
+    assert_eq!(
+        implicated_files
+            .get(&missing_file)
+            .map(|lines| lines.iter().copied().collect::<Vec<_>>()),
+        Some(vec![65])
+    );
+    assert_eq!(
+        diagnostics[0].implicated_third_party_files_details,
+        [(missing_file, "lib.rs:65".to_string())]
+    );
+}
+
+/// One error whose spans cover many lines of a pruned registry file, listed in a different
+/// order for `--no-default-features`.
+struct ManySpans;
+
+impl DiagnosticsSource for ManySpans {
+    fn run(
+        &self,
+        feature_args: &[String],
+        _feature_desc: &str,
+    ) -> Result<RawRunOutput, GetdocError> {
+        let mut lines = vec![210, 211, 212, 230, 250, 252, 270, 290, 310, 330];
+        if !feature_args.is_empty() {
+            lines.reverse();
+        }
+        let mut spans = vec![
+            serde_json::json!({"file_name": "src/main.rs", "is_primary": true, "line_start": 2, "line_end": 2, "column_start": 5, "column_end": 20}),
+        ];
+        spans.extend(lines.into_iter().map(|line| {
+            serde_json::json!({"file_name": PrunedRegistry::missing_file(), "is_primary": false, "line_start": line, "line_end": line, "column_start": 8, "column_end": 16})
+        }));
+        let message = serde_json::json!({
+            "reason": "compiler-message",
+            "message": {
+                "code": null,
+                "level": "error",
+                "message": "mismatched types",
+                "spans": spans,
+                "children": [],
+                "rendered": "error[E0308]: mismatched types\n"
+            }
+        });
+        Ok(RawRunOutput {
+            json_messages: message.to_string(),
+            stderr: String::new(),
+        })
+    }
+}
+
+#[test]
+fn nearby_implicated_lines_merge_into_ranges_and_long_lists_are_capped() {
+    let runs: Vec<_> = [
+        (vec![], "default features"),
+        (
+            vec!["--no-default-features".to_string()],
+            "--no-default-features",
+        ),
+    ]
+    .into_iter()
+    .map(|(args, desc)| {
+        let (diagnostics, _, _) = check_feature_set(&ManySpans, &args, desc).expect("check failed");
+        (desc.to_string(), diagnostics)
+    })
+    .collect();
+
+    let details: Vec<&str> = runs[0].1[0]
+        .implicated_third_party_files_details
+        .iter()
+        .map(|(_, detail)| detail.as_str())
+        .collect();
+    assert_eq!(
+        details,
+        [
+            "lib.rs:210-212",
+            "lib.rs:230",
+            "lib.rs:250-252",
+            "lib.rs:270",
+            "lib.rs:290",
+            "lib.rs:310",
+            "lib.rs:330"
+        ]
+    );
+
+    // The span order does not matter: both runs consolidate into one diagnostic.
+    let (consolidated, _) = consolidate_diagnostics(&runs);
+    assert_eq!(consolidated.len(), 1);
+    assert_eq!(consolidated[0].feature_set_descriptors.len(), 2);
+
+    let render = |collapse: bool| {
+        let report = Report {
+            consolidated_diagnostics: consolidated.clone(),
+            ..Default::default()
+        };
+        let options = if collapse {
+            Options::from_args(["getdoc", "--collapse-implicated-lines"])
+        } else {
+            Options::from_args(["getdoc"])
+        };
+        let mut markdown = Vec::new();
+        write_markdown_report(&report, &options, &mut markdown).unwrap();
+        String::from_utf8(markdown)
+            .unwrap()
+            .lines()
+            .find(|line| line.contains("(Implicates:"))
+            .unwrap()
+            .trim()
+            .to_string()
+    };
+    assert_eq!(
+        render(false),
+        "(Implicates: `lib.rs` (at `lib.rs:210-212`), `lib.rs` (at `lib.rs:230`), `lib.rs` (at `lib.rs:250-252`), `lib.rs` (at `lib.rs:270`), `lib.rs` (at `lib.rs:290`), and 2 more locations in `lib.rs` - see details below if extracted)"
+    );
+    assert_eq!(
+        render(true),
+        "(Implicates: `lib.rs` (at `lib.rs:210-212, 230, 250-252, 270, 290` and 2 more locations) - see details below if extracted)"
+    );
+}
+
+/// A workspace run: a warning from a member's binary, one from a dependency whose message
+/// names only its package, and a final `build-finished` saying whether the build succeeded.
+struct Workspace {
+    success: bool,
+}
+
+impl DiagnosticsSource for Workspace {
+    fn run(
+        &self,
+        _feature_args: &[String],
+        _feature_desc: &str,
+    ) -> Result<RawRunOutput, GetdocError> {
+        let warning = |line: usize| {
+            serde_json::json!({
+                "code": null,
+                "level": "warning",
+                "message": "unused variable: `x`",
+                "spans": [
+                    {"file_name": "src/main.rs", "is_primary": true, "line_start": line, "line_end": line, "column_start": 9, "column_end": 10}
+                ],
+                "children": [],
+                "rendered": format!("warning: unused variable: `x` (line {})\n", line)
+            })
+        };
+        let messages = [
+            serde_json::json!({
+                "reason": "compiler-message",
+                "package_id": "registry+https://github.com/rust-lang/crates.io-index#home@0.5.12",
+                "message": warning(1)
+            }),
+            serde_json::json!({
+                "reason": "compiler-artifact",
+                "package_id": "registry+https://github.com/rust-lang/crates.io-index#home@0.5.12",
+                "target": {"name": "home", "kind": ["lib"]}
+            }),
+            serde_json::json!({
+                "reason": "compiler-message",
+                "package_id": "path+file:///work/my-app#0.1.0",
+                "target": {"name": "server", "kind": ["bin"]},
+                "message": warning(2)
+            }),
+            serde_json::json!({"reason": "build-finished", "success": self.success}),
+        ];
+        let json_messages: Vec<String> = messages.iter().map(ToString::to_string).collect();
+        Ok(RawRunOutput {
+            json_messages: json_messages.join("\n"),
+            stderr: "error: linking with `cc` was interrupted\n".to_string(),
+        })
+    }
+}
+
+#[test]
+fn diagnostics_name_their_crate_and_build_finished_decides_failure() {
+    let (diagnostics, _, _) =
+        check_feature_set(&Workspace { success: true }, &[], "default").expect("check failed");
+    let producing_crates: Vec<Option<&str>> = diagnostics
+        .iter()
+        .map(|d| d.producing_crate.as_deref())
+        .collect();
+    assert_eq!(
+        producing_crates,
+        [
+            Some("home v0.5.12 (lib)"),
+            Some("my-app v0.1.0 (bin server)")
+        ]
+    );
+
+    // The report names them, since they differ.
+    let (consolidated_diagnostics, _) =
+        consolidate_diagnostics(&[("default".to_string(), diagnostics)]);
+    let report = Report {
+        consolidated_diagnostics,
+        ..Default::default()
+    };
+    let mut markdown = Vec::new();
+    write_markdown_report(&report, &Options::from_args(["getdoc"]), &mut markdown).unwrap();
+    let markdown = String::from_utf8(markdown).unwrap();
+    assert!(markdown.contains("    (Emitted while compiling my-app v0.1.0 (bin server))"));
+
+    // Warnings only, but cargo says the build failed: the run is not reported as clean.
+    let (diagnostics, _, _) =
+        check_feature_set(&Workspace { success: false }, &[], "default").expect("check failed");
+    let failure = diagnostics.last().unwrap();
+    assert_eq!(failure.level, "TOOL_ERROR");
+    assert!(
+        failure
+            .rendered
+            .ends_with("\nerror: linking with `cc` was interrupted"),
+        "{}",
+        failure.rendered
+    );
+}
+
+/// A package whose library and binary both declare `mod shared;`, in messages shaped exactly
+/// as cargo writes them: the same warning comes from both targets.
+struct SharedModule;
+
+impl DiagnosticsSource for SharedModule {
+    fn run(&self, _: &[String], _: &str) -> Result<RawRunOutput, GetdocError> {
+        let target = |kind: &str, name: &str, src_path: &str| {
+            serde_json::json!({
+                "kind": [kind],
+                "crate_types": [kind],
+                "name": name,
+                "src_path": src_path,
+                "edition": "2021",
+                "doc": true,
+                "doctest": kind == "lib",
+                "test": true
+            })
+        };
+        let message = |target: serde_json::Value| {
+            serde_json::json!({
+                "reason": "compiler-message",
+                "package_id": "path+file:///work/my-app#0.1.0",
+                "manifest_path": "/work/my-app/Cargo.toml",
+                "target": target,
+                "message": {
+                    "$message_type": "diagnostic",
+                    "code": {"code": "dead_code", "explanation": null},
+                    "level": "warning",
+                    "message": "function `helper` is never used",
+                    "spans": [
+                        {"file_name": "src/shared.rs", "byte_start": 3, "byte_end": 9, "is_primary": true, "line_start": 1, "line_end": 1, "column_start": 4, "column_end": 10, "label": null, "suggested_replacement": null, "suggestion_applicability": null, "expansion": null, "text": []}
+                    ],
+                    "children": [],
+                    "rendered": "warning: function `helper` is never used\n --> src/shared.rs:1:4\n"
+                }
+            })
+        };
+        let messages = [
+            message(target("lib", "my_app", "/work/my-app/src/lib.rs")),
+            message(target("bin", "my-app", "/work/my-app/src/main.rs")),
+            serde_json::json!({"reason": "build-finished", "success": true}),
+        ];
+        let json_messages: Vec<String> = messages.iter().map(ToString::to_string).collect();
+        Ok(RawRunOutput {
+            json_messages: json_messages.join("\n"),
+            stderr: String::new(),
+        })
+    }
+}
+
+#[test]
+fn a_diagnostic_from_several_targets_names_them_all() {
+    let (diagnostics, _, _) =
+        check_feature_set(&SharedModule, &[], "default").expect("check failed");
+    let producing_crates: Vec<Option<&str>> = diagnostics
+        .iter()
+        .map(|d| d.producing_crate.as_deref())
+        .collect();
+    // The library is named after the package; the binary, named like the package, is not.
+    assert_eq!(
+        producing_crates,
+        [
+            Some("my-app v0.1.0 (lib)"),
+            Some("my-app v0.1.0 (bin my-app)")
+        ]
+    );
+
+    let (consolidated_diagnostics, _) =
+        consolidate_diagnostics(&[("default".to_string(), diagnostics)]);
+    assert_eq!(consolidated_diagnostics.len(), 1);
+    assert_eq!(
+        consolidated_diagnostics[0].producing_crates,
+        BTreeSet::from([
+            "my-app v0.1.0 (bin my-app)".to_string(),
+            "my-app v0.1.0 (lib)".to_string()
+        ])
+    );
+    let report = Report {
+        consolidated_diagnostics,
+        ..Default::default()
+    };
+    let mut markdown = Vec::new();
+    write_markdown_report(&report, &Options::from_args(["getdoc"]), &mut markdown).unwrap();
+    let markdown = String::from_utf8(markdown).unwrap();
+    assert!(
+        markdown.contains(
+            "    (Emitted while compiling my-app v0.1.0 (bin my-app); my-app v0.1.0 (lib))"
+        )
+    );
+}
+
+#[test]
+fn repeated_runs_merge_into_each_diagnostic_once_and_report_the_flaky_ones() {
+    let warning = |line: usize| DisplayableDiagnostic {
+        level: "warning".to_string(),
+        code: Some("unused_variables".to_string()),
+        code_explanation: None,
+        message: "unused variable: `x`".to_string(),
+        rendered: format!("warning: unused variable: `x` (line {})", line),
+        primary_location_of_diagnostic: Some(Location {
+            file: PathBuf::from("src/lib.rs"),
+            line,
+            column: Some(9),
+            is_primary: true,
+        }),
+        implicated_third_party_files_details: Vec::new(),
+        suggestions: Vec::new(),
+        producing_crate: None,
+    };
+    // Line 1 comes out twice in the first run (e.g. from a lib and its tests), but only
+    // counts once for it; line 2 is in every run; line 3 is missing from the first.
+    let runs = vec![
+        vec![warning(1), warning(2), warning(1)],
+        vec![warning(2), warning(3)],
+        vec![warning(3), warning(1), warning(2)],
+    ];
+    let (merged, flaky) = merge_repeated_runs(runs, "default features");
+
+    let merged_lines: Vec<usize> = merged
+        .iter()
+        .map(|d| d.primary_location_of_diagnostic.as_ref().unwrap().line)
+        .collect();
+    assert_eq!(merged_lines, [1, 2, 3]);
+    let flaky: Vec<(usize, usize, usize, &str)> = flaky
+        .iter()
+        .map(|d| {
+            (
+                d.primary_location.as_ref().unwrap().line,
+                d.runs_seen,
+                d.runs,
+                d.feature_set_desc.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        flaky,
+        [(1, 2, 3, "default features"), (3, 2, 3, "default features")]
+    );
+}
+
+#[test]
+fn locations_sort_by_file_then_line() {
+    let location = |file: &str, line, is_primary| Location {
+        file: PathBuf::from(file),
+        line,
+        column: None,
+        is_primary,
+    };
+    let mut locations = [
+        location("src/lib.rs", 10, true),
+        location("src/lib.rs", 9, true),
+        location("src/a.rs", 100, false),
+    ];
+    locations.sort();
+
+    // Compared as strings, `src/lib.rs:10` would come before `src/lib.rs:9`.
+    let displayed: Vec<String> = locations.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        displayed,
+        [
+            "src/a.rs:100 (non-primary)",
+            "src/lib.rs:9",
+            "src/lib.rs:10"
+        ]
+    );
+}
+
+/// The same error as rendered on two machines with different cargo homes and registry
+/// index hashes: a laptop for default features, CI for `--no-default-features`.
+struct TwoMachines;
+
+impl DiagnosticsSource for TwoMachines {
+    fn run(
+        &self,
+        feature_args: &[String],
+        _feature_desc: &str,
+    ) -> Result<RawRunOutput, GetdocError> {
+        let registry = if feature_args.is_empty() {
+            "/home/me/.cargo/registry/src/index.crates.io-6f17d22bba15001f"
+        } else {
+            "/home/ci/.cargo/registry/src/index.crates.io-1949cf8c6b5b557f"
+        };
+        let message = serde_json::json!({
+            "reason": "compiler-message",
+            "message": {
+                "code": {"code": "E0277", "explanation": null},
+                "level": "error",
+                "message": "the trait bound `Foo: Serialize` is not satisfied",
+                "spans": [
+                    {"file_name": "src/lib.rs", "is_primary": true, "line_start": 4, "line_end": 4, "column_start": 5, "column_end": 9}
+                ],
+                "children": [],
+                "rendered": format!(
+                    "error[E0277]: the trait bound `Foo: Serialize` is not satisfied\nnote: required by a bound in `to_string`\n  --> {}/serde_json-1.0.140/src/ser.rs:2209:17\n",
+                    registry
+                )
+            }
+        });
+        Ok(RawRunOutput {
+            json_messages: message.to_string(),
+            stderr: String::new(),
+        })
+    }
+}
+
+#[test]
+fn cargo_home_paths_in_rendered_messages_do_not_split_consolidation() {
+    let runs: Vec<_> = [
+        (vec![], "default features"),
+        (
+            vec!["--no-default-features".to_string()],
+            "--no-default-features",
+        ),
+    ]
+    .into_iter()
+    .map(|(args, desc)| {
+        let (diagnostics, _, _) =
+            check_feature_set(&TwoMachines, &args, desc).expect("check failed");
+        (desc.to_string(), diagnostics)
+    })
+    .collect();
+
+    let (consolidated, _) = consolidate_diagnostics(&runs);
+
+    assert_eq!(consolidated.len(), 1);
+    assert!(
+        consolidated[0].rendered_message.ends_with(
+            "--> $CARGO_HOME/registry/src/index.crates.io-*/serde_json-1.0.140/src/ser.rs:2209:17"
+        ),
+        "{}",
+        consolidated[0].rendered_message
+    );
+    assert_eq!(consolidated[0].feature_set_descriptors.len(), 2);
+}
+
+/// An error in code a build script generated, as checked from two checkouts: one at the
+/// project directory, and one elsewhere, building into another target directory.
+struct TwoCheckouts;
+
+impl DiagnosticsSource for TwoCheckouts {
+    fn run(
+        &self,
+        feature_args: &[String],
+        _feature_desc: &str,
+    ) -> Result<RawRunOutput, GetdocError> {
+        let (out_dir, lib_rs) = if feature_args.is_empty() {
+            (
+                "/home/me/fixture/target/debug/build/fixture-0123456789abcdef/out".to_string(),
+                std::env::current_dir()
+                    .unwrap()
+                    .join("src/lib.rs")
+                    .display()
+                    .to_string(),
+            )
+        } else {
+            (
+                "/ci/target/release/build/fixture-fedcba9876543210/out".to_string(),
+                "src/lib.rs".to_string(),
+            )
+        };
+        let message = serde_json::json!({
+            "reason": "compiler-message",
+            "message": {
+                "code": {"code": "E0425", "explanation": null},
+                "level": "error",
+                "message": "cannot find value `VERSION` in this scope",
+                "spans": [
+                    {"file_name": format!("{}/generated.rs", out_dir), "is_primary": true, "line_start": 3, "line_end": 3, "column_start": 9, "column_end": 16}
+                ],
+                "children": [],
+                "rendered": format!(
+                    "error[E0425]: cannot find value `VERSION` in this scope\n --> {}/generated.rs:3:9\nnote: included from {}:1:1\n",
+                    out_dir, lib_rs
+                )
+            }
+        });
+        Ok(RawRunOutput {
+            json_messages: message.to_string(),
+            stderr: String::new(),
+        })
+    }
+}
+
+#[test]
+fn diagnostic_ids_do_not_depend_on_the_checkout_or_target_directory() {
+    let runs: Vec<_> = [
+        (vec![], "default features"),
+        (vec!["--release".to_string()], "release"),
+    ]
+    .into_iter()
+    .map(|(args, desc)| {
+        let (diagnostics, _, _) =
+            check_feature_set(&TwoCheckouts, &args, desc).expect("check failed");
+        (desc.to_string(), diagnostics)
+    })
+    .collect();
+
+    let (consolidated, _) = consolidate_diagnostics(&runs);
+
+    assert_eq!(consolidated.len(), 2);
+    // A golden value: IDs are stored in baselines and must not drift between releases.
+    assert_eq!(consolidated[0].id, "2bfdd6c60c726284");
+    assert_eq!(consolidated[1].id, consolidated[0].id);
+}
+
+#[test]
+fn suppressed_diagnostics_leave_the_report_with_their_implicated_files() {
+    let mut runs = Vec::new();
+    let mut implicated_files = HashMap::new();
+    let mut referencers: HashMap<PathBuf, HashSet<_>> = HashMap::new();
+    for (desc, (diagnostics, files, origins)) in run_fixture_matrix() {
+        runs.push((desc, diagnostics));
+        implicated_files.extend(files);
+        for (file, file_origins) in origins {
+            referencers.entry(file).or_default().extend(file_origins);
+        }
+    }
+    let (mut consolidated, _) = consolidate_diagnostics(&runs);
+    let generated_file = std::fs::canonicalize(fixture("build/gen-0123abcd/out/gen.rs")).unwrap();
+    assert!(implicated_files.contains_key(&generated_file));
+
+    // The E0308 is the only diagnostic pointing into the generated file.
+    let mismatch_id = consolidated[0].id.clone();
+    let (suppressed, unmatched) = suppress_diagnostics(
+        &mut consolidated,
+        &mut referencers,
+        &mut implicated_files,
+        &[mismatch_id, "0000000000000000".to_string()],
+    );
+
+    assert_eq!(suppressed, 1);
+    assert_eq!(unmatched, ["0000000000000000"]);
+    let codes: Vec<Option<&str>> = consolidated.iter().map(|d| d.code.as_deref()).collect();
+    assert_eq!(codes, [None, Some("E0425")]);
+    assert!(!implicated_files.contains_key(&generated_file));
+    assert!(!referencers.contains_key(&generated_file));
+}
+
+/// Two warnings pointing at different lines of the same generated file.
+struct SharedGeneratedFile;
+
+impl DiagnosticsSource for SharedGeneratedFile {
+    fn run(
+        &self,
+        _feature_args: &[String],
+        _feature_desc: &str,
+    ) -> Result<RawRunOutput, GetdocError> {
+        let generated = fixture("build/gen-0123abcd/out/gen.rs");
+        let warning = |name: &str, line: usize, generated_line: usize| {
+            serde_json::json!({
+                "reason": "compiler-message",
+                "message": {
+                    "code": {"code": "unused_imports", "explanation": null},
+                    "level": "warning",
+                    "message": format!("unused import: `{}`", name),
+                    "spans": [
+                        {"file_name": "src/lib.rs", "is_primary": true, "line_start": line},
+                        {"file_name": generated.display().to_string(), "is_primary": false, "line_start": generated_line}
+                    ],
+                    "children": [],
+                    "rendered": format!("warning: unused import: `{}`\n --> src/lib.rs:{}:5\n", name, line)
+                }
+            })
+            .to_string()
+        };
+        Ok(RawRunOutput {
+            json_messages: format!("{}\n{}\n", warning("a", 3, 1), warning("b", 7, 2)),
+            stderr: String::new(),
+        })
+    }
+}
+
+#[test]
+fn suppressing_one_of_two_diagnostics_keeps_only_the_other_ones_implication() {
+    let (diagnostics, mut implicated_files, mut referencers) =
+        check_feature_set(&SharedGeneratedFile, &[], "default features").expect("check failed");
+    let (mut consolidated, _) =
+        consolidate_diagnostics(&[("default features".to_string(), diagnostics)]);
+    let generated_file = std::fs::canonicalize(fixture("build/gen-0123abcd/out/gen.rs")).unwrap();
+    assert_eq!(implicated_files[&generated_file], BTreeSet::from([1, 2]));
+
+    let first_id = consolidated[0].id.clone();
+    let (suppressed, _) = suppress_diagnostics(
+        &mut consolidated,
+        &mut referencers,
+        &mut implicated_files,
+        &[first_id],
+    );
+
+    assert_eq!(suppressed, 1);
+    assert_eq!(consolidated.len(), 1);
+    assert_eq!(implicated_files[&generated_file], BTreeSet::from([2]));
+    let referencing_lines: Vec<usize> = referencers[&generated_file]
+        .iter()
+        .filter_map(|origin| origin.originating_diagnostic_span_location.as_ref())
+        .map(|location| location.line)
+        .collect();
+    assert_eq!(referencing_lines, [7]);
+}
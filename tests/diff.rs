@@ -0,0 +1,102 @@
+// Comparing two saved JSON reports (`getdoc diff`).
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use getdoc::diff::{diff_reports, write_diff_markdown};
+
+fn sets(names: &[&str]) -> BTreeSet<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn moved_and_version_bumped_diagnostics_still_match() {
+    let diff = diff_reports(
+        Path::new("tests/fixtures/diff/old.json"),
+        Path::new("tests/fixtures/diff/new.json"),
+    )
+    .expect("diff failed");
+
+    // E0061 moved from line 2 to 4, E0277 now points into serde 1.0.201.
+    assert_eq!(diff.unchanged, 2);
+
+    assert_eq!(diff.new.len(), 1, "{:#?}", diff.new);
+    assert_eq!(diff.new[0].code.as_deref(), Some("E0425"));
+    assert_eq!(diff.resolved.len(), 1, "{:#?}", diff.resolved);
+    assert_eq!(diff.resolved[0].message, "unused import: `std::fmt`");
+
+    assert_eq!(diff.changed_feature_sets.len(), 1);
+    let change = &diff.changed_feature_sets[0];
+    assert_eq!(change.diagnostic.location, "src/lib.rs:12");
+    assert!(change.added.is_empty());
+    assert_eq!(change.removed, sets(&["--all-features"]));
+
+    assert_eq!(diff.feature_sets_added, sets(&["--features extra"]));
+    assert!(diff.feature_sets_removed.is_empty());
+    assert_eq!(
+        diff.summary_line(),
+        "getdoc diff: 1 new, 1 resolved, 1 with changed feature sets, 2 unchanged (feature matrix: 1 set(s) added, 0 removed)."
+    );
+}
+
+#[test]
+fn diagnostics_under_newly_checked_feature_sets_are_marked() {
+    let old = Path::new("tests/fixtures/diff/old.json");
+    let new = Path::new("tests/fixtures/diff/new.json");
+    let diff = diff_reports(old, new).expect("diff failed");
+    let mut markdown = Vec::new();
+    write_diff_markdown(&diff, old, new, &mut markdown).unwrap();
+    let markdown = String::from_utf8(markdown).unwrap();
+
+    assert!(
+        markdown.contains("* Checked only in the new run: `--features extra`."),
+        "{}",
+        markdown
+    );
+    assert!(
+        markdown.contains(
+            "cannot find value `limit` in this scope (at `src/lib.rs:20`), under `--features extra`; these feature sets were not checked in the old run."
+        ),
+        "{}",
+        markdown
+    );
+}
+
+#[test]
+fn diagnostics_with_ids_pair_up_by_id() {
+    let diff = diff_reports(
+        Path::new("tests/fixtures/diff/ids-old.json"),
+        Path::new("tests/fixtures/diff/ids-new.json"),
+    )
+    .expect("diff failed");
+
+    // Both messages match by key; paired by feature sets, the two would swap and count as
+    // unchanged.
+    assert_eq!(diff.unchanged, 0);
+    let changes: Vec<(&str, BTreeSet<String>, BTreeSet<String>)> = diff
+        .changed_feature_sets
+        .iter()
+        .map(|change| {
+            (
+                change.diagnostic.location.as_str(),
+                change.added.clone(),
+                change.removed.clone(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        changes,
+        [
+            (
+                "src/lib.rs:10",
+                sets(&["--all-features"]),
+                sets(&["default features"])
+            ),
+            (
+                "src/lib.rs:20",
+                sets(&["default features"]),
+                sets(&["--all-features"])
+            ),
+        ]
+    );
+}
@@ -0,0 +1,226 @@
+// Checks the `--each-feature` and Comprehensive Mode matrices against a fixture manifest
+// with optional dependencies. `serde` is optional with an implicit feature; `log` is only
+// reachable via `dep:log`. Features are declared out of name order on purpose.
+// A second fixture has feature definitions that alias each other, cyclically and not.
+// Feature lists (`--features-from-file`) for the first fixture live next to its manifest.
+
+use std::path::Path;
+
+use getdoc::features::{load_manifest_features, validate_feature_set};
+use getdoc::{
+    FeaturePlanOptions, FeatureSet, GetdocError, feature_list, plan_feature_sets, read_feature_list,
+};
+
+const FIXTURE_MANIFEST: &str = "tests/fixtures/optional-deps/Cargo.toml";
+// `alpha` and `beta` enable each other, and `full` enables everything.
+const ALIASED_FIXTURE_MANIFEST: &str = "tests/fixtures/aliased-features/Cargo.toml";
+
+fn planned_args(options: &FeaturePlanOptions) -> Vec<Vec<String>> {
+    plan_feature_sets(Path::new(FIXTURE_MANIFEST), options)
+        .expect("feature planning failed")
+        .into_iter()
+        .map(|feature_set| feature_set.args)
+        .collect()
+}
+
+fn args(list: &[&str]) -> Vec<String> {
+    list.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+fn each_feature_matches_cargo_hack() {
+    let options = FeaturePlanOptions {
+        each_feature: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        planned_args(&options),
+        vec![
+            args(&[]),
+            args(&["--no-default-features"]),
+            args(&["--no-default-features", "--features", "extra"]),
+            args(&["--no-default-features", "--features", "logging"]),
+            args(&["--no-default-features", "--features", "std"]),
+            args(&["--all-features"]),
+        ]
+    );
+}
+
+#[test]
+fn each_feature_with_optional_deps_and_exclusions() {
+    let options = FeaturePlanOptions {
+        each_feature: true,
+        exclude_features: vec!["extra".to_string()],
+        optional_deps: true,
+        exclude_all_features: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        planned_args(&options),
+        vec![
+            args(&[]),
+            args(&["--no-default-features"]),
+            args(&["--no-default-features", "--features", "logging"]),
+            args(&["--no-default-features", "--features", "serde"]),
+            args(&["--no-default-features", "--features", "std"]),
+        ]
+    );
+}
+
+#[test]
+fn comprehensive_mode_plans_features_in_name_order() {
+    let plan = plan_feature_sets(Path::new(FIXTURE_MANIFEST), &FeaturePlanOptions::default())
+        .expect("feature planning failed");
+    let descriptions: Vec<&str> = plan
+        .iter()
+        .map(|feature_set| feature_set.description.as_str())
+        .collect();
+    // `std` is the default feature, so its own run folds into the default one.
+    assert_eq!(
+        descriptions,
+        [
+            "default features",
+            "--no-default-features",
+            "--no-default-features --features extra",
+            "--no-default-features --features logging",
+            "--no-default-features --features serde",
+            "--all-features",
+        ]
+    );
+    assert_eq!(
+        plan[0].equivalent_sets,
+        ["--no-default-features --features std"]
+    );
+}
+
+#[test]
+fn feature_sets_with_the_same_resolved_features_are_checked_once() {
+    let plan = plan_feature_sets(
+        Path::new(ALIASED_FIXTURE_MANIFEST),
+        &FeaturePlanOptions::default(),
+    )
+    .expect("feature planning failed");
+    let checked: Vec<(&str, &[String])> = plan
+        .iter()
+        .map(|feature_set| {
+            (
+                feature_set.description.as_str(),
+                feature_set.equivalent_sets.as_slice(),
+            )
+        })
+        .collect();
+    // The cycle resolves to {alpha, beta} either way, and `full` turns on every feature.
+    // `default` is empty, so `--no-default-features` is planned but folds into the default run.
+    let none: &[String] = &[];
+    assert_eq!(
+        checked,
+        [
+            ("default features", &args(&["--no-default-features"])[..]),
+            (
+                "--no-default-features --features alpha",
+                &args(&["--no-default-features --features beta"])[..]
+            ),
+            (
+                "--no-default-features --features full",
+                &args(&["--all-features"])[..]
+            ),
+            ("--no-default-features --features gamma", none),
+        ]
+    );
+}
+
+#[test]
+fn feature_list_is_planned_exactly_as_listed() {
+    let options = FeaturePlanOptions {
+        feature_list: Some(
+            read_feature_list(Path::new("tests/fixtures/optional-deps/feature-list.txt"))
+                .expect("feature list unreadable"),
+        ),
+        ..Default::default()
+    };
+    assert_eq!(
+        planned_args(&options),
+        vec![
+            args(&[]),
+            args(&["--no-default-features", "--features", "extra,logging"]),
+            args(&["--no-default-features"]),
+            args(&["--features", "serde"]),
+            args(&["--all-features"]),
+        ]
+    );
+}
+
+#[test]
+fn feature_list_with_undeclared_feature_is_rejected() {
+    let options = FeaturePlanOptions {
+        feature_list: Some(
+            read_feature_list(Path::new(
+                "tests/fixtures/optional-deps/feature-list-undeclared.txt",
+            ))
+            .expect("feature list unreadable"),
+        ),
+        ..Default::default()
+    };
+    match plan_feature_sets(Path::new(FIXTURE_MANIFEST), &options) {
+        Err(GetdocError::FeatureList { message, .. }) => {
+            assert_eq!(
+                message,
+                "line 1: `typo` not declared in the manifest's `[features]`"
+            )
+        }
+        other => panic!("expected a feature-list error, got {:?}", other),
+    }
+}
+
+#[test]
+fn feature_list_marks_default_and_optional_dependency_features() {
+    let listing = feature_list(Path::new(FIXTURE_MANIFEST), &[]);
+    assert_eq!(
+        listing,
+        "Features:\n\
+         \x20 default  -> std\n\
+         \x20 extra    -> std\n\
+         \x20 logging  -> dep:log\n\
+         \x20 serde    -> dep:serde  [optional dependency]\n\
+         \x20 std      -> (nothing)  [default]\n"
+    );
+
+    // `default` enables nothing here, so no feature is marked, cycles notwithstanding.
+    let listing = feature_list(Path::new(ALIASED_FIXTURE_MANIFEST), &[]);
+    assert!(listing.contains("  alpha    -> beta\n"), "{}", listing);
+    assert!(!listing.contains("[default]"), "{}", listing);
+}
+
+#[test]
+fn feature_selections_cargo_would_reject_are_caught_up_front() {
+    let manifest_features = load_manifest_features(Path::new(FIXTURE_MANIFEST), &[]);
+    let validate =
+        |list: &[&str]| validate_feature_set(&manifest_features, &FeatureSet::new(args(list)));
+
+    assert_eq!(validate(&[]), Ok(()));
+    assert_eq!(validate(&["--all-features"]), Ok(()));
+    assert_eq!(
+        validate(&["--no-default-features", "--features", "extra,serde"]),
+        Ok(())
+    );
+    // A dependency's features are left to cargo, which resolves the dependency.
+    assert_eq!(validate(&["--features", "serde/std log?/std"]), Ok(()));
+
+    assert_eq!(
+        validate(&["--no-default-features", "--features", "std,typo"]),
+        Err("the package does not contain this feature: typo".to_string())
+    );
+    // `log` has no implicit feature, as `logging` refers to it with `dep:`.
+    assert_eq!(
+        validate(&["--features", "log"]),
+        Err("the package does not contain this feature: log".to_string())
+    );
+    assert_eq!(
+        validate(&["--features", "tokio/rt"]),
+        Err("the package does not contain this feature: tokio/rt".to_string())
+    );
+    assert_eq!(
+        validate(&["--features", "dep:serde"]),
+        Err("feature `dep:serde` is not allowed to use explicit `dep:` syntax".to_string())
+    );
+}
@@ -0,0 +1,269 @@
+// Doc comments as items carry them: `///`, `#[doc = ...]`, `include_str!`, and `cfg_attr`.
+// Files that are not valid UTF-8 are still extracted, with invalid bytes replaced. Items
+// that only exist in test builds are skipped, and identical re-exports collapsed.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use getdoc::extract::{ExtractionOptions, collapse_duplicate_items, find_related_impls};
+use getdoc::{ExtractedItem, Options, extract_items};
+
+fn extract_fixture() -> Vec<ExtractedItem> {
+    let options = Options::from_args(["getdoc"]);
+    extract_items(
+        Path::new("tests/fixtures/doc-attributes/src/lib.rs"),
+        &options,
+    )
+    .expect("extraction failed")
+}
+
+fn docs_of<'a>(items: &'a [ExtractedItem], name: &str) -> Vec<&'a str> {
+    items
+        .iter()
+        .find(|item| item.name == name)
+        .unwrap_or_else(|| panic!("no item `{}`", name))
+        .doc_comments
+        .iter()
+        .map(String::as_str)
+        .collect()
+}
+
+#[test]
+fn included_docs_are_read_relative_to_the_source_file() {
+    let items = extract_fixture();
+    assert_eq!(
+        docs_of(&items, "Widget"),
+        [
+            "# Widgets",
+            "",
+            "A widget is anything with a `render` method.",
+            "",
+            "```rust",
+            "let widget = widgets::Widget;",
+            "```",
+        ]
+    );
+    assert_eq!(
+        docs_of(&items, "undocumented"),
+        ["(Docs are included from `../missing.md`, which could not be read.)"]
+    );
+}
+
+#[test]
+fn doc_attributes_and_comments_are_concatenated_in_order() {
+    let items = extract_fixture();
+    assert_eq!(
+        docs_of(&items, "render_all"),
+        [
+            "Renders every widget.",
+            "Widgets are rendered in order.",
+            "Returns how many were rendered.",
+        ]
+    );
+}
+
+#[test]
+fn indentation_in_doc_comments_is_kept() {
+    let items = extract_fixture();
+    assert_eq!(
+        docs_of(&items, "lay_out"),
+        [
+            "Lays widgets out:",
+            "",
+            "- in rows",
+            "  - left to right",
+            "- in columns",
+            "",
+            "For example:",
+            "",
+            "    let layout = Layout::Row;",
+            "",
+            "    assert!(matches!(layout, Layout::Row));",
+        ]
+    );
+}
+
+#[test]
+fn conditional_docs_name_their_condition() {
+    let items = extract_fixture();
+    assert_eq!(
+        docs_of(&items, "Layout"),
+        [
+            "How widgets are laid out.",
+            "(Only documented with `cfg(docsrs)`:)",
+            "See the guide on docs.rs.",
+            "(Only documented with `cfg(feature = \"serde\")`:)",
+            "Serializable with `serde`.",
+        ]
+    );
+}
+
+#[test]
+fn long_included_docs_are_cut_off() {
+    let dir = std::env::temp_dir().join(format!("getdoc-extract-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("GUIDE.md"), "A line of the guide.\n".repeat(2000)).unwrap();
+    std::fs::write(
+        dir.join("lib.rs"),
+        "#[doc = include_str!(\"GUIDE.md\")]\npub struct Guide;\n",
+    )
+    .unwrap();
+
+    let items = extract_items(&dir.join("lib.rs"), &Options::from_args(["getdoc"])).unwrap();
+    let docs = docs_of(&items, "Guide");
+    assert!(docs.len() < 2000);
+    assert_eq!(
+        docs.last(),
+        Some(&"(… cut off after 16384 of the 42000 bytes of `GUIDE.md`.)")
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn files_with_invalid_utf8_are_read_lossily() {
+    let dir = std::env::temp_dir().join(format!("getdoc-extract-latin1-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    // A Latin-1 `é` in a doc comment and in a plain comment.
+    std::fs::write(
+        dir.join("lib.rs"),
+        b"/// Caf\xe9 support.\npub struct Cafe;\n\n// na\xefve\npub fn after_the_bad_bytes() {}\n",
+    )
+    .unwrap();
+
+    let items = extract_items(&dir.join("lib.rs"), &Options::from_args(["getdoc"])).unwrap();
+    assert_eq!(docs_of(&items, "Cafe"), ["Caf\u{FFFD} support."]);
+    let function = items
+        .iter()
+        .find(|item| item.name == "after_the_bad_bytes")
+        .expect("items after the invalid bytes are extracted");
+    assert_eq!(function.line_start, 5);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// A documented `fn new() -> Self` in `impl <type_name>`, as the file's items.
+fn constructor_in_impl_of(type_name: &str) -> Vec<ExtractedItem> {
+    let item = |id, item_kind: &str, name: String, parent, parent_impl| ExtractedItem {
+        item_kind: item_kind.to_string(),
+        signature_or_definition: name.clone(),
+        name,
+        trait_bounds: Vec::new(),
+        doc_comments: vec!["Creates an empty value.".to_string()],
+        id,
+        parent,
+        parent_impl,
+        also_found_in: Vec::new(),
+        item_path: None,
+        line_start: id + 1,
+        line_end: id + 1,
+    };
+    let header = format!("impl {}", type_name);
+    vec![
+        item(0, "Inherent Impl Block", header.clone(), None, None),
+        item(
+            1,
+            "Impl Method",
+            "pub fn new() -> Self".to_string(),
+            Some(0),
+            Some(header),
+        ),
+    ]
+}
+
+#[test]
+fn identical_items_are_only_collapsed_under_the_same_parent() {
+    let files: Vec<PathBuf> = ["a.rs", "b.rs", "c.rs"].map(PathBuf::from).into();
+    let mut extracted: HashMap<PathBuf, Vec<ExtractedItem>> = HashMap::from([
+        (files[0].clone(), constructor_in_impl_of("Foo")),
+        (files[1].clone(), constructor_in_impl_of("Bar")),
+        (files[2].clone(), constructor_in_impl_of("Foo")),
+    ]);
+
+    let collapsed = collapse_duplicate_items(&files, &mut extracted);
+
+    assert_eq!(collapsed, 1);
+    assert_eq!(extracted[&files[0]][1].also_found_in, ["c.rs:2"]);
+    assert_eq!(extracted[&files[1]].len(), 2);
+    assert!(extracted[&files[1]][1].also_found_in.is_empty());
+    assert_eq!(extracted[&files[2]].len(), 1);
+}
+
+#[test]
+fn only_items_that_exist_solely_in_test_builds_are_skipped() {
+    let options = Options::from_args(["getdoc"]);
+    let items = extract_items(Path::new("tests/fixtures/cfg-test/lib.rs"), &options)
+        .expect("extraction failed");
+    let names: Vec<&str> = items.iter().map(|item| item.name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "outside_tests",
+            "unix_outside_tests",
+            "test_support",
+            "feature_named_test",
+            "aliased"
+        ]
+    );
+}
+
+#[test]
+fn inline_modules_are_extracted_down_to_the_depth_limit() {
+    let extract = |args: &[&str]| -> Vec<(String, String)> {
+        let options = Options::from_args(["getdoc"].iter().chain(args));
+        extract_items(Path::new("tests/fixtures/nested-modules/lib.rs"), &options)
+            .expect("extraction failed")
+            .into_iter()
+            .map(|item| (item.name, item.signature_or_definition))
+            .collect()
+    };
+    let elided = |name: &str| {
+        (
+            name.to_string(),
+            format!("pub mod {} {{ /* module elided (depth limit) */ }}", name),
+        )
+    };
+
+    // The default `--max-depth 3` lists `d` (three modules down) without its items.
+    let items = extract(&[]);
+    let names: Vec<&str> = items.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["a", "in_a", "b", "c", "in_c", "d"]);
+    assert_eq!(items[5], elided("d"));
+
+    let items = extract(&["--max-depth", "1"]);
+    let names: Vec<&str> = items.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["a", "in_a", "b"]);
+    assert_eq!(items[2], elided("b"));
+}
+
+#[test]
+fn related_impls_are_found_by_their_self_type() {
+    let implicated = PathBuf::from("tests/fixtures/related-impls/src/lib.rs");
+    let options = Options::from_args(["getdoc"]);
+    let extracted = HashMap::from([(
+        implicated.clone(),
+        extract_items(&implicated, &options).expect("extraction failed"),
+    )]);
+
+    let related = find_related_impls(&extracted, &ExtractionOptions::from(&options));
+
+    let blocks: Vec<(PathBuf, Vec<&str>)> = related[&implicated]
+        .iter()
+        .map(|block| {
+            let names = block.items.iter().map(|item| item.name.as_str()).collect();
+            (block.source_file.clone(), names)
+        })
+        .collect();
+    assert_eq!(
+        blocks,
+        [
+            (PathBuf::from("src/render.rs"), vec!["Widget", "new"]),
+            // `Mutex` is not mistaken for a `mut` reference.
+            (PathBuf::from("src/render.rs"), vec!["Mutex", "lock"]),
+            (
+                PathBuf::from("src/render.rs"),
+                vec!["impl Reset for &'a mut Widget", "reset"]
+            ),
+            // Impls inside inline modules count too.
+            (PathBuf::from("src/render.rs"), vec!["Widget", "draw"]),
+        ]
+    );
+}
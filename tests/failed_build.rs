@@ -0,0 +1,35 @@
+// A feature set whose build failed before compiling everything says nothing about which
+// diagnostics are absent under it.
+
+use std::path::PathBuf;
+
+use getdoc::{Options, run};
+
+#[test]
+fn diagnostics_are_not_marked_absent_under_a_failed_build() {
+    let target_dir =
+        std::env::temp_dir().join(format!("getdoc-failed-build-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&target_dir);
+    std::fs::create_dir_all(&target_dir).unwrap();
+    // SAFETY: this is the only test in this binary, so no other thread reads the environment.
+    unsafe { std::env::set_var("CARGO_TARGET_DIR", &target_dir) };
+    let options = Options {
+        input: Some(PathBuf::from("tests/fixtures/failed-build-capture")),
+        output: target_dir.join("report.md"),
+        force: true,
+        ..Options::default()
+    };
+
+    let report = run(&options).expect("run failed");
+    std::fs::remove_dir_all(&target_dir).unwrap();
+
+    let levels: Vec<&str> = report
+        .consolidated_diagnostics
+        .iter()
+        .map(|d| d.level.as_str())
+        .collect();
+    assert_eq!(levels, ["TOOL_ERROR", "warning"]);
+    let warning = &report.consolidated_diagnostics[1];
+    assert!(warning.absent_feature_set_descriptors.is_empty());
+    assert!(!warning.is_feature_independent());
+}
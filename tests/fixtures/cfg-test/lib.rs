@@ -0,0 +1,31 @@
+// Items gated on `cfg(test)` in various ways, and items that merely mention `test`.
+
+#[cfg(test)]
+pub fn only_in_tests() {}
+
+#[cfg(all(test, feature = "extra"))]
+pub fn in_tests_with_extra() {}
+
+#[cfg(any(test, all(test, unix)))]
+pub fn in_tests_either_way() {}
+
+#[cfg(not(not(test)))]
+pub fn doubly_negated_test() {}
+
+#[doc(hidden)]
+pub fn hidden() {}
+
+#[cfg(not(test))]
+pub fn outside_tests() {}
+
+#[cfg(all(not(test), unix))]
+pub fn unix_outside_tests() {}
+
+#[cfg(any(test, feature = "testing"))]
+pub fn test_support() {}
+
+#[cfg(all(feature = "test", unix))]
+pub fn feature_named_test() {}
+
+#[doc(alias = "hidden")]
+pub fn aliased() {}
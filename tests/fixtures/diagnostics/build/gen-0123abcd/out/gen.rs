@@ -0,0 +1,2 @@
+// Stand-in for a file written by the `gen` crate's build script.
+pub const GENERATED: u32 = 1;
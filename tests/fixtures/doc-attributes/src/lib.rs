@@ -0,0 +1,34 @@
+#[doc = include_str!("../README.md")]
+pub struct Widget;
+
+#[doc = "Renders every widget."]
+#[doc = "Widgets are rendered in order."]
+/// Returns how many were rendered.
+pub fn render_all() -> usize {
+    0
+}
+
+/// How widgets are laid out.
+#[cfg_attr(docsrs, doc = "See the guide on docs.rs.")]
+#[cfg_attr(feature = "serde", derive(Debug), doc = "Serializable with `serde`.")]
+#[cfg_attr(feature = "unused", derive(Clone))]
+pub enum Layout {
+    Row,
+    Column,
+}
+
+#[doc = include_str!("../missing.md")]
+pub fn undocumented() {}
+
+/// Lays widgets out:
+///
+/// - in rows
+///   - left to right
+/// - in columns
+///
+/// For example:
+///
+///     let layout = Layout::Row;
+///
+///     assert!(matches!(layout, Layout::Row));
+pub fn lay_out() {}
@@ -0,0 +1,13 @@
+pub mod a {
+    pub fn in_a() {}
+
+    pub mod b {
+        pub mod c {
+            pub fn in_c() {}
+
+            pub mod d {
+                pub fn in_d() {}
+            }
+        }
+    }
+}
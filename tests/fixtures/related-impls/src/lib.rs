@@ -0,0 +1,7 @@
+mod render;
+
+/// Something that can be drawn.
+pub struct Widget;
+
+/// A lock, whose name starts like the `mut` keyword.
+pub struct Mutex;
@@ -0,0 +1,32 @@
+use crate::{Mutex, Widget};
+
+impl Widget {
+    /// Creates a widget.
+    pub fn new() -> Self {
+        Widget
+    }
+}
+
+impl Mutex {
+    /// Locks the mutex.
+    pub fn lock(&self) {}
+}
+
+/// Something that can be put back to its initial state.
+pub trait Reset {
+    fn reset(self);
+}
+
+impl<'a> Reset for &'a mut Widget {
+    /// Resets the widget in place.
+    fn reset(self) {}
+}
+
+mod backends {
+    use crate::Widget;
+
+    impl Widget {
+        /// Draws the widget on a terminal.
+        pub fn draw(&self) {}
+    }
+}
@@ -0,0 +1,58 @@
+// `--exclude-path` globs against the canonical paths of implicated files, with `/` as the
+// separator on every platform.
+
+use std::path::Path;
+
+use getdoc::Options;
+use getdoc::glob::PathGlob;
+
+const REGISTRY_FILE: &str = "/home/user/.cargo/registry/src/index.crates.io-6f17d22bba15001f/prost-0.12.6/src/generated/types.pb.rs";
+
+fn matches(pattern: &str, path: &str) -> bool {
+    PathGlob::parse(pattern).unwrap().matches(Path::new(path))
+}
+
+#[test]
+fn double_star_spans_directories_and_star_stays_within_one() {
+    assert!(matches("**/generated/**", REGISTRY_FILE));
+    assert!(matches("**/prost-*/src/**", REGISTRY_FILE));
+    assert!(!matches("**/prost-*/types.pb.rs", REGISTRY_FILE));
+    assert!(matches("**/prost-*/**/types.pb.rs", REGISTRY_FILE));
+    // `**/` also matches no directory at all.
+    assert!(matches("**/src/**/generated/*.rs", REGISTRY_FILE));
+    assert!(!matches("**/tests/**", REGISTRY_FILE));
+    assert!(matches("**/prost-0.12.?/**", REGISTRY_FILE));
+    assert!(matches("**/prost-0.1[0-9].*/**", REGISTRY_FILE));
+    assert!(!matches("**/prost-0.1[!0-9].*/**", REGISTRY_FILE));
+}
+
+#[test]
+fn patterns_without_a_separator_match_the_file_name() {
+    assert!(matches("*.pb.rs", REGISTRY_FILE));
+    assert!(!matches("generated", REGISTRY_FILE));
+    assert!(!matches("*.rs.bak", REGISTRY_FILE));
+}
+
+#[test]
+fn backslash_separators_are_normalized() {
+    let windows_path =
+        r"C:\Users\user\.cargo\registry\src\index\prost-0.12.6\src\generated\types.pb.rs";
+    assert!(matches("**/generated/**", windows_path));
+    assert!(matches(r"**\generated\**", windows_path));
+    assert!(matches(r"**\generated\**", REGISTRY_FILE));
+}
+
+#[test]
+fn exclude_path_is_repeatable_and_rejects_malformed_globs() {
+    let options = Options::from_args([
+        "getdoc",
+        "--exclude-path",
+        "**/generated/**",
+        "--exclude-path",
+        "**/tests/**",
+    ]);
+    let patterns: Vec<&str> = options.exclude_path.iter().map(PathGlob::as_str).collect();
+    assert_eq!(patterns, ["**/generated/**", "**/tests/**"]);
+    assert!(PathGlob::parse("**/[abc/**").is_err());
+    assert!(PathGlob::parse("").is_err());
+}
@@ -0,0 +1,149 @@
+// Issue drafts group an implicated crate's files by the version they belong to, so two
+// versions of one crate in the dependency graph are never reported under the first one.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use getdoc::issue::{IssueOutcome, write_issue};
+use getdoc::{DisplayableDiagnostic, ExtractedItem, FeatureSet, Report, consolidate_diagnostics};
+
+/// Writes a crate `dep-crate` at `version` under `registry`, returning its `src/lib.rs`.
+fn write_crate(registry: &Path, version: &str, repository: Option<&str>) -> PathBuf {
+    let root = registry.join(format!("dep-crate-{}", version));
+    fs::create_dir_all(root.join("src")).unwrap();
+    let repository = repository
+        .map(|url| format!("repository = \"{}\"\n", url))
+        .unwrap_or_default();
+    fs::write(
+        root.join("Cargo.toml"),
+        format!(
+            "[package]\nname = \"dep-crate\"\nversion = \"{}\"\n{}",
+            version, repository
+        ),
+    )
+    .unwrap();
+    let lib = root.join("src/lib.rs");
+    fs::write(&lib, "pub fn f() {}\n").unwrap();
+    lib
+}
+
+fn item(name: &str, line: usize) -> ExtractedItem {
+    ExtractedItem {
+        item_kind: "fn".to_string(),
+        name: name.to_string(),
+        signature_or_definition: format!("pub fn {}()", name),
+        trait_bounds: Vec::new(),
+        doc_comments: Vec::new(),
+        id: 0,
+        parent: None,
+        parent_impl: None,
+        also_found_in: Vec::new(),
+        item_path: None,
+        line_start: line,
+        line_end: line,
+    }
+}
+
+/// A report with one error implicating line 1 of each file in `files`, where the item
+/// `from_<n>` is defined.
+fn report_implicating(files: &[PathBuf]) -> Report {
+    let diagnostic = DisplayableDiagnostic {
+        level: "error".to_string(),
+        code: Some("E0061".to_string()),
+        code_explanation: None,
+        message: "this function takes 1 argument but 0 arguments were supplied".to_string(),
+        rendered: "error[E0061]: this function takes 1 argument but 0 arguments were supplied"
+            .to_string(),
+        primary_location_of_diagnostic: None,
+        implicated_third_party_files_details: files
+            .iter()
+            .map(|file| (file.clone(), "lib.rs:1".to_string()))
+            .collect(),
+        suggestions: Vec::new(),
+        producing_crate: None,
+    };
+    let (consolidated_diagnostics, unique_explanations) =
+        consolidate_diagnostics(&[("default features".to_string(), vec![diagnostic])]);
+    Report {
+        mode_description: "Test Mode".to_string(),
+        consolidated_diagnostics,
+        unique_explanations,
+        sorted_file_paths: files.to_vec(),
+        implicated_lines_by_file: files
+            .iter()
+            .map(|file| (file.clone(), BTreeSet::from([1])))
+            .collect(),
+        extracted_data: files
+            .iter()
+            .enumerate()
+            .map(|(n, file)| (file.clone(), vec![item(&format!("from_{}", n), 1)]))
+            .collect::<HashMap<_, _>>(),
+        ..Default::default()
+    }
+}
+
+fn draft(report: &Report, crate_name: &str, dir: &Path) -> (String, Option<String>) {
+    let path = dir.join(format!("issue-{}.md", crate_name));
+    let outcome = write_issue(
+        report,
+        crate_name,
+        &[FeatureSet::new(Vec::new())],
+        &path,
+        true,
+    )
+    .unwrap();
+    let IssueOutcome::Written { issues_url } = outcome else {
+        panic!("`{}` should be implicated", crate_name);
+    };
+    (fs::read_to_string(&path).unwrap(), issues_url)
+}
+
+#[test]
+fn each_implicated_version_of_a_crate_is_listed_apart() {
+    let dir = std::env::temp_dir().join(format!("getdoc-issue-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let old = write_crate(&dir, "1.0.0", None);
+    let new = write_crate(
+        &dir,
+        "2.0.0",
+        Some("https://github.com/example/dep-crate.git"),
+    );
+
+    // `-` and `_` are interchangeable in the crate name asked for.
+    let (both, issues_url) = draft(
+        &report_implicating(&[old.clone(), new.clone()]),
+        "dep_crate",
+        &dir,
+    );
+    assert!(both.starts_with("# `dep-crate` 1.0.0, 2.0.0: this function takes 1 argument"));
+    assert!(both.contains("depends on `dep-crate 1.0.0`, `dep-crate 2.0.0` produces"));
+    // Only the newer version names its repository; it is used for both.
+    assert_eq!(
+        issues_url.as_deref(),
+        Some("https://github.com/example/dep-crate/issues/new")
+    );
+    let old_section = both.find("### `dep-crate 1.0.0`").unwrap();
+    let new_section = both.find("### `dep-crate 2.0.0`").unwrap();
+    let old_item = both.find("pub fn from_0()").unwrap();
+    let new_item = both.find("pub fn from_1()").unwrap();
+    assert!(old_section < old_item && old_item < new_section && new_section < new_item);
+
+    // A single version needs no per-version headings.
+    let (one, _) = draft(&report_implicating(&[new]), "dep-crate", &dir);
+    assert!(one.starts_with("# `dep-crate` 2.0.0: "));
+    assert!(one.contains("depends on `dep-crate 2.0.0` produces"));
+    assert!(!one.contains("### "));
+    assert!(one.contains("`src/lib.rs` (line(s) 1):\n\n```rust\npub fn from_0()\n```"));
+
+    let unrelated = write_issue(
+        &report_implicating(&[old]),
+        "other-crate",
+        &[],
+        &dir.join("issue-other-crate.md"),
+        true,
+    )
+    .unwrap();
+    assert!(matches!(unrelated, IssueOutcome::NotImplicated));
+    assert!(!dir.join("issue-other-crate.md").exists());
+}
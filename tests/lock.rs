@@ -0,0 +1,37 @@
+// Only one getdoc run at a time may work in a project; the lock lives under the target dir.
+
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+use getdoc::{GetdocError, Options, run};
+
+#[test]
+fn second_run_is_refused_while_the_lock_is_held() {
+    let target_dir = std::env::temp_dir().join(format!("getdoc-lock-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&target_dir);
+    fs::create_dir_all(target_dir.join("getdoc")).unwrap();
+    // SAFETY: this is the only test in this binary, so no other thread reads the environment.
+    unsafe { std::env::set_var("CARGO_TARGET_DIR", &target_dir) };
+    let options = Options {
+        input: Some(PathBuf::from("no-such-capture.json")),
+        force: true,
+        ..Options::default()
+    };
+
+    let other_run = File::create(target_dir.join("getdoc/.lock")).unwrap();
+    other_run.try_lock().unwrap();
+    match run(&options) {
+        Err(GetdocError::RunLocked { path, .. }) => {
+            assert_eq!(path, target_dir.join("getdoc/.lock"))
+        }
+        other => panic!("expected a RunLocked error, got {:?}", other),
+    }
+
+    // Once the other run is done, the analysis proceeds (and fails on the missing input).
+    drop(other_run);
+    match run(&options) {
+        Err(GetdocError::ReadInput { .. }) => {}
+        other => panic!("expected a ReadInput error, got {:?}", other),
+    }
+    fs::remove_dir_all(&target_dir).unwrap();
+}
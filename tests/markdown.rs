@@ -0,0 +1,415 @@
+// Item names and impl headers full of Markdown metacharacters are rendered into headings,
+// lists, and tables, and the report is parsed back: every code span must come out as the
+// text that went in, table rows keep their cells, and heading anchors stay unique.
+// Error-code explanations are shown in full, shortened, or not at all. Long "Referenced by"
+// lists are cut short with a pointer to where the rest can be found. `--raw-docs` keeps the
+// indentation of nested lists and indented examples.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use getdoc::markdown::{HeadingAnchors, heading_anchor, inline_code, table_code};
+use getdoc::report::{PackageFeatureTable, write_markdown_report};
+use getdoc::{
+    DiagnosticOriginInfo, DisplayableDiagnostic, ExtractedItem, Location, Options, Report,
+    consolidate_diagnostics, extract_items,
+};
+
+/// Fragments that break naive Markdown, combined pairwise into item names.
+const FRAGMENTS: &[&str] = &[
+    "impl<T: Deref<Target = [u8]>> Foo for Bar<T>",
+    "`",
+    "``",
+    "a`b",
+    "|",
+    "*",
+    "**x**",
+    "<T>",
+    " ",
+    "_",
+    "#",
+    "\\",
+    "[0]",
+];
+
+fn generated_names() -> Vec<String> {
+    let mut names = Vec::new();
+    for first in FRAGMENTS {
+        for second in FRAGMENTS {
+            names.push(format!("{}{}", first, second));
+            names.push(format!("{} {}", first, second));
+        }
+    }
+    names
+}
+
+/// The contents of the code spans in `line`, read as CommonMark does: a run of N backticks
+/// opens a span that the next run of exactly N backticks closes, and one space is stripped
+/// from both ends when both ends have one (unless the content is all spaces).
+fn code_spans(line: &str) -> Vec<String> {
+    let chars: Vec<char> = line.chars().collect();
+    let run_length = |start: usize| chars[start..].iter().take_while(|&&c| c == '`').count();
+    let mut spans = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] != '`' {
+            i += 1;
+            continue;
+        }
+        let opening = run_length(i);
+        let mut j = i + opening;
+        let mut closed = None;
+        while j < chars.len() {
+            if chars[j] == '`' {
+                let length = run_length(j);
+                if length == opening {
+                    closed = Some(j);
+                    break;
+                }
+                j += length;
+            } else {
+                j += 1;
+            }
+        }
+        let Some(end) = closed else {
+            i += opening;
+            continue;
+        };
+        let mut content: String = chars[i + opening..end].iter().collect();
+        if content.len() >= 2
+            && content.starts_with(' ')
+            && content.ends_with(' ')
+            && !content.chars().all(|c| c == ' ')
+        {
+            content = content[1..content.len() - 1].to_string();
+        }
+        spans.push(content);
+        i = end + opening;
+    }
+    spans
+}
+
+/// Splits a table row into cells at the pipes GFM splits at: all but escaped ones.
+fn table_cells(row: &str) -> Vec<String> {
+    let mut cells = vec![String::new()];
+    let mut chars = row.trim().trim_start_matches('|').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                chars.next();
+                cells.last_mut().unwrap().push('|');
+            }
+            '|' => cells.push(String::new()),
+            _ => cells.last_mut().unwrap().push(c),
+        }
+    }
+    if cells.last().is_some_and(|cell| cell.trim().is_empty()) {
+        cells.pop();
+    }
+    cells
+        .into_iter()
+        .map(|cell| cell.trim().to_string())
+        .collect()
+}
+
+fn item(id: usize, item_kind: &str, name: &str, signature: &str) -> ExtractedItem {
+    ExtractedItem {
+        item_kind: item_kind.to_string(),
+        name: name.to_string(),
+        signature_or_definition: signature.to_string(),
+        trait_bounds: vec![name.to_string()],
+        doc_comments: Vec::new(),
+        id,
+        parent: None,
+        parent_impl: Some(name.to_string()),
+        also_found_in: Vec::new(),
+        item_path: None,
+        line_start: 0,
+        line_end: 0,
+    }
+}
+
+fn render(report: &Report) -> String {
+    let mut markdown = Vec::new();
+    write_markdown_report(report, &Options::from_args(["getdoc"]), &mut markdown).unwrap();
+    String::from_utf8(markdown).unwrap()
+}
+
+#[test]
+fn code_spans_read_back_as_the_text_put_in() {
+    for name in generated_names() {
+        for rendered in [inline_code(&name), table_code(&name).replace("\\|", "|")] {
+            assert_eq!(code_spans(&rendered), vec![name.clone()], "{:?}", rendered);
+        }
+        assert!(!table_code(&name).replace("\\|", "").contains('|'));
+    }
+    assert_eq!(code_spans(&inline_code("two\nlines")), vec!["two lines"]);
+}
+
+#[test]
+fn generated_item_names_survive_rendering_with_unique_anchors() {
+    let names = generated_names();
+    let file = PathBuf::from("/home/user/.cargo/registry/src/index/weird-0.1.0/src/lib.rs");
+    let mut items = Vec::new();
+    for (index, name) in names.iter().enumerate() {
+        items.push(item(
+            2 * index,
+            "Function",
+            name,
+            &format!("fn f() -> {}", name),
+        ));
+        // Impl headings come from the signature; every one repeats the same header, as two
+        // impls of the same trait for different `cfg`s do.
+        items.push(item(
+            2 * index + 1,
+            "Impl Block",
+            "impl Foo for Bar",
+            "impl<T: Deref<Target = [u8]>> Foo for Bar<T> {}",
+        ));
+    }
+    let features: BTreeMap<String, Vec<String>> = names
+        .iter()
+        .map(|name| (name.clone(), vec![name.clone()]))
+        .collect();
+    let feature_count = features.len();
+    let report = Report {
+        mode_description: "Test Mode".to_string(),
+        sorted_file_paths: vec![file.clone()],
+        extracted_data: [(file, items)].into(),
+        feature_tables: vec![PackageFeatureTable {
+            package: None,
+            features,
+        }],
+        ..Default::default()
+    };
+    let markdown = render(&report);
+
+    let mut anchors = HashSet::new();
+    let mut function_names = Vec::new();
+    let mut impl_heading_count = 0;
+    for heading in markdown.lines().filter(|line| line.starts_with('#')) {
+        let text = heading.trim_start_matches('#');
+        assert!(text.starts_with(' '), "{}", heading);
+        assert!(
+            anchors.insert(heading_anchor(text)),
+            "duplicate anchor for {}",
+            heading
+        );
+        if let Some(rest) = text.strip_prefix(" Function ") {
+            function_names.push(code_spans(rest).remove(0));
+        } else if let Some(rest) = text.strip_prefix(" Impl Block ") {
+            assert_eq!(
+                code_spans(rest),
+                vec!["impl<T: Deref<Target = [u8]>> Foo for Bar<T>"]
+            );
+            impl_heading_count += 1;
+        }
+    }
+    assert_eq!(function_names, names);
+    assert_eq!(impl_heading_count, names.len());
+
+    // Each `_Defined in ..._` line holds one span, with the name.
+    let defined_in: Vec<String> = markdown
+        .lines()
+        .filter_map(|line| line.strip_prefix("_Defined in "))
+        .map(|line| code_spans(line).remove(0))
+        .collect();
+    assert_eq!(defined_in.len(), 2 * names.len());
+    assert!(names.iter().all(|name| defined_in.contains(name)));
+
+    // Every row of the features table has exactly its two cells.
+    let rows: Vec<Vec<String>> = markdown
+        .lines()
+        .skip_while(|line| !line.starts_with("| Feature | Enables |"))
+        .skip(2)
+        .take_while(|line| line.starts_with('|'))
+        .map(table_cells)
+        .collect();
+    assert_eq!(rows.len(), feature_count);
+    for row in rows {
+        assert_eq!(row.len(), 2, "{:?}", row);
+        assert_eq!(code_spans(&row[0]), code_spans(&row[1]));
+    }
+}
+
+#[test]
+fn repeated_headings_get_numeric_suffixes() {
+    let mut anchors = HeadingAnchors::default();
+    assert_eq!(
+        anchors.unique("Impl Block `impl Foo`".to_string()),
+        "Impl Block `impl Foo`"
+    );
+    assert_eq!(
+        anchors.unique("Impl Block ``impl Foo``".to_string()),
+        "Impl Block ``impl Foo`` (2)"
+    );
+    assert_eq!(
+        anchors.unique("Impl Block `impl Foo`".to_string()),
+        "Impl Block `impl Foo` (3)"
+    );
+    // A heading that happens to read like a suffixed one is skipped over.
+    assert_eq!(anchors.unique("Other (2)".to_string()), "Other (2)");
+    assert_eq!(anchors.unique("Other".to_string()), "Other");
+    assert_eq!(anchors.unique("Other".to_string()), "Other (3)");
+}
+
+#[test]
+fn explanations_can_be_shortened_or_left_out() {
+    const E0277: &str = "You tried to use a type which doesn't implement some trait in a place which\nexpected that trait.\n\nErroneous code example:\n\n```compile_fail,E0277\nfn main() {}\n```\n";
+    let diagnostic = |code: &str, explanation: &str| DisplayableDiagnostic {
+        level: "error".to_string(),
+        code: Some(code.to_string()),
+        code_explanation: Some(explanation.to_string()),
+        message: "the trait bound is not satisfied".to_string(),
+        rendered: format!("error[{}]: the trait bound is not satisfied", code),
+        primary_location_of_diagnostic: None,
+        implicated_third_party_files_details: Vec::new(),
+        suggestions: Vec::new(),
+        producing_crate: None,
+    };
+    // Two codes with the same explanation, as rustc gives some retired codes.
+    let runs = vec![(
+        "default features".to_string(),
+        vec![diagnostic("E0277", E0277), diagnostic("E0999", E0277)],
+    )];
+    let (consolidated_diagnostics, unique_explanations) = consolidate_diagnostics(&runs);
+    let report = Report {
+        mode_description: "Test Mode".to_string(),
+        consolidated_diagnostics,
+        unique_explanations,
+        ..Default::default()
+    };
+    let render_with = |flag: &str| {
+        let mut markdown = Vec::new();
+        write_markdown_report(
+            &report,
+            &Options::from_args(["getdoc", flag]),
+            &mut markdown,
+        )
+        .unwrap();
+        String::from_utf8(markdown).unwrap()
+    };
+
+    let full = render(&report);
+    assert!(full.contains("> ```compile_fail,E0277"));
+    assert!(full.contains("(For generic explanation of E0277, see Appendix A)"));
+    // The second code refers to the first instead of repeating it.
+    assert_eq!(full.matches("> Erroneous code example:").count(), 1);
+    assert!(
+        full.contains("### Explanation for E0999\n\n> Same as the explanation for E0277 above.")
+    );
+
+    let short = render_with("--short-explanations");
+    assert!(short.contains(
+        "> You tried to use a type which doesn't implement some trait in a place which\n> expected that trait.\n> \n> [Full explanation of E0277](https://doc.rust-lang.org/error_codes/E0277.html)\n"
+    ));
+    assert!(!short.contains("Erroneous code example"));
+
+    let omitted = render_with("--no-explanations");
+    assert!(!omitted.contains("Appendix A"));
+    assert!(!omitted.contains("You tried to use"));
+}
+
+#[test]
+fn long_referencer_lists_point_to_the_full_list() {
+    let file = PathBuf::from("/registry/dep-1.0.0/src/lib.rs");
+    let origins: HashSet<_> = (1..=3)
+        .map(|line| DiagnosticOriginInfo {
+            level: "error".to_string(),
+            code: Some("E0061".to_string()),
+            originating_diagnostic_span_location: Some(Location {
+                file: PathBuf::from("src/main.rs"),
+                line,
+                column: Some(5),
+                is_primary: true,
+            }),
+            feature_set_desc: "default features".to_string(),
+        })
+        .collect();
+    let report = Report {
+        mode_description: "Test Mode".to_string(),
+        sorted_file_paths: vec![file.clone()],
+        extracted_data: HashMap::from([(file.clone(), vec![item(0, "fn", "f", "pub fn f()")])]),
+        file_referencers: HashMap::from([(file, origins)]),
+        ..Default::default()
+    };
+    let render_with = |args: &[&str]| {
+        let mut markdown = Vec::new();
+        let args = ["getdoc", "--max-referencers", "2"].iter().chain(args);
+        write_markdown_report(&report, &Options::from_args(args), &mut markdown).unwrap();
+        String::from_utf8(markdown).unwrap()
+    };
+
+    let markdown_only = render_with(&[]);
+    assert!(markdown_only.contains("* ERROR E0061 (originating at `src/main.rs:2`"));
+    assert!(!markdown_only.contains("src/main.rs:3"));
+    assert!(
+        markdown_only
+            .contains("* … and 1 more referencing diagnostic(s) (`--max-referencers 0` lists all)")
+    );
+
+    let with_json = render_with(&["--format", "markdown,json"]);
+    assert!(
+        with_json
+            .contains("* … and 1 more referencing diagnostic(s) (see the JSON report for all)")
+    );
+}
+
+/// The report for the fixture function `lay_out`, whose docs hold a nested list and an
+/// indented example with a blank line in it, rendered with `args`.
+fn render_lay_out(args: &[&str]) -> String {
+    let source = Path::new("tests/fixtures/doc-attributes/src/lib.rs");
+    let items: Vec<ExtractedItem> = extract_items(source, &Options::from_args(["getdoc"]))
+        .unwrap()
+        .into_iter()
+        .filter(|item| item.name == "lay_out")
+        .collect();
+    let file = PathBuf::from("/registry/widgets-1.0.0/src/lib.rs");
+    let report = Report {
+        mode_description: "Test Mode".to_string(),
+        sorted_file_paths: vec![file.clone()],
+        extracted_data: HashMap::from([(file, items)]),
+        ..Default::default()
+    };
+    let mut markdown = Vec::new();
+    let args = ["getdoc"].iter().chain(args);
+    write_markdown_report(&report, &Options::from_args(args), &mut markdown).unwrap();
+    String::from_utf8(markdown).unwrap()
+}
+
+#[test]
+fn raw_docs_keep_nested_lists_and_indented_examples() {
+    let markdown = render_lay_out(&["--raw-docs"]);
+    assert!(
+        markdown.contains("- in rows\n  - left to right\n- in columns\n"),
+        "{}",
+        markdown
+    );
+    assert!(
+        markdown.contains(
+            "\n    let layout = Layout::Row;\n\n    assert!(matches!(layout, Layout::Row));\n"
+        ),
+        "{}",
+        markdown
+    );
+}
+
+#[test]
+fn shortened_docs_never_cut_an_indented_example() {
+    // The blank line inside the example is the last line within the limit, but the cut
+    // falls before the whole example instead.
+    let markdown = render_lay_out(&["--max-doc-lines", "10"]);
+    assert!(
+        markdown.contains("> For example:\n>\n> _… (4 more lines)_"),
+        "{}",
+        markdown
+    );
+    assert!(!markdown.contains("let layout"), "{}", markdown);
+
+    let markdown = render_lay_out(&["--max-doc-lines", "11"]);
+    assert!(
+        markdown.contains("> \n>     assert!(matches!(layout, Layout::Row));"),
+        "{}",
+        markdown
+    );
+}
@@ -0,0 +1,230 @@
+// Report files are replaced atomically and never clobbered without permission. Crates a
+// diagnostic sees at several registry versions are told apart by version. `--wrap` breaks
+// long message lines at spaces but leaves rustc's source snippets alone.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use getdoc::diagnostics::registry_crate_version;
+use getdoc::report::{duplicate_crate_versions, wrap_rendered_line, write_file_atomically};
+use getdoc::{DisplayableDiagnostic, GetdocError, consolidate_diagnostics};
+
+/// A fresh directory holding a `report.md` with hand-written notes in it.
+fn annotated_report(test_name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "getdoc-report-{}-{}",
+        test_name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("report.md");
+    fs::write(&path, "# Report\n\nMy notes.\n").unwrap();
+    path
+}
+
+fn directory_entries(path: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(path.parent().unwrap())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn failed_write_leaves_previous_report_intact() {
+    let path = annotated_report("failed-write");
+
+    let result = write_file_atomically(&path, true, |writer| {
+        writeln!(writer, "# GetDoc Report")?;
+        writeln!(writer, "## Consolidated Compiler Diagnostics")?;
+        Err(io::Error::other("disk full"))
+    });
+
+    match result {
+        Err(GetdocError::WriteReport { path: failed, .. }) => assert_eq!(failed, path),
+        other => panic!("expected a WriteReport error, got {:?}", other),
+    }
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "# Report\n\nMy notes.\n"
+    );
+    // The partial temporary file is cleaned up.
+    assert_eq!(directory_entries(&path), ["report.md"]);
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn existing_report_is_kept_unless_overwrite_is_allowed() {
+    let path = annotated_report("overwrite");
+
+    let refused = write_file_atomically(&path, false, |writer| writeln!(writer, "new"));
+    match refused {
+        Err(GetdocError::ReportExists { path: existing }) => assert_eq!(existing, path),
+        other => panic!("expected a ReportExists error, got {:?}", other),
+    }
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "# Report\n\nMy notes.\n"
+    );
+
+    write_file_atomically(&path, true, |writer| writeln!(writer, "new")).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+    assert_eq!(directory_entries(&path), ["report.md"]);
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn registry_paths_name_their_crate_and_version() {
+    let index = "/home/u/.cargo/registry/src/index.crates.io-6f17d22bba15001f";
+    let crate_version = |path: &str| registry_crate_version(Path::new(path));
+    let owned = |name: &str, version: &str| Some((name.to_string(), version.to_string()));
+
+    assert_eq!(
+        crate_version(&format!("{}/serde-1.0.197/src/de.rs", index)),
+        owned("serde", "1.0.197")
+    );
+    // Names may hold `-` and digits; versions may have pre-release parts.
+    assert_eq!(
+        crate_version(&format!("{}/proc-macro2-1.0.79/src/lib.rs", index)),
+        owned("proc-macro2", "1.0.79")
+    );
+    assert_eq!(
+        crate_version(&format!("{}/x25519-dalek-2.0.0-rc.3/src/lib.rs", index)),
+        owned("x25519-dalek", "2.0.0-rc.3")
+    );
+    // As written in rendered messages, with the cargo home shortened and a line attached.
+    assert_eq!(
+        crate_version("$CARGO_HOME/registry/src/index.crates.io-0/home-0.5.12/src/lib.rs:40:8"),
+        owned("home", "0.5.12")
+    );
+
+    for path in [
+        "/work/serde-1.0.197/src/de.rs",
+        "/home/u/.cargo/git/checkouts/serde-1a2b3c/1.0.197/src/de.rs",
+        "/home/u/.cargo/registry/cache/index.crates.io-0/serde-1.0.197.crate",
+        &format!("{}/serde/src/de.rs", index),
+        &format!("{}/-1.0.0/src/lib.rs", index),
+    ] {
+        assert_eq!(crate_version(path), None, "{}", path);
+    }
+}
+
+#[test]
+fn duplicate_crate_versions_come_from_implicated_files_and_rendered_paths() {
+    let index = "/home/u/.cargo/registry/src/index.crates.io-6f17d22bba15001f";
+    let diagnostic = |implicated: &[&str], rendered: String| DisplayableDiagnostic {
+        level: "error".to_string(),
+        code: Some("E0308".to_string()),
+        code_explanation: None,
+        message: "mismatched types".to_string(),
+        rendered,
+        primary_location_of_diagnostic: None,
+        implicated_third_party_files_details: implicated
+            .iter()
+            .map(|path| (PathBuf::from(path), "detail".to_string()))
+            .collect(),
+        suggestions: Vec::new(),
+        producing_crate: None,
+    };
+    let duplicates = |diagnostic| {
+        let (consolidated, _) =
+            consolidate_diagnostics(&[("default".to_string(), vec![diagnostic])]);
+        duplicate_crate_versions(&consolidated[0])
+    };
+
+    // The note about the other `Value` points into an older `serde_json`; versions are
+    // ordered numerically, not as text. `home` is seen at one version only.
+    let found = duplicates(diagnostic(
+        &[
+            &format!("{}/serde_json-1.0.100/src/value/mod.rs", index),
+            &format!("{}/home-0.5.12/src/lib.rs", index),
+        ],
+        format!(
+            "error[E0308]: mismatched types\nnote: `Value` is defined here\n  --> {}/serde_json-1.0.99/src/value/mod.rs:116:1\n",
+            index
+        ),
+    ));
+    assert_eq!(
+        found,
+        [(
+            "serde_json".to_string(),
+            vec!["1.0.99".to_string(), "1.0.100".to_string()]
+        )]
+    );
+
+    let single = duplicates(diagnostic(
+        &[&format!("{}/serde_json-1.0.100/src/value/mod.rs", index)],
+        format!(
+            "error[E0308]: mismatched types\n  --> {}/serde_json-1.0.100/src/value/mod.rs:116:1\n",
+            index
+        ),
+    ));
+    assert!(single.is_empty(), "{:?}", single);
+}
+
+#[test]
+fn message_lines_wrap_under_their_label_at_the_width() {
+    let wrap = |line: &str, width| wrap_rendered_line(line, width);
+
+    // Up to the width, and at width 0, lines are left as they are.
+    let line = "warning: unused code";
+    assert_eq!(line.len(), 20);
+    assert_eq!(wrap(line, 20), [line]);
+    assert_eq!(wrap(&line.repeat(10), 0), [line.repeat(10)]);
+    assert_eq!(wrap(line, 19), ["warning: unused", "         code"]);
+
+    // Continuations line up with the text after the label.
+    assert_eq!(
+        wrap("error[E0308]: mismatched types, expected u32", 30),
+        [
+            "error[E0308]: mismatched",
+            "              types, expected",
+            "              u32"
+        ]
+    );
+    assert_eq!(
+        wrap("   = note: expected struct `Foo` found struct `Bar`", 30),
+        [
+            "   = note: expected struct",
+            "           `Foo` found struct",
+            "           `Bar`"
+        ]
+    );
+    // A label taking more than half the width only indents continuations by half of it.
+    assert_eq!(
+        wrap("error[E0308]: mismatched types", 20),
+        ["error[E0308]:", "          mismatched", "          types"]
+    );
+
+    // A word longer than the width gets a line of its own and is not split.
+    let url = "https://doc.rust-lang.org/nightly/error_codes/E0308.html";
+    assert_eq!(
+        wrap(&format!("help: see {} for more", url), 20),
+        [
+            "help: see".to_string(),
+            format!("      {}", url),
+            "      for more".to_string()
+        ]
+    );
+    // A `|` in the message does not make the line a snippet line.
+    assert_eq!(
+        wrap("error: expected one of `,` or `|`, found `=`", 30),
+        ["error: expected one of `,` or", "       `|`, found `=`"]
+    );
+}
+
+#[test]
+fn source_snippet_lines_are_never_wrapped() {
+    for line in [
+        "  --> src/main.rs:12:18",
+        "  ::: /home/u/.cargo/registry/src/index.crates.io-0/home-0.5.12/src/lib.rs:40:8",
+        "12 |     let value: u32 = String::new();",
+        "   |                      ^^^^^^^^^^^^^ expected `u32`, found `String`",
+        "... |     more code here",
+    ] {
+        assert_eq!(wrap_rendered_line(line, 10), [line]);
+    }
+}
@@ -0,0 +1,169 @@
+// The command line printed for rerunning a feature set by hand is quoted for any POSIX shell
+// and sets up the same rustflags getdoc ran cargo with.
+
+use std::fs;
+use std::process::Command;
+use std::sync::Mutex;
+
+use getdoc::{CargoCli, DiagnosticsSource};
+
+// The command depends on rustflags and the toolchain in the environment, and on the cargo
+// configuration in the current directory, all process-wide.
+static ENVIRONMENT: Mutex<()> = Mutex::new(());
+
+/// Clears the variables the command is built from.
+fn clean_environment() {
+    // SAFETY: `ENVIRONMENT` is held, so no other test in this binary reads the environment.
+    unsafe {
+        for variable in ["CARGO_ENCODED_RUSTFLAGS", "RUSTFLAGS", "RUSTUP_TOOLCHAIN"] {
+            std::env::remove_var(variable);
+        }
+    }
+}
+
+fn strings(words: &[&str]) -> Vec<String> {
+    words.iter().map(|word| word.to_string()).collect()
+}
+
+#[test]
+fn arguments_are_quoted_only_when_needed() {
+    let _guard = ENVIRONMENT.lock().unwrap_or_else(|e| e.into_inner());
+    clean_environment();
+    let cargo = CargoCli {
+        target_args: strings(&["--bin", "it's"]),
+        rustflags: strings(&["--cfg", "foo"]),
+        ..Default::default()
+    };
+    let feature_args = strings(&["--no-default-features", "--features", "a b,serde/std", ""]);
+
+    assert_eq!(
+        cargo.reproduction_command(&feature_args),
+        r#"cargo check --no-default-features --features 'a b,serde/std' '' --bin 'it'\''s' --config 'build.rustflags=["--cfg", "foo"]'"#
+    );
+
+    // SAFETY: as in `clean_environment`.
+    unsafe { std::env::set_var("RUSTUP_TOOLCHAIN", "nightly-2026-01-01") };
+    assert_eq!(
+        CargoCli::default().reproduction_command(&[]),
+        "cargo +nightly-2026-01-01 check"
+    );
+    clean_environment();
+}
+
+#[test]
+fn rustflags_from_the_environment_are_reproduced_exactly() {
+    let _guard = ENVIRONMENT.lock().unwrap_or_else(|e| e.into_inner());
+    clean_environment();
+    let cargo = CargoCli {
+        rustflags: strings(&["--cfg", "getdoc"]),
+        ..Default::default()
+    };
+
+    // Without spaces in any flag, plain `RUSTFLAGS` will do.
+    // SAFETY: `ENVIRONMENT` is held, so no other test in this binary reads the environment.
+    unsafe { std::env::set_var("RUSTFLAGS", "-Dwarnings") };
+    assert_eq!(
+        cargo.reproduction_command(&[]),
+        "RUSTFLAGS='-Dwarnings --cfg getdoc' cargo check"
+    );
+    clean_environment();
+
+    // Flags with spaces, quotes, backslashes, and leading digits keep their 0x1f separators.
+    let inherited = ["-Clink-arg=it's", "--cfg", "feature=\"a b\"", "7\\x"].join("\x1f");
+    // SAFETY: as above.
+    unsafe { std::env::set_var("CARGO_ENCODED_RUSTFLAGS", &inherited) };
+    let command = cargo.reproduction_command(&[]);
+    clean_environment();
+    assert!(
+        command.starts_with("CARGO_ENCODED_RUSTFLAGS=\"$(printf %b "),
+        "{}",
+        command
+    );
+    assert!(!command.contains("$'"), "{}", command);
+    let (assignment, rest) = command.split_once(" cargo ").unwrap();
+    assert_eq!(rest, "check");
+    // Run through `sh`, the assignment gives cargo exactly these flags.
+    #[cfg(unix)]
+    {
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} printenv CARGO_ENCODED_RUSTFLAGS", assignment))
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            format!("{}\x1f--cfg\x1fgetdoc\n", inherited)
+        );
+    }
+}
+
+#[test]
+fn rustflags_from_target_tables_are_kept_alongside_ours() {
+    let _guard = ENVIRONMENT.lock().unwrap_or_else(|e| e.into_inner());
+    clean_environment();
+    let rustc = Command::new("rustc").arg("-vV").output().unwrap();
+    let host = String::from_utf8(rustc.stdout)
+        .unwrap()
+        .lines()
+        .find_map(|line| line.strip_prefix("host: ").map(String::from))
+        .unwrap();
+    let dir = std::env::temp_dir().join(format!("getdoc-target-rustflags-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join(".cargo")).unwrap();
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(
+        dir.join("Cargo.toml"),
+        "[package]\nname = \"flagged\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("src/lib.rs"),
+        "#[cfg(not(all(from_triple, from_cfg, getdoc)))]\ncompile_error!(\"a rustflag is missing\");\n",
+    )
+    .unwrap();
+    // Cargo uses these instead of `build.rustflags`, which would otherwise hold ours.
+    fs::write(
+        dir.join(".cargo/config.toml"),
+        format!(
+            "[build]\nrustflags = [\"--cfg\", \"from_build\"]\n\
+             [target.{}]\nrustflags = [\"--cfg\", \"from_triple\"]\n\
+             [target.'cfg(not(target_os = \"none\"))']\nrustflags = \"--cfg from_cfg\"\n",
+            host
+        ),
+    )
+    .unwrap();
+    let previous_dir = std::env::current_dir().unwrap();
+    std::env::set_current_dir(&dir).unwrap();
+    // SAFETY: `ENVIRONMENT` is held, so no other test in this binary reads the environment.
+    unsafe { std::env::set_var("CARGO_TARGET_DIR", dir.join("target")) };
+
+    let cargo = CargoCli {
+        rustflags: strings(&["--cfg", "getdoc"]),
+        ..Default::default()
+    };
+    let command = cargo.reproduction_command(&[]);
+    let output = cargo.run(&[], "default features");
+
+    std::env::set_current_dir(previous_dir).unwrap();
+    // SAFETY: as above.
+    unsafe { std::env::remove_var("CARGO_TARGET_DIR") };
+    let _ = fs::remove_dir_all(&dir);
+    assert_eq!(
+        command,
+        "RUSTFLAGS='--cfg from_triple --cfg from_cfg --cfg getdoc' cargo check"
+    );
+    let output = output.unwrap();
+    assert!(
+        !output.json_messages.contains("a rustflag is missing"),
+        "{}",
+        output.stderr
+    );
+    assert!(
+        output
+            .json_messages
+            .contains("\"reason\":\"build-finished\",\"success\":true"),
+        "{}",
+        output.stderr
+    );
+}
@@ -0,0 +1,87 @@
+// Enriches `syn`-extracted items from a saved rustdoc JSON index (`--rustdoc-json`).
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use getdoc::ExtractedItem;
+use getdoc::rustdoc_json::enrich_file_items;
+
+fn widget_struct() -> ExtractedItem {
+    ExtractedItem {
+        item_kind: "Struct".to_string(),
+        name: "Widget".to_string(),
+        signature_or_definition: "pub struct Widget".to_string(),
+        trait_bounds: Vec::new(),
+        doc_comments: Vec::new(),
+        id: 0,
+        parent: None,
+        parent_impl: None,
+        also_found_in: Vec::new(),
+        item_path: None,
+        line_start: 5,
+        line_end: 6,
+    }
+}
+
+#[test]
+fn rustdoc_json_adds_paths_docs_and_macro_generated_items() {
+    let index: serde_json::Value = serde_json::from_str(
+        &fs::read_to_string("tests/fixtures/rustdoc-json/widgets.json").unwrap(),
+    )
+    .unwrap();
+    let crate_root = Path::new("/registry/widgets-1.0.0");
+    let mut items = vec![widget_struct()];
+
+    enrich_file_items(
+        &index,
+        crate_root,
+        &crate_root.join("src/lib.rs"),
+        &BTreeSet::from([12]),
+        &mut items,
+    );
+
+    assert_eq!(items.len(), 2, "{:#?}", items);
+    let widget = &items[0];
+    assert_eq!(widget.item_path.as_deref(), Some("widgets::Widget"));
+    assert_eq!(
+        widget.doc_comments,
+        ["A widget, as documented by `include_str!`."]
+    );
+    assert_eq!(widget.signature_or_definition, "pub struct Widget");
+
+    // Written by a macro at line 12, so only rustdoc knows about it.
+    let generated = &items[1];
+    assert_eq!(generated.item_kind, "Function");
+    assert_eq!(
+        generated.signature_or_definition,
+        "fn widget_count(widgets: &[Widget], limit: usize) -> Option<u32>"
+    );
+    assert_eq!(
+        generated.item_path.as_deref(),
+        Some("widgets::widget_count")
+    );
+    assert_eq!(generated.doc_comments, ["Generated by `counter!`."]);
+    assert_eq!((generated.line_start, generated.line_end), (12, 12));
+}
+
+#[test]
+fn macro_generated_items_away_from_implicated_lines_are_left_out() {
+    let index: serde_json::Value = serde_json::from_str(
+        &fs::read_to_string("tests/fixtures/rustdoc-json/widgets.json").unwrap(),
+    )
+    .unwrap();
+    let crate_root = Path::new("/registry/widgets-1.0.0");
+    let mut items = vec![widget_struct()];
+
+    enrich_file_items(
+        &index,
+        crate_root,
+        &crate_root.join("src/lib.rs"),
+        &BTreeSet::from([5]),
+        &mut items,
+    );
+
+    assert_eq!(items.len(), 1, "{:#?}", items);
+    assert_eq!(items[0].item_path.as_deref(), Some("widgets::Widget"));
+}
@@ -0,0 +1,107 @@
+// The `--serve` viewer answers plain HTTP requests from this machine, and refuses requests
+// naming another host, as a DNS-rebinding page in a browser would send.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+
+/// A getdoc process serving the report of a replayed fixture.
+struct Viewer {
+    child: Child,
+    address: String,
+    dir: PathBuf,
+}
+
+impl Drop for Viewer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn start_viewer() -> Viewer {
+    let dir = std::env::temp_dir().join(format!("getdoc-serve-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = std::fs::canonicalize("tests/fixtures/diagnostics/default.json").unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_getdoc"))
+        .arg("--input")
+        .arg(input)
+        .args(["--serve", "--serve-port", "0"])
+        .current_dir(&dir)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let stdout = BufReader::new(child.stdout.take().unwrap());
+    let address = stdout
+        .lines()
+        .map(Result::unwrap)
+        .find_map(|line| {
+            let rest = line.strip_prefix("[getdoc] Serving the report at http://")?;
+            Some(rest.split('/').next()?.to_string())
+        })
+        .expect("getdoc did not start serving");
+    Viewer {
+        child,
+        address,
+        dir,
+    }
+}
+
+/// Sends `request` and returns the response's status line and body.
+fn request(viewer: &Viewer, request: &str) -> (String, String) {
+    let mut stream = TcpStream::connect(&viewer.address).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    (head.lines().next().unwrap().to_string(), body.to_string())
+}
+
+#[test]
+fn the_viewer_serves_local_requests_and_refuses_other_hosts() {
+    let viewer = start_viewer();
+    let port = viewer.address.rsplit_once(':').unwrap().1.to_string();
+    let get = |path: &str, host: &str| {
+        request(
+            &viewer,
+            &format!("GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, host),
+        )
+    };
+
+    let (status, page) = get("/", &format!("127.0.0.1:{}", port));
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert!(page.contains("<html"), "{}", page);
+    let (status, json) = get("/report.json", &format!("LOCALHOST:{}", port));
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert!(
+        json.contains("\"mode_description\": \"Pre-captured Input Mode\""),
+        "{}",
+        json
+    );
+    assert_eq!(get("/missing", "localhost").0, "HTTP/1.1 404 Not Found");
+    assert_eq!(
+        request(
+            &viewer,
+            "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\n\r\n"
+        )
+        .0,
+        "HTTP/1.1 405 Method Not Allowed"
+    );
+
+    // A page at attacker.example whose name now resolves to 127.0.0.1 sends its own name.
+    for host in [
+        format!("attacker.example:{}", port),
+        "127.0.0.1.attacker.example".to_string(),
+        "localhost.attacker.example:80".to_string(),
+    ] {
+        let (status, body) = get("/report.json", &host);
+        assert_eq!(status, "HTTP/1.1 403 Forbidden", "{}", host);
+        assert!(!body.contains("mode_description"));
+    }
+    let (status, _) = request(&viewer, "GET /report.json HTTP/1.0\r\n\r\n");
+    assert_eq!(status, "HTTP/1.1 403 Forbidden");
+}
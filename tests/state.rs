@@ -0,0 +1,122 @@
+// Cached feature-set checks are replayed while nothing they depend on has changed.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use getdoc::state::{RunCache, RunOutcome};
+use getdoc::{CargoCli, FeatureSet, RawRunOutput};
+
+// The cache works in the current directory and under `CARGO_TARGET_DIR`, both process-wide.
+static PROJECT_DIR: Mutex<()> = Mutex::new(());
+
+/// A one-file library in a fresh temporary directory, made the current directory.
+fn enter_project(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("getdoc-state-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(
+        dir.join("Cargo.toml"),
+        "[package]\nname = \"cached\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+    )
+    .unwrap();
+    fs::write(dir.join("src/lib.rs"), "pub fn f() {}\n").unwrap();
+    std::env::set_current_dir(&dir).unwrap();
+    // SAFETY: `PROJECT_DIR` is held, so no other test in this binary reads the environment.
+    unsafe { std::env::set_var("CARGO_TARGET_DIR", dir.join("target")) };
+    dir
+}
+
+fn output(marker: &str) -> RawRunOutput {
+    RawRunOutput {
+        json_messages: format!("{{\"reason\":\"{}\"}}\n", marker),
+        stderr: format!("stderr of {}", marker),
+    }
+}
+
+/// The messages a fresh cache for `matrix` would replay for `feature_set`.
+fn reused_messages(
+    matrix: &[FeatureSet],
+    feature_set: &FeatureSet,
+    resume: bool,
+    outputs: &[PathBuf],
+) -> Option<String> {
+    RunCache::new(matrix, &CargoCli::default(), outputs)
+        .reusable(feature_set, resume)
+        .map(|reused| reused.output.json_messages)
+}
+
+#[test]
+fn stored_results_round_trip_and_are_reused_only_for_their_feature_set() {
+    let _guard = PROJECT_DIR.lock().unwrap_or_else(|e| e.into_inner());
+    enter_project("round-trip");
+    let default = FeatureSet::new(Vec::new());
+    let all = FeatureSet::new(vec!["--all-features".to_string()]);
+    let matrix = [default.clone(), all.clone()];
+
+    let cache = RunCache::new(&matrix, &CargoCli::default(), &[]);
+    assert!(cache.reusable(&default, false).is_none());
+    cache.store(&default, RunOutcome::Warnings, output("default"));
+
+    let cache = RunCache::new(&matrix, &CargoCli::default(), &[]);
+    let reused = cache
+        .reusable(&default, false)
+        .expect("an unchanged check is reused");
+    assert_eq!(reused.output.json_messages, output("default").json_messages);
+    assert_eq!(reused.output.stderr, "stderr of default");
+    assert!(!reused.checked_at.is_empty());
+    assert!(cache.reusable(&all, false).is_none());
+}
+
+#[test]
+fn changed_inputs_invalidate_the_cache_but_getdoc_outputs_do_not() {
+    let _guard = PROJECT_DIR.lock().unwrap_or_else(|e| e.into_inner());
+    let dir = enter_project("invalidation");
+    let report = dir.join("report.md");
+    let outputs = [report.clone()];
+    let clean = FeatureSet::new(Vec::new());
+    let failing = FeatureSet::new(vec!["--all-features".to_string()]);
+    let matrix = [clean.clone(), failing.clone()];
+    let store = |feature_set: &FeatureSet, outcome, marker| {
+        RunCache::new(&matrix, &CargoCli::default(), &outputs).store(
+            feature_set,
+            outcome,
+            output(marker),
+        )
+    };
+    // A symlink cycle is hashed as a link, not walked.
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&dir, dir.join("src/loop")).unwrap();
+    store(&clean, RunOutcome::Clean, "clean");
+    store(&failing, RunOutcome::Errors, "failing");
+    let expected = Some(output("clean").json_messages);
+
+    // Rewriting the report changes nothing.
+    fs::write(&report, "# Report\n").unwrap();
+    assert_eq!(reused_messages(&matrix, &clean, false, &outputs), expected);
+
+    // Any other file in the project may be read by the build, so it counts.
+    fs::write(dir.join("src/data.txt"), "included with include_str!\n").unwrap();
+    assert_eq!(reused_messages(&matrix, &clean, false, &outputs), None);
+    // `--resume` still replays sets that passed, but never a failed one.
+    assert_eq!(reused_messages(&matrix, &clean, true, &outputs), expected);
+    assert_eq!(reused_messages(&matrix, &failing, true, &outputs), None);
+
+    store(&clean, RunOutcome::Clean, "clean");
+    assert_eq!(reused_messages(&matrix, &clean, false, &outputs), expected);
+    fs::create_dir_all(dir.join(".cargo")).unwrap();
+    fs::write(
+        dir.join(".cargo/config.toml"),
+        "[build]\nrustflags = [\"-Dwarnings\"]\n",
+    )
+    .unwrap();
+    assert_eq!(reused_messages(&matrix, &clean, false, &outputs), None);
+
+    store(&clean, RunOutcome::Clean, "clean");
+    // SAFETY: `PROJECT_DIR` is held, so no other test in this binary reads the environment.
+    unsafe { std::env::set_var("RUSTFLAGS", "-Dwarnings") };
+    let with_rustflags = reused_messages(&matrix, &clean, false, &outputs);
+    unsafe { std::env::remove_var("RUSTFLAGS") };
+    assert_eq!(with_rustflags, None);
+    assert_eq!(reused_messages(&matrix, &clean, false, &outputs), expected);
+}